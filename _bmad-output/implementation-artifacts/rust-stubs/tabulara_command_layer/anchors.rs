@@ -0,0 +1,687 @@
+use std::cmp::Ordering;
+use std::collections::HashSet;
+
+use chrono::{DateTime, Utc};
+use regex::{Regex, RegexBuilder};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::commands::{
+    AddAnchorRule, AnyCommand, AssignFieldValue, AssignFieldValuePayload, DisableAnchorRule,
+};
+use crate::errors::{unsupported_command, DomainError, DomainResult, ErrorCode};
+use crate::extraction::{BoundingBox, ExtractionLine, ExtractionToken, PageCoverage};
+use crate::interfaces::{
+    AnchorStore, CommandContext, CommandOutcome, ExtractionStore, GenericCommandHandler,
+    StateDelta, ValidationTrigger,
+};
+use crate::provenance::{Origin, Provenance};
+use crate::retroactive::{reapplier_missing, LearningRule, ReapplyPlan, RetroactiveReapplier};
+
+/// How the anchor label is recognized on the page.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum LabelMatcher {
+    Text {
+        text: String,
+        #[serde(default)]
+        case_sensitive: bool,
+    },
+    Regex {
+        pattern: String,
+    },
+}
+
+/// Where the value sits relative to the label.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum AnchorDirection {
+    Right,
+    Below,
+    Left,
+    Above,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Default)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum PageConstraint {
+    #[default]
+    Any,
+    First,
+    Last,
+    Numbers {
+        pages: Vec<u32>,
+    },
+}
+
+impl PageConstraint {
+    pub fn allows(&self, page_number: u32, page_count: u32) -> bool {
+        match self {
+            PageConstraint::Any => true,
+            PageConstraint::First => page_number == 1,
+            PageConstraint::Last => page_number == page_count,
+            PageConstraint::Numbers { pages } => pages.contains(&page_number),
+        }
+    }
+}
+
+/// Typed `AddAnchorRule` rule body.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct AnchorRuleSpec {
+    pub label: LabelMatcher,
+    pub direction: AnchorDirection,
+    /// Largest gap between label and value, in page-normalized units.
+    pub max_offset: f64,
+    #[serde(default)]
+    pub search_region: Option<BoundingBox>,
+    /// Optional regex the value text must contain; only the matched part is proposed.
+    #[serde(default)]
+    pub value_pattern: Option<String>,
+    #[serde(default)]
+    pub pages: PageConstraint,
+}
+
+/// `anchors` row.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AnchorRule {
+    pub anchor_id: Uuid,
+    pub project_id: Uuid,
+    pub schema_field_id: Uuid,
+    pub spec: AnchorRuleSpec,
+    pub enabled: bool,
+    /// Actor that added the rule.
+    pub created_by: String,
+}
+
+/// Anchor rule with its regexes compiled; building one validates the rule. A text
+/// label is compiled to an escaped regex so matching and offsets use the line itself.
+pub struct CompiledAnchor {
+    pub rule: AnchorRule,
+    label: Regex,
+    value: Option<Regex>,
+}
+
+impl CompiledAnchor {
+    pub fn compile(rule: AnchorRule) -> DomainResult<Self> {
+        validate_spec(&rule.spec)?;
+        let label = match &rule.spec.label {
+            LabelMatcher::Text {
+                text,
+                case_sensitive,
+            } => {
+                let flags = if *case_sensitive { "" } else { "(?i)" };
+                compile_regex("label", &format!("{flags}{}", regex::escape(text)))?
+            }
+            LabelMatcher::Regex { pattern } => compile_regex("label", pattern)?,
+        };
+        let value = match &rule.spec.value_pattern {
+            Some(pattern) => Some(compile_regex("value_pattern", pattern)?),
+            None => None,
+        };
+        Ok(Self { rule, label, value })
+    }
+
+    /// Byte range of the label within `text`, if present. A text label only matches
+    /// whole words: "Total" does not match inside "Subtotal".
+    fn find_label(&self, text: &str) -> Option<(usize, usize)> {
+        match &self.rule.spec.label {
+            LabelMatcher::Regex { .. } => self.label.find(text).map(|m| (m.start(), m.end())),
+            LabelMatcher::Text { .. } => self
+                .label
+                .find_iter(text)
+                .find(|m| on_word_boundaries(text, m.start(), m.end()))
+                .map(|m| (m.start(), m.end())),
+        }
+    }
+}
+
+/// Whether `text[start..end]` is not glued to a letter or digit on either side. An
+/// edge that is itself punctuation needs no boundary.
+fn on_word_boundaries(text: &str, start: usize, end: usize) -> bool {
+    let word = |c: Option<char>| c.is_some_and(char::is_alphanumeric);
+    let matched = &text[start..end];
+    let open = !word(matched.chars().next()) || !word(text[..start].chars().next_back());
+    let close = !word(matched.chars().next_back()) || !word(text[end..].chars().next());
+    open && close
+}
+
+pub fn validate_spec(spec: &AnchorRuleSpec) -> DomainResult<()> {
+    let invalid = |message: &str| DomainError {
+        code: ErrorCode::PreconditionFailed,
+        message: message.to_string(),
+        details: Some(serde_json::json!({ "rule": spec })),
+    };
+
+    if let LabelMatcher::Text { text, .. } = &spec.label {
+        if text.trim().is_empty() {
+            return Err(invalid("Anchor label text must not be empty"));
+        }
+    }
+    if !(spec.max_offset > 0.0 && spec.max_offset <= 1.0) {
+        return Err(invalid("Anchor max_offset must be within (0.0, 1.0]"));
+    }
+    if let Some(region) = &spec.search_region {
+        if !region.is_normalized() || region.area() <= 0.0 {
+            return Err(invalid("Anchor search_region must be a non-empty page-normalized box"));
+        }
+    }
+    if let PageConstraint::Numbers { pages } = &spec.pages {
+        if pages.is_empty() || pages.contains(&0) {
+            return Err(invalid("Anchor page numbers must be non-empty and 1-based"));
+        }
+    }
+    Ok(())
+}
+
+fn compile_regex(part: &str, pattern: &str) -> DomainResult<Regex> {
+    RegexBuilder::new(pattern)
+        .size_limit(1 << 20)
+        .build()
+        .map_err(|e| DomainError {
+            code: ErrorCode::PreconditionFailed,
+            message: "Anchor rule regex failed to compile".to_string(),
+            details: Some(serde_json::json!({ "part": part, "pattern": pattern, "error": e.to_string() })),
+        })
+}
+
+/// One page of extraction output an anchor is evaluated against.
+pub struct AnchorPage<'p> {
+    pub coverage: &'p PageCoverage,
+    pub page_count: u32,
+    pub extraction_run_id: Uuid,
+    pub tokens: &'p [ExtractionToken],
+    pub lines: &'p [ExtractionLine],
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AnchorProposal {
+    pub anchor_id: Uuid,
+    pub schema_field_id: Uuid,
+    pub document_id: Uuid,
+    pub page_id: Uuid,
+    pub extraction_run_id: Uuid,
+    pub raw_value: String,
+    pub label_token_ids: Vec<Uuid>,
+    pub value_token_ids: Vec<Uuid>,
+    pub bbox: BoundingBox,
+    pub confidence: f32,
+}
+
+impl AnchorProposal {
+    pub fn provenance(&self) -> Provenance {
+        Provenance::extracted(
+            Origin::Anchor {
+                anchor_id: self.anchor_id,
+                label_token_ids: self.label_token_ids.clone(),
+            },
+            self.extraction_run_id,
+            self.page_id,
+            self.bbox,
+            self.value_token_ids.clone(),
+            self.confidence,
+        )
+    }
+
+    pub fn to_command(&self, session_id: Uuid, actor: &str, now: DateTime<Utc>) -> AnyCommand {
+        AnyCommand::AssignFieldValue(AssignFieldValue {
+            command_id: Uuid::now_v7(),
+            actor: actor.to_string(),
+            timestamp: now,
+            expected_version: None,
+            payload: AssignFieldValuePayload {
+                session_id,
+                document_id: self.document_id,
+                schema_field_id: self.schema_field_id,
+                raw_value: self.raw_value.clone(),
+                normalized_value: None,
+                provenance: self.provenance(),
+            },
+        })
+    }
+}
+
+/// Evaluates one anchor against one page; returns the first hit in reading order.
+pub fn evaluate_page(anchor: &CompiledAnchor, page: &AnchorPage<'_>) -> Option<AnchorProposal> {
+    let spec = &anchor.rule.spec;
+    if !spec.pages.allows(page.coverage.page_number, page.page_count) {
+        return None;
+    }
+
+    let mut lines: Vec<&ExtractionLine> = page.lines.iter().collect();
+    lines.sort_by_key(|l| l.reading_order);
+
+    for line in lines {
+        let line_tokens: Vec<&ExtractionToken> = line
+            .token_ids
+            .iter()
+            .filter_map(|id| page.tokens.iter().find(|t| t.token_id == *id))
+            .collect();
+        let (joined, offsets) = join_with_offsets(&line_tokens);
+        let Some((start, end)) = anchor.find_label(&joined) else {
+            continue;
+        };
+
+        let label_tokens: Vec<&ExtractionToken> = line_tokens
+            .iter()
+            .zip(offsets.iter())
+            .filter(|(_, (s, e))| *s < end && *e > start)
+            .map(|(t, _)| *t)
+            .collect();
+        let Some(label_box) = union_box(&label_tokens) else {
+            continue;
+        };
+        let label_ids: Vec<Uuid> = label_tokens.iter().map(|t| t.token_id).collect();
+
+        if let Some(proposal) = find_value(anchor, page, &label_box, &label_ids) {
+            return Some(proposal);
+        }
+    }
+    None
+}
+
+fn find_value(
+    anchor: &CompiledAnchor,
+    page: &AnchorPage<'_>,
+    label: &BoundingBox,
+    label_ids: &[Uuid],
+) -> Option<AnchorProposal> {
+    let spec = &anchor.rule.spec;
+    let excluded: HashSet<Uuid> = label_ids.iter().copied().collect();
+    let candidates: Vec<(&ExtractionToken, f64)> = page
+        .tokens
+        .iter()
+        .filter(|t| !excluded.contains(&t.token_id))
+        .filter(|t| {
+            spec.search_region.is_none_or(|region| {
+                let (x, y) = t.bbox.center();
+                region.contains_point(x, y)
+            })
+        })
+        .filter_map(|t| gap(spec.direction, label, &t.bbox).map(|g| (t, g)))
+        .filter(|(_, g)| *g <= spec.max_offset)
+        .collect();
+
+    let (nearest, _) = candidates
+        .iter()
+        .min_by(|a, b| a.1.partial_cmp(&b.1).unwrap_or(Ordering::Equal))?;
+
+    // The value is the run of candidate tokens sharing the nearest token's text row.
+    let band = (nearest.bbox.y, nearest.bbox.bottom());
+    let mut row: Vec<&ExtractionToken> = candidates
+        .iter()
+        .map(|(t, _)| *t)
+        .filter(|t| {
+            let (_, cy) = t.bbox.center();
+            cy >= band.0 && cy <= band.1
+        })
+        .collect();
+    row.sort_by(|a, b| a.bbox.x.partial_cmp(&b.bbox.x).unwrap_or(Ordering::Equal));
+
+    let (joined, offsets) = join_with_offsets(&row);
+    let (start, end) = match &anchor.value {
+        Some(regex) => {
+            let m = regex.find(&joined)?;
+            (m.start(), m.end())
+        }
+        None => (0, joined.len()),
+    };
+    let value_tokens: Vec<&ExtractionToken> = row
+        .iter()
+        .zip(offsets.iter())
+        .filter(|(_, (s, e))| *s < end && *e > start)
+        .map(|(t, _)| *t)
+        .collect();
+    let bbox = union_box(&value_tokens)?;
+    let raw_value = joined[start..end].trim().to_string();
+    if raw_value.is_empty() {
+        return None;
+    }
+
+    Some(AnchorProposal {
+        anchor_id: anchor.rule.anchor_id,
+        schema_field_id: anchor.rule.schema_field_id,
+        document_id: page.coverage.document_id,
+        page_id: page.coverage.page_id,
+        extraction_run_id: page.extraction_run_id,
+        raw_value,
+        label_token_ids: label_ids.to_vec(),
+        value_token_ids: value_tokens.iter().map(|t| t.token_id).collect(),
+        bbox,
+        confidence: value_tokens.iter().map(|t| t.confidence).fold(1.0, f32::min),
+    })
+}
+
+/// Distance from the label to `target` along `direction`, or `None` when the target
+/// is not on that side of the label or does not overlap it on the cross axis.
+fn gap(direction: AnchorDirection, label: &BoundingBox, target: &BoundingBox) -> Option<f64> {
+    let (cx, cy) = target.center();
+    let same_row = cy >= label.y - label.height / 2.0 && cy <= label.bottom() + label.height / 2.0;
+    let same_column = target.right() >= label.x && target.x <= label.right();
+    let distance = match direction {
+        AnchorDirection::Right if same_row && cx > label.right() => target.x - label.right(),
+        AnchorDirection::Left if same_row && cx < label.x => label.x - target.right(),
+        AnchorDirection::Below if same_column && cy > label.bottom() => target.y - label.bottom(),
+        AnchorDirection::Above if same_column && cy < label.y => label.y - target.bottom(),
+        _ => return None,
+    };
+    Some(distance.max(0.0))
+}
+
+fn join_with_offsets(tokens: &[&ExtractionToken]) -> (String, Vec<(usize, usize)>) {
+    let mut joined = String::new();
+    let mut offsets = Vec::with_capacity(tokens.len());
+    for token in tokens {
+        if !joined.is_empty() {
+            joined.push(' ');
+        }
+        let start = joined.len();
+        joined.push_str(token.text.trim());
+        offsets.push((start, joined.len()));
+    }
+    (joined, offsets)
+}
+
+fn union_box(tokens: &[&ExtractionToken]) -> Option<BoundingBox> {
+    let first = tokens.first()?;
+    Some(tokens.iter().skip(1).fold(first.bbox, |acc, t| acc.union(&t.bbox)))
+}
+
+/// Runs a project's enabled anchors against the latest extraction of a document.
+pub struct AnchorEvaluator<'a> {
+    extraction: &'a dyn ExtractionStore,
+    anchors: &'a dyn AnchorStore,
+}
+
+impl<'a> AnchorEvaluator<'a> {
+    pub fn new(extraction: &'a dyn ExtractionStore, anchors: &'a dyn AnchorStore) -> Self {
+        Self {
+            extraction,
+            anchors,
+        }
+    }
+
+    pub fn evaluate_document(
+        &self,
+        project_id: Uuid,
+        session_id: Uuid,
+        document_id: Uuid,
+    ) -> DomainResult<Vec<AnchorProposal>> {
+        let rules: Vec<AnchorRule> = self
+            .anchors
+            .rules(project_id)?
+            .into_iter()
+            .filter(|r| r.enabled)
+            .collect();
+        self.evaluate_rules(session_id, document_id, rules)
+    }
+
+    /// Evaluates `rules` (enabled or not) against a document; used for previews.
+    pub fn evaluate_rules(
+        &self,
+        session_id: Uuid,
+        document_id: Uuid,
+        rules: Vec<AnchorRule>,
+    ) -> DomainResult<Vec<AnchorProposal>> {
+        let mut pages: Vec<PageCoverage> = Vec::new();
+        for page in self
+            .extraction
+            .runs_for_session(session_id)?
+            .iter()
+            .flat_map(|r| r.pages.iter())
+        {
+            if page.document_id == document_id && !pages.iter().any(|p| p.page_id == page.page_id) {
+                pages.push(page.clone());
+            }
+        }
+        pages.sort_by_key(|p| p.page_number);
+        let page_count = pages.iter().map(|p| p.page_number).max().unwrap_or(0);
+
+        let mut loaded = Vec::with_capacity(pages.len());
+        for coverage in &pages {
+            let Some(run) = self.extraction.latest_run_for_page(session_id, coverage.page_id)? else {
+                continue;
+            };
+            let tokens = self
+                .extraction
+                .tokens_for_page(run.extraction_run_id, coverage.page_id)?;
+            let lines = self
+                .extraction
+                .lines_for_page(run.extraction_run_id, coverage.page_id)?;
+            loaded.push((coverage, run.extraction_run_id, tokens, lines));
+        }
+
+        let mut proposals = Vec::new();
+        for rule in rules {
+            let anchor = CompiledAnchor::compile(rule)?;
+            let hit = loaded.iter().find_map(|(coverage, run_id, tokens, lines)| {
+                evaluate_page(
+                    &anchor,
+                    &AnchorPage {
+                        coverage,
+                        page_count,
+                        extraction_run_id: *run_id,
+                        tokens,
+                        lines,
+                    },
+                )
+            });
+            proposals.extend(hit);
+        }
+        Ok(proposals)
+    }
+}
+
+pub struct AddAnchorRuleHandler<'a> {
+    anchors: &'a dyn AnchorStore,
+    reapplier: Option<&'a RetroactiveReapplier<'a>>,
+}
+
+impl<'a> AddAnchorRuleHandler<'a> {
+    pub fn new(anchors: &'a dyn AnchorStore) -> Self {
+        Self {
+            anchors,
+            reapplier: None,
+        }
+    }
+
+    /// Enables `retroactive: true` on the command.
+    pub fn with_reapplier(mut self, reapplier: &'a RetroactiveReapplier<'a>) -> Self {
+        self.reapplier = Some(reapplier);
+        self
+    }
+
+    fn add(&self, cmd: &AddAnchorRule) -> DomainResult<CommandOutcome> {
+        let rule = AnchorRule {
+            anchor_id: Uuid::now_v7(),
+            project_id: cmd.payload.project_id,
+            schema_field_id: cmd.payload.schema_field_id,
+            spec: cmd.payload.rule.clone(),
+            enabled: true,
+            created_by: cmd.actor.clone(),
+        };
+        let compiled = CompiledAnchor::compile(rule)?;
+        let reapplier = match (cmd.payload.retroactive, self.reapplier) {
+            (false, _) => None,
+            (true, Some(reapplier)) => Some(reapplier),
+            (true, None) => return Err(reapplier_missing("AddAnchorRule")),
+        };
+        self.anchors.add(&compiled.rule)?;
+
+        let learned = LearningRule::Anchor(compiled.rule.clone());
+        let plan = match reapplier {
+            Some(reapplier) => reapplier.plan(&learned)?,
+            None => ReapplyPlan::default(),
+        };
+
+        Ok(CommandOutcome {
+            state_delta: StateDelta {
+                summary: "Anchor rule created".to_string(),
+                data: serde_json::json!({
+                    "anchor": compiled.rule,
+                    "retroactive": reapplier.map(|_| plan.preview()),
+                }),
+            },
+            transition: None,
+            review_actions: plan.review_actions(&learned),
+            validation_trigger: ValidationTrigger::None,
+            events: plan.events(&learned),
+        })
+    }
+}
+
+impl<'a> GenericCommandHandler for AddAnchorRuleHandler<'a> {
+    fn can_handle(&self, command_type: &str) -> bool {
+        command_type == "AddAnchorRule"
+    }
+
+    fn handle(&self, _ctx: &mut CommandContext, cmd: &AnyCommand) -> DomainResult<CommandOutcome> {
+        match cmd {
+            AnyCommand::AddAnchorRule(c) => self.add(c),
+            _ => Err(unsupported_command("AddAnchorRuleHandler")),
+        }
+    }
+}
+
+pub struct DisableAnchorRuleHandler<'a> {
+    anchors: &'a dyn AnchorStore,
+}
+
+impl<'a> DisableAnchorRuleHandler<'a> {
+    pub fn new(anchors: &'a dyn AnchorStore) -> Self {
+        Self { anchors }
+    }
+
+    fn set_enabled(&self, cmd: &DisableAnchorRule) -> DomainResult<CommandOutcome> {
+        let anchor = self.anchors.get(cmd.payload.anchor_id)?;
+        if anchor.project_id != cmd.payload.project_id {
+            return Err(DomainError {
+                code: ErrorCode::PreconditionFailed,
+                message: "Anchor rule does not belong to project".to_string(),
+                details: Some(serde_json::json!({
+                    "anchor_id": cmd.payload.anchor_id,
+                    "project_id": cmd.payload.project_id,
+                })),
+            });
+        }
+        self.anchors
+            .set_enabled(cmd.payload.anchor_id, cmd.payload.enabled)?;
+
+        Ok(CommandOutcome {
+            state_delta: StateDelta {
+                summary: if cmd.payload.enabled {
+                    "Anchor rule enabled".to_string()
+                } else {
+                    "Anchor rule disabled".to_string()
+                },
+                data: serde_json::json!({
+                    "anchor_id": cmd.payload.anchor_id,
+                    "enabled": cmd.payload.enabled,
+                }),
+            },
+            transition: None,
+            review_actions: Vec::new(),
+            validation_trigger: ValidationTrigger::None,
+            events: Vec::new(),
+        })
+    }
+}
+
+impl<'a> GenericCommandHandler for DisableAnchorRuleHandler<'a> {
+    fn can_handle(&self, command_type: &str) -> bool {
+        command_type == "DisableAnchorRule"
+    }
+
+    fn handle(&self, _ctx: &mut CommandContext, cmd: &AnyCommand) -> DomainResult<CommandOutcome> {
+        match cmd {
+            AnyCommand::DisableAnchorRule(c) => self.set_enabled(c),
+            _ => Err(unsupported_command("DisableAnchorRuleHandler")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::in_memory_reference_impl::InMemoryReferenceBundle;
+    use crate::test_support::{command, ctx};
+
+    fn anchor(label: serde_json::Value) -> CompiledAnchor {
+        let spec: AnchorRuleSpec = serde_json::from_value(serde_json::json!({
+            "label": label,
+            "direction": "right",
+            "max_offset": 0.2,
+        }))
+        .unwrap();
+        CompiledAnchor::compile(AnchorRule {
+            anchor_id: Uuid::now_v7(),
+            project_id: Uuid::now_v7(),
+            schema_field_id: Uuid::now_v7(),
+            spec,
+            enabled: true,
+            created_by: "tester".to_string(),
+        })
+        .unwrap()
+    }
+
+    fn found<'t>(anchor: &CompiledAnchor, text: &'t str) -> Option<&'t str> {
+        anchor
+            .find_label(text)
+            .map(|(start, end)| &text[start..end])
+    }
+
+    #[test]
+    fn text_labels_match_whole_words_only() {
+        let total = anchor(serde_json::json!({ "kind": "text", "text": "Total" }));
+        assert_eq!(found(&total, "Subtotal 10.00"), None);
+        assert_eq!(
+            total.find_label("Subtotal 10.00 TOTAL 12.00"),
+            Some((15, 20))
+        );
+        assert_eq!(found(&total, "Totals 12.00"), None);
+
+        let colon = anchor(serde_json::json!({ "kind": "text", "text": "Total:" }));
+        assert_eq!(found(&colon, "Subtotal: 10 Total: 12"), Some("Total:"));
+        assert_eq!(found(&colon, "Total:12"), Some("Total:"));
+
+        let exact =
+            anchor(serde_json::json!({ "kind": "text", "text": "TOTAL", "case_sensitive": true }));
+        assert_eq!(found(&exact, "Total 12"), None);
+        assert_eq!(found(&exact, "Total TOTAL 12"), Some("TOTAL"));
+    }
+
+    #[test]
+    fn offsets_index_the_original_text() {
+        // Lowercasing "İ" takes more bytes, which used to shift or drop the match.
+        let total = anchor(serde_json::json!({ "kind": "text", "text": "total" }));
+        assert_eq!(found(&total, "İSTANBUL Total 12"), Some("Total"));
+        let city = anchor(serde_json::json!({ "kind": "text", "text": "straße" }));
+        assert_eq!(found(&city, "Lieferung STRASSE Straße 1"), Some("Straße"));
+    }
+
+    #[test]
+    fn rules_record_the_actor() {
+        let b = InMemoryReferenceBundle::new();
+        let outcome = AddAnchorRuleHandler::new(&b.anchors)
+            .handle(
+                &mut ctx(),
+                &command(serde_json::json!({
+                    "type": "AddAnchorRule",
+                    "actor": "reviewer",
+                    "payload": {
+                        "project_id": Uuid::now_v7(),
+                        "schema_field_id": Uuid::now_v7(),
+                        "rule": {
+                            "label": { "kind": "text", "text": "Total" },
+                            "direction": "right",
+                            "max_offset": 0.2,
+                        }
+                    }
+                })),
+            )
+            .unwrap();
+        let anchor_id: Uuid =
+            serde_json::from_value(outcome.state_delta.data["anchor"]["anchor_id"].clone())
+                .unwrap();
+        assert_eq!(b.anchors.get(anchor_id).unwrap().created_by, "reviewer");
+    }
+}
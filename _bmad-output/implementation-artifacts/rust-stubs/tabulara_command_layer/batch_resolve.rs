@@ -0,0 +1,685 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::commands::{AnyCommand, BatchResolveField};
+use crate::dictionary::DictionaryEngine;
+use crate::errors::{unsupported_command, DomainError, DomainResult, ErrorCode};
+use crate::interfaces::{
+    CommandContext, CommandOutcome, DictionaryStore, GenericCommandHandler, MappingStore,
+    NormalizationContextResolver, OutcomeEvent, PageReader, ReviewTaskStore, SchemaStore,
+    SessionReader, StateDelta, TemplateStore, ValidationTrigger,
+};
+use crate::locks::locked_item_rows;
+use crate::mapping::{FieldValue, ItemValue, ValueChange, WrittenValue};
+use crate::provenance::{Origin, Provenance};
+use crate::review_tasks::{ReviewQueue, ReviewResolution, ReviewTask};
+use crate::schema::{session_schema, SchemaField};
+use crate::types::{FieldScope, SessionStatus, SessionStatusTransition};
+use crate::value_parsing::{parse_value, ValueLocale};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum BatchResolveAction {
+    /// Accepts the current value of every targeted task that has one.
+    AcceptAll,
+    /// Accepts values whose recorded extraction confidence is at least `threshold`.
+    AcceptAboveConfidence {
+        threshold: f32,
+    },
+    /// Writes one manual value to every targeted field.
+    SetValue {
+        raw_value: String,
+    },
+    /// Re-normalizes current values with the project's dictionary rules.
+    ApplyDictionary,
+    Skip {
+        reason: String,
+    },
+}
+
+/// Narrows the open tasks on the field. Confidence bounds are inclusive; values
+/// without a recorded confidence fall outside any bound.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct BatchResolveFilter {
+    /// Empty means every document of the session.
+    #[serde(default)]
+    pub document_ids: Vec<Uuid>,
+    #[serde(default)]
+    pub template_id: Option<Uuid>,
+    #[serde(default)]
+    pub min_confidence: Option<f32>,
+    #[serde(default)]
+    pub max_confidence: Option<f32>,
+}
+
+impl BatchResolveFilter {
+    fn has_confidence_bounds(&self) -> bool {
+        self.min_confidence.is_some() || self.max_confidence.is_some()
+    }
+
+    fn accepts_confidence(&self, confidence: Option<f32>) -> bool {
+        if !self.has_confidence_bounds() {
+            return true;
+        }
+        confidence.is_some_and(|c| {
+            self.min_confidence.is_none_or(|min| c >= min)
+                && self.max_confidence.is_none_or(|max| c <= max)
+        })
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LeftOpenTask {
+    pub review_task_id: Uuid,
+    pub reason: String,
+}
+
+/// Every targeted task lands in exactly one list, in queue order.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct BatchResolveResult {
+    pub resolved: Vec<Uuid>,
+    pub skipped: Vec<Uuid>,
+    pub left_open: Vec<LeftOpenTask>,
+}
+
+/// The value a task points at, whether a document field or an item field.
+#[derive(Debug, Clone)]
+enum TargetValue {
+    Field(FieldValue),
+    Item(ItemValue),
+}
+
+impl TargetValue {
+    fn raw_value(&self) -> &str {
+        match self {
+            TargetValue::Field(v) => &v.raw_value,
+            TargetValue::Item(v) => &v.raw_value,
+        }
+    }
+
+    fn provenance(&self) -> &Provenance {
+        match self {
+            TargetValue::Field(v) => &v.provenance,
+            TargetValue::Item(v) => &v.provenance,
+        }
+    }
+
+    fn locked(&self) -> bool {
+        match self {
+            TargetValue::Field(v) => v.locked,
+            TargetValue::Item(v) => v.locked,
+        }
+    }
+
+    /// Extraction confidence recorded by anchors, zones and table cells.
+    fn confidence(&self) -> Option<f32> {
+        self.provenance().confidence
+    }
+
+    fn set(&mut self, raw_value: String, normalized_value: Option<String>, provenance: Provenance) {
+        match self {
+            TargetValue::Field(v) => {
+                v.raw_value = raw_value;
+                v.normalized_value = normalized_value;
+                v.provenance = provenance;
+            }
+            TargetValue::Item(v) => {
+                v.raw_value = raw_value;
+                v.normalized_value = normalized_value;
+                v.provenance = provenance;
+            }
+        }
+    }
+
+    /// Replaces the normalized value, keeping the raw value as read.
+    fn renormalize(&mut self, normalized_value: Option<String>, provenance: Provenance) {
+        match self {
+            TargetValue::Field(v) => {
+                v.normalized_value = normalized_value;
+                v.provenance = provenance;
+            }
+            TargetValue::Item(v) => {
+                v.normalized_value = normalized_value;
+                v.provenance = provenance;
+            }
+        }
+    }
+}
+
+/// What the batch does to one task.
+enum Decision {
+    Resolve(ReviewResolution, Option<Box<TargetValue>>),
+    Skip(String),
+    LeaveOpen(String),
+}
+
+pub struct BatchResolveDeps<'a> {
+    pub tasks: &'a dyn ReviewTaskStore,
+    pub mapping: &'a dyn MappingStore,
+    pub sessions: &'a dyn SessionReader,
+    pub schemas: &'a dyn SchemaStore,
+    pub pages: &'a dyn PageReader,
+    pub templates: &'a dyn TemplateStore,
+    pub dictionary: &'a dyn DictionaryStore,
+    pub contexts: &'a dyn NormalizationContextResolver,
+}
+
+/// Applies one action to the open review tasks on a field. Values are written and
+/// tasks closed by the same command, so the batch is applied as a whole or not at
+/// all; tasks the action cannot settle stay open and are listed with the reason.
+pub struct BatchResolveFieldHandler<'a> {
+    deps: BatchResolveDeps<'a>,
+}
+
+impl<'a> BatchResolveFieldHandler<'a> {
+    pub fn new(deps: BatchResolveDeps<'a>) -> Self {
+        Self { deps }
+    }
+
+    fn batch(&self, ctx: &CommandContext, cmd: &BatchResolveField) -> DomainResult<CommandOutcome> {
+        let payload = &cmd.payload;
+        let session_id = payload.session_id;
+        let session = self.deps.sessions.get_session(session_id)?;
+        let status = self.deps.sessions.get_status(session_id)?;
+        let schema = session_schema(self.deps.sessions, self.deps.schemas, session_id)?;
+        let field = schema
+            .field_by_key(&payload.field_key)
+            .ok_or_else(|| DomainError {
+                code: ErrorCode::NotFound,
+                message: "Field is not part of the session's schema version".to_string(),
+                details: Some(serde_json::json!({ "field_key": payload.field_key })),
+            })?;
+        check_request(&payload.action, &payload.filter)?;
+        let locale = self.deps.schemas.get_project(session.project_id)?.locale;
+
+        // Like manual entry, a value that does not parse rejects the whole batch.
+        let set_normalized = match &payload.action {
+            BatchResolveAction::SetValue { raw_value } => parse_value(field, raw_value, &locale)
+                .map_err(|failure| DomainError {
+                    code: ErrorCode::PreconditionFailed,
+                    message: format!("Value does not parse as {:?}", failure.field_type),
+                    details: Some(serde_json::json!({
+                        "field_key": field.field_key,
+                        "raw_value": raw_value,
+                        "reason": failure.reason,
+                    })),
+                })?,
+            _ => None,
+        };
+        let dictionary = match &payload.action {
+            BatchResolveAction::ApplyDictionary => Some(DictionaryEngine::new(
+                self.deps.dictionary.rules(session.project_id)?,
+            )?),
+            _ => None,
+        };
+
+        // Updated as the batch writes, so later tasks on the same value see the write.
+        let mut current = self.current_values(session_id, field)?;
+        let locked_rows = locked_item_rows(self.deps.mapping, session_id)?;
+        let mut targeted = Vec::new();
+        for queued in ReviewQueue::new(self.deps.tasks, self.deps.pages, self.deps.mapping)
+            .ordered(session_id)?
+        {
+            let task = queued.task;
+            if !task.is_open() || task.target.schema_field_id != Some(field.schema_field_id) {
+                continue;
+            }
+            let filter = &payload.filter;
+            if !filter.document_ids.is_empty()
+                && !filter.document_ids.contains(&task.target.document_id)
+            {
+                continue;
+            }
+            if let Some(template_id) = filter.template_id {
+                let matched = self
+                    .deps
+                    .templates
+                    .document_template(session_id, task.target.document_id)?
+                    .and_then(|t| t.matched)
+                    .map(|m| m.template_id);
+                if matched != Some(template_id) {
+                    continue;
+                }
+            }
+            let owner = task.target.item_id.unwrap_or(task.target.document_id);
+            let confidence = current.get(&owner).and_then(TargetValue::confidence);
+            if !filter.accepts_confidence(confidence) {
+                continue;
+            }
+            targeted.push((task, owner));
+        }
+        if targeted.is_empty() {
+            return Err(DomainError {
+                code: ErrorCode::PreconditionFailed,
+                message: "No open review task matches the batch".to_string(),
+                details: Some(serde_json::json!({
+                    "field_key": field.field_key,
+                    "filter": payload.filter,
+                })),
+            });
+        }
+
+        let mut result = BatchResolveResult::default();
+        let mut events = Vec::new();
+        let mut changed = Vec::new();
+        let mut written = Vec::new();
+        for (mut task, owner) in targeted {
+            let value = current.get(&owner).cloned();
+            let row_locked = task
+                .target
+                .item_id
+                .is_some_and(|item_id| locked_rows.contains(&item_id));
+            let decision = match &payload.action {
+                BatchResolveAction::Skip { reason } => Decision::Skip(reason.trim().to_string()),
+                _ if row_locked => Decision::LeaveOpen("Item row is locked".to_string()),
+                BatchResolveAction::AcceptAll => accept(value, |_| true),
+                BatchResolveAction::AcceptAboveConfidence { threshold } => {
+                    accept(value, |v| v.confidence().is_some_and(|c| c >= *threshold))
+                }
+                BatchResolveAction::SetValue { raw_value } => {
+                    let provenance = Provenance::new(Origin::Manual {
+                        actor: ctx.actor.clone(),
+                        unknown_fragment_id: None,
+                        batch_command_id: Some(cmd.command_id),
+                    });
+                    match self.new_value(&task, field, value, &provenance) {
+                        Ok(mut value) => {
+                            value.set(raw_value.clone(), set_normalized.clone(), provenance);
+                            Decision::Resolve(ReviewResolution::Edited, Some(Box::new(value)))
+                        }
+                        Err(reason) => Decision::LeaveOpen(reason),
+                    }
+                }
+                BatchResolveAction::ApplyDictionary => match (&dictionary, value) {
+                    (Some(engine), Some(value)) => {
+                        self.normalize(engine, &task, field, &locale, value)?
+                    }
+                    _ => Decision::LeaveOpen("Field has no value".to_string()),
+                },
+            };
+
+            match decision {
+                Decision::Resolve(resolution, new_value) => {
+                    if let Some(value) = new_value {
+                        let change = ValueChange {
+                            document_id: task.target.document_id,
+                            schema_field_id: field.schema_field_id,
+                        };
+                        if !changed.contains(&change) {
+                            changed.push(change);
+                        }
+                        match value.as_ref() {
+                            TargetValue::Field(v) => {
+                                self.deps.mapping.put_field_value(v)?;
+                                written.push(WrittenValue::Field(v.clone()));
+                            }
+                            TargetValue::Item(v) => {
+                                self.deps.mapping.put_item_value(v)?;
+                                written.push(WrittenValue::Item(v.clone()));
+                            }
+                        }
+                        current.insert(owner, *value);
+                    }
+                    task.resolve(resolution, &ctx.actor, ctx.now);
+                    result.resolved.push(task.review_task_id);
+                }
+                Decision::Skip(reason) => {
+                    task.skip(&reason, &ctx.actor, ctx.now);
+                    result.skipped.push(task.review_task_id);
+                }
+                Decision::LeaveOpen(reason) => {
+                    result.left_open.push(LeftOpenTask {
+                        review_task_id: task.review_task_id,
+                        reason,
+                    });
+                    continue;
+                }
+            }
+            self.deps.tasks.put_task(&task)?;
+            events.push(task.closed_event());
+        }
+
+        events.push(OutcomeEvent {
+            event_type: "FieldBatchConfirmed".to_string(),
+            session_id: Some(session_id),
+            data: serde_json::json!({
+                "field_key": field.field_key,
+                "action": payload.action,
+                "result": result,
+            }),
+        });
+        let transition = (!changed.is_empty() && status == SessionStatus::Validated).then_some(
+            SessionStatusTransition {
+                from: status,
+                to: SessionStatus::Review,
+            },
+        );
+
+        Ok(CommandOutcome {
+            state_delta: StateDelta {
+                summary: format!(
+                    "Batch on {}: {} resolved, {} skipped, {} left open",
+                    field.field_key,
+                    result.resolved.len(),
+                    result.skipped.len(),
+                    result.left_open.len()
+                ),
+                data: serde_json::json!({
+                    "field_key": field.field_key,
+                    "action": payload.action,
+                    "filter": payload.filter,
+                    "result": result,
+                    "changed_values": changed,
+                    "written_values": written,
+                }),
+            },
+            transition,
+            review_actions: Vec::new(),
+            validation_trigger: if changed.is_empty() {
+                ValidationTrigger::None
+            } else {
+                ValidationTrigger::Async
+            },
+            events,
+        })
+    }
+
+    /// Current values of the field keyed by document id, or by item id for item fields.
+    fn current_values(
+        &self,
+        session_id: Uuid,
+        field: &SchemaField,
+    ) -> DomainResult<HashMap<Uuid, TargetValue>> {
+        Ok(match field.scope {
+            FieldScope::Document => self
+                .deps
+                .mapping
+                .field_values(session_id)?
+                .into_iter()
+                .filter(|v| v.schema_field_id == field.schema_field_id)
+                .map(|v| (v.document_id, TargetValue::Field(v)))
+                .collect(),
+            FieldScope::Item => self
+                .deps
+                .mapping
+                .item_values(session_id)?
+                .into_iter()
+                .filter(|v| v.schema_field_id == field.schema_field_id)
+                .map(|v| (v.item_id, TargetValue::Item(v)))
+                .collect(),
+            // Review tasks never target extra-table cells.
+            FieldScope::ExtraTable => HashMap::new(),
+        })
+    }
+
+    /// The value a `set_value` batch writes for a task: the current one, or a new one
+    /// when the field is still empty.
+    fn new_value(
+        &self,
+        task: &ReviewTask,
+        field: &SchemaField,
+        current: Option<TargetValue>,
+        provenance: &Provenance,
+    ) -> Result<TargetValue, String> {
+        if let Some(value) = current {
+            return if value.locked() {
+                Err("Value is locked".to_string())
+            } else {
+                Ok(value)
+            };
+        }
+        Ok(match (field.scope, task.target.item_id) {
+            (FieldScope::Document, _) => TargetValue::Field(FieldValue {
+                field_value_id: Uuid::now_v7(),
+                session_id: task.session_id,
+                document_id: task.target.document_id,
+                schema_field_id: field.schema_field_id,
+                raw_value: String::new(),
+                normalized_value: None,
+                provenance: provenance.clone(),
+                locked: false,
+            }),
+            (FieldScope::Item, Some(item_id)) => TargetValue::Item(ItemValue {
+                item_value_id: Uuid::now_v7(),
+                session_id: task.session_id,
+                item_id,
+                schema_field_id: field.schema_field_id,
+                raw_value: String::new(),
+                normalized_value: None,
+                provenance: provenance.clone(),
+                locked: false,
+            }),
+            (FieldScope::Item, None) => return Err("Task has no item row".to_string()),
+            (FieldScope::ExtraTable, _) => {
+                return Err("Extra-table cells are not batch resolved".to_string())
+            }
+        })
+    }
+
+    fn normalize(
+        &self,
+        engine: &DictionaryEngine,
+        task: &ReviewTask,
+        field: &SchemaField,
+        locale: &ValueLocale,
+        mut value: TargetValue,
+    ) -> DomainResult<Decision> {
+        if value.locked() {
+            return Ok(Decision::LeaveOpen("Value is locked".to_string()));
+        }
+        let norm_ctx = self.deps.contexts.context_for(
+            task.session_id,
+            task.target.document_id,
+            field.schema_field_id,
+        )?;
+        let outcome = engine.apply(value.raw_value(), &norm_ctx);
+        if !outcome.changed() {
+            return Ok(Decision::LeaveOpen(
+                "No dictionary rule matched the value".to_string(),
+            ));
+        }
+        let Ok(normalized) = parse_value(field, &outcome.value, locale) else {
+            return Ok(Decision::LeaveOpen(format!(
+                "Normalized value '{}' does not parse as {:?}",
+                outcome.value, field.field_type
+            )));
+        };
+        let mut provenance = value.provenance().clone();
+        outcome.record_in(&mut provenance);
+        value.renormalize(normalized, provenance);
+        Ok(Decision::Resolve(
+            ReviewResolution::Edited,
+            Some(Box::new(value)),
+        ))
+    }
+}
+
+fn accept(value: Option<TargetValue>, passes: impl Fn(&TargetValue) -> bool) -> Decision {
+    match value {
+        None => Decision::LeaveOpen("Field has no value".to_string()),
+        Some(v) if v.raw_value().trim().is_empty() => {
+            Decision::LeaveOpen("Field has no value".to_string())
+        }
+        Some(v) if !passes(&v) => {
+            Decision::LeaveOpen("Confidence is below the threshold".to_string())
+        }
+        Some(_) => Decision::Resolve(ReviewResolution::Accepted, None),
+    }
+}
+
+fn check_request(action: &BatchResolveAction, filter: &BatchResolveFilter) -> DomainResult<()> {
+    let invalid = |message: &str| DomainError {
+        code: ErrorCode::PreconditionFailed,
+        message: message.to_string(),
+        details: Some(serde_json::json!({ "action": action, "filter": filter })),
+    };
+    let in_range = |c: f32| (0.0..=1.0).contains(&c);
+    match action {
+        BatchResolveAction::AcceptAboveConfidence { threshold } if !in_range(*threshold) => {
+            return Err(invalid("Confidence threshold must be between 0 and 1"));
+        }
+        BatchResolveAction::Skip { reason } if reason.trim().is_empty() => {
+            return Err(invalid("Skipping review tasks requires a reason"));
+        }
+        _ => {}
+    }
+    if filter
+        .min_confidence
+        .into_iter()
+        .chain(filter.max_confidence)
+        .any(|c| !in_range(c))
+    {
+        return Err(invalid("Confidence bounds must be between 0 and 1"));
+    }
+    if let (Some(min), Some(max)) = (filter.min_confidence, filter.max_confidence) {
+        if min > max {
+            return Err(invalid("Minimum confidence is above the maximum"));
+        }
+    }
+    Ok(())
+}
+
+impl<'a> GenericCommandHandler for BatchResolveFieldHandler<'a> {
+    fn can_handle(&self, command_type: &str) -> bool {
+        command_type == "BatchResolveField"
+    }
+
+    fn handle(&self, ctx: &mut CommandContext, cmd: &AnyCommand) -> DomainResult<CommandOutcome> {
+        match cmd {
+            AnyCommand::BatchResolveField(c) => self.batch(ctx, c),
+            _ => Err(unsupported_command("BatchResolveFieldHandler")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dictionary::AddDictionaryRuleHandler;
+    use crate::interfaces::ReviewAction;
+    use crate::review_tasks::{open_review_tasks, ReviewCategory, ReviewTarget};
+    use crate::test_support::{command, ctx, World};
+
+    fn batch(w: &World, session_id: Uuid, action: serde_json::Value) -> CommandOutcome {
+        BatchResolveFieldHandler::new(BatchResolveDeps {
+            tasks: &w.b.review_tasks,
+            mapping: &w.b.mapping,
+            sessions: &w.b.sessions,
+            schemas: &w.b.schemas,
+            pages: &w.b.pages,
+            templates: &w.b.templates,
+            dictionary: &w.b.dictionary,
+            contexts: &w.b.contexts,
+        })
+        .handle(
+            &mut ctx(),
+            &command(serde_json::json!({
+                "type": "BatchResolveField",
+                "payload": { "session_id": session_id, "field_key": "vendor", "action": action }
+            })),
+        )
+        .unwrap()
+    }
+
+    fn open_tasks(w: &World, session_id: Uuid, document_id: Uuid, categories: &[ReviewCategory]) {
+        let target = ReviewTarget::field(document_id, w.field_id("vendor"));
+        let actions: Vec<ReviewAction> = categories
+            .iter()
+            .map(|c| ReviewAction::new(session_id, *c, target, serde_json::json!({})))
+            .collect();
+        open_review_tasks(&w.b.review_tasks, &actions, ctx().now).unwrap();
+    }
+
+    #[test]
+    fn set_value_writes_one_value_per_field() {
+        let w = World::new(&[("vendor", "string", "document", true)]);
+        let session_id = w.session(SessionStatus::Review);
+        let (document_id, _) = w.document(session_id);
+        open_tasks(
+            &w,
+            session_id,
+            document_id,
+            &[
+                ReviewCategory::RequiredMissing,
+                ReviewCategory::LowConfidence,
+            ],
+        );
+
+        let outcome = batch(
+            &w,
+            session_id,
+            serde_json::json!({ "kind": "set_value", "raw_value": "Acme" }),
+        );
+
+        assert_eq!(
+            outcome.state_delta.data["result"]["resolved"]
+                .as_array()
+                .unwrap()
+                .len(),
+            2
+        );
+        let values = w.b.mapping.field_values(session_id).unwrap();
+        assert_eq!(values.len(), 1);
+        assert_eq!(values[0].raw_value, "Acme");
+    }
+
+    #[test]
+    fn apply_dictionary_keeps_the_raw_value() {
+        let w = World::new(&[("vendor", "string", "document", false)]);
+        let session_id = w.session(SessionStatus::Review);
+        let (document_id, page_id) = w.document(session_id);
+        w.b.mapping
+            .put_field_value(&FieldValue {
+                field_value_id: Uuid::now_v7(),
+                session_id,
+                document_id,
+                schema_field_id: w.field_id("vendor"),
+                raw_value: "ACME CORP".to_string(),
+                normalized_value: Some("ACME CORP".to_string()),
+                provenance: Provenance {
+                    page_id: Some(page_id),
+                    confidence: Some(0.4),
+                    ..Provenance::new(Origin::Anchor {
+                        anchor_id: Uuid::now_v7(),
+                        label_token_ids: Vec::new(),
+                    })
+                },
+                locked: false,
+            })
+            .unwrap();
+        AddDictionaryRuleHandler::new(&w.b.dictionary)
+            .handle(
+                &mut ctx(),
+                &command(serde_json::json!({
+                    "type": "AddDictionaryRule",
+                    "payload": {
+                        "project_id": w.project_id,
+                        "scope": "global",
+                        "match_type": "exact",
+                        "match_value": "ACME CORP",
+                        "replace_value": "Acme Corporation"
+                    }
+                })),
+            )
+            .unwrap();
+        open_tasks(
+            &w,
+            session_id,
+            document_id,
+            &[ReviewCategory::LowConfidence],
+        );
+
+        batch(
+            &w,
+            session_id,
+            serde_json::json!({ "kind": "apply_dictionary" }),
+        );
+
+        let value = w.b.mapping.field_values(session_id).unwrap().remove(0);
+        assert_eq!(value.raw_value, "ACME CORP");
+        assert_eq!(value.normalized_value.as_deref(), Some("Acme Corporation"));
+        assert_eq!(value.provenance.dictionary_rules.len(), 1);
+    }
+}
@@ -55,23 +55,40 @@ pub fn command_type(command: &AnyCommand) -> &'static str {
         AnyCommand::ReprocessDocument(_) => "ReprocessDocument",
         AnyCommand::RunExtraction(_) => "RunExtraction",
         AnyCommand::ReRunExtraction(_) => "ReRunExtraction",
+        AnyCommand::CompleteExtractionJob(_) => "CompleteExtractionJob",
+        AnyCommand::CancelExtractionJob(_) => "CancelExtractionJob",
         AnyCommand::AssignFieldValue(_) => "AssignFieldValue",
         AnyCommand::LockField(_) => "LockField",
         AnyCommand::AddItemRow(_) => "AddItemRow",
         AnyCommand::DeleteItemRow(_) => "DeleteItemRow",
+        AnyCommand::MoveItemRow(_) => "MoveItemRow",
         AnyCommand::AssignItemValue(_) => "AssignItemValue",
         AnyCommand::LockItemRow(_) => "LockItemRow",
+        AnyCommand::MapTableColumns(_) => "MapTableColumns",
         AnyCommand::AddExtraRow(_) => "AddExtraRow",
         AnyCommand::AssignExtraValue(_) => "AssignExtraValue",
+        AnyCommand::PromoteUnknownFragment(_) => "PromoteUnknownFragment",
         AnyCommand::AddAnchorRule(_) => "AddAnchorRule",
         AnyCommand::DisableAnchorRule(_) => "DisableAnchorRule",
         AnyCommand::AddDictionaryRule(_) => "AddDictionaryRule",
         AnyCommand::DisableDictionaryRule(_) => "DisableDictionaryRule",
+        AnyCommand::RegisterTemplate(_) => "RegisterTemplate",
+        AnyCommand::AddZone(_) => "AddZone",
+        AnyCommand::CreateProject(_) => "CreateProject",
+        AnyCommand::CreateSchema(_) => "CreateSchema",
+        AnyCommand::AddSchemaField(_) => "AddSchemaField",
+        AnyCommand::AddExtraTable(_) => "AddExtraTable",
+        AnyCommand::RenameSchemaField(_) => "RenameSchemaField",
+        AnyCommand::RetireSchemaField(_) => "RetireSchemaField",
+        AnyCommand::ChangeSchemaFieldType(_) => "ChangeSchemaFieldType",
         AnyCommand::ResolveReviewTask(_) => "ResolveReviewTask",
         AnyCommand::SkipReviewTask(_) => "SkipReviewTask",
         AnyCommand::BatchResolveField(_) => "BatchResolveField",
         AnyCommand::RunValidation(_) => "RunValidation",
+        AnyCommand::AddValidationRule(_) => "AddValidationRule",
         AnyCommand::OverrideValidation(_) => "OverrideValidation",
+        AnyCommand::ApproveValidationOverride(_) => "ApproveValidationOverride",
+        AnyCommand::ConfigureValidationOverrides(_) => "ConfigureValidationOverrides",
         AnyCommand::ExportSession(_) => "ExportSession",
     }
 }
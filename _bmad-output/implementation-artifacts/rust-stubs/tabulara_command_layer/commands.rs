@@ -1,10 +1,25 @@
+use std::collections::BTreeMap;
+
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
+use crate::anchors::AnchorRuleSpec;
+use crate::batch_resolve::{BatchResolveAction, BatchResolveFilter};
+use crate::extraction::{BoundingBox, ExtractionScope};
+use crate::item_rows::ItemRowDeletion;
+use crate::overrides::OverrideReasonCode;
+use crate::provenance::Provenance;
+use crate::review_tasks::ReviewResolution;
+use crate::schema::{ExtraTableSpec, SchemaFieldSpec};
+use crate::table_mapping::ColumnAssignment;
 use crate::types::{
-    DictionaryScope, ExportFormat, MatchType, SourceType, ValidationRuleScope,
+    DictionaryScope, ExportFormat, FieldType, MatchType, Severity, ValidationRuleScope,
 };
+use crate::unknown_bucket::PromotionTarget;
+use crate::validation::ValidationCheck;
+use crate::value_parsing::ValueLocale;
+use crate::zones::ZoneRegistration;
 
 pub trait CommandDto {
     fn command_id(&self) -> Uuid;
@@ -12,6 +27,13 @@ pub trait CommandDto {
     fn actor(&self) -> &str;
     fn timestamp(&self) -> DateTime<Utc>;
     fn session_id(&self) -> Option<Uuid>;
+    /// Session version the caller last saw. When set, the command is rejected with
+    /// `CONCURRENCY_CONFLICT` unless the session is still at that version.
+    fn expected_version(&self) -> Option<u64>;
+    /// Project a global command writes to; `None` for session commands and `CreateProject`.
+    fn project_id(&self) -> Option<Uuid> {
+        None
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -21,6 +43,8 @@ pub struct CommandEnvelope<TPayload> {
     pub command_type: String,
     pub actor: String,
     pub timestamp: DateTime<Utc>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expected_version: Option<u64>,
     pub payload: TPayload,
 }
 
@@ -32,6 +56,18 @@ macro_rules! impl_command_dto {
             fn actor(&self) -> &str { &self.actor }
             fn timestamp(&self) -> DateTime<Utc> { self.timestamp }
             fn session_id(&self) -> Option<Uuid> { $session_expr(self) }
+            fn expected_version(&self) -> Option<u64> { self.expected_version }
+        }
+    };
+    ($cmd:ident, $type_name:literal, $session_expr:expr, $project_expr:expr) => {
+        impl CommandDto for $cmd {
+            fn command_id(&self) -> Uuid { self.command_id }
+            fn command_type(&self) -> &'static str { $type_name }
+            fn actor(&self) -> &str { &self.actor }
+            fn timestamp(&self) -> DateTime<Utc> { self.timestamp }
+            fn session_id(&self) -> Option<Uuid> { $session_expr(self) }
+            fn expected_version(&self) -> Option<u64> { self.expected_version }
+            fn project_id(&self) -> Option<Uuid> { $project_expr(self) }
         }
     };
 }
@@ -41,6 +77,8 @@ pub struct CreateSession {
     pub command_id: Uuid,
     pub actor: String,
     pub timestamp: DateTime<Utc>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expected_version: Option<u64>,
     pub payload: CreateSessionPayload,
 }
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -48,14 +86,19 @@ pub struct CreateSessionPayload {
     pub project_id: Uuid,
     pub schema_id: Uuid,
     pub source: String,
+    /// Pins an older schema version; defaults to the latest.
+    #[serde(default)]
+    pub schema_version: Option<u32>,
 }
-impl_command_dto!(CreateSession, "CreateSession", |_| None);
+impl_command_dto!(CreateSession, "CreateSession", |_| None, |c: &CreateSession| Some(c.payload.project_id));
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CreateCorrectionSession {
     pub command_id: Uuid,
     pub actor: String,
     pub timestamp: DateTime<Utc>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expected_version: Option<u64>,
     pub payload: CreateCorrectionSessionPayload,
 }
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -64,13 +107,15 @@ pub struct CreateCorrectionSessionPayload {
     pub schema_id: Uuid,
     pub base_session_id: Uuid,
 }
-impl_command_dto!(CreateCorrectionSession, "CreateCorrectionSession", |_| None);
+impl_command_dto!(CreateCorrectionSession, "CreateCorrectionSession", |_| None, |c: &CreateCorrectionSession| Some(c.payload.project_id));
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct LockSession {
     pub command_id: Uuid,
     pub actor: String,
     pub timestamp: DateTime<Utc>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expected_version: Option<u64>,
     pub payload: LockSessionPayload,
 }
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -85,6 +130,8 @@ pub struct PinSession {
     pub command_id: Uuid,
     pub actor: String,
     pub timestamp: DateTime<Utc>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expected_version: Option<u64>,
     pub payload: PinSessionPayload,
 }
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -99,6 +146,8 @@ pub struct ImportDocument {
     pub command_id: Uuid,
     pub actor: String,
     pub timestamp: DateTime<Utc>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expected_version: Option<u64>,
     pub payload: ImportDocumentPayload,
 }
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -114,6 +163,8 @@ pub struct ConfirmDuplicate {
     pub command_id: Uuid,
     pub actor: String,
     pub timestamp: DateTime<Utc>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expected_version: Option<u64>,
     pub payload: ConfirmDuplicatePayload,
 }
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -129,6 +180,8 @@ pub struct ApplyPreprocessing {
     pub command_id: Uuid,
     pub actor: String,
     pub timestamp: DateTime<Utc>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expected_version: Option<u64>,
     pub payload: ApplyPreprocessingPayload,
 }
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -144,6 +197,8 @@ pub struct ReprocessDocument {
     pub command_id: Uuid,
     pub actor: String,
     pub timestamp: DateTime<Utc>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expected_version: Option<u64>,
     pub payload: ReprocessDocumentPayload,
 }
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -159,6 +214,8 @@ pub struct RunExtraction {
     pub command_id: Uuid,
     pub actor: String,
     pub timestamp: DateTime<Utc>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expected_version: Option<u64>,
     pub payload: RunExtractionPayload,
 }
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -174,22 +231,58 @@ pub struct ReRunExtraction {
     pub command_id: Uuid,
     pub actor: String,
     pub timestamp: DateTime<Utc>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expected_version: Option<u64>,
     pub payload: ReRunExtractionPayload,
 }
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ReRunExtractionPayload {
     pub session_id: Uuid,
-    pub scope: String,
-    pub target_id: Uuid,
+    pub scope: ExtractionScope,
     pub params: serde_json::Value,
 }
 impl_command_dto!(ReRunExtraction, "ReRunExtraction", |c: &ReRunExtraction| Some(c.payload.session_id));
 
+/// Internal: dispatched by the job runner once an extraction job has finished.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CompleteExtractionJob {
+    pub command_id: Uuid,
+    pub actor: String,
+    pub timestamp: DateTime<Utc>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expected_version: Option<u64>,
+    pub payload: CompleteExtractionJobPayload,
+}
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CompleteExtractionJobPayload {
+    pub session_id: Uuid,
+    pub job_id: Uuid,
+}
+impl_command_dto!(CompleteExtractionJob, "CompleteExtractionJob", |c: &CompleteExtractionJob| Some(c.payload.session_id));
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CancelExtractionJob {
+    pub command_id: Uuid,
+    pub actor: String,
+    pub timestamp: DateTime<Utc>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expected_version: Option<u64>,
+    pub payload: CancelExtractionJobPayload,
+}
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CancelExtractionJobPayload {
+    pub session_id: Uuid,
+    pub job_id: Uuid,
+}
+impl_command_dto!(CancelExtractionJob, "CancelExtractionJob", |c: &CancelExtractionJob| Some(c.payload.session_id));
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AssignFieldValue {
     pub command_id: Uuid,
     pub actor: String,
     pub timestamp: DateTime<Utc>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expected_version: Option<u64>,
     pub payload: AssignFieldValuePayload,
 }
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -198,9 +291,10 @@ pub struct AssignFieldValuePayload {
     pub document_id: Uuid,
     pub schema_field_id: Uuid,
     pub raw_value: String,
+    /// Ignored by the backend, which derives the normalized value from the field type.
+    #[serde(default)]
     pub normalized_value: Option<String>,
-    pub source: SourceType,
-    pub source_ref: serde_json::Value,
+    pub provenance: Provenance,
 }
 impl_command_dto!(AssignFieldValue, "AssignFieldValue", |c: &AssignFieldValue| Some(c.payload.session_id));
 
@@ -209,6 +303,8 @@ pub struct LockField {
     pub command_id: Uuid,
     pub actor: String,
     pub timestamp: DateTime<Utc>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expected_version: Option<u64>,
     pub payload: LockFieldPayload,
 }
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -224,13 +320,20 @@ pub struct AddItemRow {
     pub command_id: Uuid,
     pub actor: String,
     pub timestamp: DateTime<Utc>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expected_version: Option<u64>,
     pub payload: AddItemRowPayload,
 }
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AddItemRowPayload {
     pub session_id: Uuid,
     pub document_id: Uuid,
+    /// Signed, unlike the stored `ItemRow::row_index`, so that a negative position
+    /// reaches the handler and is rejected as `PRECONDITION_FAILED`.
     pub row_index: i32,
+    /// Client-assigned id so proposed `AssignItemValue` commands can reference the row.
+    #[serde(default)]
+    pub item_id: Option<Uuid>,
 }
 impl_command_dto!(AddItemRow, "AddItemRow", |c: &AddItemRow| Some(c.payload.session_id));
 
@@ -239,6 +342,8 @@ pub struct DeleteItemRow {
     pub command_id: Uuid,
     pub actor: String,
     pub timestamp: DateTime<Utc>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expected_version: Option<u64>,
     pub payload: DeleteItemRowPayload,
 }
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -248,11 +353,32 @@ pub struct DeleteItemRowPayload {
 }
 impl_command_dto!(DeleteItemRow, "DeleteItemRow", |c: &DeleteItemRow| Some(c.payload.session_id));
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MoveItemRow {
+    pub command_id: Uuid,
+    pub actor: String,
+    pub timestamp: DateTime<Utc>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expected_version: Option<u64>,
+    pub payload: MoveItemRowPayload,
+}
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MoveItemRowPayload {
+    pub session_id: Uuid,
+    pub item_id: Uuid,
+    /// Position the row takes; rows between the old and new position shift by one.
+    /// Signed like [`AddItemRowPayload::row_index`].
+    pub row_index: i32,
+}
+impl_command_dto!(MoveItemRow, "MoveItemRow", |c: &MoveItemRow| Some(c.payload.session_id));
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AssignItemValue {
     pub command_id: Uuid,
     pub actor: String,
     pub timestamp: DateTime<Utc>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expected_version: Option<u64>,
     pub payload: AssignItemValuePayload,
 }
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -261,9 +387,10 @@ pub struct AssignItemValuePayload {
     pub item_id: Uuid,
     pub schema_field_id: Uuid,
     pub raw_value: String,
+    /// Ignored by the backend, which derives the normalized value from the field type.
+    #[serde(default)]
     pub normalized_value: Option<String>,
-    pub source: SourceType,
-    pub source_ref: serde_json::Value,
+    pub provenance: Provenance,
 }
 impl_command_dto!(AssignItemValue, "AssignItemValue", |c: &AssignItemValue| Some(c.payload.session_id));
 
@@ -272,6 +399,8 @@ pub struct LockItemRow {
     pub command_id: Uuid,
     pub actor: String,
     pub timestamp: DateTime<Utc>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expected_version: Option<u64>,
     pub payload: LockItemRowPayload,
 }
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -282,11 +411,33 @@ pub struct LockItemRowPayload {
 }
 impl_command_dto!(LockItemRow, "LockItemRow", |c: &LockItemRow| Some(c.payload.session_id));
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MapTableColumns {
+    pub command_id: Uuid,
+    pub actor: String,
+    pub timestamp: DateTime<Utc>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expected_version: Option<u64>,
+    pub payload: MapTableColumnsPayload,
+}
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MapTableColumnsPayload {
+    pub session_id: Uuid,
+    pub document_id: Uuid,
+    pub table_id: Uuid,
+    /// Corrections to the header match; empty maps every column by its header.
+    #[serde(default)]
+    pub columns: Vec<ColumnAssignment>,
+}
+impl_command_dto!(MapTableColumns, "MapTableColumns", |c: &MapTableColumns| Some(c.payload.session_id));
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AddExtraRow {
     pub command_id: Uuid,
     pub actor: String,
     pub timestamp: DateTime<Utc>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expected_version: Option<u64>,
     pub payload: AddExtraRowPayload,
 }
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -303,6 +454,8 @@ pub struct AssignExtraValue {
     pub command_id: Uuid,
     pub actor: String,
     pub timestamp: DateTime<Utc>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expected_version: Option<u64>,
     pub payload: AssignExtraValuePayload,
 }
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -311,32 +464,60 @@ pub struct AssignExtraValuePayload {
     pub extra_row_id: Uuid,
     pub schema_field_id: Uuid,
     pub raw_value: String,
+    /// Ignored by the backend, which derives the normalized value from the field type.
+    #[serde(default)]
     pub normalized_value: Option<String>,
-    pub source: SourceType,
-    pub source_ref: serde_json::Value,
+    pub provenance: Provenance,
 }
 impl_command_dto!(AssignExtraValue, "AssignExtraValue", |c: &AssignExtraValue| Some(c.payload.session_id));
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PromoteUnknownFragment {
+    pub command_id: Uuid,
+    pub actor: String,
+    pub timestamp: DateTime<Utc>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expected_version: Option<u64>,
+    pub payload: PromoteUnknownFragmentPayload,
+}
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PromoteUnknownFragmentPayload {
+    pub session_id: Uuid,
+    pub fragment_id: Uuid,
+    pub target: PromotionTarget,
+    /// Promotes only these tokens of the fragment; empty promotes all unassigned ones.
+    #[serde(default)]
+    pub token_ids: Vec<Uuid>,
+}
+impl_command_dto!(PromoteUnknownFragment, "PromoteUnknownFragment", |c: &PromoteUnknownFragment| Some(c.payload.session_id));
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AddAnchorRule {
     pub command_id: Uuid,
     pub actor: String,
     pub timestamp: DateTime<Utc>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expected_version: Option<u64>,
     pub payload: AddAnchorRulePayload,
 }
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AddAnchorRulePayload {
     pub project_id: Uuid,
     pub schema_field_id: Uuid,
-    pub rule_json: serde_json::Value,
+    pub rule: AnchorRuleSpec,
+    /// Propose the rule's results for existing values in the project's open sessions.
+    #[serde(default)]
+    pub retroactive: bool,
 }
-impl_command_dto!(AddAnchorRule, "AddAnchorRule", |_| None);
+impl_command_dto!(AddAnchorRule, "AddAnchorRule", |_| None, |c: &AddAnchorRule| Some(c.payload.project_id));
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DisableAnchorRule {
     pub command_id: Uuid,
     pub actor: String,
     pub timestamp: DateTime<Utc>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expected_version: Option<u64>,
     pub payload: DisableAnchorRulePayload,
 }
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -345,30 +526,40 @@ pub struct DisableAnchorRulePayload {
     pub anchor_id: Uuid,
     pub enabled: bool,
 }
-impl_command_dto!(DisableAnchorRule, "DisableAnchorRule", |_| None);
+impl_command_dto!(DisableAnchorRule, "DisableAnchorRule", |_| None, |c: &DisableAnchorRule| Some(c.payload.project_id));
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AddDictionaryRule {
     pub command_id: Uuid,
     pub actor: String,
     pub timestamp: DateTime<Utc>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expected_version: Option<u64>,
     pub payload: AddDictionaryRulePayload,
 }
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AddDictionaryRulePayload {
     pub project_id: Uuid,
     pub scope: DictionaryScope,
+    /// Field key, vendor or dictionary name for scoped rules; omitted for `global`.
+    #[serde(default)]
+    pub scope_key: Option<String>,
     pub match_type: MatchType,
     pub match_value: String,
     pub replace_value: String,
+    /// Propose the rule's results for existing values in the project's open sessions.
+    #[serde(default)]
+    pub retroactive: bool,
 }
-impl_command_dto!(AddDictionaryRule, "AddDictionaryRule", |_| None);
+impl_command_dto!(AddDictionaryRule, "AddDictionaryRule", |_| None, |c: &AddDictionaryRule| Some(c.payload.project_id));
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DisableDictionaryRule {
     pub command_id: Uuid,
     pub actor: String,
     pub timestamp: DateTime<Utc>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expected_version: Option<u64>,
     pub payload: DisableDictionaryRulePayload,
 }
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -377,20 +568,202 @@ pub struct DisableDictionaryRulePayload {
     pub dictionary_rule_id: Uuid,
     pub enabled: bool,
 }
-impl_command_dto!(DisableDictionaryRule, "DisableDictionaryRule", |_| None);
+impl_command_dto!(DisableDictionaryRule, "DisableDictionaryRule", |_| None, |c: &DisableDictionaryRule| Some(c.payload.project_id));
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RegisterTemplate {
+    pub command_id: Uuid,
+    pub actor: String,
+    pub timestamp: DateTime<Utc>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expected_version: Option<u64>,
+    pub payload: RegisterTemplatePayload,
+}
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RegisterTemplatePayload {
+    pub project_id: Uuid,
+    /// Sample document whose first page is fingerprinted.
+    pub session_id: Uuid,
+    pub document_id: Uuid,
+    pub name: String,
+    #[serde(default)]
+    pub vendor: Option<String>,
+    #[serde(default)]
+    pub anchor_ids: Vec<Uuid>,
+    #[serde(default)]
+    pub dictionary_rule_ids: Vec<Uuid>,
+}
+impl_command_dto!(RegisterTemplate, "RegisterTemplate", |_| None, |c: &RegisterTemplate| Some(c.payload.project_id));
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AddZone {
+    pub command_id: Uuid,
+    pub actor: String,
+    pub timestamp: DateTime<Utc>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expected_version: Option<u64>,
+    pub payload: AddZonePayload,
+}
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AddZonePayload {
+    pub project_id: Uuid,
+    pub template_id: Uuid,
+    pub schema_field_id: Uuid,
+    pub page_number: u32,
+    pub rect: BoundingBox,
+    #[serde(default)]
+    pub registration: Option<ZoneRegistration>,
+}
+impl_command_dto!(AddZone, "AddZone", |_| None, |c: &AddZone| Some(c.payload.project_id));
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CreateProject {
+    pub command_id: Uuid,
+    pub actor: String,
+    pub timestamp: DateTime<Utc>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expected_version: Option<u64>,
+    pub payload: CreateProjectPayload,
+}
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CreateProjectPayload {
+    pub name: String,
+    #[serde(default)]
+    pub locale: ValueLocale,
+    #[serde(default)]
+    pub item_row_deletion: ItemRowDeletion,
+}
+impl_command_dto!(CreateProject, "CreateProject", |_| None);
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CreateSchema {
+    pub command_id: Uuid,
+    pub actor: String,
+    pub timestamp: DateTime<Utc>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expected_version: Option<u64>,
+    pub payload: CreateSchemaPayload,
+}
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CreateSchemaPayload {
+    pub project_id: Uuid,
+    pub name: String,
+    pub fields: Vec<SchemaFieldSpec>,
+    #[serde(default)]
+    pub extra_tables: Vec<ExtraTableSpec>,
+}
+impl_command_dto!(CreateSchema, "CreateSchema", |_| None, |c: &CreateSchema| Some(c.payload.project_id));
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AddSchemaField {
+    pub command_id: Uuid,
+    pub actor: String,
+    pub timestamp: DateTime<Utc>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expected_version: Option<u64>,
+    pub payload: AddSchemaFieldPayload,
+}
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AddSchemaFieldPayload {
+    pub project_id: Uuid,
+    pub schema_id: Uuid,
+    pub field: SchemaFieldSpec,
+}
+impl_command_dto!(AddSchemaField, "AddSchemaField", |_| None, |c: &AddSchemaField| Some(c.payload.project_id));
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AddExtraTable {
+    pub command_id: Uuid,
+    pub actor: String,
+    pub timestamp: DateTime<Utc>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expected_version: Option<u64>,
+    pub payload: AddExtraTablePayload,
+}
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AddExtraTablePayload {
+    pub project_id: Uuid,
+    pub schema_id: Uuid,
+    pub table: ExtraTableSpec,
+}
+impl_command_dto!(AddExtraTable, "AddExtraTable", |_| None, |c: &AddExtraTable| Some(c.payload.project_id));
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RenameSchemaField {
+    pub command_id: Uuid,
+    pub actor: String,
+    pub timestamp: DateTime<Utc>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expected_version: Option<u64>,
+    pub payload: RenameSchemaFieldPayload,
+}
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RenameSchemaFieldPayload {
+    pub project_id: Uuid,
+    pub schema_id: Uuid,
+    pub schema_field_id: Uuid,
+    pub label: String,
+    /// Also changes the field key when set.
+    #[serde(default)]
+    pub field_key: Option<String>,
+}
+impl_command_dto!(RenameSchemaField, "RenameSchemaField", |_| None, |c: &RenameSchemaField| Some(c.payload.project_id));
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RetireSchemaField {
+    pub command_id: Uuid,
+    pub actor: String,
+    pub timestamp: DateTime<Utc>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expected_version: Option<u64>,
+    pub payload: RetireSchemaFieldPayload,
+}
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RetireSchemaFieldPayload {
+    pub project_id: Uuid,
+    pub schema_id: Uuid,
+    pub schema_field_id: Uuid,
+}
+impl_command_dto!(RetireSchemaField, "RetireSchemaField", |_| None, |c: &RetireSchemaField| Some(c.payload.project_id));
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChangeSchemaFieldType {
+    pub command_id: Uuid,
+    pub actor: String,
+    pub timestamp: DateTime<Utc>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expected_version: Option<u64>,
+    pub payload: ChangeSchemaFieldTypePayload,
+}
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChangeSchemaFieldTypePayload {
+    pub project_id: Uuid,
+    pub schema_id: Uuid,
+    pub schema_field_id: Uuid,
+    pub field_type: FieldType,
+    #[serde(default)]
+    pub enum_values: Vec<String>,
+    #[serde(default)]
+    pub enum_synonyms: BTreeMap<String, String>,
+    #[serde(default)]
+    pub required: Option<bool>,
+}
+impl_command_dto!(ChangeSchemaFieldType, "ChangeSchemaFieldType", |_| None, |c: &ChangeSchemaFieldType| Some(c.payload.project_id));
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ResolveReviewTask {
     pub command_id: Uuid,
     pub actor: String,
     pub timestamp: DateTime<Utc>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expected_version: Option<u64>,
     pub payload: ResolveReviewTaskPayload,
 }
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ResolveReviewTaskPayload {
     pub session_id: Uuid,
     pub review_task_id: Uuid,
-    pub resolution: String,
+    pub resolution: ReviewResolution,
 }
 impl_command_dto!(ResolveReviewTask, "ResolveReviewTask", |c: &ResolveReviewTask| Some(c.payload.session_id));
 
@@ -399,6 +772,8 @@ pub struct SkipReviewTask {
     pub command_id: Uuid,
     pub actor: String,
     pub timestamp: DateTime<Utc>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expected_version: Option<u64>,
     pub payload: SkipReviewTaskPayload,
 }
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -414,13 +789,17 @@ pub struct BatchResolveField {
     pub command_id: Uuid,
     pub actor: String,
     pub timestamp: DateTime<Utc>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expected_version: Option<u64>,
     pub payload: BatchResolveFieldPayload,
 }
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct BatchResolveFieldPayload {
     pub session_id: Uuid,
     pub field_key: String,
-    pub action: String,
+    pub action: BatchResolveAction,
+    #[serde(default)]
+    pub filter: BatchResolveFilter,
 }
 impl_command_dto!(BatchResolveField, "BatchResolveField", |c: &BatchResolveField| Some(c.payload.session_id));
 
@@ -429,6 +808,8 @@ pub struct RunValidation {
     pub command_id: Uuid,
     pub actor: String,
     pub timestamp: DateTime<Utc>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expected_version: Option<u64>,
     pub payload: RunValidationPayload,
 }
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -438,26 +819,84 @@ pub struct RunValidationPayload {
 }
 impl_command_dto!(RunValidation, "RunValidation", |c: &RunValidation| Some(c.payload.session_id));
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AddValidationRule {
+    pub command_id: Uuid,
+    pub actor: String,
+    pub timestamp: DateTime<Utc>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expected_version: Option<u64>,
+    pub payload: AddValidationRulePayload,
+}
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AddValidationRulePayload {
+    pub project_id: Uuid,
+    pub name: String,
+    pub check: ValidationCheck,
+    pub severity: Severity,
+}
+impl_command_dto!(AddValidationRule, "AddValidationRule", |_| None, |c: &AddValidationRule| Some(c.payload.project_id));
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct OverrideValidation {
     pub command_id: Uuid,
     pub actor: String,
     pub timestamp: DateTime<Utc>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expected_version: Option<u64>,
     pub payload: OverrideValidationPayload,
 }
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct OverrideValidationPayload {
     pub session_id: Uuid,
     pub validation_result_id: Uuid,
+    /// One of the project's configured reason codes.
+    pub reason_code: String,
     pub reason: String,
 }
 impl_command_dto!(OverrideValidation, "OverrideValidation", |c: &OverrideValidation| Some(c.payload.session_id));
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ApproveValidationOverride {
+    pub command_id: Uuid,
+    pub actor: String,
+    pub timestamp: DateTime<Utc>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expected_version: Option<u64>,
+    pub payload: ApproveValidationOverridePayload,
+}
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ApproveValidationOverridePayload {
+    pub session_id: Uuid,
+    pub override_id: Uuid,
+}
+impl_command_dto!(ApproveValidationOverride, "ApproveValidationOverride", |c: &ApproveValidationOverride| Some(c.payload.session_id));
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConfigureValidationOverrides {
+    pub command_id: Uuid,
+    pub actor: String,
+    pub timestamp: DateTime<Utc>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expected_version: Option<u64>,
+    pub payload: ConfigureValidationOverridesPayload,
+}
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConfigureValidationOverridesPayload {
+    pub project_id: Uuid,
+    pub reason_codes: Vec<OverrideReasonCode>,
+    #[serde(default)]
+    pub blocking_requires_approval: bool,
+}
+impl_command_dto!(ConfigureValidationOverrides, "ConfigureValidationOverrides", |_| None, |c: &ConfigureValidationOverrides| Some(c.payload.project_id));
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ExportSession {
     pub command_id: Uuid,
     pub actor: String,
     pub timestamp: DateTime<Utc>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expected_version: Option<u64>,
     pub payload: ExportSessionPayload,
 }
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -466,6 +905,9 @@ pub struct ExportSessionPayload {
     pub format: ExportFormat,
     pub include_in_vault: bool,
     pub export_path: Option<String>,
+    /// Adds the `unknown_text` table of text no value consumed.
+    #[serde(default)]
+    pub include_unknown_bucket: bool,
 }
 impl_command_dto!(ExportSession, "ExportSession", |c: &ExportSession| Some(c.payload.session_id));
 
@@ -482,22 +924,39 @@ pub enum AnyCommand {
     ReprocessDocument(ReprocessDocument),
     RunExtraction(RunExtraction),
     ReRunExtraction(ReRunExtraction),
+    CompleteExtractionJob(CompleteExtractionJob),
+    CancelExtractionJob(CancelExtractionJob),
     AssignFieldValue(AssignFieldValue),
     LockField(LockField),
     AddItemRow(AddItemRow),
     DeleteItemRow(DeleteItemRow),
+    MoveItemRow(MoveItemRow),
     AssignItemValue(AssignItemValue),
     LockItemRow(LockItemRow),
+    MapTableColumns(MapTableColumns),
     AddExtraRow(AddExtraRow),
     AssignExtraValue(AssignExtraValue),
+    PromoteUnknownFragment(PromoteUnknownFragment),
     AddAnchorRule(AddAnchorRule),
     DisableAnchorRule(DisableAnchorRule),
     AddDictionaryRule(AddDictionaryRule),
     DisableDictionaryRule(DisableDictionaryRule),
+    RegisterTemplate(RegisterTemplate),
+    AddZone(AddZone),
+    CreateProject(CreateProject),
+    CreateSchema(CreateSchema),
+    AddSchemaField(AddSchemaField),
+    AddExtraTable(AddExtraTable),
+    RenameSchemaField(RenameSchemaField),
+    RetireSchemaField(RetireSchemaField),
+    ChangeSchemaFieldType(ChangeSchemaFieldType),
     ResolveReviewTask(ResolveReviewTask),
     SkipReviewTask(SkipReviewTask),
     BatchResolveField(BatchResolveField),
     RunValidation(RunValidation),
+    AddValidationRule(AddValidationRule),
     OverrideValidation(OverrideValidation),
+    ApproveValidationOverride(ApproveValidationOverride),
+    ConfigureValidationOverrides(ConfigureValidationOverrides),
     ExportSession(ExportSession),
 }
@@ -0,0 +1,455 @@
+use std::collections::HashSet;
+
+use regex::{Regex, RegexBuilder};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::commands::{AddDictionaryRule, AnyCommand, DisableDictionaryRule};
+use crate::errors::{unsupported_command, DomainError, DomainResult, ErrorCode};
+use crate::interfaces::{
+    CommandContext, CommandOutcome, DictionaryStore, GenericCommandHandler,
+    NormalizationContextResolver, StateDelta, ValidationTrigger,
+};
+use crate::provenance::Provenance;
+use crate::retroactive::{reapplier_missing, LearningRule, ReapplyPlan, RetroactiveReapplier};
+use crate::types::{DictionaryScope, MatchType};
+
+/// `dictionary_rules` row.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DictionaryRule {
+    pub dictionary_rule_id: Uuid,
+    pub project_id: Uuid,
+    pub scope: DictionaryScope,
+    /// Field key, vendor or dictionary name the rule is limited to; `None` for `Global`.
+    pub scope_key: Option<String>,
+    pub match_type: MatchType,
+    pub match_value: String,
+    pub replace_value: String,
+    pub enabled: bool,
+    /// Actor that added the rule.
+    pub created_by: String,
+}
+
+/// Higher wins: `Name > Vendor > FieldKey > Global`.
+pub fn scope_precedence(scope: DictionaryScope) -> u8 {
+    match scope {
+        DictionaryScope::Name => 3,
+        DictionaryScope::Vendor => 2,
+        DictionaryScope::FieldKey => 1,
+        DictionaryScope::Global => 0,
+    }
+}
+
+/// What a value is being normalized for; decides which scoped rules apply.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct NormalizationContext {
+    pub field_key: Option<String>,
+    pub vendor: Option<String>,
+    pub dictionary_name: Option<String>,
+}
+
+impl NormalizationContext {
+    fn matches(&self, rule: &DictionaryRule) -> bool {
+        let key = |candidate: &Option<String>| match (candidate, &rule.scope_key) {
+            (Some(value), Some(scope_key)) => value.trim().eq_ignore_ascii_case(scope_key.trim()),
+            _ => false,
+        };
+        match rule.scope {
+            DictionaryScope::Global => true,
+            DictionaryScope::FieldKey => key(&self.field_key),
+            DictionaryScope::Vendor => key(&self.vendor),
+            DictionaryScope::Name => key(&self.dictionary_name),
+        }
+    }
+}
+
+/// One rule firing, recorded in the value's provenance.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct DictionaryTraceStep {
+    pub dictionary_rule_id: Uuid,
+    pub scope: DictionaryScope,
+    pub before: String,
+    pub after: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DictionaryOutcome {
+    pub value: String,
+    pub trace: Vec<DictionaryTraceStep>,
+    /// Rules rewrote the value back to an earlier form; `value` is the last distinct result.
+    pub loop_detected: bool,
+}
+
+impl DictionaryOutcome {
+    pub fn changed(&self) -> bool {
+        !self.trace.is_empty()
+    }
+
+    /// Records the fired rules in the value's provenance.
+    pub fn record_in(&self, provenance: &mut Provenance) {
+        provenance.dictionary_rules = self.trace.clone();
+    }
+}
+
+pub struct CompiledDictionaryRule {
+    pub rule: DictionaryRule,
+    regex: Option<Regex>,
+}
+
+impl CompiledDictionaryRule {
+    pub fn compile(rule: DictionaryRule) -> DomainResult<Self> {
+        let invalid = |message: &str| DomainError {
+            code: ErrorCode::PreconditionFailed,
+            message: message.to_string(),
+            details: Some(serde_json::json!({ "rule": rule })),
+        };
+
+        if rule.match_value.trim().is_empty() {
+            return Err(invalid("Dictionary match_value must not be empty"));
+        }
+        match (rule.scope, &rule.scope_key) {
+            (DictionaryScope::Global, Some(_)) => {
+                return Err(invalid("Global dictionary rules must not set scope_key"))
+            }
+            (DictionaryScope::Global, None) => {}
+            (_, Some(key)) if !key.trim().is_empty() => {}
+            _ => return Err(invalid("Scoped dictionary rules require scope_key")),
+        }
+
+        let regex = match rule.match_type {
+            MatchType::Exact => None,
+            MatchType::Regex => Some(
+                RegexBuilder::new(&rule.match_value)
+                    .size_limit(1 << 20)
+                    .build()
+                    .map_err(|e| DomainError {
+                        code: ErrorCode::PreconditionFailed,
+                        message: "Dictionary regex failed to compile".to_string(),
+                        details: Some(serde_json::json!({
+                            "pattern": rule.match_value,
+                            "error": e.to_string(),
+                        })),
+                    })?,
+            ),
+        };
+        Ok(Self { rule, regex })
+    }
+
+    /// Rewritten value when the rule matches, otherwise `None`.
+    fn apply(&self, value: &str) -> Option<String> {
+        match &self.regex {
+            Some(regex) if regex.is_match(value) => {
+                Some(regex.replace_all(value, self.rule.replace_value.as_str()).into_owned())
+            }
+            Some(_) => None,
+            None if value.trim().eq_ignore_ascii_case(self.rule.match_value.trim()) => {
+                Some(self.rule.replace_value.clone())
+            }
+            None => None,
+        }
+    }
+}
+
+/// Applies dictionary rules with deterministic precedence.
+///
+/// Each pass fires the highest-precedence applicable rule (oldest first within a scope)
+/// that changes the value; passes repeat until no rule fires, so rules may chain.
+pub struct DictionaryEngine {
+    rules: Vec<CompiledDictionaryRule>,
+}
+
+impl DictionaryEngine {
+    /// Compiles the enabled rules; disabled rules never fire.
+    pub fn new(rules: Vec<DictionaryRule>) -> DomainResult<Self> {
+        let mut compiled = rules
+            .into_iter()
+            .filter(|r| r.enabled)
+            .map(CompiledDictionaryRule::compile)
+            .collect::<DomainResult<Vec<_>>>()?;
+        compiled.sort_by(|a, b| {
+            scope_precedence(b.rule.scope)
+                .cmp(&scope_precedence(a.rule.scope))
+                .then(a.rule.dictionary_rule_id.cmp(&b.rule.dictionary_rule_id))
+        });
+        Ok(Self { rules: compiled })
+    }
+
+    pub fn apply(&self, raw_value: &str, ctx: &NormalizationContext) -> DictionaryOutcome {
+        let applicable: Vec<&CompiledDictionaryRule> =
+            self.rules.iter().filter(|r| ctx.matches(&r.rule)).collect();
+
+        let mut value = raw_value.to_string();
+        let mut seen = HashSet::from([value.clone()]);
+        let mut trace = Vec::new();
+
+        // Every pass changes the value to one not seen before, so the number of passes
+        // is bounded by the number of distinct values the rules can produce.
+        for _ in 0..=applicable.len() {
+            let fired = applicable.iter().find_map(|rule| {
+                rule.apply(&value)
+                    .filter(|next| *next != value)
+                    .map(|next| (rule, next))
+            });
+            let Some((rule, next)) = fired else {
+                return DictionaryOutcome {
+                    value,
+                    trace,
+                    loop_detected: false,
+                };
+            };
+
+            if !seen.insert(next.clone()) {
+                return DictionaryOutcome {
+                    value,
+                    trace,
+                    loop_detected: true,
+                };
+            }
+            trace.push(DictionaryTraceStep {
+                dictionary_rule_id: rule.rule.dictionary_rule_id,
+                scope: rule.rule.scope,
+                before: value.clone(),
+                after: next.clone(),
+            });
+            value = next;
+        }
+
+        DictionaryOutcome {
+            value,
+            trace,
+            loop_detected: true,
+        }
+    }
+}
+
+/// Rejects a new rule that duplicates or contradicts an enabled rule with the same
+/// scope and match, or that would make normalization cycle.
+pub fn check_new_rule(candidate: &DictionaryRule, existing: &[DictionaryRule]) -> DomainResult<()> {
+    CompiledDictionaryRule::compile(candidate.clone())?;
+
+    let same_scope_key = |a: &Option<String>, b: &Option<String>| match (a, b) {
+        (Some(a), Some(b)) => a.trim().eq_ignore_ascii_case(b.trim()),
+        (None, None) => true,
+        _ => false,
+    };
+    if let Some(clash) = existing.iter().find(|r| {
+        r.enabled
+            && r.scope == candidate.scope
+            && same_scope_key(&r.scope_key, &candidate.scope_key)
+            && r.match_type == candidate.match_type
+            && r.match_value.trim() == candidate.match_value.trim()
+    }) {
+        let message = if clash.replace_value == candidate.replace_value {
+            "Dictionary rule duplicates an existing rule"
+        } else {
+            "Dictionary rule conflicts with an existing rule"
+        };
+        return Err(DomainError {
+            code: ErrorCode::PreconditionFailed,
+            message: message.to_string(),
+            details: Some(serde_json::json!({
+                "dictionary_rule_id": clash.dictionary_rule_id,
+                "existing_replace_value": clash.replace_value,
+                "replace_value": candidate.replace_value,
+            })),
+        });
+    }
+
+    let mut rules: Vec<DictionaryRule> = existing.to_vec();
+    rules.push(candidate.clone());
+    let engine = DictionaryEngine::new(rules)?;
+    let ctx = NormalizationContext {
+        field_key: candidate.scope_key.clone().filter(|_| candidate.scope == DictionaryScope::FieldKey),
+        vendor: candidate.scope_key.clone().filter(|_| candidate.scope == DictionaryScope::Vendor),
+        dictionary_name: candidate.scope_key.clone().filter(|_| candidate.scope == DictionaryScope::Name),
+    };
+    for probe in [&candidate.match_value, &candidate.replace_value] {
+        let outcome = engine.apply(probe, &ctx);
+        if outcome.loop_detected {
+            return Err(DomainError {
+                code: ErrorCode::PreconditionFailed,
+                message: "Dictionary rule would create a normalization loop".to_string(),
+                details: Some(serde_json::json!({
+                    "probe": probe,
+                    "trace": outcome.trace,
+                })),
+            });
+        }
+    }
+    Ok(())
+}
+
+/// Normalizes assigned values against a project's stored dictionary rules, scoped by
+/// the context of the value's session, document and field.
+#[derive(Clone, Copy)]
+pub struct DictionaryNormalizer<'a> {
+    dictionary: &'a dyn DictionaryStore,
+    contexts: &'a dyn NormalizationContextResolver,
+}
+
+impl<'a> DictionaryNormalizer<'a> {
+    pub fn new(
+        dictionary: &'a dyn DictionaryStore,
+        contexts: &'a dyn NormalizationContextResolver,
+    ) -> Self {
+        Self {
+            dictionary,
+            contexts,
+        }
+    }
+
+    pub fn normalize(
+        &self,
+        project_id: Uuid,
+        session_id: Uuid,
+        document_id: Uuid,
+        schema_field_id: Uuid,
+        raw_value: &str,
+    ) -> DomainResult<DictionaryOutcome> {
+        let ctx = self
+            .contexts
+            .context_for(session_id, document_id, schema_field_id)?;
+        let engine = DictionaryEngine::new(self.dictionary.rules(project_id)?)?;
+        Ok(engine.apply(raw_value, &ctx))
+    }
+}
+
+pub struct AddDictionaryRuleHandler<'a> {
+    dictionary: &'a dyn DictionaryStore,
+    reapplier: Option<&'a RetroactiveReapplier<'a>>,
+}
+
+impl<'a> AddDictionaryRuleHandler<'a> {
+    pub fn new(dictionary: &'a dyn DictionaryStore) -> Self {
+        Self {
+            dictionary,
+            reapplier: None,
+        }
+    }
+
+    /// Enables `retroactive: true` on the command.
+    pub fn with_reapplier(mut self, reapplier: &'a RetroactiveReapplier<'a>) -> Self {
+        self.reapplier = Some(reapplier);
+        self
+    }
+
+    fn add(&self, cmd: &AddDictionaryRule) -> DomainResult<CommandOutcome> {
+        let rule = DictionaryRule {
+            dictionary_rule_id: Uuid::now_v7(),
+            project_id: cmd.payload.project_id,
+            scope: cmd.payload.scope,
+            scope_key: cmd.payload.scope_key.clone(),
+            match_type: cmd.payload.match_type,
+            match_value: cmd.payload.match_value.clone(),
+            replace_value: cmd.payload.replace_value.clone(),
+            enabled: true,
+            created_by: cmd.actor.clone(),
+        };
+        check_new_rule(&rule, &self.dictionary.rules(cmd.payload.project_id)?)?;
+        let reapplier = match (cmd.payload.retroactive, self.reapplier) {
+            (false, _) => None,
+            (true, Some(reapplier)) => Some(reapplier),
+            (true, None) => return Err(reapplier_missing("AddDictionaryRule")),
+        };
+        self.dictionary.add(&rule)?;
+
+        let learned = LearningRule::Dictionary(rule.clone());
+        let plan = match reapplier {
+            Some(reapplier) => reapplier.plan(&learned)?,
+            None => ReapplyPlan::default(),
+        };
+
+        Ok(CommandOutcome {
+            state_delta: StateDelta {
+                summary: "Dictionary rule learned".to_string(),
+                data: serde_json::json!({
+                    "dictionary_rule": rule,
+                    "retroactive": reapplier.map(|_| plan.preview()),
+                }),
+            },
+            transition: None,
+            review_actions: plan.review_actions(&learned),
+            validation_trigger: ValidationTrigger::None,
+            events: plan.events(&learned),
+        })
+    }
+}
+
+impl<'a> GenericCommandHandler for AddDictionaryRuleHandler<'a> {
+    fn can_handle(&self, command_type: &str) -> bool {
+        command_type == "AddDictionaryRule"
+    }
+
+    fn handle(&self, _ctx: &mut CommandContext, cmd: &AnyCommand) -> DomainResult<CommandOutcome> {
+        match cmd {
+            AnyCommand::AddDictionaryRule(c) => self.add(c),
+            _ => Err(unsupported_command("AddDictionaryRuleHandler")),
+        }
+    }
+}
+
+pub struct DisableDictionaryRuleHandler<'a> {
+    dictionary: &'a dyn DictionaryStore,
+}
+
+impl<'a> DisableDictionaryRuleHandler<'a> {
+    pub fn new(dictionary: &'a dyn DictionaryStore) -> Self {
+        Self { dictionary }
+    }
+
+    fn set_enabled(&self, cmd: &DisableDictionaryRule) -> DomainResult<CommandOutcome> {
+        let rule = self.dictionary.get(cmd.payload.dictionary_rule_id)?;
+        if rule.project_id != cmd.payload.project_id {
+            return Err(DomainError {
+                code: ErrorCode::PreconditionFailed,
+                message: "Dictionary rule does not belong to project".to_string(),
+                details: Some(serde_json::json!({
+                    "dictionary_rule_id": cmd.payload.dictionary_rule_id,
+                    "project_id": cmd.payload.project_id,
+                })),
+            });
+        }
+        if cmd.payload.enabled && !rule.enabled {
+            let others: Vec<DictionaryRule> = self
+                .dictionary
+                .rules(cmd.payload.project_id)?
+                .into_iter()
+                .filter(|r| r.dictionary_rule_id != rule.dictionary_rule_id)
+                .collect();
+            check_new_rule(&rule, &others)?;
+        }
+        self.dictionary
+            .set_enabled(cmd.payload.dictionary_rule_id, cmd.payload.enabled)?;
+
+        Ok(CommandOutcome {
+            state_delta: StateDelta {
+                summary: if cmd.payload.enabled {
+                    "Dictionary rule enabled".to_string()
+                } else {
+                    "Dictionary rule disabled".to_string()
+                },
+                data: serde_json::json!({
+                    "dictionary_rule_id": cmd.payload.dictionary_rule_id,
+                    "enabled": cmd.payload.enabled,
+                }),
+            },
+            transition: None,
+            review_actions: Vec::new(),
+            validation_trigger: ValidationTrigger::None,
+            events: Vec::new(),
+        })
+    }
+}
+
+impl<'a> GenericCommandHandler for DisableDictionaryRuleHandler<'a> {
+    fn can_handle(&self, command_type: &str) -> bool {
+        command_type == "DisableDictionaryRule"
+    }
+
+    fn handle(&self, _ctx: &mut CommandContext, cmd: &AnyCommand) -> DomainResult<CommandOutcome> {
+        match cmd {
+            AnyCommand::DisableDictionaryRule(c) => self.set_enabled(c),
+            _ => Err(unsupported_command("DisableDictionaryRuleHandler")),
+        }
+    }
+}
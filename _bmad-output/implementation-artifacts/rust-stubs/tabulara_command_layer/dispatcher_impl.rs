@@ -9,7 +9,7 @@ use crate::commands::{AnyCommand, CommandDto};
 use crate::errors::{DomainError, DomainResult, ErrorCode};
 use crate::interfaces::{
     CommandContext, CommandDispatcher, DispatcherDeps, GenericCommandHandler, IdempotencyState,
-    UnitOfWork,
+    UnitOfWork, WriteScope,
 };
 use crate::types::{DispatchResult, SessionStatus};
 
@@ -34,23 +34,40 @@ impl<'a, U: UnitOfWork> DefaultCommandDispatcher<'a, U> {
             AnyCommand::ReprocessDocument(c) => c,
             AnyCommand::RunExtraction(c) => c,
             AnyCommand::ReRunExtraction(c) => c,
+            AnyCommand::CompleteExtractionJob(c) => c,
+            AnyCommand::CancelExtractionJob(c) => c,
             AnyCommand::AssignFieldValue(c) => c,
             AnyCommand::LockField(c) => c,
             AnyCommand::AddItemRow(c) => c,
             AnyCommand::DeleteItemRow(c) => c,
+            AnyCommand::MoveItemRow(c) => c,
             AnyCommand::AssignItemValue(c) => c,
             AnyCommand::LockItemRow(c) => c,
+            AnyCommand::MapTableColumns(c) => c,
             AnyCommand::AddExtraRow(c) => c,
             AnyCommand::AssignExtraValue(c) => c,
+            AnyCommand::PromoteUnknownFragment(c) => c,
             AnyCommand::AddAnchorRule(c) => c,
             AnyCommand::DisableAnchorRule(c) => c,
             AnyCommand::AddDictionaryRule(c) => c,
             AnyCommand::DisableDictionaryRule(c) => c,
+            AnyCommand::RegisterTemplate(c) => c,
+            AnyCommand::AddZone(c) => c,
+            AnyCommand::CreateProject(c) => c,
+            AnyCommand::CreateSchema(c) => c,
+            AnyCommand::AddSchemaField(c) => c,
+            AnyCommand::AddExtraTable(c) => c,
+            AnyCommand::RenameSchemaField(c) => c,
+            AnyCommand::RetireSchemaField(c) => c,
+            AnyCommand::ChangeSchemaFieldType(c) => c,
             AnyCommand::ResolveReviewTask(c) => c,
             AnyCommand::SkipReviewTask(c) => c,
             AnyCommand::BatchResolveField(c) => c,
             AnyCommand::RunValidation(c) => c,
+            AnyCommand::AddValidationRule(c) => c,
             AnyCommand::OverrideValidation(c) => c,
+            AnyCommand::ApproveValidationOverride(c) => c,
+            AnyCommand::ConfigureValidationOverrides(c) => c,
             AnyCommand::ExportSession(c) => c,
         }
     }
@@ -63,6 +80,45 @@ impl<'a, U: UnitOfWork> DefaultCommandDispatcher<'a, U> {
             .find(|handler| handler.can_handle(command_type))
     }
 
+    fn write_scope(&self, dto: &dyn CommandDto) -> DomainResult<WriteScope> {
+        Ok(match (dto.session_id(), dto.project_id()) {
+            (Some(session_id), _) => WriteScope::Session {
+                project_id: self.deps.sessions.project_of(session_id)?,
+                session_id,
+            },
+            (None, Some(project_id)) => WriteScope::Project { project_id },
+            (None, None) => WriteScope::Global,
+        })
+    }
+
+    /// Rejects a command issued against a session version other than the current one.
+    fn check_expected_version(&self, dto: &dyn CommandDto) -> DomainResult<()> {
+        let Some(expected) = dto.expected_version() else {
+            return Ok(());
+        };
+        let Some(session_id) = dto.session_id() else {
+            return Err(DomainError {
+                code: ErrorCode::PreconditionFailed,
+                message: "expected_version applies to session commands only".to_string(),
+                details: Some(serde_json::json!({ "command_type": dto.command_type() })),
+            });
+        };
+        let current = self.deps.event_reader.session_version(session_id)?;
+        if current != expected {
+            return Err(DomainError {
+                code: ErrorCode::ConcurrencyConflict,
+                message: "Session changed since the expected version; refresh and retry"
+                    .to_string(),
+                details: Some(serde_json::json!({
+                    "session_id": session_id,
+                    "expected_version": expected,
+                    "session_version": current,
+                })),
+            });
+        }
+        Ok(())
+    }
+
     fn request_hash(command: &AnyCommand) -> DomainResult<String> {
         let bytes = serde_json::to_vec(command).map_err(|e| DomainError {
             code: ErrorCode::PreconditionFailed,
@@ -98,6 +154,9 @@ impl<'a, U: UnitOfWork> CommandDispatcher for DefaultCommandDispatcher<'a, U> {
         }
 
         let result = self.deps.uow.within_tx(|| {
+            // Held until the closure returns, so the status read below cannot go stale.
+            let _lock = self.deps.locks.acquire(self.write_scope(dto)?)?;
+            self.check_expected_version(dto)?;
             let session_id = dto.session_id();
             let command_type = dto.command_type();
 
@@ -151,11 +210,16 @@ impl<'a, U: UnitOfWork> CommandDispatcher for DefaultCommandDispatcher<'a, U> {
 
             let next = outcome.transition.as_ref().map(|t| t.to);
             let session_status = Self::choose_status(current_status, next);
+            let session_version = match session_id {
+                Some(sid) => Some(self.deps.event_reader.session_version(sid)?),
+                None => None,
+            };
 
             Ok(DispatchResult {
                 command_id: dto.command_id(),
                 event_ids: events.iter().map(|e| e.event_id).collect::<Vec<Uuid>>(),
                 session_status,
+                session_version,
                 idempotent_replay: false,
             })
         });
@@ -164,6 +228,10 @@ impl<'a, U: UnitOfWork> CommandDispatcher for DefaultCommandDispatcher<'a, U> {
             Ok(ref committed) => {
                 self.deps.idempotency.commit(dto.command_id(), committed)?;
             }
+            Err(ref err) if err.code.is_retryable() => {
+                // The command never ran; free its id for the caller's retry.
+                let _ = self.deps.idempotency.release(dto.command_id());
+            }
             Err(ref err) => {
                 // Best-effort status write for observability; dispatch error is returned regardless.
                 let _ = self.deps.idempotency.mark_failed(dto.command_id(), err);
@@ -173,3 +241,326 @@ impl<'a, U: UnitOfWork> CommandDispatcher for DefaultCommandDispatcher<'a, U> {
         result
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::sync::Mutex;
+    use std::time::Duration;
+
+    use super::*;
+    use crate::anchors::AddAnchorRuleHandler;
+    use crate::dictionary::DictionaryNormalizer;
+    use crate::export::{ExportDeps, ExportSessionHandler};
+    use crate::in_memory_reference_impl::{
+        InMemoryReferenceBundle, InMemoryUnitOfWork, InMemoryWriteLocks,
+    };
+    use crate::interfaces::{
+        AnchorStore, CommandDispatcher, EventReader, MappingStore, SessionReader, TransitionPolicy,
+        WriteLockManager,
+    };
+    use crate::mapping::AssignFieldValueHandler;
+    use crate::provenance::ProvenanceChecker;
+    use crate::test_support::{command, World};
+    use crate::transition_policy::MatrixTransitionPolicy;
+    use crate::validation::{RunValidationHandler, ValidationDeps};
+
+    /// Runs `f` against a dispatcher over `b` that handles value assignments,
+    /// validation runs, exports and anchor rules.
+    fn with_dispatcher<R>(
+        b: &InMemoryReferenceBundle,
+        f: impl FnOnce(&DefaultCommandDispatcher<'_, InMemoryUnitOfWork>) -> R,
+    ) -> R {
+        let policy = MatrixTransitionPolicy::new();
+        let assign = AssignFieldValueHandler::new(
+            &b.mapping,
+            &b.sessions,
+            &b.schemas,
+            ProvenanceChecker {
+                pages: &b.pages,
+                extraction: &b.extraction,
+                anchors: &b.anchors,
+                zones: &b.zones,
+                templates: &b.templates,
+                dictionary: &b.dictionary,
+            },
+            DictionaryNormalizer::new(&b.dictionary, &b.contexts),
+        );
+        let validate = RunValidationHandler::new(ValidationDeps {
+            mapping: &b.mapping,
+            sessions: &b.sessions,
+            schemas: &b.schemas,
+            pages: &b.pages,
+            validation: &b.validation,
+            events: &b.events,
+            overrides: &b.overrides,
+        });
+        let export = ExportSessionHandler::new(ExportDeps {
+            sessions: &b.sessions,
+            schemas: &b.schemas,
+            validation: &b.validation,
+            overrides: &b.overrides,
+            mapping: &b.mapping,
+            pages: &b.pages,
+            bucket: &b.unknown_bucket,
+        });
+        let anchor = AddAnchorRuleHandler::new(&b.anchors);
+        let dispatcher = DefaultCommandDispatcher::new(DispatcherDeps {
+            handlers: vec![&assign, &validate, &export, &anchor],
+            transitions: &policy,
+            idempotency: &b.idempotency,
+            events: &b.events,
+            event_reader: &b.events,
+            event_factory: &b.event_factory,
+            invariants: &b.invariants,
+            sessions: &b.sessions,
+            projections: &b.projections,
+            locks: &b.locks,
+            uow: &b.uow,
+        });
+        f(&dispatcher)
+    }
+
+    fn assign(
+        w: &World,
+        command_id: Uuid,
+        session_id: Uuid,
+        document_id: Uuid,
+        raw_value: &str,
+    ) -> AnyCommand {
+        command(serde_json::json!({
+            "type": "AssignFieldValue",
+            "command_id": command_id,
+            "payload": {
+                "session_id": session_id,
+                "document_id": document_id,
+                "schema_field_id": w.field_id("note"),
+                "raw_value": raw_value,
+                "provenance": { "source": "manual", "actor": "tester" }
+            }
+        }))
+    }
+
+    struct Dispatched {
+        command_id: Uuid,
+        session_id: Option<Uuid>,
+        /// The value an `AssignFieldValue` wrote.
+        raw_value: Option<String>,
+        /// Commands reaching an exported session are rejected.
+        result: DomainResult<DispatchResult>,
+    }
+
+    #[test]
+    fn busy_write_scopes_can_be_retried_with_the_same_command_id() {
+        let mut w = World::new(&[("note", "string", "document", false)]);
+        w.b.locks = InMemoryWriteLocks::with_timeout(Duration::from_millis(20));
+        let session_id = w.session(SessionStatus::Review);
+        let (document_id, _) = w.document(session_id);
+        let command_id = Uuid::now_v7();
+
+        with_dispatcher(&w.b, |dispatcher| {
+            let held =
+                w.b.locks
+                    .acquire(WriteScope::Session {
+                        project_id: w.project_id,
+                        session_id,
+                    })
+                    .unwrap();
+            let err = dispatcher
+                .dispatch(assign(&w, command_id, session_id, document_id, "Acme"))
+                .unwrap_err();
+            assert!(matches!(err.code, ErrorCode::WriteScopeBusy));
+            assert!(err.code.is_retryable());
+            drop(held);
+
+            let retried = dispatcher
+                .dispatch(assign(&w, command_id, session_id, document_id, "Acme"))
+                .unwrap();
+            assert!(!retried.idempotent_replay);
+            assert_eq!(retried.session_version, Some(1));
+        });
+        assert_eq!(
+            w.b.mapping.field_values(session_id).unwrap()[0].raw_value,
+            "Acme"
+        );
+    }
+
+    #[test]
+    fn concurrent_dispatch_neither_loses_nor_duplicates_writes() {
+        const THREADS: usize = 8;
+        const PER_THREAD: usize = 500;
+        /// Exports are only attempted this close to the end, so most writes land first.
+        const EXPORT_WINDOW: usize = 50;
+        const SESSIONS: usize = 4;
+
+        let w = World::new(&[("note", "string", "document", false)]);
+        let sessions: Vec<(Uuid, Uuid)> = (0..SESSIONS)
+            .map(|_| {
+                let session_id = w.session(SessionStatus::Review);
+                (session_id, w.document(session_id).0)
+            })
+            .collect();
+
+        let dispatched: Mutex<Vec<Dispatched>> = Mutex::new(Vec::new());
+        std::thread::scope(|scope| {
+            for t in 0..THREADS {
+                let (w, sessions, dispatched) = (&w, &sessions, &dispatched);
+                scope.spawn(move || {
+                    with_dispatcher(&w.b, |dispatcher| {
+                        for i in 0..PER_THREAD {
+                            let (session_id, document_id) = sessions[(t + i) % SESSIONS];
+                            let command_id = Uuid::now_v7();
+                            let (cmd, session, raw_value) = match (t * 7 + i) % 5 {
+                                0 if i % 25 == 0 => (
+                                    command(serde_json::json!({
+                                        "type": "AddAnchorRule",
+                                        "command_id": command_id,
+                                        "payload": {
+                                            "project_id": w.project_id,
+                                            "schema_field_id": w.field_id("note"),
+                                            "rule": {
+                                                "label": { "kind": "text", "text": format!("L{t}-{i}") },
+                                                "direction": "right",
+                                                "max_offset": 0.2
+                                            }
+                                        }
+                                    })),
+                                    None,
+                                    None,
+                                ),
+                                _ if i >= PER_THREAD - EXPORT_WINDOW && i % 10 == 0 => (
+                                    command(serde_json::json!({
+                                        "type": "ExportSession",
+                                        "command_id": command_id,
+                                        "payload": {
+                                            "session_id": session_id,
+                                            "format": "json",
+                                            "include_in_vault": false,
+                                            "export_path": format!("/tmp/{command_id}.json"),
+                                            "include_unknown_bucket": false
+                                        }
+                                    })),
+                                    Some(session_id),
+                                    None,
+                                ),
+                                0..=2 => {
+                                    let raw_value = format!("v{t}-{i}");
+                                    (
+                                        assign(w, command_id, session_id, document_id, &raw_value),
+                                        Some(session_id),
+                                        Some(raw_value),
+                                    )
+                                }
+                                _ => (
+                                    command(serde_json::json!({
+                                        "type": "RunValidation",
+                                        "command_id": command_id,
+                                        "payload": { "session_id": session_id, "rule_scope": "all" }
+                                    })),
+                                    Some(session_id),
+                                    None,
+                                ),
+                            };
+                            let result = dispatcher.dispatch(cmd);
+                            if let Err(err) = &result {
+                                assert!(
+                                    matches!(err.code, ErrorCode::CommandNotAllowedInState),
+                                    "{err:?}"
+                                );
+                            }
+                            dispatched.lock().unwrap().push(Dispatched {
+                                command_id,
+                                session_id: session,
+                                raw_value,
+                                result,
+                            });
+                        }
+                    })
+                });
+            }
+        });
+        let dispatched = dispatched.into_inner().unwrap();
+        assert_eq!(dispatched.len(), THREADS * PER_THREAD);
+
+        // Every accepted command's events are in the log exactly once, and nothing else is.
+        let log = w.b.events.all_events().unwrap();
+        let mut logged: HashMap<Uuid, usize> = HashMap::new();
+        for event in &log {
+            *logged.entry(event.event_id).or_default() += 1;
+        }
+        let accepted: Vec<(&Dispatched, &DispatchResult)> = dispatched
+            .iter()
+            .filter_map(|d| Some((d, d.result.as_ref().ok()?)))
+            .collect();
+        let emitted: usize = accepted.iter().map(|(_, r)| r.event_ids.len()).sum();
+        assert_eq!(log.len(), emitted);
+        for (_, result) in &accepted {
+            for event_id in &result.event_ids {
+                assert_eq!(logged.get(event_id), Some(&1));
+            }
+        }
+        let anchors = dispatched.iter().filter(|d| d.session_id.is_none()).count();
+        assert_eq!(w.b.anchors.rules(w.project_id).unwrap().len(), anchors);
+
+        let by_command: HashMap<Uuid, (&Dispatched, &DispatchResult)> = accepted
+            .iter()
+            .map(|(d, r)| (d.command_id, (*d, *r)))
+            .collect();
+        let policy = MatrixTransitionPolicy::new();
+        for (session_id, _) in &sessions {
+            let mut status = SessionStatus::Review;
+            let mut seen: Vec<Uuid> = Vec::new();
+            let mut last_write = None;
+            for event in w.b.events.session_events(*session_id).unwrap() {
+                // A command's events are contiguous in the log.
+                if seen.last() == Some(&event.caused_by) {
+                    continue;
+                }
+                assert!(
+                    !seen.contains(&event.caused_by),
+                    "events of a command interleaved"
+                );
+                seen.push(event.caused_by);
+
+                let command_type = event
+                    .event_type
+                    .split(':')
+                    .next()
+                    .unwrap()
+                    .trim_end_matches("Processed");
+                // No rule can fail, so the transition follows from the status the
+                // command ran against; a stale read shows up as a wrong transition.
+                let expected = match (command_type, status) {
+                    ("AssignFieldValue", SessionStatus::Validated) => Some(SessionStatus::Review),
+                    ("RunValidation", SessionStatus::Review) => Some(SessionStatus::Validated),
+                    ("ExportSession", SessionStatus::Validated) => Some(SessionStatus::Exported),
+                    _ => None,
+                };
+                assert_eq!(
+                    event.event_type.contains(":transition_to_"),
+                    expected.is_some()
+                );
+                if let Some(to) = expected {
+                    policy.assert_transition(status, to).unwrap();
+                    status = to;
+                }
+                let (d, result) = by_command[&event.caused_by];
+                assert_eq!(result.session_status, Some(status));
+                if d.raw_value.is_some() {
+                    last_write = d.raw_value.clone();
+                }
+            }
+            let commands = accepted
+                .iter()
+                .filter(|(d, _)| d.session_id == Some(*session_id))
+                .count();
+            assert_eq!(seen.len(), commands);
+            assert_eq!(w.b.sessions.get_status(*session_id).unwrap(), status);
+
+            // The stored value is the last write in the log, held in a single row.
+            let values = w.b.mapping.field_values(*session_id).unwrap();
+            assert_eq!(values.len(), 1);
+            assert_eq!(Some(values[0].raw_value.clone()), last_write);
+        }
+    }
+}
@@ -11,13 +11,46 @@ pub struct DomainError {
 #[serde(rename_all = "SCREAMING_SNAKE_CASE")]
 pub enum ErrorCode {
     SessionLocked,
+    /// A locked field value, item value or item row would be changed.
+    ValueLocked,
     InvalidStateTransition,
     CommandNotAllowedInState,
     IdempotencyConflict,
+    /// The session moved past the command's `expected_version`.
+    ConcurrencyConflict,
+    /// The command's write scope stayed busy past the lock wait. Nothing ran, so the
+    /// same command may be dispatched again.
+    WriteScopeBusy,
     PreconditionFailed,
     InvariantViolation,
     NotFound,
     Internal,
 }
 
+impl ErrorCode {
+    /// Whether the rejection came before the command ran and a retry with the same
+    /// `command_id` can succeed.
+    pub fn is_retryable(&self) -> bool {
+        matches!(self, ErrorCode::WriteScopeBusy)
+    }
+}
+
 pub type DomainResult<T> = Result<T, DomainError>;
+
+/// Error for a handler invoked with a command type it did not claim in `can_handle`.
+pub fn unsupported_command(handler: &str) -> DomainError {
+    DomainError {
+        code: ErrorCode::Internal,
+        message: format!("{handler} received unsupported command"),
+        details: None,
+    }
+}
+
+/// Error for a command that would change a locked field value, item value or item row.
+pub fn value_locked(message: &str, details: serde_json::Value) -> DomainError {
+    DomainError {
+        code: ErrorCode::ValueLocked,
+        message: message.to_string(),
+        details: Some(details),
+    }
+}
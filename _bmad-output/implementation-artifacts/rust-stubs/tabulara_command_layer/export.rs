@@ -0,0 +1,431 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::commands::{AnyCommand, ExportSession};
+use crate::errors::{unsupported_command, DomainError, DomainResult, ErrorCode};
+use crate::extra_tables::ExtraTableExport;
+use crate::interfaces::{
+    CommandContext, CommandOutcome, GenericCommandHandler, MappingStore, OutcomeEvent,
+    OverrideStore, PageReader, SchemaStore, SessionReader, StateDelta, UnknownBucketStore,
+    ValidationStore, ValidationTrigger,
+};
+use crate::overrides::OverrideManifest;
+use crate::schema::{session_schema, SchemaRef};
+use crate::types::{ExportFormat, SessionStatus, SessionStatusTransition};
+use crate::unknown_bucket::{UnknownBucket, UnknownBucketQuery, UnknownTextRow};
+
+/// Manifest of an export: what was exported, by whom, and the validation state that
+/// cleared it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExportManifest {
+    pub session_id: Uuid,
+    pub schema: SchemaRef,
+    pub format: ExportFormat,
+    pub include_in_vault: bool,
+    pub export_path: Option<String>,
+    pub exported_at: DateTime<Utc>,
+    pub exported_by: String,
+    /// The validation run the export was cleared by.
+    pub validation_run_id: Uuid,
+    pub validation_overrides: OverrideManifest,
+}
+
+/// Tables the export adapter writes next to the session's values.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ExportTables {
+    /// One table per extra table the session's schema version declares.
+    pub extra_tables: Vec<ExtraTableExport>,
+    /// Text no value consumed; only when `include_unknown_bucket` is set.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub unknown_text: Option<Vec<UnknownTextRow>>,
+}
+
+pub struct ExportDeps<'a> {
+    pub sessions: &'a dyn SessionReader,
+    pub schemas: &'a dyn SchemaStore,
+    pub validation: &'a dyn ValidationStore,
+    pub overrides: &'a dyn OverrideStore,
+    pub mapping: &'a dyn MappingStore,
+    pub pages: &'a dyn PageReader,
+    pub bucket: &'a dyn UnknownBucketStore,
+}
+
+/// Builds the export of a validated session. The manifest goes into the state delta
+/// and the `ExportManifestCreated` event, the tables into the state delta; writing
+/// the files in the requested format is left to the export adapter. The session
+/// must have a validation run against its pinned schema version with no open
+/// blocking results.
+pub struct ExportSessionHandler<'a> {
+    deps: ExportDeps<'a>,
+}
+
+impl<'a> ExportSessionHandler<'a> {
+    pub fn new(deps: ExportDeps<'a>) -> Self {
+        Self { deps }
+    }
+
+    fn export(&self, ctx: &CommandContext, cmd: &ExportSession) -> DomainResult<CommandOutcome> {
+        let payload = &cmd.payload;
+        let session_id = payload.session_id;
+        let status = self.deps.sessions.get_status(session_id)?;
+        if status != SessionStatus::Validated {
+            return Err(DomainError {
+                code: ErrorCode::PreconditionFailed,
+                message: "Only validated sessions can be exported".to_string(),
+                details: Some(serde_json::json!({
+                    "session_id": session_id,
+                    "status": status,
+                })),
+            });
+        }
+        let session = self.deps.sessions.get_session(session_id)?;
+        let run = self
+            .deps
+            .validation
+            .latest_run(session_id)?
+            .filter(|run| run.schema == session.schema)
+            .ok_or_else(|| DomainError {
+                code: ErrorCode::PreconditionFailed,
+                message: "Session has not been validated against its schema version".to_string(),
+                details: Some(serde_json::json!({ "session_id": session_id })),
+            })?;
+        if run.blocking > 0 {
+            return Err(DomainError {
+                code: ErrorCode::PreconditionFailed,
+                message: "Session has unresolved blocking validation errors".to_string(),
+                details: Some(serde_json::json!({
+                    "validation_run_id": run.validation_run_id,
+                    "blocking": run.blocking,
+                })),
+            });
+        }
+
+        let manifest = ExportManifest {
+            session_id,
+            schema: session.schema,
+            format: payload.format,
+            include_in_vault: payload.include_in_vault,
+            export_path: payload.export_path.clone(),
+            exported_at: ctx.now,
+            exported_by: ctx.actor.clone(),
+            validation_run_id: run.validation_run_id,
+            validation_overrides: OverrideManifest::load(self.deps.overrides, session_id)?,
+        };
+        let schema = session_schema(self.deps.sessions, self.deps.schemas, session_id)?;
+        let mut tables = ExportTables {
+            extra_tables: ExtraTableExport::build(&schema, self.deps.mapping, session_id)?,
+            unknown_text: None,
+        };
+        if payload.include_unknown_bucket {
+            let bucket = UnknownBucket::new(self.deps.bucket, self.deps.mapping, self.deps.pages);
+            let fragments = bucket.unassigned(session_id, &UnknownBucketQuery::default())?;
+            tables.unknown_text = Some(fragments.iter().map(UnknownTextRow::from).collect());
+        }
+
+        Ok(CommandOutcome {
+            state_delta: StateDelta {
+                summary: "Session exported".to_string(),
+                data: serde_json::json!({ "manifest": manifest, "tables": tables }),
+            },
+            transition: Some(SessionStatusTransition {
+                from: status,
+                to: SessionStatus::Exported,
+            }),
+            review_actions: Vec::new(),
+            validation_trigger: ValidationTrigger::None,
+            events: vec![OutcomeEvent {
+                event_type: "ExportManifestCreated".to_string(),
+                session_id: Some(session_id),
+                data: serde_json::json!({ "manifest": manifest }),
+            }],
+        })
+    }
+}
+
+impl<'a> GenericCommandHandler for ExportSessionHandler<'a> {
+    fn can_handle(&self, command_type: &str) -> bool {
+        command_type == "ExportSession"
+    }
+
+    fn handle(&self, ctx: &mut CommandContext, cmd: &AnyCommand) -> DomainResult<CommandOutcome> {
+        match cmd {
+            AnyCommand::ExportSession(c) => self.export(ctx, c),
+            _ => Err(unsupported_command("ExportSessionHandler")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::extra_tables::{AddExtraRowHandler, AssignExtraValueHandler, ExtraTableDeps};
+    use crate::extraction::BoundingBox;
+    use crate::mapping::FieldValue;
+    use crate::overrides::{OverrideDeps, OverrideValidationHandler};
+    use crate::test_support::{command, ctx, World};
+    use crate::unknown_bucket::{UnknownFragment, UnknownToken};
+    use crate::validation::{AddValidationRuleHandler, RunValidationHandler, ValidationDeps};
+
+    fn validate(w: &World, session_id: Uuid) -> CommandOutcome {
+        RunValidationHandler::new(ValidationDeps {
+            mapping: &w.b.mapping,
+            sessions: &w.b.sessions,
+            schemas: &w.b.schemas,
+            pages: &w.b.pages,
+            validation: &w.b.validation,
+            events: &w.b.events,
+            overrides: &w.b.overrides,
+        })
+        .handle(
+            &mut ctx(),
+            &command(serde_json::json!({
+                "type": "RunValidation",
+                "payload": { "session_id": session_id, "rule_scope": "all" }
+            })),
+        )
+        .unwrap()
+    }
+
+    fn export(
+        w: &World,
+        session_id: Uuid,
+        include_unknown_bucket: bool,
+    ) -> DomainResult<CommandOutcome> {
+        ExportSessionHandler::new(ExportDeps {
+            sessions: &w.b.sessions,
+            schemas: &w.b.schemas,
+            validation: &w.b.validation,
+            overrides: &w.b.overrides,
+            mapping: &w.b.mapping,
+            pages: &w.b.pages,
+            bucket: &w.b.unknown_bucket,
+        })
+        .handle(
+            &mut ctx(),
+            &command(serde_json::json!({
+                "type": "ExportSession",
+                "payload": {
+                    "session_id": session_id,
+                    "format": "json",
+                    "include_in_vault": true,
+                    "export_path": null,
+                    "include_unknown_bucket": include_unknown_bucket
+                }
+            })),
+        )
+    }
+
+    fn tables(outcome: &CommandOutcome) -> ExportTables {
+        serde_json::from_value(outcome.state_delta.data["tables"].clone()).unwrap()
+    }
+
+    #[test]
+    fn manifest_lists_the_overrides_that_cleared_validation() {
+        let w = World::new(&[("invoice_number", "string", "document", true)]);
+        let session_id = w.session(SessionStatus::Validated);
+        w.document(session_id);
+
+        let run = validate(&w, session_id);
+        assert!(export(&w, session_id, false).is_err());
+
+        OverrideValidationHandler::new(OverrideDeps {
+            validation: &w.b.validation,
+            overrides: &w.b.overrides,
+            mapping: &w.b.mapping,
+            sessions: &w.b.sessions,
+        })
+        .handle(
+            &mut ctx(),
+            &command(serde_json::json!({
+                "type": "OverrideValidation",
+                "payload": {
+                    "session_id": session_id,
+                    "validation_result_id": run.state_delta.data["results"][0]["validation_result_id"],
+                    "reason_code": "known_exception",
+                    "reason": "Receipt without a number"
+                }
+            })),
+        )
+        .unwrap();
+        validate(&w, session_id);
+
+        let exported = export(&w, session_id, false).unwrap();
+        let manifest: ExportManifest =
+            serde_json::from_value(exported.state_delta.data["manifest"].clone()).unwrap();
+        assert_eq!(manifest.validation_overrides.active, 1);
+        assert_eq!(manifest.validation_overrides.overrides.len(), 1);
+        assert_eq!(
+            exported.transition.map(|t| t.to),
+            Some(SessionStatus::Exported)
+        );
+        assert_eq!(exported.events[0].event_type, "ExportManifestCreated");
+    }
+
+    #[test]
+    fn unknown_text_lists_only_unconsumed_fragments_when_requested() {
+        let w = World::new(&[("vendor", "string", "document", false)]);
+        let session_id = w.session(SessionStatus::Validated);
+        let (document_id, page_id) = w.document(session_id);
+        let extraction_run_id = Uuid::now_v7();
+        let fragment = |text: &str, y: f64, reading_order: u32| UnknownFragment {
+            fragment_id: Uuid::now_v7(),
+            session_id,
+            document_id,
+            page_id,
+            extraction_run_id,
+            reading_order,
+            tokens: vec![UnknownToken {
+                token_id: Uuid::now_v7(),
+                text: text.to_string(),
+                bbox: BoundingBox::new(10.0, y, 80.0, 12.0),
+                confidence: 0.9,
+            }],
+        };
+        let vendor = fragment("Acme", 10.0, 0);
+        let note = fragment("Thank you for your business", 700.0, 1);
+        w.b.unknown_bucket
+            .replace_page(session_id, page_id, &[vendor.clone(), note.clone()])
+            .unwrap();
+        w.b.mapping
+            .put_field_value(&FieldValue {
+                field_value_id: Uuid::now_v7(),
+                session_id,
+                document_id,
+                schema_field_id: w.field_id("vendor"),
+                raw_value: "Acme".to_string(),
+                normalized_value: Some("Acme".to_string()),
+                provenance: vendor.provenance("tester"),
+                locked: false,
+            })
+            .unwrap();
+        validate(&w, session_id);
+
+        let without = export(&w, session_id, false).unwrap();
+        assert!(tables(&without).unknown_text.is_none());
+
+        let with = export(&w, session_id, true).unwrap();
+        let rows = tables(&with).unknown_text.unwrap();
+        assert_eq!(rows.len(), 1);
+        assert_eq!(rows[0].fragment_id, note.fragment_id);
+        assert_eq!(rows[0].text, "Thank you for your business");
+        assert_eq!(rows[0].extraction_run_id, extraction_run_id);
+    }
+
+    #[test]
+    fn extra_tables_are_exported_and_their_values_covered_by_overrides() {
+        let w = World::with_extra_tables(
+            &[("invoice_number", "string", "document", false)],
+            serde_json::json!([{
+                "table_name": "fees",
+                "label": "Fees",
+                "columns": [
+                    { "field_key": "amount", "label": "Amount", "field_type": "currency", "required": true },
+                    { "field_key": "note", "label": "Note", "field_type": "string" }
+                ]
+            }]),
+        );
+        let session_id = w.session(SessionStatus::Validated);
+        let (document_id, _) = w.document(session_id);
+        let deps = || ExtraTableDeps {
+            mapping: &w.b.mapping,
+            sessions: &w.b.sessions,
+            schemas: &w.b.schemas,
+            pages: &w.b.pages,
+            provenance: w.provenance(),
+            dictionary: w.dictionary(),
+        };
+        let amount = w
+            .schema
+            .field_by_key("fees.amount")
+            .unwrap()
+            .schema_field_id;
+        for (row_index, raw_value) in [(0, "$2.00"), (1, "$3.00")] {
+            let added = AddExtraRowHandler::new(deps())
+                .handle(
+                    &mut ctx(),
+                    &command(serde_json::json!({
+                        "type": "AddExtraRow",
+                        "payload": {
+                            "session_id": session_id,
+                            "document_id": document_id,
+                            "table_name": "fees",
+                            "row_index": row_index
+                        }
+                    })),
+                )
+                .unwrap();
+            AssignExtraValueHandler::new(deps())
+                .handle(
+                    &mut ctx(),
+                    &command(serde_json::json!({
+                        "type": "AssignExtraValue",
+                        "payload": {
+                            "session_id": session_id,
+                            "extra_row_id": added.state_delta.data["extra_row"]["extra_row_id"],
+                            "schema_field_id": amount,
+                            "raw_value": raw_value,
+                            "provenance": { "source": "manual", "actor": "tester" }
+                        }
+                    })),
+                )
+                .unwrap();
+        }
+        AddValidationRuleHandler::new(&w.b.validation, &w.b.schemas)
+            .handle(
+                &mut ctx(),
+                &command(serde_json::json!({
+                    "type": "AddValidationRule",
+                    "payload": {
+                        "project_id": w.project_id,
+                        "name": "Fees stay small",
+                        "check": { "check": "expression", "expression": "sum(extra.fees.amount) < 1" },
+                        "severity": "blocking"
+                    }
+                })),
+            )
+            .unwrap();
+        let run = validate(&w, session_id);
+        OverrideValidationHandler::new(OverrideDeps {
+            validation: &w.b.validation,
+            overrides: &w.b.overrides,
+            mapping: &w.b.mapping,
+            sessions: &w.b.sessions,
+        })
+        .handle(
+            &mut ctx(),
+            &command(serde_json::json!({
+                "type": "OverrideValidation",
+                "payload": {
+                    "session_id": session_id,
+                    "validation_result_id": run.state_delta.data["results"][0]["validation_result_id"],
+                    "reason_code": "known_exception",
+                    "reason": "Fees agreed with the vendor"
+                }
+            })),
+        )
+        .unwrap();
+        validate(&w, session_id);
+
+        let exported = export(&w, session_id, false).unwrap();
+        let manifest: ExportManifest =
+            serde_json::from_value(exported.state_delta.data["manifest"].clone()).unwrap();
+        assert_eq!(manifest.validation_overrides.active, 1);
+        assert_eq!(
+            manifest.validation_overrides.overrides[0]
+                .covered_values
+                .len(),
+            2
+        );
+        let tables = tables(&exported);
+        assert_eq!(tables.extra_tables.len(), 1);
+        let fees = &tables.extra_tables[0];
+        assert_eq!(fees.table_name, "fees");
+        let keys: Vec<&str> = fees.columns.iter().map(|c| c.field_key.as_str()).collect();
+        assert_eq!(keys, ["amount", "note"]);
+        assert_eq!(fees.rows.len(), 2);
+        assert_eq!(fees.rows[0].cells.len(), 2);
+        assert!(fees.rows[0].cells[0].is_some());
+        assert_eq!(fees.rows[0].cells[1], None);
+    }
+}
@@ -0,0 +1,445 @@
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::commands::{AddExtraRow, AnyCommand, AssignExtraValue};
+use crate::dictionary::DictionaryNormalizer;
+use crate::errors::{unsupported_command, value_locked, DomainError, DomainResult, ErrorCode};
+use crate::interfaces::{
+    CommandContext, CommandOutcome, GenericCommandHandler, MappingStore, PageReader, ReviewAction,
+    SchemaStore, SessionReader, StateDelta, ValidationTrigger,
+};
+use crate::mapping::{ExtraRow, ExtraValue, ValueChange, WrittenValue};
+use crate::provenance::ProvenanceChecker;
+use crate::review_tasks::{ReviewCategory, ReviewTarget};
+use crate::schema::{session_schema, ExtraTable, SchemaField, SchemaVersion};
+use crate::types::{FieldType, SessionStatus, SessionStatusTransition, SourceType};
+use crate::value_parsing::parse_value;
+
+pub(crate) fn extra_row(
+    mapping: &dyn MappingStore,
+    session_id: Uuid,
+    extra_row_id: Uuid,
+) -> DomainResult<ExtraRow> {
+    mapping
+        .extra_rows(session_id)?
+        .into_iter()
+        .find(|r| r.extra_row_id == extra_row_id)
+        .ok_or_else(|| DomainError {
+            code: ErrorCode::NotFound,
+            message: "Extra row not found".to_string(),
+            details: Some(serde_json::json!({ "extra_row_id": extra_row_id })),
+        })
+}
+
+/// The declared table of a stored row. Rows of a table the session's schema version
+/// does not declare are kept but take no values.
+fn declared_table<'s>(schema: &'s SchemaVersion, row: &ExtraRow) -> DomainResult<&'s ExtraTable> {
+    schema
+        .extra_table_by_name(&row.table_name)
+        .ok_or_else(|| unknown_table(schema, &row.table_name))
+}
+
+fn unknown_table(schema: &SchemaVersion, table_name: &str) -> DomainError {
+    DomainError {
+        code: ErrorCode::NotFound,
+        message: "Extra table not declared in the session schema".to_string(),
+        details: Some(serde_json::json!({
+            "table_name": table_name,
+            "schema_id": schema.schema_id,
+            "version": schema.version,
+        })),
+    }
+}
+
+/// A field an extra row's cell may be written to: an active column of the row's
+/// table.
+pub(crate) fn writable_column<'s>(
+    schema: &'s SchemaVersion,
+    row: &ExtraRow,
+    schema_field_id: Uuid,
+) -> DomainResult<&'s SchemaField> {
+    let table = declared_table(schema, row)?;
+    let field = schema.writable_field(schema_field_id)?;
+    if field.extra_table_id != Some(table.extra_table_id) {
+        return Err(DomainError {
+            code: ErrorCode::PreconditionFailed,
+            message: format!(
+                "Field is not a column of extra table '{}'",
+                table.table_name
+            ),
+            details: Some(serde_json::json!({
+                "extra_row_id": row.extra_row_id,
+                "table_name": table.table_name,
+                "schema_field_id": field.schema_field_id,
+                "field_key": field.field_key,
+            })),
+        });
+    }
+    Ok(field)
+}
+
+fn demotion(status: SessionStatus) -> Option<SessionStatusTransition> {
+    (status == SessionStatus::Validated).then_some(SessionStatusTransition {
+        from: status,
+        to: SessionStatus::Review,
+    })
+}
+
+pub struct ExtraTableDeps<'a> {
+    pub mapping: &'a dyn MappingStore,
+    pub sessions: &'a dyn SessionReader,
+    pub schemas: &'a dyn SchemaStore,
+    pub pages: &'a dyn PageReader,
+    pub provenance: ProvenanceChecker<'a>,
+    pub dictionary: DictionaryNormalizer<'a>,
+}
+
+/// Inserts a row into one of the document's extra tables at `row_index`; later rows
+/// of the same table shift down one position. The table must be declared by the
+/// session's schema version.
+pub struct AddExtraRowHandler<'a> {
+    deps: ExtraTableDeps<'a>,
+}
+
+impl<'a> AddExtraRowHandler<'a> {
+    pub fn new(deps: ExtraTableDeps<'a>) -> Self {
+        Self { deps }
+    }
+
+    fn add(&self, cmd: &AddExtraRow) -> DomainResult<CommandOutcome> {
+        let payload = &cmd.payload;
+        let session_id = payload.session_id;
+        let status = self.deps.sessions.get_status(session_id)?;
+        let schema = session_schema(self.deps.sessions, self.deps.schemas, session_id)?;
+        let table_name = payload.table_name.trim();
+        let table = schema
+            .extra_table_by_name(table_name)
+            .ok_or_else(|| unknown_table(&schema, table_name))?;
+        if !self
+            .deps
+            .pages
+            .session_pages(session_id)?
+            .iter()
+            .any(|p| p.document_id == payload.document_id)
+        {
+            return Err(DomainError {
+                code: ErrorCode::NotFound,
+                message: "Document is not part of the session".to_string(),
+                details: Some(serde_json::json!({ "document_id": payload.document_id })),
+            });
+        }
+
+        let mut rows: Vec<ExtraRow> = self
+            .deps
+            .mapping
+            .extra_rows(session_id)?
+            .into_iter()
+            .filter(|r| r.document_id == payload.document_id && r.table_name == table.table_name)
+            .collect();
+        rows.sort_by_key(|r| (r.row_index, r.extra_row_id));
+        let index = usize::try_from(payload.row_index)
+            .ok()
+            .filter(|index| *index <= rows.len())
+            .ok_or_else(|| DomainError {
+                code: ErrorCode::PreconditionFailed,
+                message: format!("row_index must be between 0 and {}", rows.len()),
+                details: Some(serde_json::json!({
+                    "row_index": payload.row_index,
+                    "max": rows.len(),
+                })),
+            })?;
+        let row = ExtraRow {
+            extra_row_id: Uuid::now_v7(),
+            session_id,
+            document_id: payload.document_id,
+            table_name: table.table_name.clone(),
+            row_index: index as i32,
+        };
+        rows.insert(
+            index,
+            ExtraRow {
+                // Never a position, so the loop below writes the new row.
+                row_index: -1,
+                ..row.clone()
+            },
+        );
+        let mut shifted = Vec::new();
+        for (position, stored) in rows.iter_mut().enumerate() {
+            let position = position as i32;
+            if stored.row_index == position {
+                continue;
+            }
+            if stored.extra_row_id != row.extra_row_id {
+                shifted.push(stored.extra_row_id);
+            }
+            stored.row_index = position;
+            self.deps.mapping.put_extra_row(stored)?;
+        }
+
+        let changed: Vec<ValueChange> = schema
+            .columns(table.extra_table_id)
+            .map(|f| ValueChange {
+                document_id: payload.document_id,
+                schema_field_id: f.schema_field_id,
+            })
+            .collect();
+        Ok(CommandOutcome {
+            state_delta: StateDelta {
+                summary: format!("Row added to extra table '{}'", table.table_name),
+                data: serde_json::json!({
+                    "extra_row": row,
+                    "shifted_extra_row_ids": shifted,
+                    "changed_values": changed,
+                }),
+            },
+            transition: demotion(status),
+            review_actions: Vec::new(),
+            validation_trigger: ValidationTrigger::Async,
+            events: Vec::new(),
+        })
+    }
+}
+
+impl<'a> GenericCommandHandler for AddExtraRowHandler<'a> {
+    fn can_handle(&self, command_type: &str) -> bool {
+        command_type == "AddExtraRow"
+    }
+
+    fn handle(&self, _ctx: &mut CommandContext, cmd: &AnyCommand) -> DomainResult<CommandOutcome> {
+        match cmd {
+            AnyCommand::AddExtraRow(c) => self.add(c),
+            _ => Err(unsupported_command("AddExtraRowHandler")),
+        }
+    }
+}
+
+/// Stores a cell of an extra row. The field must be a column of the row's table;
+/// dictionary normalization, parsing and provenance checks follow `AssignFieldValue`
+/// and a locked cell is rejected with `VALUE_LOCKED`.
+pub struct AssignExtraValueHandler<'a> {
+    deps: ExtraTableDeps<'a>,
+}
+
+impl<'a> AssignExtraValueHandler<'a> {
+    pub fn new(deps: ExtraTableDeps<'a>) -> Self {
+        Self { deps }
+    }
+
+    fn assign(&self, cmd: &AssignExtraValue) -> DomainResult<CommandOutcome> {
+        let payload = &cmd.payload;
+        let session_id = payload.session_id;
+        let session = self.deps.sessions.get_session(session_id)?;
+        let status = self.deps.sessions.get_status(session_id)?;
+        let schema = session_schema(self.deps.sessions, self.deps.schemas, session_id)?;
+        let row = extra_row(self.deps.mapping, session_id, payload.extra_row_id)?;
+        let field = writable_column(&schema, &row, payload.schema_field_id)?;
+        self.deps
+            .provenance
+            .check(&payload.provenance, &cmd.actor, &session, row.document_id)?;
+        let locale = self.deps.schemas.get_project(session.project_id)?.locale;
+
+        let normalized = self.deps.dictionary.normalize(
+            session.project_id,
+            session_id,
+            row.document_id,
+            field.schema_field_id,
+            &payload.raw_value,
+        )?;
+        let mut provenance = payload.provenance.clone();
+        normalized.record_in(&mut provenance);
+        let parsed = parse_value(field, &normalized.value, &locale);
+        if let (Err(failure), SourceType::Manual) = (&parsed, payload.provenance.source()) {
+            return Err(DomainError {
+                code: ErrorCode::PreconditionFailed,
+                message: format!("Value does not parse as {:?}", failure.field_type),
+                details: Some(serde_json::json!({
+                    "schema_field_id": field.schema_field_id,
+                    "field_key": field.field_key,
+                    "raw_value": payload.raw_value,
+                    "normalized_input": normalized.value,
+                    "reason": failure.reason,
+                })),
+            });
+        }
+
+        let current = self
+            .deps
+            .mapping
+            .extra_values(session_id)?
+            .into_iter()
+            .find(|v| {
+                v.extra_row_id == row.extra_row_id && v.schema_field_id == field.schema_field_id
+            });
+        if let Some(current) = current.as_ref().filter(|v| v.locked) {
+            return Err(value_locked(
+                "Extra value is locked",
+                serde_json::json!({
+                    "extra_value_id": current.extra_value_id,
+                    "extra_row_id": row.extra_row_id,
+                    "field_key": field.field_key,
+                }),
+            ));
+        }
+        let value = ExtraValue {
+            extra_value_id: current
+                .as_ref()
+                .map_or_else(Uuid::now_v7, |v| v.extra_value_id),
+            session_id,
+            extra_row_id: row.extra_row_id,
+            schema_field_id: field.schema_field_id,
+            raw_value: payload.raw_value.clone(),
+            normalized_value: parsed.clone().unwrap_or(None),
+            provenance,
+            locked: false,
+        };
+        self.deps.mapping.put_extra_value(&value)?;
+
+        let review_actions = match &parsed {
+            Err(failure) => vec![ReviewAction::new(
+                session_id,
+                ReviewCategory::UnparseableValue,
+                ReviewTarget::field(row.document_id, field.schema_field_id),
+                serde_json::json!({
+                    "extra_value_id": value.extra_value_id,
+                    "extra_row_id": row.extra_row_id,
+                    "table_name": row.table_name,
+                    "field_key": field.field_key,
+                    "raw_value": value.raw_value,
+                    "field_type": failure.field_type,
+                    "reason": failure.reason,
+                }),
+            )],
+            Ok(_) => Vec::new(),
+        };
+
+        Ok(CommandOutcome {
+            state_delta: StateDelta {
+                summary: if current.is_some() {
+                    "Extra value updated".to_string()
+                } else {
+                    "Extra value assigned".to_string()
+                },
+                data: serde_json::json!({
+                    "extra_value": value,
+                    "field_type": field.field_type,
+                    "parse_error": parsed.err().map(|f| f.reason),
+                    "changed_values": [ValueChange {
+                        document_id: row.document_id,
+                        schema_field_id: field.schema_field_id,
+                    }],
+                    "written_values": [WrittenValue::Extra(value.clone())],
+                }),
+            },
+            transition: demotion(status),
+            review_actions,
+            validation_trigger: ValidationTrigger::Async,
+            events: Vec::new(),
+        })
+    }
+}
+
+impl<'a> GenericCommandHandler for AssignExtraValueHandler<'a> {
+    fn can_handle(&self, command_type: &str) -> bool {
+        command_type == "AssignExtraValue"
+    }
+
+    fn handle(&self, _ctx: &mut CommandContext, cmd: &AnyCommand) -> DomainResult<CommandOutcome> {
+        match cmd {
+            AnyCommand::AssignExtraValue(c) => self.assign(c),
+            _ => Err(unsupported_command("AssignExtraValueHandler")),
+        }
+    }
+}
+
+/// A declared column in an export.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExtraTableColumn {
+    pub schema_field_id: Uuid,
+    pub field_key: String,
+    pub label: String,
+    pub field_type: FieldType,
+    pub required: bool,
+}
+
+/// One extra row in an export. `cells` line up with the table's `columns`; a column
+/// without a value is `None`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExtraTableExportRow {
+    pub document_id: Uuid,
+    pub extra_row_id: Uuid,
+    pub row_index: i32,
+    pub cells: Vec<Option<String>>,
+}
+
+/// Export table for one declared extra table, shaped by the schema version rather
+/// than by the values present: every active column is listed, even one no row
+/// fills, and values of other fields are left out.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExtraTableExport {
+    pub table_name: String,
+    pub label: String,
+    pub columns: Vec<ExtraTableColumn>,
+    pub rows: Vec<ExtraTableExportRow>,
+}
+
+impl ExtraTableExport {
+    /// Every table the schema version declares, in declaration order, with rows in
+    /// `(document_id, row_index)` order. Cells hold the normalized value, falling
+    /// back to the raw value.
+    pub fn build(
+        schema: &SchemaVersion,
+        mapping: &dyn MappingStore,
+        session_id: Uuid,
+    ) -> DomainResult<Vec<Self>> {
+        let mut rows = mapping.extra_rows(session_id)?;
+        rows.sort_by_key(|r| (r.document_id, r.row_index, r.extra_row_id));
+        let values = mapping.extra_values(session_id)?;
+        Ok(schema
+            .extra_tables
+            .iter()
+            .map(|table| {
+                let columns: Vec<&SchemaField> = schema.columns(table.extra_table_id).collect();
+                let rows = rows
+                    .iter()
+                    .filter(|r| r.table_name == table.table_name)
+                    .map(|row| ExtraTableExportRow {
+                        document_id: row.document_id,
+                        extra_row_id: row.extra_row_id,
+                        row_index: row.row_index,
+                        cells: columns
+                            .iter()
+                            .map(|column| {
+                                values
+                                    .iter()
+                                    .find(|v| {
+                                        v.extra_row_id == row.extra_row_id
+                                            && v.schema_field_id == column.schema_field_id
+                                    })
+                                    .map(|v| {
+                                        v.normalized_value
+                                            .clone()
+                                            .unwrap_or_else(|| v.raw_value.clone())
+                                    })
+                            })
+                            .collect(),
+                    })
+                    .collect();
+                Self {
+                    table_name: table.table_name.clone(),
+                    label: table.label.clone(),
+                    columns: columns
+                        .into_iter()
+                        .map(|f| ExtraTableColumn {
+                            schema_field_id: f.schema_field_id,
+                            field_key: f.field_key.clone(),
+                            label: f.label.clone(),
+                            field_type: f.field_type,
+                            required: f.required,
+                        })
+                        .collect(),
+                    rows,
+                }
+            })
+            .collect())
+    }
+}
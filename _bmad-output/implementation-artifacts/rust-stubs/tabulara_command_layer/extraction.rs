@@ -0,0 +1,430 @@
+use std::collections::HashSet;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::errors::{DomainError, DomainResult, ErrorCode};
+
+/// Axis-aligned rectangle in page-normalized coordinates (origin top-left, 0.0..=1.0).
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+pub struct BoundingBox {
+    pub x: f64,
+    pub y: f64,
+    pub width: f64,
+    pub height: f64,
+}
+
+impl BoundingBox {
+    pub fn new(x: f64, y: f64, width: f64, height: f64) -> Self {
+        Self { x, y, width, height }
+    }
+
+    pub fn right(&self) -> f64 {
+        self.x + self.width
+    }
+
+    pub fn bottom(&self) -> f64 {
+        self.y + self.height
+    }
+
+    pub fn center(&self) -> (f64, f64) {
+        (self.x + self.width / 2.0, self.y + self.height / 2.0)
+    }
+
+    pub fn area(&self) -> f64 {
+        self.width.max(0.0) * self.height.max(0.0)
+    }
+
+    pub fn is_normalized(&self) -> bool {
+        self.width >= 0.0
+            && self.height >= 0.0
+            && self.x >= 0.0
+            && self.y >= 0.0
+            && self.right() <= 1.0 + f64::EPSILON
+            && self.bottom() <= 1.0 + f64::EPSILON
+    }
+
+    pub fn contains_point(&self, x: f64, y: f64) -> bool {
+        x >= self.x && x <= self.right() && y >= self.y && y <= self.bottom()
+    }
+
+    pub fn intersection_area(&self, other: &BoundingBox) -> f64 {
+        let w = self.right().min(other.right()) - self.x.max(other.x);
+        let h = self.bottom().min(other.bottom()) - self.y.max(other.y);
+        if w <= 0.0 || h <= 0.0 {
+            0.0
+        } else {
+            w * h
+        }
+    }
+
+    pub fn intersects(&self, other: &BoundingBox) -> bool {
+        self.intersection_area(other) > 0.0
+    }
+
+    /// Intersection over union; 0.0 for disjoint or degenerate boxes.
+    pub fn iou(&self, other: &BoundingBox) -> f64 {
+        let inter = self.intersection_area(other);
+        let union = self.area() + other.area() - inter;
+        if union <= 0.0 {
+            0.0
+        } else {
+            inter / union
+        }
+    }
+
+    pub fn union(&self, other: &BoundingBox) -> BoundingBox {
+        let x = self.x.min(other.x);
+        let y = self.y.min(other.y);
+        BoundingBox {
+            x,
+            y,
+            width: self.right().max(other.right()) - x,
+            height: self.bottom().max(other.bottom()) - y,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ExtractionRunStatus {
+    Completed,
+    Failed,
+}
+
+/// One derivative page processed by an extraction run.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct PageCoverage {
+    pub document_id: Uuid,
+    pub page_id: Uuid,
+    pub page_number: u32,
+}
+
+/// `extraction_runs` row.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExtractionRun {
+    pub extraction_run_id: Uuid,
+    pub session_id: Uuid,
+    pub caused_by: Uuid,
+    pub engine: String,
+    pub params: serde_json::Value,
+    pub started_at: DateTime<Utc>,
+    pub finished_at: Option<DateTime<Utc>>,
+    pub status: ExtractionRunStatus,
+    pub pages: Vec<PageCoverage>,
+}
+
+impl ExtractionRun {
+    pub fn covers_page(&self, page_id: Uuid) -> bool {
+        self.pages.iter().any(|p| p.page_id == page_id)
+    }
+}
+
+/// `tokens` row. Geometry is normalized to the derivative page it was read from.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExtractionToken {
+    pub token_id: Uuid,
+    pub extraction_run_id: Uuid,
+    pub page_id: Uuid,
+    pub text: String,
+    pub bbox: BoundingBox,
+    pub confidence: f32,
+    pub reading_order: u32,
+}
+
+/// `lines` row. `token_ids` are listed in reading order.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExtractionLine {
+    pub line_id: Uuid,
+    pub extraction_run_id: Uuid,
+    pub page_id: Uuid,
+    pub text: String,
+    pub bbox: BoundingBox,
+    pub confidence: f32,
+    pub reading_order: u32,
+    pub token_ids: Vec<Uuid>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DetectedTableCell {
+    pub row: u32,
+    pub column: u32,
+    pub row_span: u32,
+    pub column_span: u32,
+    pub bbox: BoundingBox,
+    pub confidence: f32,
+    pub token_ids: Vec<Uuid>,
+}
+
+/// `tables_detected` row: a table candidate with its cell grid.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DetectedTable {
+    pub table_id: Uuid,
+    pub extraction_run_id: Uuid,
+    pub page_id: Uuid,
+    pub bbox: BoundingBox,
+    pub confidence: f32,
+    pub row_count: u32,
+    pub column_count: u32,
+    pub cells: Vec<DetectedTableCell>,
+}
+
+/// Everything one extraction run produced, persisted as a single unit.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExtractionArtifacts {
+    pub run: ExtractionRun,
+    pub tokens: Vec<ExtractionToken>,
+    pub lines: Vec<ExtractionLine>,
+    pub tables: Vec<DetectedTable>,
+}
+
+impl ExtractionArtifacts {
+    /// Rejects artifacts that are not tied to a page covered by the run, reference
+    /// unknown tokens, or carry out-of-range geometry/confidence.
+    pub fn validate(&self) -> DomainResult<()> {
+        let run_id = self.run.extraction_run_id;
+        let pages: HashSet<Uuid> = self.run.pages.iter().map(|p| p.page_id).collect();
+        let mut token_ids = HashSet::new();
+
+        for token in &self.tokens {
+            check_artifact(run_id, "token", token.token_id, token.extraction_run_id, token.page_id, &pages)?;
+            check_geometry("token", token.token_id, &token.bbox, token.confidence)?;
+            if !token_ids.insert(token.token_id) {
+                return Err(artifact_error("Duplicate token id in extraction run", "token", token.token_id));
+            }
+        }
+
+        for line in &self.lines {
+            check_artifact(run_id, "line", line.line_id, line.extraction_run_id, line.page_id, &pages)?;
+            check_geometry("line", line.line_id, &line.bbox, line.confidence)?;
+            check_token_refs("line", line.line_id, &line.token_ids, &token_ids)?;
+        }
+
+        for table in &self.tables {
+            check_artifact(run_id, "table", table.table_id, table.extraction_run_id, table.page_id, &pages)?;
+            check_geometry("table", table.table_id, &table.bbox, table.confidence)?;
+            for cell in &table.cells {
+                let last_row = cell.row.saturating_add(cell.row_span.max(1));
+                let last_column = cell.column.saturating_add(cell.column_span.max(1));
+                if last_row > table.row_count || last_column > table.column_count {
+                    return Err(artifact_error("Table cell outside declared grid", "table", table.table_id));
+                }
+                check_geometry("table", table.table_id, &cell.bbox, cell.confidence)?;
+                check_token_refs("table", table.table_id, &cell.token_ids, &token_ids)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// What a `ReRunExtraction` re-reads. Region and table scopes stay on a single page.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum ExtractionScope {
+    Document { document_id: Uuid },
+    Page { page_id: Uuid },
+    Region { page_id: Uuid, bbox: BoundingBox },
+    Table { table_id: Uuid },
+}
+
+/// Input handed to an extraction engine adapter.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExtractionRequest {
+    pub extraction_run_id: Uuid,
+    pub session_id: Uuid,
+    pub engine: String,
+    pub params: serde_json::Value,
+    pub pages: Vec<PageCoverage>,
+    pub region: Option<BoundingBox>,
+}
+
+/// Raw engine output; artifacts must carry the request's `extraction_run_id`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct EngineOutput {
+    pub tokens: Vec<ExtractionToken>,
+    pub lines: Vec<ExtractionLine>,
+    pub tables: Vec<DetectedTable>,
+}
+
+fn check_artifact(
+    run_id: Uuid,
+    kind: &str,
+    id: Uuid,
+    artifact_run_id: Uuid,
+    page_id: Uuid,
+    pages: &HashSet<Uuid>,
+) -> DomainResult<()> {
+    if artifact_run_id != run_id {
+        return Err(artifact_error("Artifact belongs to a different extraction run", kind, id));
+    }
+    if !pages.contains(&page_id) {
+        return Err(DomainError {
+            code: ErrorCode::PreconditionFailed,
+            message: "Artifact page is not covered by the extraction run".to_string(),
+            details: Some(serde_json::json!({ "kind": kind, "id": id, "page_id": page_id })),
+        });
+    }
+    Ok(())
+}
+
+fn check_geometry(kind: &str, id: Uuid, bbox: &BoundingBox, confidence: f32) -> DomainResult<()> {
+    if !bbox.is_normalized() {
+        return Err(artifact_error("Artifact bounding box is not page-normalized", kind, id));
+    }
+    if !(0.0..=1.0).contains(&confidence) {
+        return Err(artifact_error("Artifact confidence must be within 0.0..=1.0", kind, id));
+    }
+    Ok(())
+}
+
+fn check_token_refs(kind: &str, id: Uuid, refs: &[Uuid], known: &HashSet<Uuid>) -> DomainResult<()> {
+    match refs.iter().find(|t| !known.contains(t)) {
+        Some(missing) => Err(DomainError {
+            code: ErrorCode::PreconditionFailed,
+            message: "Artifact references a token outside the extraction run".to_string(),
+            details: Some(serde_json::json!({ "kind": kind, "id": id, "token_id": missing })),
+        }),
+        None => Ok(()),
+    }
+}
+
+fn artifact_error(message: &str, kind: &str, id: Uuid) -> DomainError {
+    DomainError {
+        code: ErrorCode::PreconditionFailed,
+        message: message.to_string(),
+        details: Some(serde_json::json!({ "kind": kind, "id": id })),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// One covered page with a token, a line over it and a 2x2 table.
+    fn artifacts() -> ExtractionArtifacts {
+        let (extraction_run_id, page_id) = (Uuid::now_v7(), Uuid::now_v7());
+        let bbox = BoundingBox::new(0.1, 0.1, 0.2, 0.05);
+        let token_id = Uuid::now_v7();
+        ExtractionArtifacts {
+            run: ExtractionRun {
+                extraction_run_id,
+                session_id: Uuid::now_v7(),
+                caused_by: Uuid::now_v7(),
+                engine: "test".to_string(),
+                params: serde_json::json!({}),
+                started_at: Utc::now(),
+                finished_at: None,
+                status: ExtractionRunStatus::Completed,
+                pages: vec![PageCoverage {
+                    document_id: Uuid::now_v7(),
+                    page_id,
+                    page_number: 1,
+                }],
+            },
+            tokens: vec![ExtractionToken {
+                token_id,
+                extraction_run_id,
+                page_id,
+                text: "Total".to_string(),
+                bbox,
+                confidence: 0.9,
+                reading_order: 0,
+            }],
+            lines: vec![ExtractionLine {
+                line_id: Uuid::now_v7(),
+                extraction_run_id,
+                page_id,
+                text: "Total".to_string(),
+                bbox,
+                confidence: 0.9,
+                reading_order: 0,
+                token_ids: vec![token_id],
+            }],
+            tables: vec![DetectedTable {
+                table_id: Uuid::now_v7(),
+                extraction_run_id,
+                page_id,
+                bbox: BoundingBox::new(0.0, 0.5, 1.0, 0.4),
+                confidence: 0.8,
+                row_count: 2,
+                column_count: 2,
+                cells: vec![DetectedTableCell {
+                    row: 1,
+                    column: 0,
+                    row_span: 1,
+                    column_span: 2,
+                    bbox: BoundingBox::new(0.0, 0.7, 1.0, 0.2),
+                    confidence: 0.8,
+                    token_ids: vec![token_id],
+                }],
+            }],
+        }
+    }
+
+    fn rejected(artifacts: &ExtractionArtifacts) -> String {
+        let err = artifacts.validate().unwrap_err();
+        assert!(matches!(err.code, ErrorCode::PreconditionFailed));
+        err.message
+    }
+
+    #[test]
+    fn consistent_artifacts_are_accepted() {
+        artifacts().validate().unwrap();
+    }
+
+    #[test]
+    fn artifacts_must_reference_known_tokens_and_covered_pages() {
+        let mut a = artifacts();
+        a.lines[0].token_ids.push(Uuid::now_v7());
+        assert_eq!(
+            rejected(&a),
+            "Artifact references a token outside the extraction run"
+        );
+
+        let mut a = artifacts();
+        a.tokens[0].page_id = Uuid::now_v7();
+        assert_eq!(
+            rejected(&a),
+            "Artifact page is not covered by the extraction run"
+        );
+
+        let mut a = artifacts();
+        a.lines[0].extraction_run_id = Uuid::now_v7();
+        assert_eq!(
+            rejected(&a),
+            "Artifact belongs to a different extraction run"
+        );
+    }
+
+    #[test]
+    fn geometry_must_stay_inside_the_unit_square() {
+        let mut a = artifacts();
+        a.tokens[0].bbox = BoundingBox::new(0.9, 0.1, 0.2, 0.05);
+        assert_eq!(rejected(&a), "Artifact bounding box is not page-normalized");
+
+        let mut a = artifacts();
+        a.tables[0].cells[0].bbox = BoundingBox::new(-0.1, 0.7, 0.5, 0.2);
+        assert_eq!(rejected(&a), "Artifact bounding box is not page-normalized");
+
+        let mut a = artifacts();
+        a.lines[0].confidence = 1.5;
+        assert_eq!(rejected(&a), "Artifact confidence must be within 0.0..=1.0");
+    }
+
+    #[test]
+    fn table_cells_must_fit_the_declared_grid() {
+        let mut a = artifacts();
+        a.tables[0].cells[0].row = 2;
+        assert_eq!(rejected(&a), "Table cell outside declared grid");
+
+        let mut a = artifacts();
+        a.tables[0].cells[0].column = 1;
+        assert_eq!(rejected(&a), "Table cell outside declared grid");
+
+        let mut a = artifacts();
+        a.tables[0].cells[0].row_span = 2;
+        assert_eq!(rejected(&a), "Table cell outside declared grid");
+    }
+}
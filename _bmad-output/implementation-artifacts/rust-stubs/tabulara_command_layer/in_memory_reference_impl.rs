@@ -1,16 +1,39 @@
-use std::collections::HashMap;
-use std::sync::{Arc, Mutex};
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Condvar, Mutex, PoisonError};
+use std::time::{Duration, Instant};
 
-use chrono::Utc;
+use chrono::{DateTime, Utc};
 use uuid::Uuid;
 
+use crate::anchors::AnchorRule;
 use crate::commands::CommandDto;
+use crate::dictionary::{DictionaryRule, NormalizationContext};
 use crate::errors::{DomainError, DomainResult, ErrorCode};
+use crate::extraction::{
+    DetectedTable, EngineOutput, ExtractionArtifacts, ExtractionLine, ExtractionRun,
+    ExtractionRunStatus, ExtractionToken,
+};
 use crate::interfaces::{
-    CommandOutcome, EventFactory, EventStore, IdempotencyState, IdempotencyStore,
-    InvariantEngine, ProjectionWriter, ReviewAction, UnitOfWork, ValidationTrigger,
+    AnchorStore, CommandOutcome, DictionaryStore, EventFactory, EventReader, EventStore,
+    ExtractionStore, HeaderSynonymStore, IdempotencyState, IdempotencyStore, InvariantEngine,
+    JobStore,
+    MappingStore, NormalizationContextResolver, OverrideStore, PageReader, ProjectionWriter,
+    ReviewAction, ReviewTaskStore, SchemaStore, SessionWriter, TemplateStore, UnitOfWork,
+    UnknownBucketStore, ValidationStore, ValidationTrigger, WriteLock, WriteLockManager,
+    WriteScope, ZoneStore,
 };
+use crate::jobs::{ExtractionJob, JobStatus};
+use crate::mapping::{ExtraRow, ExtraValue, FieldValue, ItemRow, ItemRowTombstone, ItemValue};
+use crate::overrides::{OverridePolicy, ValidationOverride};
+use crate::review_tasks::{open_review_tasks, ReviewTask};
+use crate::schema::{Project, Schema, SchemaVersion};
+use crate::sessions::SessionRecord;
+use crate::table_mapping::{normalize_header, HeaderSynonym};
+use crate::templates::{DocumentTemplate, PageLayout, Template};
 use crate::types::{DispatchResult, EventEnvelope, SessionStatus};
+use crate::unknown_bucket::UnknownFragment;
+use crate::validation::{ValidationResult, ValidationRule, ValidationRun};
+use crate::zones::Zone;
 
 #[derive(Debug, Clone)]
 struct IdempotencyEntry {
@@ -87,6 +110,17 @@ impl IdempotencyStore for InMemoryIdempotencyStore {
         entry.error = Some(error.clone());
         Ok(())
     }
+
+    fn release(&self, command_id: Uuid) -> DomainResult<()> {
+        let mut guard = self.entries.lock().map_err(lock_poisoned)?;
+        if guard
+            .get(&command_id)
+            .is_some_and(|e| e.status == EntryStatus::InProgress)
+        {
+            guard.remove(&command_id);
+        }
+        Ok(())
+    }
 }
 
 #[derive(Clone, Default)]
@@ -109,9 +143,35 @@ impl EventStore for InMemoryEventStore {
     }
 }
 
+impl EventReader for InMemoryEventStore {
+    fn session_events(&self, session_id: Uuid) -> DomainResult<Vec<EventEnvelope>> {
+        let guard = self.events.lock().map_err(lock_poisoned)?;
+        let session_id = session_id.to_string();
+        Ok(guard
+            .iter()
+            .filter(|e| {
+                e.data.get("session_id").and_then(|v| v.as_str()) == Some(session_id.as_str())
+            })
+            .cloned()
+            .collect())
+    }
+
+    fn session_version(&self, session_id: Uuid) -> DomainResult<u64> {
+        let guard = self.events.lock().map_err(lock_poisoned)?;
+        let session_id = session_id.to_string();
+        Ok(guard
+            .iter()
+            .filter(|e| {
+                e.data.get("session_id").and_then(|v| v.as_str()) == Some(session_id.as_str())
+            })
+            .count() as u64)
+    }
+}
+
 #[derive(Clone, Default)]
 pub struct InMemorySessionReader {
     statuses: Arc<Mutex<HashMap<Uuid, SessionStatus>>>,
+    records: Arc<Mutex<HashMap<Uuid, SessionRecord>>>,
 }
 
 impl InMemorySessionReader {
@@ -121,6 +181,15 @@ impl InMemorySessionReader {
         Ok(())
     }
 
+    pub fn register_session(&self, record: SessionRecord, status: SessionStatus) -> DomainResult<()> {
+        let session_id = record.session_id;
+        self.records
+            .lock()
+            .map_err(lock_poisoned)?
+            .insert(session_id, record);
+        self.set_status(session_id, status)
+    }
+
     pub fn statuses(&self) -> DomainResult<HashMap<Uuid, SessionStatus>> {
         let guard = self.statuses.lock().map_err(lock_poisoned)?;
         Ok(guard.clone())
@@ -136,13 +205,918 @@ impl crate::interfaces::SessionReader for InMemorySessionReader {
             details: Some(serde_json::json!({ "session_id": session_id })),
         })
     }
+
+    fn get_session(&self, session_id: Uuid) -> DomainResult<SessionRecord> {
+        let guard = self.records.lock().map_err(lock_poisoned)?;
+        guard.get(&session_id).cloned().ok_or_else(|| DomainError {
+            code: ErrorCode::NotFound,
+            message: "Session not found".to_string(),
+            details: Some(serde_json::json!({ "session_id": session_id })),
+        })
+    }
+
+    fn project_of(&self, session_id: Uuid) -> DomainResult<Uuid> {
+        Ok(self.get_session(session_id)?.project_id)
+    }
+
+    fn project_sessions(&self, project_id: Uuid) -> DomainResult<Vec<(Uuid, SessionStatus)>> {
+        let records = self.records.lock().map_err(lock_poisoned)?;
+        let statuses = self.statuses.lock().map_err(lock_poisoned)?;
+        let mut sessions: Vec<(Uuid, SessionStatus)> = records
+            .values()
+            .filter(|record| record.project_id == project_id)
+            .filter_map(|record| {
+                statuses
+                    .get(&record.session_id)
+                    .map(|status| (record.session_id, *status))
+            })
+            .collect();
+        sessions.sort_by_key(|(session, _)| *session);
+        Ok(sessions)
+    }
+}
+
+impl SessionWriter for InMemorySessionReader {
+    fn create_session(&self, record: &SessionRecord, status: SessionStatus) -> DomainResult<()> {
+        if self
+            .records
+            .lock()
+            .map_err(lock_poisoned)?
+            .contains_key(&record.session_id)
+        {
+            return Err(DomainError {
+                code: ErrorCode::PreconditionFailed,
+                message: "Session already exists".to_string(),
+                details: Some(serde_json::json!({ "session_id": record.session_id })),
+            });
+        }
+        self.register_session(record.clone(), status)
+    }
+}
+
+#[derive(Default)]
+struct SchemaTables {
+    projects: Vec<Project>,
+    schemas: Vec<Schema>,
+    versions: Vec<SchemaVersion>,
+}
+
+#[derive(Clone, Default)]
+pub struct InMemorySchemaStore {
+    tables: Arc<Mutex<SchemaTables>>,
+}
+
+impl SchemaStore for InMemorySchemaStore {
+    fn create_project(&self, project: &Project) -> DomainResult<()> {
+        let mut guard = self.tables.lock().map_err(lock_poisoned)?;
+        guard.projects.push(project.clone());
+        Ok(())
+    }
+
+    fn get_project(&self, project_id: Uuid) -> DomainResult<Project> {
+        let guard = self.tables.lock().map_err(lock_poisoned)?;
+        guard
+            .projects
+            .iter()
+            .find(|p| p.project_id == project_id)
+            .cloned()
+            .ok_or_else(|| DomainError {
+                code: ErrorCode::NotFound,
+                message: "Project not found".to_string(),
+                details: Some(serde_json::json!({ "project_id": project_id })),
+            })
+    }
+
+    fn create_schema(&self, schema: &Schema) -> DomainResult<()> {
+        let mut guard = self.tables.lock().map_err(lock_poisoned)?;
+        guard.schemas.push(schema.clone());
+        Ok(())
+    }
+
+    fn get_schema(&self, schema_id: Uuid) -> DomainResult<Schema> {
+        let guard = self.tables.lock().map_err(lock_poisoned)?;
+        guard
+            .schemas
+            .iter()
+            .find(|s| s.schema_id == schema_id)
+            .cloned()
+            .ok_or_else(|| schema_not_found(schema_id, None))
+    }
+
+    fn project_schemas(&self, project_id: Uuid) -> DomainResult<Vec<Schema>> {
+        let guard = self.tables.lock().map_err(lock_poisoned)?;
+        Ok(guard
+            .schemas
+            .iter()
+            .filter(|s| s.project_id == project_id)
+            .cloned()
+            .collect())
+    }
+
+    fn append_version(&self, version: &SchemaVersion) -> DomainResult<()> {
+        let mut guard = self.tables.lock().map_err(lock_poisoned)?;
+        let latest = guard
+            .versions
+            .iter()
+            .filter(|v| v.schema_id == version.schema_id)
+            .map(|v| v.version)
+            .max()
+            .unwrap_or(0);
+        if version.version != latest + 1 {
+            return Err(DomainError {
+                code: ErrorCode::InvariantViolation,
+                message: "Schema versions are append-only".to_string(),
+                details: Some(serde_json::json!({
+                    "schema_id": version.schema_id,
+                    "latest_version": latest,
+                    "version": version.version,
+                })),
+            });
+        }
+        guard.versions.push(version.clone());
+        Ok(())
+    }
+
+    fn version(&self, schema_id: Uuid, version: u32) -> DomainResult<SchemaVersion> {
+        let guard = self.tables.lock().map_err(lock_poisoned)?;
+        guard
+            .versions
+            .iter()
+            .find(|v| v.schema_id == schema_id && v.version == version)
+            .cloned()
+            .ok_or_else(|| schema_not_found(schema_id, Some(version)))
+    }
+
+    fn latest_version(&self, schema_id: Uuid) -> DomainResult<SchemaVersion> {
+        let guard = self.tables.lock().map_err(lock_poisoned)?;
+        guard
+            .versions
+            .iter()
+            .filter(|v| v.schema_id == schema_id)
+            .max_by_key(|v| v.version)
+            .cloned()
+            .ok_or_else(|| schema_not_found(schema_id, None))
+    }
+}
+
+fn schema_not_found(schema_id: Uuid, version: Option<u32>) -> DomainError {
+    DomainError {
+        code: ErrorCode::NotFound,
+        message: "Schema not found".to_string(),
+        details: Some(serde_json::json!({ "schema_id": schema_id, "version": version })),
+    }
+}
+
+#[derive(Clone, Default)]
+pub struct InMemoryPageReader {
+    pages: Arc<Mutex<HashMap<Uuid, Vec<PageLayout>>>>,
+}
+
+impl InMemoryPageReader {
+    pub fn add_page(&self, session_id: Uuid, page: PageLayout) -> DomainResult<()> {
+        let mut guard = self.pages.lock().map_err(lock_poisoned)?;
+        guard.entry(session_id).or_default().push(page);
+        Ok(())
+    }
+}
+
+impl PageReader for InMemoryPageReader {
+    fn session_pages(&self, session_id: Uuid) -> DomainResult<Vec<PageLayout>> {
+        let guard = self.pages.lock().map_err(lock_poisoned)?;
+        let mut pages = guard.get(&session_id).cloned().unwrap_or_default();
+        pages.sort_by_key(|p| (p.document_id, p.page_number));
+        Ok(pages)
+    }
+}
+
+#[derive(Default)]
+struct ExtractionTables {
+    runs: Vec<ExtractionRun>,
+    tokens: Vec<ExtractionToken>,
+    lines: Vec<ExtractionLine>,
+    tables: Vec<DetectedTable>,
+}
+
+#[derive(Clone, Default)]
+pub struct InMemoryExtractionStore {
+    tables: Arc<Mutex<ExtractionTables>>,
+}
+
+impl ExtractionStore for InMemoryExtractionStore {
+    fn record_run(&self, artifacts: &ExtractionArtifacts) -> DomainResult<()> {
+        artifacts.validate()?;
+        let mut guard = self.tables.lock().map_err(lock_poisoned)?;
+        let run_id = artifacts.run.extraction_run_id;
+        if guard.runs.iter().any(|r| r.extraction_run_id == run_id) {
+            return Err(DomainError {
+                code: ErrorCode::InvariantViolation,
+                message: "Extraction run already recorded".to_string(),
+                details: Some(serde_json::json!({ "extraction_run_id": run_id })),
+            });
+        }
+
+        guard.runs.push(artifacts.run.clone());
+        guard.tokens.extend(artifacts.tokens.iter().cloned());
+        guard.lines.extend(artifacts.lines.iter().cloned());
+        guard.tables.extend(artifacts.tables.iter().cloned());
+        Ok(())
+    }
+
+    fn get_run(&self, extraction_run_id: Uuid) -> DomainResult<ExtractionRun> {
+        let guard = self.tables.lock().map_err(lock_poisoned)?;
+        guard
+            .runs
+            .iter()
+            .find(|r| r.extraction_run_id == extraction_run_id)
+            .cloned()
+            .ok_or_else(|| DomainError {
+                code: ErrorCode::NotFound,
+                message: "Extraction run not found".to_string(),
+                details: Some(serde_json::json!({ "extraction_run_id": extraction_run_id })),
+            })
+    }
+
+    fn runs_for_session(&self, session_id: Uuid) -> DomainResult<Vec<ExtractionRun>> {
+        let guard = self.tables.lock().map_err(lock_poisoned)?;
+        Ok(guard
+            .runs
+            .iter()
+            .filter(|r| r.session_id == session_id)
+            .cloned()
+            .collect())
+    }
+
+    fn latest_run_for_page(&self, session_id: Uuid, page_id: Uuid) -> DomainResult<Option<ExtractionRun>> {
+        let guard = self.tables.lock().map_err(lock_poisoned)?;
+        Ok(guard
+            .runs
+            .iter()
+            .rev()
+            .find(|r| {
+                r.session_id == session_id
+                    && r.status == ExtractionRunStatus::Completed
+                    && r.covers_page(page_id)
+            })
+            .cloned())
+    }
+
+    fn tokens_for_page(&self, extraction_run_id: Uuid, page_id: Uuid) -> DomainResult<Vec<ExtractionToken>> {
+        let guard = self.tables.lock().map_err(lock_poisoned)?;
+        let mut tokens: Vec<ExtractionToken> = guard
+            .tokens
+            .iter()
+            .filter(|t| t.extraction_run_id == extraction_run_id && t.page_id == page_id)
+            .cloned()
+            .collect();
+        tokens.sort_by_key(|t| t.reading_order);
+        Ok(tokens)
+    }
+
+    fn lines_for_page(&self, extraction_run_id: Uuid, page_id: Uuid) -> DomainResult<Vec<ExtractionLine>> {
+        let guard = self.tables.lock().map_err(lock_poisoned)?;
+        let mut lines: Vec<ExtractionLine> = guard
+            .lines
+            .iter()
+            .filter(|l| l.extraction_run_id == extraction_run_id && l.page_id == page_id)
+            .cloned()
+            .collect();
+        lines.sort_by_key(|l| l.reading_order);
+        Ok(lines)
+    }
+
+    fn tables_for_page(&self, extraction_run_id: Uuid, page_id: Uuid) -> DomainResult<Vec<DetectedTable>> {
+        let guard = self.tables.lock().map_err(lock_poisoned)?;
+        Ok(guard
+            .tables
+            .iter()
+            .filter(|t| t.extraction_run_id == extraction_run_id && t.page_id == page_id)
+            .cloned()
+            .collect())
+    }
+
+    fn get_tokens(&self, token_ids: &[Uuid]) -> DomainResult<Vec<ExtractionToken>> {
+        let guard = self.tables.lock().map_err(lock_poisoned)?;
+        token_ids
+            .iter()
+            .map(|id| {
+                guard
+                    .tokens
+                    .iter()
+                    .find(|t| t.token_id == *id)
+                    .cloned()
+                    .ok_or_else(|| DomainError {
+                        code: ErrorCode::NotFound,
+                        message: "Extraction token not found".to_string(),
+                        details: Some(serde_json::json!({ "token_id": id })),
+                    })
+            })
+            .collect()
+    }
+
+    fn get_table(&self, table_id: Uuid) -> DomainResult<DetectedTable> {
+        let guard = self.tables.lock().map_err(lock_poisoned)?;
+        guard
+            .tables
+            .iter()
+            .find(|t| t.table_id == table_id)
+            .cloned()
+            .ok_or_else(|| DomainError {
+                code: ErrorCode::NotFound,
+                message: "Detected table not found".to_string(),
+                details: Some(serde_json::json!({ "table_id": table_id })),
+            })
+    }
+}
+
+#[derive(Clone, Default)]
+pub struct InMemoryMappingStore {
+    field_values: Arc<Mutex<Vec<FieldValue>>>,
+    item_rows: Arc<Mutex<Vec<ItemRow>>>,
+    item_row_tombstones: Arc<Mutex<Vec<ItemRowTombstone>>>,
+    item_values: Arc<Mutex<Vec<ItemValue>>>,
+    extra_rows: Arc<Mutex<Vec<ExtraRow>>>,
+    extra_values: Arc<Mutex<Vec<ExtraValue>>>,
+}
+
+impl MappingStore for InMemoryMappingStore {
+    fn field_values(&self, session_id: Uuid) -> DomainResult<Vec<FieldValue>> {
+        let guard = self.field_values.lock().map_err(lock_poisoned)?;
+        Ok(guard
+            .iter()
+            .filter(|v| v.session_id == session_id)
+            .cloned()
+            .collect())
+    }
+
+    fn put_field_value(&self, value: &FieldValue) -> DomainResult<()> {
+        let mut guard = self.field_values.lock().map_err(lock_poisoned)?;
+        match guard
+            .iter_mut()
+            .find(|v| v.field_value_id == value.field_value_id)
+        {
+            Some(existing) => *existing = value.clone(),
+            None => guard.push(value.clone()),
+        }
+        Ok(())
+    }
+
+    fn item_rows(&self, session_id: Uuid) -> DomainResult<Vec<ItemRow>> {
+        let guard = self.item_rows.lock().map_err(lock_poisoned)?;
+        Ok(guard
+            .iter()
+            .filter(|r| r.session_id == session_id)
+            .cloned()
+            .collect())
+    }
+
+    fn put_item_row(&self, row: &ItemRow) -> DomainResult<()> {
+        let mut guard = self.item_rows.lock().map_err(lock_poisoned)?;
+        match guard.iter_mut().find(|r| r.item_id == row.item_id) {
+            Some(existing) => *existing = row.clone(),
+            None => guard.push(row.clone()),
+        }
+        Ok(())
+    }
+
+    fn delete_item_row(&self, item_id: Uuid) -> DomainResult<()> {
+        self.item_rows
+            .lock()
+            .map_err(lock_poisoned)?
+            .retain(|r| r.item_id != item_id);
+        self.item_values
+            .lock()
+            .map_err(lock_poisoned)?
+            .retain(|v| v.item_id != item_id);
+        Ok(())
+    }
+
+    fn item_row_tombstones(&self, session_id: Uuid) -> DomainResult<Vec<ItemRowTombstone>> {
+        let guard = self.item_row_tombstones.lock().map_err(lock_poisoned)?;
+        Ok(guard
+            .iter()
+            .filter(|t| t.session_id == session_id)
+            .cloned()
+            .collect())
+    }
+
+    fn put_item_row_tombstone(&self, tombstone: &ItemRowTombstone) -> DomainResult<()> {
+        let mut guard = self.item_row_tombstones.lock().map_err(lock_poisoned)?;
+        match guard.iter_mut().find(|t| t.item_id == tombstone.item_id) {
+            Some(existing) => *existing = tombstone.clone(),
+            None => guard.push(tombstone.clone()),
+        }
+        Ok(())
+    }
+
+    fn item_values(&self, session_id: Uuid) -> DomainResult<Vec<ItemValue>> {
+        let guard = self.item_values.lock().map_err(lock_poisoned)?;
+        Ok(guard
+            .iter()
+            .filter(|v| v.session_id == session_id)
+            .cloned()
+            .collect())
+    }
+
+    fn put_item_value(&self, value: &ItemValue) -> DomainResult<()> {
+        let mut guard = self.item_values.lock().map_err(lock_poisoned)?;
+        match guard
+            .iter_mut()
+            .find(|v| v.item_value_id == value.item_value_id)
+        {
+            Some(existing) => *existing = value.clone(),
+            None => guard.push(value.clone()),
+        }
+        Ok(())
+    }
+
+    fn extra_rows(&self, session_id: Uuid) -> DomainResult<Vec<ExtraRow>> {
+        let guard = self.extra_rows.lock().map_err(lock_poisoned)?;
+        Ok(guard
+            .iter()
+            .filter(|r| r.session_id == session_id)
+            .cloned()
+            .collect())
+    }
+
+    fn put_extra_row(&self, row: &ExtraRow) -> DomainResult<()> {
+        let mut guard = self.extra_rows.lock().map_err(lock_poisoned)?;
+        match guard
+            .iter_mut()
+            .find(|r| r.extra_row_id == row.extra_row_id)
+        {
+            Some(existing) => *existing = row.clone(),
+            None => guard.push(row.clone()),
+        }
+        Ok(())
+    }
+
+    fn extra_values(&self, session_id: Uuid) -> DomainResult<Vec<ExtraValue>> {
+        let guard = self.extra_values.lock().map_err(lock_poisoned)?;
+        Ok(guard
+            .iter()
+            .filter(|v| v.session_id == session_id)
+            .cloned()
+            .collect())
+    }
+
+    fn put_extra_value(&self, value: &ExtraValue) -> DomainResult<()> {
+        let mut guard = self.extra_values.lock().map_err(lock_poisoned)?;
+        match guard
+            .iter_mut()
+            .find(|v| v.extra_value_id == value.extra_value_id)
+        {
+            Some(existing) => *existing = value.clone(),
+            None => guard.push(value.clone()),
+        }
+        Ok(())
+    }
+}
+
+#[derive(Default)]
+struct OverrideTables {
+    policies: HashMap<Uuid, OverridePolicy>,
+    overrides: Vec<ValidationOverride>,
+}
+
+#[derive(Clone, Default)]
+pub struct InMemoryOverrideStore {
+    tables: Arc<Mutex<OverrideTables>>,
+}
+
+impl OverrideStore for InMemoryOverrideStore {
+    fn policy(&self, project_id: Uuid) -> DomainResult<Option<OverridePolicy>> {
+        let guard = self.tables.lock().map_err(lock_poisoned)?;
+        Ok(guard.policies.get(&project_id).cloned())
+    }
+
+    fn set_policy(&self, policy: &OverridePolicy) -> DomainResult<()> {
+        let mut guard = self.tables.lock().map_err(lock_poisoned)?;
+        guard.policies.insert(policy.project_id, policy.clone());
+        Ok(())
+    }
+
+    fn put_override(&self, record: &ValidationOverride) -> DomainResult<()> {
+        let mut guard = self.tables.lock().map_err(lock_poisoned)?;
+        match guard
+            .overrides
+            .iter_mut()
+            .find(|o| o.override_id == record.override_id)
+        {
+            Some(existing) => *existing = record.clone(),
+            None => guard.overrides.push(record.clone()),
+        }
+        Ok(())
+    }
+
+    fn overrides(&self, session_id: Uuid) -> DomainResult<Vec<ValidationOverride>> {
+        let guard = self.tables.lock().map_err(lock_poisoned)?;
+        Ok(guard
+            .overrides
+            .iter()
+            .filter(|o| o.session_id == session_id)
+            .cloned()
+            .collect())
+    }
+}
+
+#[derive(Clone, Default)]
+pub struct InMemoryReviewTaskStore {
+    tasks: Arc<Mutex<Vec<ReviewTask>>>,
+}
+
+impl ReviewTaskStore for InMemoryReviewTaskStore {
+    fn put_task(&self, task: &ReviewTask) -> DomainResult<()> {
+        let mut guard = self.tasks.lock().map_err(lock_poisoned)?;
+        match guard
+            .iter_mut()
+            .find(|t| t.review_task_id == task.review_task_id)
+        {
+            Some(existing) => *existing = task.clone(),
+            None => guard.push(task.clone()),
+        }
+        Ok(())
+    }
+
+    fn get_task(&self, review_task_id: Uuid) -> DomainResult<Option<ReviewTask>> {
+        let guard = self.tasks.lock().map_err(lock_poisoned)?;
+        Ok(guard
+            .iter()
+            .find(|t| t.review_task_id == review_task_id)
+            .cloned())
+    }
+
+    fn tasks(&self, session_id: Uuid) -> DomainResult<Vec<ReviewTask>> {
+        let guard = self.tasks.lock().map_err(lock_poisoned)?;
+        Ok(guard
+            .iter()
+            .filter(|t| t.session_id == session_id)
+            .cloned()
+            .collect())
+    }
+}
+
+#[derive(Clone, Default)]
+pub struct InMemoryUnknownBucketStore {
+    fragments: Arc<Mutex<Vec<UnknownFragment>>>,
+}
+
+impl UnknownBucketStore for InMemoryUnknownBucketStore {
+    fn replace_page(
+        &self,
+        session_id: Uuid,
+        page_id: Uuid,
+        fragments: &[UnknownFragment],
+    ) -> DomainResult<()> {
+        let mut guard = self.fragments.lock().map_err(lock_poisoned)?;
+        guard.retain(|f| !(f.session_id == session_id && f.page_id == page_id));
+        guard.extend_from_slice(fragments);
+        Ok(())
+    }
+
+    fn fragments(&self, session_id: Uuid) -> DomainResult<Vec<UnknownFragment>> {
+        let guard = self.fragments.lock().map_err(lock_poisoned)?;
+        Ok(guard
+            .iter()
+            .filter(|f| f.session_id == session_id)
+            .cloned()
+            .collect())
+    }
+}
+
+#[derive(Default)]
+struct JobTables {
+    jobs: Vec<ExtractionJob>,
+    outputs: HashMap<Uuid, Vec<(Uuid, EngineOutput)>>,
+}
+
+impl JobTables {
+    fn job_mut(&mut self, job_id: Uuid) -> DomainResult<&mut ExtractionJob> {
+        self.jobs
+            .iter_mut()
+            .find(|j| j.job_id == job_id)
+            .ok_or_else(|| job_not_found(job_id))
+    }
+}
+
+fn job_not_found(job_id: Uuid) -> DomainError {
+    DomainError {
+        code: ErrorCode::NotFound,
+        message: "Job not found".to_string(),
+        details: Some(serde_json::json!({ "job_id": job_id })),
+    }
+}
+
+#[derive(Clone, Default)]
+pub struct InMemoryJobStore {
+    tables: Arc<Mutex<JobTables>>,
+}
+
+impl JobStore for InMemoryJobStore {
+    fn put_job(&self, job: &ExtractionJob) -> DomainResult<()> {
+        let mut guard = self.tables.lock().map_err(lock_poisoned)?;
+        match guard.jobs.iter_mut().find(|j| j.job_id == job.job_id) {
+            Some(existing) => *existing = job.clone(),
+            None => guard.jobs.push(job.clone()),
+        }
+        Ok(())
+    }
+
+    fn get_job(&self, job_id: Uuid) -> DomainResult<ExtractionJob> {
+        let mut guard = self.tables.lock().map_err(lock_poisoned)?;
+        guard.job_mut(job_id).cloned()
+    }
+
+    fn session_jobs(&self, session_id: Uuid) -> DomainResult<Vec<ExtractionJob>> {
+        let guard = self.tables.lock().map_err(lock_poisoned)?;
+        Ok(guard
+            .jobs
+            .iter()
+            .filter(|j| j.session_id == session_id)
+            .cloned()
+            .collect())
+    }
+
+    fn pending_jobs(&self) -> DomainResult<Vec<ExtractionJob>> {
+        let guard = self.tables.lock().map_err(lock_poisoned)?;
+        Ok(guard.jobs.iter().filter(|j| !j.completed).cloned().collect())
+    }
+
+    fn claim_next(&self, now: DateTime<Utc>) -> DomainResult<Option<ExtractionJob>> {
+        let mut guard = self.tables.lock().map_err(lock_poisoned)?;
+        let Some(job) = guard
+            .jobs
+            .iter_mut()
+            .find(|j| j.status == JobStatus::Queued)
+        else {
+            return Ok(None);
+        };
+        job.status = JobStatus::Running;
+        job.attempts += 1;
+        job.started_at.get_or_insert(now);
+        Ok(Some(job.clone()))
+    }
+
+    fn requeue(&self, job_id: Uuid) -> DomainResult<()> {
+        let mut guard = self.tables.lock().map_err(lock_poisoned)?;
+        let job = guard.job_mut(job_id)?;
+        if job.status == JobStatus::Running {
+            job.status = JobStatus::Queued;
+        }
+        Ok(())
+    }
+
+    fn record_page_output(
+        &self,
+        job_id: Uuid,
+        page_id: Uuid,
+        output: &EngineOutput,
+    ) -> DomainResult<ExtractionJob> {
+        let mut guard = self.tables.lock().map_err(lock_poisoned)?;
+        guard.job_mut(job_id)?;
+        let outputs = guard.outputs.entry(job_id).or_default();
+        outputs.retain(|(p, _)| *p != page_id);
+        outputs.push((page_id, output.clone()));
+        let pages_done = outputs.len();
+        let job = guard.job_mut(job_id)?;
+        job.progress.pages_done = pages_done;
+        Ok(job.clone())
+    }
+
+    fn page_outputs(&self, job_id: Uuid) -> DomainResult<Vec<(Uuid, EngineOutput)>> {
+        let mut guard = self.tables.lock().map_err(lock_poisoned)?;
+        let order: Vec<Uuid> = guard
+            .job_mut(job_id)?
+            .request
+            .pages
+            .iter()
+            .map(|p| p.page_id)
+            .collect();
+        let mut outputs = guard.outputs.get(&job_id).cloned().unwrap_or_default();
+        outputs.sort_by_key(|(page_id, _)| order.iter().position(|p| p == page_id));
+        Ok(outputs)
+    }
+
+    fn request_cancel(&self, job_id: Uuid) -> DomainResult<ExtractionJob> {
+        let mut guard = self.tables.lock().map_err(lock_poisoned)?;
+        let job = guard.job_mut(job_id)?;
+        job.cancel_requested = true;
+        Ok(job.clone())
+    }
+
+    fn finish(
+        &self,
+        job_id: Uuid,
+        status: JobStatus,
+        error: Option<DomainError>,
+        now: DateTime<Utc>,
+    ) -> DomainResult<()> {
+        let mut guard = self.tables.lock().map_err(lock_poisoned)?;
+        let job = guard.job_mut(job_id)?;
+        job.status = status;
+        job.error = error;
+        job.finished_at = Some(now);
+        Ok(())
+    }
+
+    fn mark_completed(&self, job_id: Uuid, error: Option<DomainError>) -> DomainResult<()> {
+        let mut guard = self.tables.lock().map_err(lock_poisoned)?;
+        let job = guard.job_mut(job_id)?;
+        job.completed = true;
+        job.completion_error = error;
+        Ok(())
+    }
+}
+
+#[derive(Default)]
+struct ValidationTables {
+    rules: Vec<ValidationRule>,
+    runs: Vec<ValidationRun>,
+    results: Vec<ValidationResult>,
+}
+
+#[derive(Clone, Default)]
+pub struct InMemoryValidationStore {
+    tables: Arc<Mutex<ValidationTables>>,
+}
+
+impl ValidationStore for InMemoryValidationStore {
+    fn add_rule(&self, rule: &ValidationRule) -> DomainResult<()> {
+        let mut guard = self.tables.lock().map_err(lock_poisoned)?;
+        guard.rules.push(rule.clone());
+        Ok(())
+    }
+
+    fn rules(&self, project_id: Uuid) -> DomainResult<Vec<ValidationRule>> {
+        let guard = self.tables.lock().map_err(lock_poisoned)?;
+        Ok(guard
+            .rules
+            .iter()
+            .filter(|r| r.project_id == project_id)
+            .cloned()
+            .collect())
+    }
+
+    fn record_run(&self, run: &ValidationRun, results: &[ValidationResult]) -> DomainResult<()> {
+        let mut guard = self.tables.lock().map_err(lock_poisoned)?;
+        guard.runs.push(run.clone());
+        guard.results.extend_from_slice(results);
+        Ok(())
+    }
+
+    fn latest_run(&self, session_id: Uuid) -> DomainResult<Option<ValidationRun>> {
+        let guard = self.tables.lock().map_err(lock_poisoned)?;
+        Ok(guard
+            .runs
+            .iter()
+            .rev()
+            .find(|r| r.session_id == session_id)
+            .cloned())
+    }
+
+    fn results(&self, validation_run_id: Uuid) -> DomainResult<Vec<ValidationResult>> {
+        let guard = self.tables.lock().map_err(lock_poisoned)?;
+        Ok(guard
+            .results
+            .iter()
+            .filter(|r| r.validation_run_id == validation_run_id)
+            .cloned()
+            .collect())
+    }
+}
+
+#[derive(Clone, Default)]
+pub struct InMemoryHeaderSynonymStore {
+    synonyms: Arc<Mutex<Vec<HeaderSynonym>>>,
+}
+
+impl HeaderSynonymStore for InMemoryHeaderSynonymStore {
+    fn synonyms(&self, project_id: Uuid) -> DomainResult<Vec<HeaderSynonym>> {
+        let guard = self.synonyms.lock().map_err(lock_poisoned)?;
+        Ok(guard
+            .iter()
+            .filter(|s| s.project_id == project_id)
+            .cloned()
+            .collect())
+    }
+
+    fn learn(&self, synonym: &HeaderSynonym) -> DomainResult<()> {
+        let mut guard = self.synonyms.lock().map_err(lock_poisoned)?;
+        let header = normalize_header(&synonym.header_text);
+        let known = guard.iter().any(|s| {
+            s.project_id == synonym.project_id
+                && s.schema_field_id == synonym.schema_field_id
+                && normalize_header(&s.header_text) == header
+        });
+        if !known {
+            guard.push(synonym.clone());
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone, Default)]
+pub struct InMemoryAnchorStore {
+    anchors: Arc<Mutex<Vec<AnchorRule>>>,
+}
+
+impl AnchorStore for InMemoryAnchorStore {
+    fn add(&self, rule: &AnchorRule) -> DomainResult<()> {
+        let mut guard = self.anchors.lock().map_err(lock_poisoned)?;
+        guard.push(rule.clone());
+        Ok(())
+    }
+
+    fn get(&self, anchor_id: Uuid) -> DomainResult<AnchorRule> {
+        let guard = self.anchors.lock().map_err(lock_poisoned)?;
+        guard
+            .iter()
+            .find(|a| a.anchor_id == anchor_id)
+            .cloned()
+            .ok_or_else(|| anchor_not_found(anchor_id))
+    }
+
+    fn set_enabled(&self, anchor_id: Uuid, enabled: bool) -> DomainResult<()> {
+        let mut guard = self.anchors.lock().map_err(lock_poisoned)?;
+        let anchor = guard
+            .iter_mut()
+            .find(|a| a.anchor_id == anchor_id)
+            .ok_or_else(|| anchor_not_found(anchor_id))?;
+        anchor.enabled = enabled;
+        Ok(())
+    }
+
+    fn rules(&self, project_id: Uuid) -> DomainResult<Vec<AnchorRule>> {
+        let guard = self.anchors.lock().map_err(lock_poisoned)?;
+        Ok(guard
+            .iter()
+            .filter(|a| a.project_id == project_id)
+            .cloned()
+            .collect())
+    }
+}
+
+fn anchor_not_found(anchor_id: Uuid) -> DomainError {
+    DomainError {
+        code: ErrorCode::NotFound,
+        message: "Anchor rule not found".to_string(),
+        details: Some(serde_json::json!({ "anchor_id": anchor_id })),
+    }
+}
+
+#[derive(Clone, Default)]
+pub struct InMemoryDictionaryStore {
+    rules: Arc<Mutex<Vec<DictionaryRule>>>,
+}
+
+impl DictionaryStore for InMemoryDictionaryStore {
+    fn add(&self, rule: &DictionaryRule) -> DomainResult<()> {
+        let mut guard = self.rules.lock().map_err(lock_poisoned)?;
+        guard.push(rule.clone());
+        Ok(())
+    }
+
+    fn get(&self, dictionary_rule_id: Uuid) -> DomainResult<DictionaryRule> {
+        let guard = self.rules.lock().map_err(lock_poisoned)?;
+        guard
+            .iter()
+            .find(|r| r.dictionary_rule_id == dictionary_rule_id)
+            .cloned()
+            .ok_or_else(|| dictionary_rule_not_found(dictionary_rule_id))
+    }
+
+    fn set_enabled(&self, dictionary_rule_id: Uuid, enabled: bool) -> DomainResult<()> {
+        let mut guard = self.rules.lock().map_err(lock_poisoned)?;
+        let rule = guard
+            .iter_mut()
+            .find(|r| r.dictionary_rule_id == dictionary_rule_id)
+            .ok_or_else(|| dictionary_rule_not_found(dictionary_rule_id))?;
+        rule.enabled = enabled;
+        Ok(())
+    }
+
+    fn rules(&self, project_id: Uuid) -> DomainResult<Vec<DictionaryRule>> {
+        let guard = self.rules.lock().map_err(lock_poisoned)?;
+        Ok(guard
+            .iter()
+            .filter(|r| r.project_id == project_id)
+            .cloned()
+            .collect())
+    }
+}
+
+fn dictionary_rule_not_found(dictionary_rule_id: Uuid) -> DomainError {
+    DomainError {
+        code: ErrorCode::NotFound,
+        message: "Dictionary rule not found".to_string(),
+        details: Some(serde_json::json!({ "dictionary_rule_id": dictionary_rule_id })),
+    }
 }
 
 #[derive(Clone, Default)]
 pub struct InMemoryProjectionWriter {
     statuses: Arc<Mutex<HashMap<Uuid, SessionStatus>>>,
     deltas: Arc<Mutex<Vec<serde_json::Value>>>,
-    review_actions: Arc<Mutex<Vec<ReviewAction>>>,
+    review_tasks: InMemoryReviewTaskStore,
     validation_triggers: Arc<Mutex<Vec<ValidationTrigger>>>,
 }
 
@@ -151,10 +1125,16 @@ impl InMemoryProjectionWriter {
         Self {
             statuses,
             deltas: Arc::new(Mutex::new(Vec::new())),
-            review_actions: Arc::new(Mutex::new(Vec::new())),
+            review_tasks: InMemoryReviewTaskStore::default(),
             validation_triggers: Arc::new(Mutex::new(Vec::new())),
         }
     }
+
+    /// Opens review tasks in `review_tasks` instead of a private store.
+    pub fn with_review_tasks(mut self, review_tasks: InMemoryReviewTaskStore) -> Self {
+        self.review_tasks = review_tasks;
+        self
+    }
 }
 
 impl ProjectionWriter for InMemoryProjectionWriter {
@@ -165,9 +1145,7 @@ impl ProjectionWriter for InMemoryProjectionWriter {
     }
 
     fn apply_review_actions(&self, actions: &[ReviewAction]) -> DomainResult<()> {
-        let mut guard = self.review_actions.lock().map_err(lock_poisoned)?;
-        guard.extend(actions.iter().cloned());
-        Ok(())
+        open_review_tasks(&self.review_tasks, actions, Utc::now())
     }
 
     fn apply_validation_trigger(&self, trigger: &ValidationTrigger) -> DomainResult<()> {
@@ -204,6 +1182,305 @@ impl UnitOfWork for InMemoryUnitOfWork {
     }
 }
 
+#[derive(Default)]
+struct WriteLockTable {
+    /// Projects held by a project command.
+    projects: HashSet<Uuid>,
+    /// Sessions held by session commands, per project.
+    sessions: HashMap<Uuid, HashSet<Uuid>>,
+    /// Project commands waiting per project; new session holds queue behind them.
+    waiting: HashMap<Uuid, usize>,
+    global: bool,
+}
+
+impl WriteLockTable {
+    fn is_free(&self, scope: WriteScope) -> bool {
+        match scope {
+            WriteScope::Session {
+                project_id,
+                session_id,
+            } => {
+                !self.projects.contains(&project_id)
+                    && self.waiting.get(&project_id).copied().unwrap_or(0) == 0
+                    && !self
+                        .sessions
+                        .get(&project_id)
+                        .is_some_and(|held| held.contains(&session_id))
+            }
+            WriteScope::Project { project_id } => {
+                !self.projects.contains(&project_id)
+                    && self
+                        .sessions
+                        .get(&project_id)
+                        .is_none_or(|held| held.is_empty())
+            }
+            WriteScope::Global => !self.global,
+        }
+    }
+
+    fn set_waiting(&mut self, scope: WriteScope, waiting: bool) {
+        let WriteScope::Project { project_id } = scope else {
+            return;
+        };
+        let count = self.waiting.entry(project_id).or_default();
+        if waiting {
+            *count += 1;
+        } else {
+            *count -= 1;
+            if *count == 0 {
+                self.waiting.remove(&project_id);
+            }
+        }
+    }
+
+    fn set_held(&mut self, scope: WriteScope, held: bool) {
+        match scope {
+            WriteScope::Session {
+                project_id,
+                session_id,
+            } => {
+                let sessions = self.sessions.entry(project_id).or_default();
+                if held {
+                    sessions.insert(session_id);
+                } else {
+                    sessions.remove(&session_id);
+                    if sessions.is_empty() {
+                        self.sessions.remove(&project_id);
+                    }
+                }
+            }
+            WriteScope::Project { project_id } => {
+                if held {
+                    self.projects.insert(project_id);
+                } else {
+                    self.projects.remove(&project_id);
+                }
+            }
+            WriteScope::Global => self.global = held,
+        }
+    }
+}
+
+/// Blocking write locks shared by every clone. Waiters give up after `timeout`.
+#[derive(Clone)]
+pub struct InMemoryWriteLocks {
+    table: Arc<(Mutex<WriteLockTable>, Condvar)>,
+    timeout: Duration,
+}
+
+impl Default for InMemoryWriteLocks {
+    fn default() -> Self {
+        Self::with_timeout(Duration::from_secs(30))
+    }
+}
+
+impl InMemoryWriteLocks {
+    pub fn with_timeout(timeout: Duration) -> Self {
+        Self {
+            table: Arc::default(),
+            timeout,
+        }
+    }
+
+    fn release(&self, scope: WriteScope) {
+        let (table, freed) = &*self.table;
+        // A panicking holder must still release its scope.
+        table
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .set_held(scope, false);
+        freed.notify_all();
+    }
+}
+
+struct HeldWriteScope<'l> {
+    locks: &'l InMemoryWriteLocks,
+    scope: WriteScope,
+}
+
+impl Drop for HeldWriteScope<'_> {
+    fn drop(&mut self) {
+        self.locks.release(self.scope);
+    }
+}
+
+impl WriteLockManager for InMemoryWriteLocks {
+    fn acquire(&self, scope: WriteScope) -> DomainResult<WriteLock<'_>> {
+        let (table, freed) = &*self.table;
+        let deadline = Instant::now() + self.timeout;
+        let mut held = table.lock().map_err(lock_poisoned)?;
+        held.set_waiting(scope, true);
+        while !held.is_free(scope) {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining.is_zero() {
+                held.set_waiting(scope, false);
+                drop(held);
+                freed.notify_all();
+                return Err(DomainError {
+                    code: ErrorCode::WriteScopeBusy,
+                    message: "Write scope is busy; retry the command".to_string(),
+                    details: Some(serde_json::json!({
+                        "scope": scope,
+                        "waited_ms": self.timeout.as_millis() as u64,
+                    })),
+                });
+            }
+            held = freed
+                .wait_timeout(held, remaining)
+                .map_err(lock_poisoned)?
+                .0;
+        }
+        held.set_waiting(scope, false);
+        held.set_held(scope, true);
+        Ok(WriteLock::new(HeldWriteScope { locks: self, scope }))
+    }
+}
+
+#[derive(Default)]
+struct TemplateTables {
+    templates: Vec<Template>,
+    assignments: HashMap<(Uuid, Uuid), DocumentTemplate>,
+}
+
+#[derive(Clone, Default)]
+pub struct InMemoryTemplateStore {
+    tables: Arc<Mutex<TemplateTables>>,
+}
+
+impl TemplateStore for InMemoryTemplateStore {
+    fn add(&self, template: &Template) -> DomainResult<()> {
+        let mut guard = self.tables.lock().map_err(lock_poisoned)?;
+        guard.templates.push(template.clone());
+        Ok(())
+    }
+
+    fn get(&self, template_id: Uuid) -> DomainResult<Template> {
+        let guard = self.tables.lock().map_err(lock_poisoned)?;
+        guard
+            .templates
+            .iter()
+            .find(|t| t.template_id == template_id)
+            .cloned()
+            .ok_or_else(|| DomainError {
+                code: ErrorCode::NotFound,
+                message: "Template not found".to_string(),
+                details: Some(serde_json::json!({ "template_id": template_id })),
+            })
+    }
+
+    fn templates(&self, project_id: Uuid) -> DomainResult<Vec<Template>> {
+        let guard = self.tables.lock().map_err(lock_poisoned)?;
+        Ok(guard
+            .templates
+            .iter()
+            .filter(|t| t.project_id == project_id)
+            .cloned()
+            .collect())
+    }
+
+    fn record_match(&self, assignment: &DocumentTemplate) -> DomainResult<()> {
+        let mut guard = self.tables.lock().map_err(lock_poisoned)?;
+        guard.assignments.insert(
+            (assignment.session_id, assignment.document_id),
+            assignment.clone(),
+        );
+        Ok(())
+    }
+
+    fn document_template(
+        &self,
+        session_id: Uuid,
+        document_id: Uuid,
+    ) -> DomainResult<Option<DocumentTemplate>> {
+        let guard = self.tables.lock().map_err(lock_poisoned)?;
+        Ok(guard.assignments.get(&(session_id, document_id)).cloned())
+    }
+}
+
+#[derive(Clone, Default)]
+pub struct InMemoryZoneStore {
+    zones: Arc<Mutex<Vec<Zone>>>,
+}
+
+impl ZoneStore for InMemoryZoneStore {
+    fn add(&self, zone: &Zone) -> DomainResult<()> {
+        let mut guard = self.zones.lock().map_err(lock_poisoned)?;
+        guard.push(zone.clone());
+        Ok(())
+    }
+
+    fn get(&self, zone_id: Uuid) -> DomainResult<Zone> {
+        let guard = self.zones.lock().map_err(lock_poisoned)?;
+        guard
+            .iter()
+            .find(|z| z.zone_id == zone_id)
+            .cloned()
+            .ok_or_else(|| DomainError {
+                code: ErrorCode::NotFound,
+                message: "Zone not found".to_string(),
+                details: Some(serde_json::json!({ "zone_id": zone_id })),
+            })
+    }
+
+    fn zones_for_template(&self, template_id: Uuid) -> DomainResult<Vec<Zone>> {
+        let guard = self.zones.lock().map_err(lock_poisoned)?;
+        Ok(guard
+            .iter()
+            .filter(|z| z.template_id == template_id)
+            .cloned()
+            .collect())
+    }
+}
+
+#[derive(Default)]
+struct ContextKeys {
+    field_keys: HashMap<Uuid, String>,
+    vendors: HashMap<Uuid, String>,
+    dictionary_names: HashMap<Uuid, String>,
+}
+
+/// Field keys by schema field, vendors by document, dictionary names by schema field.
+#[derive(Clone, Default)]
+pub struct InMemoryNormalizationContexts {
+    keys: Arc<Mutex<ContextKeys>>,
+}
+
+impl InMemoryNormalizationContexts {
+    pub fn set_field_key(&self, schema_field_id: Uuid, field_key: &str) -> DomainResult<()> {
+        let mut guard = self.keys.lock().map_err(lock_poisoned)?;
+        guard.field_keys.insert(schema_field_id, field_key.to_string());
+        Ok(())
+    }
+
+    pub fn set_vendor(&self, document_id: Uuid, vendor: &str) -> DomainResult<()> {
+        let mut guard = self.keys.lock().map_err(lock_poisoned)?;
+        guard.vendors.insert(document_id, vendor.to_string());
+        Ok(())
+    }
+
+    pub fn set_dictionary_name(&self, schema_field_id: Uuid, name: &str) -> DomainResult<()> {
+        let mut guard = self.keys.lock().map_err(lock_poisoned)?;
+        guard.dictionary_names.insert(schema_field_id, name.to_string());
+        Ok(())
+    }
+}
+
+impl NormalizationContextResolver for InMemoryNormalizationContexts {
+    fn context_for(
+        &self,
+        _session_id: Uuid,
+        document_id: Uuid,
+        schema_field_id: Uuid,
+    ) -> DomainResult<NormalizationContext> {
+        let guard = self.keys.lock().map_err(lock_poisoned)?;
+        Ok(NormalizationContext {
+            field_key: guard.field_keys.get(&schema_field_id).cloned(),
+            vendor: guard.vendors.get(&document_id).cloned(),
+            dictionary_name: guard.dictionary_names.get(&schema_field_id).cloned(),
+        })
+    }
+}
+
 #[derive(Clone, Default)]
 pub struct SimpleEventFactory;
 
@@ -219,16 +1496,31 @@ impl EventFactory for SimpleEventFactory {
             format!("{}Processed", command.command_type())
         };
 
-        Ok(vec![EventEnvelope {
+        let mut events = vec![EventEnvelope {
             event_id: Uuid::now_v7(),
             caused_by: command.command_id(),
             event_type,
             timestamp: Utc::now(),
             data: serde_json::json!({
                 "session_id": command.session_id(),
+                "actor": command.actor(),
                 "delta": outcome.state_delta.data,
             }),
-        }])
+        }];
+        for extra in &outcome.events {
+            events.push(EventEnvelope {
+                event_id: Uuid::now_v7(),
+                caused_by: command.command_id(),
+                event_type: extra.event_type.clone(),
+                timestamp: Utc::now(),
+                data: serde_json::json!({
+                    "session_id": extra.session_id,
+                    "actor": command.actor(),
+                    "delta": extra.data,
+                }),
+            });
+        }
+        Ok(events)
     }
 }
 
@@ -237,24 +1529,59 @@ pub struct InMemoryReferenceBundle {
     pub idempotency: InMemoryIdempotencyStore,
     pub events: InMemoryEventStore,
     pub sessions: InMemorySessionReader,
+    pub schemas: InMemorySchemaStore,
     pub projections: InMemoryProjectionWriter,
+    pub extraction: InMemoryExtractionStore,
+    pub mapping: InMemoryMappingStore,
+    pub header_synonyms: InMemoryHeaderSynonymStore,
+    pub anchors: InMemoryAnchorStore,
+    pub dictionary: InMemoryDictionaryStore,
+    pub contexts: InMemoryNormalizationContexts,
+    pub pages: InMemoryPageReader,
+    pub templates: InMemoryTemplateStore,
+    pub zones: InMemoryZoneStore,
+    pub validation: InMemoryValidationStore,
+    pub overrides: InMemoryOverrideStore,
+    pub review_tasks: InMemoryReviewTaskStore,
+    pub unknown_bucket: InMemoryUnknownBucketStore,
+    pub jobs: InMemoryJobStore,
     pub invariants: NoopInvariantEngine,
     pub event_factory: SimpleEventFactory,
+    pub locks: InMemoryWriteLocks,
     pub uow: InMemoryUnitOfWork,
 }
 
 impl InMemoryReferenceBundle {
     pub fn new() -> Self {
         let statuses = Arc::new(Mutex::new(HashMap::new()));
+        let review_tasks = InMemoryReviewTaskStore::default();
         Self {
             idempotency: InMemoryIdempotencyStore::default(),
             events: InMemoryEventStore::default(),
             sessions: InMemorySessionReader {
                 statuses: statuses.clone(),
+                records: Arc::default(),
             },
-            projections: InMemoryProjectionWriter::with_statuses(statuses),
+            schemas: InMemorySchemaStore::default(),
+            projections: InMemoryProjectionWriter::with_statuses(statuses)
+                .with_review_tasks(review_tasks.clone()),
+            extraction: InMemoryExtractionStore::default(),
+            mapping: InMemoryMappingStore::default(),
+            header_synonyms: InMemoryHeaderSynonymStore::default(),
+            anchors: InMemoryAnchorStore::default(),
+            dictionary: InMemoryDictionaryStore::default(),
+            contexts: InMemoryNormalizationContexts::default(),
+            pages: InMemoryPageReader::default(),
+            templates: InMemoryTemplateStore::default(),
+            zones: InMemoryZoneStore::default(),
+            validation: InMemoryValidationStore::default(),
+            overrides: InMemoryOverrideStore::default(),
+            review_tasks,
+            unknown_bucket: InMemoryUnknownBucketStore::default(),
+            jobs: InMemoryJobStore::default(),
             invariants: NoopInvariantEngine,
             event_factory: SimpleEventFactory,
+            locks: InMemoryWriteLocks::default(),
             uow: InMemoryUnitOfWork,
         }
     }
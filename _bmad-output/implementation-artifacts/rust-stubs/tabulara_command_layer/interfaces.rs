@@ -2,9 +2,26 @@ use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
+use crate::anchors::AnchorRule;
 use crate::commands::{AnyCommand, CommandDto};
+use crate::dictionary::{DictionaryRule, NormalizationContext};
 use crate::errors::{DomainError, DomainResult};
+use crate::extraction::{
+    DetectedTable, EngineOutput, ExtractionArtifacts, ExtractionLine, ExtractionRequest,
+    ExtractionRun, ExtractionToken,
+};
+use crate::jobs::{ExtractionJob, JobStatus};
+use crate::mapping::{ExtraRow, ExtraValue, FieldValue, ItemRow, ItemRowTombstone, ItemValue};
+use crate::overrides::{OverridePolicy, ValidationOverride};
+use crate::review_tasks::{ReviewCategory, ReviewPriority, ReviewTarget, ReviewTask};
+use crate::schema::{Project, Schema, SchemaVersion};
+use crate::sessions::SessionRecord;
+use crate::table_mapping::HeaderSynonym;
+use crate::templates::{DocumentTemplate, PageLayout, Template};
 use crate::types::{DispatchResult, EventEnvelope, SessionStatus, SessionStatusTransition};
+use crate::unknown_bucket::UnknownFragment;
+use crate::validation::{ValidationResult, ValidationRule, ValidationRun};
+use crate::zones::Zone;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct StateDelta {
@@ -14,7 +31,12 @@ pub struct StateDelta {
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ReviewAction {
-    pub kind: String,
+    pub session_id: Uuid,
+    pub category: ReviewCategory,
+    pub target: ReviewTarget,
+    pub priority: ReviewPriority,
+    /// Tells apart tasks of one category on the same target, e.g. the validation rule.
+    pub subject: Option<String>,
     pub payload: serde_json::Value,
 }
 
@@ -25,12 +47,22 @@ pub enum ValidationTrigger {
     Sync,
 }
 
+/// Named event a handler asks for in addition to the command's primary event, e.g. one
+/// event per session touched by a project-scoped command.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OutcomeEvent {
+    pub event_type: String,
+    pub session_id: Option<Uuid>,
+    pub data: serde_json::Value,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CommandOutcome {
     pub state_delta: StateDelta,
     pub transition: Option<SessionStatusTransition>,
     pub review_actions: Vec<ReviewAction>,
     pub validation_trigger: ValidationTrigger,
+    pub events: Vec<OutcomeEvent>,
 }
 
 #[derive(Debug, Clone)]
@@ -53,6 +85,9 @@ pub trait IdempotencyStore {
     fn begin(&self, command: &dyn CommandDto, request_hash: &str) -> DomainResult<IdempotencyState>;
     fn commit(&self, command_id: Uuid, result: &DispatchResult) -> DomainResult<()>;
     fn mark_failed(&self, command_id: Uuid, error: &DomainError) -> DomainResult<()>;
+    /// Drops an in-progress entry after a retryable rejection, so the same
+    /// `command_id` can be dispatched again.
+    fn release(&self, command_id: Uuid) -> DomainResult<()>;
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -74,12 +109,193 @@ pub trait EventStore {
     fn append(&self, events: &[EventEnvelope]) -> DomainResult<()>;
 }
 
+pub trait EventReader {
+    /// Events whose data names the session, in append order.
+    fn session_events(&self, session_id: Uuid) -> DomainResult<Vec<EventEnvelope>>;
+
+    /// Number of events recorded for the session; grows by one per event and
+    /// starts at 0 for a session without events.
+    fn session_version(&self, session_id: Uuid) -> DomainResult<u64> {
+        Ok(self.session_events(session_id)?.len() as u64)
+    }
+}
+
 pub trait InvariantEngine {
     fn assert_all(&self, session_id: Option<Uuid>) -> DomainResult<()>;
 }
 
 pub trait SessionReader {
     fn get_status(&self, session_id: Uuid) -> DomainResult<SessionStatus>;
+    fn get_session(&self, session_id: Uuid) -> DomainResult<SessionRecord>;
+    fn project_of(&self, session_id: Uuid) -> DomainResult<Uuid>;
+    fn project_sessions(&self, project_id: Uuid) -> DomainResult<Vec<(Uuid, SessionStatus)>>;
+}
+
+pub trait SessionWriter {
+    fn create_session(&self, record: &SessionRecord, status: SessionStatus) -> DomainResult<()>;
+}
+
+pub trait SchemaStore {
+    fn create_project(&self, project: &Project) -> DomainResult<()>;
+    fn get_project(&self, project_id: Uuid) -> DomainResult<Project>;
+    fn create_schema(&self, schema: &Schema) -> DomainResult<()>;
+    fn get_schema(&self, schema_id: Uuid) -> DomainResult<Schema>;
+    fn project_schemas(&self, project_id: Uuid) -> DomainResult<Vec<Schema>>;
+    /// Versions are append-only and must follow the latest version without gaps.
+    fn append_version(&self, version: &SchemaVersion) -> DomainResult<()>;
+    fn version(&self, schema_id: Uuid, version: u32) -> DomainResult<SchemaVersion>;
+    fn latest_version(&self, schema_id: Uuid) -> DomainResult<SchemaVersion>;
+}
+
+pub trait PageReader {
+    fn session_pages(&self, session_id: Uuid) -> DomainResult<Vec<PageLayout>>;
+}
+
+pub trait ExtractionStore {
+    fn record_run(&self, artifacts: &ExtractionArtifacts) -> DomainResult<()>;
+    fn get_run(&self, extraction_run_id: Uuid) -> DomainResult<ExtractionRun>;
+    fn runs_for_session(&self, session_id: Uuid) -> DomainResult<Vec<ExtractionRun>>;
+    /// Most recent completed run that covered `page_id`.
+    fn latest_run_for_page(&self, session_id: Uuid, page_id: Uuid) -> DomainResult<Option<ExtractionRun>>;
+    fn tokens_for_page(&self, extraction_run_id: Uuid, page_id: Uuid) -> DomainResult<Vec<ExtractionToken>>;
+    fn lines_for_page(&self, extraction_run_id: Uuid, page_id: Uuid) -> DomainResult<Vec<ExtractionLine>>;
+    fn tables_for_page(&self, extraction_run_id: Uuid, page_id: Uuid) -> DomainResult<Vec<DetectedTable>>;
+    fn get_tokens(&self, token_ids: &[Uuid]) -> DomainResult<Vec<ExtractionToken>>;
+    fn get_table(&self, table_id: Uuid) -> DomainResult<DetectedTable>;
+}
+
+pub trait ExtractionEngine {
+    fn extract(&self, request: &ExtractionRequest) -> DomainResult<EngineOutput>;
+}
+
+/// Background extraction jobs. Every method is atomic, so workers and command
+/// handlers can update the same job concurrently.
+pub trait JobStore {
+    fn put_job(&self, job: &ExtractionJob) -> DomainResult<()>;
+    fn get_job(&self, job_id: Uuid) -> DomainResult<ExtractionJob>;
+    fn session_jobs(&self, session_id: Uuid) -> DomainResult<Vec<ExtractionJob>>;
+    /// Jobs whose completion command has not been dispatched yet, oldest first.
+    fn pending_jobs(&self) -> DomainResult<Vec<ExtractionJob>>;
+    /// Marks the oldest queued job running, counting the attempt, and returns it.
+    fn claim_next(&self, now: DateTime<Utc>) -> DomainResult<Option<ExtractionJob>>;
+    /// Moves an interrupted running job back to the queue.
+    fn requeue(&self, job_id: Uuid) -> DomainResult<()>;
+    /// Stores one page's engine output and advances the progress; returns the job.
+    fn record_page_output(
+        &self,
+        job_id: Uuid,
+        page_id: Uuid,
+        output: &EngineOutput,
+    ) -> DomainResult<ExtractionJob>;
+    /// Page outputs recorded so far, in the job's page order.
+    fn page_outputs(&self, job_id: Uuid) -> DomainResult<Vec<(Uuid, EngineOutput)>>;
+    fn request_cancel(&self, job_id: Uuid) -> DomainResult<ExtractionJob>;
+    fn finish(
+        &self,
+        job_id: Uuid,
+        status: JobStatus,
+        error: Option<DomainError>,
+        now: DateTime<Utc>,
+    ) -> DomainResult<()>;
+    fn mark_completed(&self, job_id: Uuid, error: Option<DomainError>) -> DomainResult<()>;
+}
+
+pub trait MappingStore {
+    fn field_values(&self, session_id: Uuid) -> DomainResult<Vec<FieldValue>>;
+    fn put_field_value(&self, value: &FieldValue) -> DomainResult<()>;
+    fn item_rows(&self, session_id: Uuid) -> DomainResult<Vec<ItemRow>>;
+    fn put_item_row(&self, row: &ItemRow) -> DomainResult<()>;
+    /// Removes the row together with its item values.
+    fn delete_item_row(&self, item_id: Uuid) -> DomainResult<()>;
+    fn item_row_tombstones(&self, session_id: Uuid) -> DomainResult<Vec<ItemRowTombstone>>;
+    /// Inserts or replaces by `item_id`.
+    fn put_item_row_tombstone(&self, tombstone: &ItemRowTombstone) -> DomainResult<()>;
+    fn item_values(&self, session_id: Uuid) -> DomainResult<Vec<ItemValue>>;
+    fn put_item_value(&self, value: &ItemValue) -> DomainResult<()>;
+    fn extra_rows(&self, session_id: Uuid) -> DomainResult<Vec<ExtraRow>>;
+    fn put_extra_row(&self, row: &ExtraRow) -> DomainResult<()>;
+    fn extra_values(&self, session_id: Uuid) -> DomainResult<Vec<ExtraValue>>;
+    fn put_extra_value(&self, value: &ExtraValue) -> DomainResult<()>;
+}
+
+pub trait HeaderSynonymStore {
+    fn synonyms(&self, project_id: Uuid) -> DomainResult<Vec<HeaderSynonym>>;
+    fn learn(&self, synonym: &HeaderSynonym) -> DomainResult<()>;
+}
+
+pub trait AnchorStore {
+    fn add(&self, rule: &AnchorRule) -> DomainResult<()>;
+    fn get(&self, anchor_id: Uuid) -> DomainResult<AnchorRule>;
+    fn set_enabled(&self, anchor_id: Uuid, enabled: bool) -> DomainResult<()>;
+    fn rules(&self, project_id: Uuid) -> DomainResult<Vec<AnchorRule>>;
+}
+
+pub trait DictionaryStore {
+    fn add(&self, rule: &DictionaryRule) -> DomainResult<()>;
+    fn get(&self, dictionary_rule_id: Uuid) -> DomainResult<DictionaryRule>;
+    fn set_enabled(&self, dictionary_rule_id: Uuid, enabled: bool) -> DomainResult<()>;
+    fn rules(&self, project_id: Uuid) -> DomainResult<Vec<DictionaryRule>>;
+}
+
+pub trait TemplateStore {
+    fn add(&self, template: &Template) -> DomainResult<()>;
+    fn get(&self, template_id: Uuid) -> DomainResult<Template>;
+    fn templates(&self, project_id: Uuid) -> DomainResult<Vec<Template>>;
+    fn record_match(&self, assignment: &DocumentTemplate) -> DomainResult<()>;
+    fn document_template(&self, session_id: Uuid, document_id: Uuid) -> DomainResult<Option<DocumentTemplate>>;
+}
+
+pub trait ZoneStore {
+    fn add(&self, zone: &Zone) -> DomainResult<()>;
+    fn get(&self, zone_id: Uuid) -> DomainResult<Zone>;
+    fn zones_for_template(&self, template_id: Uuid) -> DomainResult<Vec<Zone>>;
+}
+
+pub trait ValidationStore {
+    fn add_rule(&self, rule: &ValidationRule) -> DomainResult<()>;
+    fn rules(&self, project_id: Uuid) -> DomainResult<Vec<ValidationRule>>;
+    fn record_run(&self, run: &ValidationRun, results: &[ValidationResult]) -> DomainResult<()>;
+    fn latest_run(&self, session_id: Uuid) -> DomainResult<Option<ValidationRun>>;
+    fn results(&self, validation_run_id: Uuid) -> DomainResult<Vec<ValidationResult>>;
+}
+
+pub trait OverrideStore {
+    /// `None` until the project configures its own policy.
+    fn policy(&self, project_id: Uuid) -> DomainResult<Option<OverridePolicy>>;
+    fn set_policy(&self, policy: &OverridePolicy) -> DomainResult<()>;
+    /// Inserts or replaces by `override_id`.
+    fn put_override(&self, record: &ValidationOverride) -> DomainResult<()>;
+    fn overrides(&self, session_id: Uuid) -> DomainResult<Vec<ValidationOverride>>;
+}
+
+pub trait ReviewTaskStore {
+    /// Inserts or replaces by `review_task_id`.
+    fn put_task(&self, task: &ReviewTask) -> DomainResult<()>;
+    fn get_task(&self, review_task_id: Uuid) -> DomainResult<Option<ReviewTask>>;
+    fn tasks(&self, session_id: Uuid) -> DomainResult<Vec<ReviewTask>>;
+}
+
+pub trait UnknownBucketStore {
+    /// Replaces every fragment stored for the page.
+    fn replace_page(
+        &self,
+        session_id: Uuid,
+        page_id: Uuid,
+        fragments: &[UnknownFragment],
+    ) -> DomainResult<()>;
+    fn fragments(&self, session_id: Uuid) -> DomainResult<Vec<UnknownFragment>>;
+}
+
+/// Supplies field key, vendor and dictionary name for dictionary rule scoping of the
+/// value a session holds for `schema_field_id` in a document (for item and extra
+/// values, the document of their row).
+pub trait NormalizationContextResolver {
+    fn context_for(
+        &self,
+        session_id: Uuid,
+        document_id: Uuid,
+        schema_field_id: Uuid,
+    ) -> DomainResult<NormalizationContext>;
 }
 
 pub trait ProjectionWriter {
@@ -95,6 +311,37 @@ pub trait UnitOfWork {
         F: FnOnce() -> DomainResult<T>;
 }
 
+/// What a command's writes are serialized on. Session commands hold their session
+/// and share their project; project commands (rules, templates, schemas, session
+/// creation) hold the whole project, since they can re-apply to its open sessions.
+/// `Global` covers commands outside any project, i.e. `CreateProject`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(tag = "scope", rename_all = "snake_case")]
+pub enum WriteScope {
+    Session { project_id: Uuid, session_id: Uuid },
+    Project { project_id: Uuid },
+    Global,
+}
+
+/// A held write scope, released when dropped.
+pub struct WriteLock<'l> {
+    _held: Box<dyn Send + 'l>,
+}
+
+impl<'l> WriteLock<'l> {
+    pub fn new(held: impl Send + 'l) -> Self {
+        Self {
+            _held: Box::new(held),
+        }
+    }
+}
+
+pub trait WriteLockManager {
+    /// Waits until `scope` is free and holds it. A scope that stays busy past the
+    /// implementation's bound is rejected with `WRITE_SCOPE_BUSY`.
+    fn acquire(&self, scope: WriteScope) -> DomainResult<WriteLock<'_>>;
+}
+
 pub trait CommandDispatcher {
     fn dispatch(&self, command: AnyCommand) -> DomainResult<DispatchResult>;
 }
@@ -104,9 +351,11 @@ pub struct DispatcherDeps<'a, U: UnitOfWork> {
     pub transitions: &'a dyn TransitionPolicy,
     pub idempotency: &'a dyn IdempotencyStore,
     pub events: &'a dyn EventStore,
+    pub event_reader: &'a dyn EventReader,
     pub event_factory: &'a dyn EventFactory,
     pub invariants: &'a dyn InvariantEngine,
     pub sessions: &'a dyn SessionReader,
     pub projections: &'a dyn ProjectionWriter,
+    pub locks: &'a dyn WriteLockManager,
     pub uow: &'a U,
 }
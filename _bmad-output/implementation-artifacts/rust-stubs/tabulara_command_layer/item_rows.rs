@@ -0,0 +1,694 @@
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::commands::{AddItemRow, AnyCommand, DeleteItemRow, MoveItemRow};
+use crate::errors::{unsupported_command, value_locked, DomainError, DomainResult, ErrorCode};
+use crate::interfaces::{
+    CommandContext, CommandOutcome, GenericCommandHandler, MappingStore, PageReader, SchemaStore,
+    SessionReader, StateDelta, ValidationTrigger,
+};
+use crate::mapping::{item_row, ItemRow, ItemRowTombstone, ItemValue, ValueChange, WrittenValue};
+use crate::schema::session_schema;
+use crate::types::{FieldScope, SessionStatus, SessionStatusTransition};
+
+/// What deleting an item row does to the rows after it.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ItemRowDeletion {
+    /// Later rows move up one position.
+    #[default]
+    Compact,
+    /// The row leaves a tombstone at its position; later rows keep theirs.
+    Tombstone,
+}
+
+enum Slot {
+    Row(ItemRow),
+    Tombstone(ItemRowTombstone),
+}
+
+impl Slot {
+    fn item_id(&self) -> Uuid {
+        match self {
+            Slot::Row(row) => row.item_id,
+            Slot::Tombstone(tombstone) => tombstone.item_id,
+        }
+    }
+
+    fn row_index(&self) -> u32 {
+        match self {
+            Slot::Row(row) => row.row_index,
+            Slot::Tombstone(tombstone) => tombstone.row_index,
+        }
+    }
+}
+
+/// Item rows and tombstones of one document in position order. Positions are
+/// always `0..len`: a document whose stored indexes collide or have gaps is
+/// renumbered in `(row_index, item_id)` order on its next change. Rows are
+/// identified by `item_id`, so values, provenance and review tasks follow a row
+/// wherever it moves.
+struct DocumentRows {
+    slots: Vec<Slot>,
+}
+
+impl DocumentRows {
+    fn load(mapping: &dyn MappingStore, session_id: Uuid, document_id: Uuid) -> DomainResult<Self> {
+        let rows = mapping
+            .item_rows(session_id)?
+            .into_iter()
+            .filter(|r| r.document_id == document_id)
+            .map(Slot::Row);
+        let tombstones = mapping
+            .item_row_tombstones(session_id)?
+            .into_iter()
+            .filter(|t| t.document_id == document_id)
+            .map(Slot::Tombstone);
+        let mut slots: Vec<Slot> = rows.chain(tombstones).collect();
+        slots.sort_by_key(|s| (s.row_index(), s.item_id()));
+        Ok(Self { slots })
+    }
+
+    fn position(&self, item_id: Uuid) -> Option<usize> {
+        self.slots.iter().position(|s| s.item_id() == item_id)
+    }
+
+    /// Writes every slot whose position changed and returns the moved item ids.
+    fn store(&mut self, mapping: &dyn MappingStore) -> DomainResult<Vec<Uuid>> {
+        let mut moved = Vec::new();
+        for (position, slot) in self.slots.iter_mut().enumerate() {
+            let position = position as u32;
+            if slot.row_index() == position {
+                continue;
+            }
+            moved.push(slot.item_id());
+            match slot {
+                Slot::Row(row) => {
+                    row.row_index = position;
+                    mapping.put_item_row(row)?;
+                }
+                Slot::Tombstone(tombstone) => {
+                    tombstone.row_index = position;
+                    mapping.put_item_row_tombstone(tombstone)?;
+                }
+            }
+        }
+        Ok(moved)
+    }
+}
+
+/// Position for `row_index` among `len` slots; `max` is `len` for an insert and
+/// `len - 1` for a move.
+fn slot_index(row_index: i32, max: usize) -> DomainResult<usize> {
+    usize::try_from(row_index)
+        .ok()
+        .filter(|index| *index <= max)
+        .ok_or_else(|| DomainError {
+            code: ErrorCode::PreconditionFailed,
+            message: format!("row_index must be between 0 and {max}"),
+            details: Some(serde_json::json!({ "row_index": row_index, "max": max })),
+        })
+}
+
+/// Adding, removing or reordering a row changes every item field of its document.
+fn row_changes(
+    sessions: &dyn SessionReader,
+    schemas: &dyn SchemaStore,
+    session_id: Uuid,
+    document_id: Uuid,
+) -> DomainResult<Vec<ValueChange>> {
+    let schema = session_schema(sessions, schemas, session_id)?;
+    Ok(schema
+        .active_fields()
+        .filter(|f| f.scope == FieldScope::Item)
+        .map(|f| ValueChange {
+            document_id,
+            schema_field_id: f.schema_field_id,
+        })
+        .collect())
+}
+
+fn row_outcome(
+    summary: &str,
+    status: SessionStatus,
+    data: serde_json::Value,
+    changed: Vec<ValueChange>,
+) -> CommandOutcome {
+    let mut data = data;
+    data["changed_values"] = serde_json::json!(changed);
+    let transition = (status == SessionStatus::Validated).then_some(SessionStatusTransition {
+        from: status,
+        to: SessionStatus::Review,
+    });
+    CommandOutcome {
+        state_delta: StateDelta {
+            summary: summary.to_string(),
+            data,
+        },
+        transition,
+        review_actions: Vec::new(),
+        validation_trigger: ValidationTrigger::Async,
+        events: Vec::new(),
+    }
+}
+
+pub struct ItemRowDeps<'a> {
+    pub mapping: &'a dyn MappingStore,
+    pub sessions: &'a dyn SessionReader,
+    pub schemas: &'a dyn SchemaStore,
+    pub pages: &'a dyn PageReader,
+}
+
+/// Inserts an item row at `row_index`; the row there and every later row shift down
+/// one position. `row_index` may be the row count to append.
+pub struct AddItemRowHandler<'a> {
+    deps: ItemRowDeps<'a>,
+}
+
+impl<'a> AddItemRowHandler<'a> {
+    pub fn new(deps: ItemRowDeps<'a>) -> Self {
+        Self { deps }
+    }
+
+    fn add(&self, cmd: &AddItemRow) -> DomainResult<CommandOutcome> {
+        let payload = &cmd.payload;
+        let session_id = payload.session_id;
+        let status = self.deps.sessions.get_status(session_id)?;
+        if !self
+            .deps
+            .pages
+            .session_pages(session_id)?
+            .iter()
+            .any(|p| p.document_id == payload.document_id)
+        {
+            return Err(DomainError {
+                code: ErrorCode::NotFound,
+                message: "Document is not part of the session".to_string(),
+                details: Some(serde_json::json!({ "document_id": payload.document_id })),
+            });
+        }
+        let item_id = payload.item_id.unwrap_or_else(Uuid::now_v7);
+        let taken = self
+            .deps
+            .mapping
+            .item_rows(session_id)?
+            .iter()
+            .any(|r| r.item_id == item_id)
+            || self
+                .deps
+                .mapping
+                .item_row_tombstones(session_id)?
+                .iter()
+                .any(|t| t.item_id == item_id);
+        if taken {
+            return Err(DomainError {
+                code: ErrorCode::PreconditionFailed,
+                message: "Item id is already used".to_string(),
+                details: Some(serde_json::json!({ "item_id": item_id })),
+            });
+        }
+
+        let mut rows = DocumentRows::load(self.deps.mapping, session_id, payload.document_id)?;
+        let index = slot_index(payload.row_index, rows.slots.len())?;
+        let row = ItemRow {
+            item_id,
+            session_id,
+            document_id: payload.document_id,
+            // Never a position, so `store` writes the new row.
+            row_index: u32::MAX,
+            locked: false,
+        };
+        rows.slots.insert(index, Slot::Row(row.clone()));
+        let mut shifted = rows.store(self.deps.mapping)?;
+        shifted.retain(|id| *id != item_id);
+
+        let changed = row_changes(
+            self.deps.sessions,
+            self.deps.schemas,
+            session_id,
+            payload.document_id,
+        )?;
+        Ok(row_outcome(
+            "Item row added",
+            status,
+            serde_json::json!({
+                "item_row": ItemRow {
+                    row_index: index as u32,
+                    ..row
+                },
+                "shifted_item_ids": shifted,
+            }),
+            changed,
+        ))
+    }
+}
+
+impl<'a> GenericCommandHandler for AddItemRowHandler<'a> {
+    fn can_handle(&self, command_type: &str) -> bool {
+        command_type == "AddItemRow"
+    }
+
+    fn handle(&self, _ctx: &mut CommandContext, cmd: &AnyCommand) -> DomainResult<CommandOutcome> {
+        match cmd {
+            AnyCommand::AddItemRow(c) => self.add(c),
+            _ => Err(unsupported_command("AddItemRowHandler")),
+        }
+    }
+}
+
+/// Removes an item row and its values, then compacts the document's rows or leaves
+/// a tombstone as the project's `item_row_deletion` says. A locked row, or a row
+/// holding a locked value, is rejected with `VALUE_LOCKED`.
+pub struct DeleteItemRowHandler<'a> {
+    deps: ItemRowDeps<'a>,
+}
+
+impl<'a> DeleteItemRowHandler<'a> {
+    pub fn new(deps: ItemRowDeps<'a>) -> Self {
+        Self { deps }
+    }
+
+    fn delete(&self, ctx: &CommandContext, cmd: &DeleteItemRow) -> DomainResult<CommandOutcome> {
+        let payload = &cmd.payload;
+        let session_id = payload.session_id;
+        let session = self.deps.sessions.get_session(session_id)?;
+        let status = self.deps.sessions.get_status(session_id)?;
+        let row = item_row(self.deps.mapping, session_id, payload.item_id)?;
+        let values: Vec<ItemValue> = self
+            .deps
+            .mapping
+            .item_values(session_id)?
+            .into_iter()
+            .filter(|v| v.item_id == row.item_id)
+            .collect();
+        let locked_values: Vec<Uuid> = values
+            .iter()
+            .filter(|v| v.locked)
+            .map(|v| v.item_value_id)
+            .collect();
+        if row.locked || !locked_values.is_empty() {
+            return Err(value_locked(
+                "Item row is locked",
+                serde_json::json!({
+                    "item_id": row.item_id,
+                    "row_locked": row.locked,
+                    "locked_item_value_ids": locked_values,
+                }),
+            ));
+        }
+        let deletion = self
+            .deps
+            .schemas
+            .get_project(session.project_id)?
+            .item_row_deletion;
+
+        let mut rows = DocumentRows::load(self.deps.mapping, session_id, row.document_id)?;
+        let position = rows.position(row.item_id).ok_or_else(|| DomainError {
+            code: ErrorCode::Internal,
+            message: "Item row missing from its document's rows".to_string(),
+            details: Some(serde_json::json!({ "item_id": row.item_id })),
+        })?;
+        self.deps.mapping.delete_item_row(row.item_id)?;
+        let tombstone = match deletion {
+            ItemRowDeletion::Compact => {
+                rows.slots.remove(position);
+                None
+            }
+            ItemRowDeletion::Tombstone => {
+                let tombstone = ItemRowTombstone {
+                    item_id: row.item_id,
+                    session_id,
+                    document_id: row.document_id,
+                    row_index: row.row_index,
+                    deleted_by: ctx.actor.clone(),
+                    deleted_at: ctx.now,
+                };
+                self.deps.mapping.put_item_row_tombstone(&tombstone)?;
+                rows.slots[position] = Slot::Tombstone(tombstone.clone());
+                Some(tombstone)
+            }
+        };
+        let mut shifted = rows.store(self.deps.mapping)?;
+        shifted.retain(|id| *id != row.item_id);
+
+        let changed = row_changes(
+            self.deps.sessions,
+            self.deps.schemas,
+            session_id,
+            row.document_id,
+        )?;
+        Ok(row_outcome(
+            "Item row deleted",
+            status,
+            serde_json::json!({
+                "item_row": row,
+                "deletion": deletion,
+                "tombstone": tombstone,
+                "shifted_item_ids": shifted,
+                "removed_values": values.into_iter().map(WrittenValue::Item).collect::<Vec<_>>(),
+            }),
+            changed,
+        ))
+    }
+}
+
+impl<'a> GenericCommandHandler for DeleteItemRowHandler<'a> {
+    fn can_handle(&self, command_type: &str) -> bool {
+        command_type == "DeleteItemRow"
+    }
+
+    fn handle(&self, ctx: &mut CommandContext, cmd: &AnyCommand) -> DomainResult<CommandOutcome> {
+        match cmd {
+            AnyCommand::DeleteItemRow(c) => self.delete(ctx, c),
+            _ => Err(unsupported_command("DeleteItemRowHandler")),
+        }
+    }
+}
+
+/// Moves an item row to `row_index` within its document; rows in between shift by
+/// one toward the old position. Tombstones count as positions.
+pub struct MoveItemRowHandler<'a> {
+    deps: ItemRowDeps<'a>,
+}
+
+impl<'a> MoveItemRowHandler<'a> {
+    pub fn new(deps: ItemRowDeps<'a>) -> Self {
+        Self { deps }
+    }
+
+    fn move_row(&self, cmd: &MoveItemRow) -> DomainResult<CommandOutcome> {
+        let payload = &cmd.payload;
+        let session_id = payload.session_id;
+        let status = self.deps.sessions.get_status(session_id)?;
+        let row = item_row(self.deps.mapping, session_id, payload.item_id)?;
+        let mut rows = DocumentRows::load(self.deps.mapping, session_id, row.document_id)?;
+        let from = rows.position(row.item_id).ok_or_else(|| DomainError {
+            code: ErrorCode::Internal,
+            message: "Item row missing from its document's rows".to_string(),
+            details: Some(serde_json::json!({ "item_id": row.item_id })),
+        })?;
+        let to = slot_index(payload.row_index, rows.slots.len() - 1)?;
+        let slot = rows.slots.remove(from);
+        rows.slots.insert(to, slot);
+        let shifted = rows.store(self.deps.mapping)?;
+        if shifted.is_empty() {
+            return Ok(CommandOutcome {
+                state_delta: StateDelta {
+                    summary: "Item row already at position".to_string(),
+                    data: serde_json::json!({ "item_id": row.item_id, "row_index": to }),
+                },
+                transition: None,
+                review_actions: Vec::new(),
+                validation_trigger: ValidationTrigger::None,
+                events: Vec::new(),
+            });
+        }
+
+        let changed = row_changes(
+            self.deps.sessions,
+            self.deps.schemas,
+            session_id,
+            row.document_id,
+        )?;
+        Ok(row_outcome(
+            "Item row moved",
+            status,
+            serde_json::json!({
+                "item_id": row.item_id,
+                "from_row_index": from,
+                "row_index": to,
+                "shifted_item_ids": shifted
+                    .into_iter()
+                    .filter(|id| *id != row.item_id)
+                    .collect::<Vec<_>>(),
+            }),
+            changed,
+        ))
+    }
+}
+
+impl<'a> GenericCommandHandler for MoveItemRowHandler<'a> {
+    fn can_handle(&self, command_type: &str) -> bool {
+        command_type == "MoveItemRow"
+    }
+
+    fn handle(&self, _ctx: &mut CommandContext, cmd: &AnyCommand) -> DomainResult<CommandOutcome> {
+        match cmd {
+            AnyCommand::MoveItemRow(c) => self.move_row(c),
+            _ => Err(unsupported_command("MoveItemRowHandler")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::provenance::{Origin, Provenance};
+    use crate::schema::{CreateProjectHandler, SchemaRef};
+    use crate::sessions::SessionRecord;
+    use crate::test_support::{command, ctx, World};
+
+    fn deps(w: &World) -> ItemRowDeps<'_> {
+        ItemRowDeps {
+            mapping: &w.b.mapping,
+            sessions: &w.b.sessions,
+            schemas: &w.b.schemas,
+            pages: &w.b.pages,
+        }
+    }
+
+    fn add(w: &World, session_id: Uuid, document_id: Uuid, row_index: i32) -> DomainResult<Uuid> {
+        let item_id = Uuid::now_v7();
+        AddItemRowHandler::new(deps(w)).handle(
+            &mut ctx(),
+            &command(serde_json::json!({
+                "type": "AddItemRow",
+                "payload": {
+                    "session_id": session_id,
+                    "document_id": document_id,
+                    "row_index": row_index,
+                    "item_id": item_id
+                }
+            })),
+        )?;
+        Ok(item_id)
+    }
+
+    fn move_row(
+        w: &World,
+        session_id: Uuid,
+        item_id: Uuid,
+        row_index: i32,
+    ) -> DomainResult<CommandOutcome> {
+        MoveItemRowHandler::new(deps(w)).handle(
+            &mut ctx(),
+            &command(serde_json::json!({
+                "type": "MoveItemRow",
+                "payload": { "session_id": session_id, "item_id": item_id, "row_index": row_index }
+            })),
+        )
+    }
+
+    /// Rows and tombstones of the session by position; positions must be `0..len`.
+    fn order(w: &World, session_id: Uuid) -> Vec<Uuid> {
+        let mut slots: Vec<(u32, Uuid)> =
+            w.b.mapping
+                .item_rows(session_id)
+                .unwrap()
+                .iter()
+                .map(|r| (r.row_index, r.item_id))
+                .chain(
+                    w.b.mapping
+                        .item_row_tombstones(session_id)
+                        .unwrap()
+                        .iter()
+                        .map(|t| (t.row_index, t.item_id)),
+                )
+                .collect();
+        slots.sort();
+        assert!(slots.iter().map(|s| s.0).eq(0..slots.len() as u32));
+        slots.into_iter().map(|s| s.1).collect()
+    }
+
+    fn rejected(result: DomainResult<impl Sized>) -> bool {
+        matches!(
+            result,
+            Err(DomainError {
+                code: ErrorCode::PreconditionFailed,
+                ..
+            })
+        )
+    }
+
+    fn delete(w: &World, session_id: Uuid, item_id: Uuid) -> DomainResult<CommandOutcome> {
+        DeleteItemRowHandler::new(deps(w)).handle(
+            &mut ctx(),
+            &command(serde_json::json!({
+                "type": "DeleteItemRow",
+                "payload": { "session_id": session_id, "item_id": item_id }
+            })),
+        )
+    }
+
+    #[test]
+    fn locked_rows_and_rows_with_locked_values_cannot_be_deleted() {
+        let w = World::new(&[("description", "string", "item", false)]);
+        let session_id = w.session(SessionStatus::Review);
+        let (document_id, _) = w.document(session_id);
+        let mut row = ItemRow {
+            item_id: Uuid::now_v7(),
+            session_id,
+            document_id,
+            row_index: 0,
+            locked: true,
+        };
+        w.b.mapping.put_item_row(&row).unwrap();
+
+        let err = delete(&w, session_id, row.item_id).unwrap_err();
+        assert!(matches!(err.code, ErrorCode::ValueLocked));
+
+        row.locked = false;
+        w.b.mapping.put_item_row(&row).unwrap();
+        let mut value = ItemValue {
+            item_value_id: Uuid::now_v7(),
+            session_id,
+            item_id: row.item_id,
+            schema_field_id: w.field_id("description"),
+            raw_value: "Widget".to_string(),
+            normalized_value: Some("Widget".to_string()),
+            provenance: Provenance::new(Origin::Manual {
+                actor: "tester".to_string(),
+                unknown_fragment_id: None,
+                batch_command_id: None,
+            }),
+            locked: true,
+        };
+        w.b.mapping.put_item_value(&value).unwrap();
+
+        let err = delete(&w, session_id, row.item_id).unwrap_err();
+        assert!(matches!(err.code, ErrorCode::ValueLocked));
+        assert_eq!(w.b.mapping.item_rows(session_id).unwrap().len(), 1);
+
+        value.locked = false;
+        w.b.mapping.put_item_value(&value).unwrap();
+        delete(&w, session_id, row.item_id).unwrap();
+        assert!(w.b.mapping.item_rows(session_id).unwrap().is_empty());
+        assert!(w.b.mapping.item_values(session_id).unwrap().is_empty());
+    }
+
+    #[test]
+    fn rows_insert_at_an_occupied_index_or_append() {
+        let w = World::new(&[("description", "string", "item", false)]);
+        let session_id = w.session(SessionStatus::Review);
+        let (document_id, _) = w.document(session_id);
+
+        let a = add(&w, session_id, document_id, 0).unwrap();
+        let b = add(&w, session_id, document_id, 1).unwrap();
+        let c = add(&w, session_id, document_id, 1).unwrap();
+        assert_eq!(order(&w, session_id), [a, c, b]);
+        let d = add(&w, session_id, document_id, 3).unwrap();
+        let e = add(&w, session_id, document_id, 0).unwrap();
+        assert_eq!(order(&w, session_id), [e, a, c, b, d]);
+    }
+
+    #[test]
+    fn out_of_range_row_indexes_are_rejected() {
+        let w = World::new(&[("description", "string", "item", false)]);
+        let session_id = w.session(SessionStatus::Review);
+        let (document_id, _) = w.document(session_id);
+        let a = add(&w, session_id, document_id, 0).unwrap();
+        let b = add(&w, session_id, document_id, 1).unwrap();
+
+        assert!(rejected(add(&w, session_id, document_id, -1)));
+        assert!(rejected(add(&w, session_id, document_id, 3)));
+        assert!(rejected(add(&w, session_id, document_id, i32::MAX)));
+        assert!(rejected(move_row(&w, session_id, a, -1)));
+        assert!(rejected(move_row(&w, session_id, a, 2)));
+        assert!(rejected(move_row(&w, session_id, a, i32::MIN)));
+        assert_eq!(order(&w, session_id), [a, b]);
+    }
+
+    #[test]
+    fn rows_move_to_the_last_slot() {
+        let w = World::new(&[("description", "string", "item", false)]);
+        let session_id = w.session(SessionStatus::Review);
+        let (document_id, _) = w.document(session_id);
+        let rows: Vec<Uuid> = (0..3)
+            .map(|i| add(&w, session_id, document_id, i).unwrap())
+            .collect();
+
+        let moved = move_row(&w, session_id, rows[0], 2).unwrap();
+        assert_eq!(order(&w, session_id), [rows[1], rows[2], rows[0]]);
+        assert_eq!(moved.state_delta.data["from_row_index"], 0);
+        assert_eq!(moved.state_delta.data["row_index"], 2);
+        assert_eq!(
+            moved.state_delta.data["shifted_item_ids"],
+            serde_json::json!([rows[1], rows[2]])
+        );
+
+        let unchanged = move_row(&w, session_id, rows[0], 2).unwrap();
+        assert!(matches!(
+            unchanged.validation_trigger,
+            ValidationTrigger::None
+        ));
+        assert_eq!(order(&w, session_id), [rows[1], rows[2], rows[0]]);
+    }
+
+    #[test]
+    fn rows_renumber_around_tombstones() {
+        let w = World::new(&[("description", "string", "item", false)]);
+        let created = CreateProjectHandler::new(&w.b.schemas)
+            .handle(
+                &mut ctx(),
+                &command(serde_json::json!({
+                    "type": "CreateProject",
+                    "payload": { "name": "Ledgers", "item_row_deletion": "tombstone" }
+                })),
+            )
+            .unwrap();
+        let session_id = Uuid::now_v7();
+        w.b.sessions
+            .register_session(
+                SessionRecord {
+                    session_id,
+                    project_id: serde_json::from_value(
+                        created.state_delta.data["project"]["project_id"].clone(),
+                    )
+                    .unwrap(),
+                    schema: SchemaRef {
+                        schema_id: w.schema.schema_id,
+                        version: w.schema.version,
+                    },
+                    source: "manual".to_string(),
+                    base_session_id: None,
+                    created_by: "tester".to_string(),
+                },
+                SessionStatus::Review,
+            )
+            .unwrap();
+        let (document_id, _) = w.document(session_id);
+        let rows: Vec<Uuid> = (0..3)
+            .map(|i| add(&w, session_id, document_id, i).unwrap())
+            .collect();
+
+        // The deleted row keeps its position as a tombstone; later rows stay put.
+        let deleted = delete(&w, session_id, rows[1]).unwrap();
+        assert_eq!(
+            deleted.state_delta.data["shifted_item_ids"],
+            serde_json::json!([])
+        );
+        assert_eq!(order(&w, session_id), rows);
+        assert_eq!(
+            w.b.mapping.item_row_tombstones(session_id).unwrap().len(),
+            1
+        );
+
+        // Inserting before the tombstone shifts it like a row.
+        let d = add(&w, session_id, document_id, 1).unwrap();
+        assert_eq!(order(&w, session_id), [rows[0], d, rows[1], rows[2]]);
+        assert!(rejected(add(&w, session_id, document_id, 5)));
+
+        move_row(&w, session_id, rows[2], 0).unwrap();
+        assert_eq!(order(&w, session_id), [rows[2], rows[0], d, rows[1]]);
+    }
+}
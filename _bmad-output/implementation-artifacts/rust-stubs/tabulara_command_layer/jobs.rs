@@ -0,0 +1,569 @@
+use std::collections::HashSet;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::commands::{
+    AnyCommand, CancelExtractionJob, CompleteExtractionJob, CompleteExtractionJobPayload,
+};
+use crate::errors::{unsupported_command, DomainError, DomainResult, ErrorCode};
+use crate::extraction::{EngineOutput, ExtractionRequest};
+use crate::interfaces::{
+    CommandContext, CommandDispatcher, CommandOutcome, ExtractionEngine, GenericCommandHandler,
+    JobStore, OutcomeEvent, StateDelta, ValidationTrigger,
+};
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum JobStatus {
+    Queued,
+    Running,
+    Succeeded,
+    Failed,
+    Cancelled,
+}
+
+impl JobStatus {
+    pub fn is_finished(self) -> bool {
+        matches!(
+            self,
+            JobStatus::Succeeded | JobStatus::Failed | JobStatus::Cancelled
+        )
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub struct JobProgress {
+    pub pages_done: usize,
+    pub pages_total: usize,
+}
+
+/// A `RunExtraction` accepted for background processing. The engine runs page by
+/// page outside the dispatcher transaction; the results are applied by the internal
+/// `CompleteExtractionJob` command once the job has finished.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExtractionJob {
+    pub job_id: Uuid,
+    pub session_id: Uuid,
+    /// The `RunExtraction` command that was accepted.
+    pub caused_by: Uuid,
+    pub actor: String,
+    pub request: ExtractionRequest,
+    pub status: JobStatus,
+    pub progress: JobProgress,
+    /// Number of times a worker started the job; restarts count again.
+    pub attempts: u32,
+    pub cancel_requested: bool,
+    pub error: Option<DomainError>,
+    pub accepted_at: DateTime<Utc>,
+    pub started_at: Option<DateTime<Utc>>,
+    pub finished_at: Option<DateTime<Utc>>,
+    /// Fixed at acceptance, so a completion sent again after a restart replays
+    /// instead of applying the results twice.
+    pub completion_command_id: Uuid,
+    /// Set once the completion command was dispatched, whatever its outcome.
+    pub completed: bool,
+    pub completion_error: Option<DomainError>,
+}
+
+impl ExtractionJob {
+    pub fn new(
+        session_id: Uuid,
+        caused_by: Uuid,
+        actor: &str,
+        request: ExtractionRequest,
+        now: DateTime<Utc>,
+    ) -> Self {
+        Self {
+            job_id: Uuid::now_v7(),
+            session_id,
+            caused_by,
+            actor: actor.to_string(),
+            progress: JobProgress {
+                pages_done: 0,
+                pages_total: request.pages.len(),
+            },
+            request,
+            status: JobStatus::Queued,
+            attempts: 0,
+            cancel_requested: false,
+            error: None,
+            accepted_at: now,
+            started_at: None,
+            finished_at: None,
+            completion_command_id: Uuid::now_v7(),
+            completed: false,
+            completion_error: None,
+        }
+    }
+
+    /// The internal command applying this job's outcome. Every field is derived from
+    /// the stored job, so re-sending it yields the same idempotency hash.
+    pub fn completion_command(&self) -> AnyCommand {
+        AnyCommand::CompleteExtractionJob(CompleteExtractionJob {
+            command_id: self.completion_command_id,
+            actor: self.actor.clone(),
+            timestamp: self.finished_at.unwrap_or(self.accepted_at),
+            expected_version: None,
+            payload: CompleteExtractionJobPayload {
+                session_id: self.session_id,
+                job_id: self.job_id,
+            },
+        })
+    }
+
+    /// The job's page outputs merged in page order.
+    pub fn merged_output(outputs: Vec<(Uuid, EngineOutput)>) -> EngineOutput {
+        let mut merged = EngineOutput::default();
+        for (_, output) in outputs {
+            merged.tokens.extend(output.tokens);
+            merged.lines.extend(output.lines);
+            merged.tables.extend(output.tables);
+        }
+        merged
+    }
+}
+
+/// A session's job, or `PRECONDITION_FAILED` when the job belongs to another session.
+pub fn session_job(
+    jobs: &dyn JobStore,
+    session_id: Uuid,
+    job_id: Uuid,
+) -> DomainResult<ExtractionJob> {
+    let job = jobs.get_job(job_id)?;
+    if job.session_id != session_id {
+        return Err(DomainError {
+            code: ErrorCode::PreconditionFailed,
+            message: "Job does not belong to the session".to_string(),
+            details: Some(serde_json::json!({ "job_id": job_id, "session_id": session_id })),
+        });
+    }
+    Ok(job)
+}
+
+/// Rejects a new job while another extraction job of the session is still running.
+pub fn ensure_no_active_job(jobs: &dyn JobStore, session_id: Uuid) -> DomainResult<()> {
+    if let Some(active) = jobs
+        .session_jobs(session_id)?
+        .into_iter()
+        .find(|j| !j.completed)
+    {
+        return Err(DomainError {
+            code: ErrorCode::PreconditionFailed,
+            message: "An extraction job is already active for the session".to_string(),
+            details: Some(serde_json::json!({
+                "job_id": active.job_id,
+                "status": active.status,
+            })),
+        });
+    }
+    Ok(())
+}
+
+/// Requests cancellation. A worker stops the job before its next page, or before
+/// the first one when the job is still queued; the completion command then records
+/// the cancellation.
+pub struct CancelExtractionJobHandler<'a> {
+    jobs: &'a dyn JobStore,
+}
+
+impl<'a> CancelExtractionJobHandler<'a> {
+    pub fn new(jobs: &'a dyn JobStore) -> Self {
+        Self { jobs }
+    }
+
+    fn cancel(&self, cmd: &CancelExtractionJob) -> DomainResult<CommandOutcome> {
+        let session_id = cmd.payload.session_id;
+        let job = session_job(self.jobs, session_id, cmd.payload.job_id)?;
+        if job.status.is_finished() {
+            return Err(DomainError {
+                code: ErrorCode::PreconditionFailed,
+                message: "Job has already finished".to_string(),
+                details: Some(serde_json::json!({ "job_id": job.job_id, "status": job.status })),
+            });
+        }
+        let job = self.jobs.request_cancel(job.job_id)?;
+        let data = serde_json::json!({
+            "job_id": job.job_id,
+            "status": job.status,
+            "progress": job.progress,
+        });
+        Ok(CommandOutcome {
+            state_delta: StateDelta {
+                summary: format!(
+                    "Cancellation requested after {} of {} page(s)",
+                    job.progress.pages_done, job.progress.pages_total
+                ),
+                data: data.clone(),
+            },
+            transition: None,
+            review_actions: Vec::new(),
+            validation_trigger: ValidationTrigger::None,
+            events: vec![OutcomeEvent {
+                event_type: "ExtractionJobCancellationRequested".to_string(),
+                session_id: Some(session_id),
+                data,
+            }],
+        })
+    }
+}
+
+impl<'a> GenericCommandHandler for CancelExtractionJobHandler<'a> {
+    fn can_handle(&self, command_type: &str) -> bool {
+        command_type == "CancelExtractionJob"
+    }
+
+    fn handle(&self, _ctx: &mut CommandContext, cmd: &AnyCommand) -> DomainResult<CommandOutcome> {
+        match cmd {
+            AnyCommand::CancelExtractionJob(c) => self.cancel(c),
+            _ => Err(unsupported_command("CancelExtractionJobHandler")),
+        }
+    }
+}
+
+/// Jobs touched by `JobRunner::recover`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct JobRecovery {
+    /// Interrupted jobs queued again; pages already read are not read again.
+    pub requeued: Vec<Uuid>,
+    /// Interrupted jobs failed after `max_attempts` starts, or cancelled on request.
+    pub finished: Vec<Uuid>,
+    /// Finished jobs whose completion command was sent again.
+    pub completed: Vec<Uuid>,
+}
+
+/// Runs extraction jobs outside the dispatcher and reports their outcome through it.
+/// The dispatcher must be shareable across workers; it is called once per job, with
+/// the job's completion command.
+#[derive(Clone)]
+pub struct JobRunner {
+    jobs: Arc<dyn JobStore + Send + Sync>,
+    engine: Arc<dyn ExtractionEngine + Send + Sync>,
+    dispatcher: Arc<dyn CommandDispatcher + Send + Sync>,
+    max_attempts: u32,
+    stop: Arc<AtomicBool>,
+}
+
+impl JobRunner {
+    pub fn new(
+        jobs: Arc<dyn JobStore + Send + Sync>,
+        engine: Arc<dyn ExtractionEngine + Send + Sync>,
+        dispatcher: Arc<dyn CommandDispatcher + Send + Sync>,
+    ) -> Self {
+        Self {
+            jobs,
+            engine,
+            dispatcher,
+            max_attempts: 3,
+            stop: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    /// Starts after which an interrupted job fails instead of resuming.
+    pub fn with_max_attempts(mut self, max_attempts: u32) -> Self {
+        self.max_attempts = max_attempts.max(1);
+        self
+    }
+
+    /// Brings jobs left behind by a previous process to a consistent state; run it
+    /// once at startup, before any worker claims a job.
+    pub fn recover(&self) -> DomainResult<JobRecovery> {
+        let mut recovery = JobRecovery::default();
+        let now = Utc::now();
+        for job in self.jobs.pending_jobs()? {
+            match job.status {
+                JobStatus::Queued => {}
+                JobStatus::Running if job.cancel_requested => {
+                    self.jobs
+                        .finish(job.job_id, JobStatus::Cancelled, None, now)?;
+                    recovery.finished.push(job.job_id);
+                }
+                JobStatus::Running if job.attempts >= self.max_attempts => {
+                    let error = DomainError {
+                        code: ErrorCode::Internal,
+                        message: "Extraction job was interrupted too many times".to_string(),
+                        details: Some(serde_json::json!({ "attempts": job.attempts })),
+                    };
+                    self.jobs
+                        .finish(job.job_id, JobStatus::Failed, Some(error), now)?;
+                    recovery.finished.push(job.job_id);
+                }
+                JobStatus::Running => {
+                    self.jobs.requeue(job.job_id)?;
+                    recovery.requeued.push(job.job_id);
+                    continue;
+                }
+                _ => {}
+            }
+            if self.jobs.get_job(job.job_id)?.status.is_finished() && self.complete(job.job_id)? {
+                recovery.completed.push(job.job_id);
+            }
+        }
+        Ok(recovery)
+    }
+
+    /// Claims and runs the oldest queued job, then dispatches its completion.
+    /// Returns `false` when no job was queued.
+    pub fn run_next(&self) -> DomainResult<bool> {
+        let Some(job) = self.jobs.claim_next(Utc::now())? else {
+            return Ok(false);
+        };
+        let job_id = job.job_id;
+        if self.execute(job)? {
+            self.complete(job_id)?;
+        }
+        Ok(true)
+    }
+
+    /// Starts `workers` threads polling for queued jobs every `poll_interval`.
+    pub fn start(&self, workers: usize, poll_interval: Duration) -> JobWorkerPool {
+        let handles = (0..workers.max(1))
+            .map(|_| {
+                let runner = self.clone();
+                thread::spawn(move || runner.work(poll_interval))
+            })
+            .collect();
+        JobWorkerPool {
+            stop: self.stop.clone(),
+            handles,
+        }
+    }
+
+    fn work(&self, poll_interval: Duration) {
+        while !self.stop.load(Ordering::Acquire) {
+            // A store error leaves the job to `recover`; keep polling.
+            if !matches!(self.run_next(), Ok(true)) {
+                thread::sleep(poll_interval);
+            }
+        }
+    }
+
+    /// Reads the pages not read by an earlier attempt, one engine call per page.
+    /// Returns `false` when stopped midway, leaving the job running for `recover`.
+    fn execute(&self, job: ExtractionJob) -> DomainResult<bool> {
+        let done: HashSet<Uuid> = self
+            .jobs
+            .page_outputs(job.job_id)?
+            .into_iter()
+            .map(|(page_id, _)| page_id)
+            .collect();
+        let mut cancelled = job.cancel_requested;
+        for page in job
+            .request
+            .pages
+            .iter()
+            .filter(|p| !done.contains(&p.page_id))
+        {
+            if cancelled {
+                break;
+            }
+            if self.stop.load(Ordering::Acquire) {
+                return Ok(false);
+            }
+            let request = ExtractionRequest {
+                pages: vec![page.clone()],
+                ..job.request.clone()
+            };
+            match self.engine.extract(&request) {
+                Ok(output) => {
+                    cancelled = self
+                        .jobs
+                        .record_page_output(job.job_id, page.page_id, &output)?
+                        .cancel_requested;
+                }
+                Err(error) => {
+                    self.jobs
+                        .finish(job.job_id, JobStatus::Failed, Some(error), Utc::now())?;
+                    return Ok(true);
+                }
+            }
+        }
+        let status = if cancelled {
+            JobStatus::Cancelled
+        } else {
+            JobStatus::Succeeded
+        };
+        self.jobs.finish(job.job_id, status, None, Utc::now())?;
+        Ok(true)
+    }
+
+    /// Dispatches the completion command. A rejected completion is recorded on the
+    /// job, which then counts as completed; its results are not applied. A retryable
+    /// rejection (a busy write scope) leaves the job pending for `recover`, and `false`
+    /// is returned.
+    fn complete(&self, job_id: Uuid) -> DomainResult<bool> {
+        let job = self.jobs.get_job(job_id)?;
+        let error = match self.dispatcher.dispatch(job.completion_command()) {
+            Ok(_) => None,
+            Err(error) if error.code.is_retryable() => return Ok(false),
+            Err(error) => Some(error),
+        };
+        self.jobs.mark_completed(job_id, error)?;
+        Ok(true)
+    }
+}
+
+/// Worker threads of a `JobRunner`.
+pub struct JobWorkerPool {
+    stop: Arc<AtomicBool>,
+    handles: Vec<JoinHandle<()>>,
+}
+
+impl JobWorkerPool {
+    /// Stops the workers and waits for them. A job stopped between pages stays
+    /// `running` and resumes from its next unread page after `recover`.
+    pub fn shutdown(self) {
+        self.stop.store(true, Ordering::Release);
+        for handle in self.handles {
+            let _ = handle.join();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::dispatcher_impl::DefaultCommandDispatcher;
+    use crate::extraction::{BoundingBox, ExtractionRunStatus, ExtractionToken, PageCoverage};
+    use crate::in_memory_reference_impl::{InMemoryReferenceBundle, InMemoryWriteLocks};
+    use crate::interfaces::{
+        DispatcherDeps, ExtractionStore, SessionReader, WriteLockManager, WriteScope,
+    };
+    use crate::run_extraction::{RunExtractionDeps, RunExtractionHandler};
+    use crate::test_support::{token, World};
+    use crate::transition_policy::MatrixTransitionPolicy;
+    use crate::types::{DispatchResult, SessionStatus};
+
+    /// Reads one token per page.
+    struct StubEngine;
+
+    impl ExtractionEngine for StubEngine {
+        fn extract(&self, request: &ExtractionRequest) -> DomainResult<EngineOutput> {
+            let tokens = request
+                .pages
+                .iter()
+                .map(|page| ExtractionToken {
+                    extraction_run_id: request.extraction_run_id,
+                    page_id: page.page_id,
+                    ..token("Acme", BoundingBox::new(0.1, 0.1, 0.2, 0.05))
+                })
+                .collect();
+            Ok(EngineOutput {
+                tokens,
+                ..EngineOutput::default()
+            })
+        }
+    }
+
+    /// Dispatches job completions against a shared in-memory bundle.
+    struct App {
+        b: InMemoryReferenceBundle,
+    }
+
+    impl CommandDispatcher for App {
+        fn dispatch(&self, command: AnyCommand) -> DomainResult<DispatchResult> {
+            let b = &self.b;
+            let policy = MatrixTransitionPolicy::new();
+            let extraction = RunExtractionHandler::new(RunExtractionDeps {
+                engine: &StubEngine,
+                extraction: &b.extraction,
+                mapping: &b.mapping,
+                sessions: &b.sessions,
+                pages: &b.pages,
+                templates: &b.templates,
+                anchors: &b.anchors,
+                dictionary: &b.dictionary,
+                contexts: &b.contexts,
+                zones: &b.zones,
+                schemas: &b.schemas,
+                unknown: &b.unknown_bucket,
+            })
+            .with_jobs(&b.jobs);
+            DefaultCommandDispatcher::new(DispatcherDeps {
+                handlers: vec![&extraction],
+                transitions: &policy,
+                idempotency: &b.idempotency,
+                events: &b.events,
+                event_reader: &b.events,
+                event_factory: &b.event_factory,
+                invariants: &b.invariants,
+                sessions: &b.sessions,
+                projections: &b.projections,
+                locks: &b.locks,
+                uow: &b.uow,
+            })
+            .dispatch(command)
+        }
+    }
+
+    #[test]
+    fn a_busy_completion_is_left_for_recover() {
+        let mut w = World::new(&[("note", "string", "document", false)]);
+        w.b.locks = InMemoryWriteLocks::with_timeout(Duration::from_millis(20));
+        let session_id = w.session(SessionStatus::Processing);
+        let (document_id, page_id) = w.document(session_id);
+        let extraction_run_id = Uuid::now_v7();
+        let job = ExtractionJob::new(
+            session_id,
+            Uuid::now_v7(),
+            "tester",
+            ExtractionRequest {
+                extraction_run_id,
+                session_id,
+                engine: "stub".to_string(),
+                params: serde_json::json!({}),
+                pages: vec![PageCoverage {
+                    document_id,
+                    page_id,
+                    page_number: 1,
+                }],
+                region: None,
+            },
+            Utc::now(),
+        );
+        w.b.jobs.put_job(&job).unwrap();
+        let runner = JobRunner::new(
+            Arc::new(w.b.jobs.clone()),
+            Arc::new(StubEngine),
+            Arc::new(App { b: w.b.clone() }),
+        );
+
+        let held =
+            w.b.locks
+                .acquire(WriteScope::Session {
+                    project_id: w.project_id,
+                    session_id,
+                })
+                .unwrap();
+        assert!(runner.run_next().unwrap());
+        let pending = w.b.jobs.get_job(job.job_id).unwrap();
+        assert_eq!(pending.status, JobStatus::Succeeded);
+        assert!(!pending.completed);
+        assert_eq!(
+            w.b.sessions.get_status(session_id).unwrap(),
+            SessionStatus::Processing
+        );
+        drop(held);
+
+        let recovery = runner.recover().unwrap();
+        assert_eq!(recovery.completed, vec![job.job_id]);
+        let completed = w.b.jobs.get_job(job.job_id).unwrap();
+        assert!(completed.completed);
+        assert!(completed.completion_error.is_none());
+        assert_eq!(
+            w.b.sessions.get_status(session_id).unwrap(),
+            SessionStatus::Review
+        );
+        assert_eq!(
+            w.b.extraction.get_run(extraction_run_id).unwrap().status,
+            ExtractionRunStatus::Completed
+        );
+    }
+}
@@ -0,0 +1,367 @@
+use std::collections::HashSet;
+
+use uuid::Uuid;
+
+use crate::commands::{AnyCommand, LockField, LockItemRow};
+use crate::errors::{unsupported_command, value_locked, DomainError, DomainResult, ErrorCode};
+use crate::interfaces::{
+    CommandContext, CommandOutcome, GenericCommandHandler, MappingStore, OutcomeEvent,
+    SessionReader, StateDelta, ValidationTrigger,
+};
+use crate::mapping::item_row;
+
+/// Ids of the session's locked item rows.
+pub fn locked_item_rows(
+    mapping: &dyn MappingStore,
+    session_id: Uuid,
+) -> DomainResult<HashSet<Uuid>> {
+    Ok(mapping
+        .item_rows(session_id)?
+        .into_iter()
+        .filter(|r| r.locked)
+        .map(|r| r.item_id)
+        .collect())
+}
+
+/// Locked values can only be released in a correction session, where the unlock is
+/// recorded as its own event.
+fn ensure_unlock_allowed(
+    sessions: &dyn SessionReader,
+    session_id: Uuid,
+    details: serde_json::Value,
+) -> DomainResult<()> {
+    if sessions.get_session(session_id)?.base_session_id.is_none() {
+        return Err(value_locked(
+            "Locked values can only be unlocked in a correction session",
+            details,
+        ));
+    }
+    Ok(())
+}
+
+/// Lock changes neither values nor status; the lock or unlock is the extra event.
+fn lock_outcome(
+    summary: &str,
+    session_id: Uuid,
+    event_type: Option<&str>,
+    data: serde_json::Value,
+) -> CommandOutcome {
+    CommandOutcome {
+        state_delta: StateDelta {
+            summary: summary.to_string(),
+            data: data.clone(),
+        },
+        transition: None,
+        review_actions: Vec::new(),
+        validation_trigger: ValidationTrigger::None,
+        events: event_type
+            .map(|event_type| OutcomeEvent {
+                event_type: event_type.to_string(),
+                session_id: Some(session_id),
+                data,
+            })
+            .into_iter()
+            .collect(),
+    }
+}
+
+/// Locks a document field value so extraction, anchors, batches and edits leave it
+/// alone. Setting a value to its current lock state is a no-op.
+pub struct LockFieldHandler<'a> {
+    mapping: &'a dyn MappingStore,
+    sessions: &'a dyn SessionReader,
+}
+
+impl<'a> LockFieldHandler<'a> {
+    pub fn new(mapping: &'a dyn MappingStore, sessions: &'a dyn SessionReader) -> Self {
+        Self { mapping, sessions }
+    }
+
+    fn lock(&self, ctx: &CommandContext, cmd: &LockField) -> DomainResult<CommandOutcome> {
+        let payload = &cmd.payload;
+        let mut value = self
+            .mapping
+            .field_values(payload.session_id)?
+            .into_iter()
+            .find(|v| v.field_value_id == payload.field_value_id)
+            .ok_or_else(|| DomainError {
+                code: ErrorCode::NotFound,
+                message: "Field value not found".to_string(),
+                details: Some(serde_json::json!({ "field_value_id": payload.field_value_id })),
+            })?;
+        let data = serde_json::json!({
+            "field_value_id": value.field_value_id,
+            "document_id": value.document_id,
+            "schema_field_id": value.schema_field_id,
+            "locked": payload.locked,
+            "actor": ctx.actor,
+        });
+        if value.locked == payload.locked {
+            return Ok(lock_outcome(
+                "Field lock unchanged",
+                payload.session_id,
+                None,
+                data,
+            ));
+        }
+        if !payload.locked {
+            ensure_unlock_allowed(self.sessions, payload.session_id, data.clone())?;
+        }
+        value.locked = payload.locked;
+        self.mapping.put_field_value(&value)?;
+
+        Ok(if payload.locked {
+            lock_outcome(
+                "Field locked",
+                payload.session_id,
+                Some("FieldLocked"),
+                data,
+            )
+        } else {
+            lock_outcome(
+                "Field unlocked",
+                payload.session_id,
+                Some("FieldUnlocked"),
+                data,
+            )
+        })
+    }
+}
+
+impl<'a> GenericCommandHandler for LockFieldHandler<'a> {
+    fn can_handle(&self, command_type: &str) -> bool {
+        command_type == "LockField"
+    }
+
+    fn handle(&self, ctx: &mut CommandContext, cmd: &AnyCommand) -> DomainResult<CommandOutcome> {
+        match cmd {
+            AnyCommand::LockField(c) => self.lock(ctx, c),
+            _ => Err(unsupported_command("LockFieldHandler")),
+        }
+    }
+}
+
+/// Locks an item row; its values then reject edits and the row cannot be deleted.
+pub struct LockItemRowHandler<'a> {
+    mapping: &'a dyn MappingStore,
+    sessions: &'a dyn SessionReader,
+}
+
+impl<'a> LockItemRowHandler<'a> {
+    pub fn new(mapping: &'a dyn MappingStore, sessions: &'a dyn SessionReader) -> Self {
+        Self { mapping, sessions }
+    }
+
+    fn lock(&self, ctx: &CommandContext, cmd: &LockItemRow) -> DomainResult<CommandOutcome> {
+        let payload = &cmd.payload;
+        let mut row = item_row(self.mapping, payload.session_id, payload.item_id)?;
+        let data = serde_json::json!({
+            "item_id": row.item_id,
+            "document_id": row.document_id,
+            "locked": payload.locked,
+            "actor": ctx.actor,
+        });
+        if row.locked == payload.locked {
+            return Ok(lock_outcome(
+                "Item row lock unchanged",
+                payload.session_id,
+                None,
+                data,
+            ));
+        }
+        if !payload.locked {
+            ensure_unlock_allowed(self.sessions, payload.session_id, data.clone())?;
+        }
+        row.locked = payload.locked;
+        self.mapping.put_item_row(&row)?;
+
+        Ok(if payload.locked {
+            lock_outcome(
+                "Item row locked",
+                payload.session_id,
+                Some("ItemRowLocked"),
+                data,
+            )
+        } else {
+            lock_outcome(
+                "Item row unlocked",
+                payload.session_id,
+                Some("ItemRowUnlocked"),
+                data,
+            )
+        })
+    }
+}
+
+impl<'a> GenericCommandHandler for LockItemRowHandler<'a> {
+    fn can_handle(&self, command_type: &str) -> bool {
+        command_type == "LockItemRow"
+    }
+
+    fn handle(&self, ctx: &mut CommandContext, cmd: &AnyCommand) -> DomainResult<CommandOutcome> {
+        match cmd {
+            AnyCommand::LockItemRow(c) => self.lock(ctx, c),
+            _ => Err(unsupported_command("LockItemRowHandler")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mapping::{FieldValue, ItemRow};
+    use crate::provenance::{Origin, Provenance};
+    use crate::schema::SchemaRef;
+    use crate::sessions::SessionRecord;
+    use crate::test_support::{command, ctx, World};
+    use crate::types::SessionStatus;
+
+    fn correction_session(w: &World, base_session_id: Uuid) -> Uuid {
+        let session_id = Uuid::now_v7();
+        w.b.sessions
+            .register_session(
+                SessionRecord {
+                    session_id,
+                    project_id: w.project_id,
+                    schema: SchemaRef {
+                        schema_id: w.schema.schema_id,
+                        version: w.schema.version,
+                    },
+                    source: "correction".to_string(),
+                    base_session_id: Some(base_session_id),
+                    created_by: "tester".to_string(),
+                },
+                SessionStatus::Review,
+            )
+            .unwrap();
+        session_id
+    }
+
+    fn lock_field(
+        w: &World,
+        session_id: Uuid,
+        field_value_id: Uuid,
+        locked: bool,
+    ) -> DomainResult<CommandOutcome> {
+        LockFieldHandler::new(&w.b.mapping, &w.b.sessions).handle(
+            &mut ctx(),
+            &command(serde_json::json!({
+                "type": "LockField",
+                "payload": {
+                    "session_id": session_id,
+                    "field_value_id": field_value_id,
+                    "locked": locked
+                }
+            })),
+        )
+    }
+
+    fn lock_row(
+        w: &World,
+        session_id: Uuid,
+        item_id: Uuid,
+        locked: bool,
+    ) -> DomainResult<CommandOutcome> {
+        LockItemRowHandler::new(&w.b.mapping, &w.b.sessions).handle(
+            &mut ctx(),
+            &command(serde_json::json!({
+                "type": "LockItemRow",
+                "payload": { "session_id": session_id, "item_id": item_id, "locked": locked }
+            })),
+        )
+    }
+
+    fn field_value(w: &World, session_id: Uuid, document_id: Uuid) -> FieldValue {
+        let value = FieldValue {
+            field_value_id: Uuid::now_v7(),
+            session_id,
+            document_id,
+            schema_field_id: w.field_id("vendor"),
+            raw_value: "Acme".to_string(),
+            normalized_value: Some("Acme".to_string()),
+            provenance: Provenance::new(Origin::Manual {
+                actor: "tester".to_string(),
+                unknown_fragment_id: None,
+                batch_command_id: None,
+            }),
+            locked: false,
+        };
+        w.b.mapping.put_field_value(&value).unwrap();
+        value
+    }
+
+    fn item_row(w: &World, session_id: Uuid, document_id: Uuid) -> ItemRow {
+        let row = ItemRow {
+            item_id: Uuid::now_v7(),
+            session_id,
+            document_id,
+            row_index: 0,
+            locked: false,
+        };
+        w.b.mapping.put_item_row(&row).unwrap();
+        row
+    }
+
+    #[test]
+    fn field_values_are_unlocked_only_in_correction_sessions() {
+        let w = World::new(&[("vendor", "string", "document", false)]);
+        let session_id = w.session(SessionStatus::Review);
+        let (document_id, _) = w.document(session_id);
+        let value = field_value(&w, session_id, document_id);
+
+        let locked = lock_field(&w, session_id, value.field_value_id, true).unwrap();
+        assert_eq!(locked.events[0].event_type, "FieldLocked");
+        assert!(w.b.mapping.field_values(session_id).unwrap()[0].locked);
+        let unchanged = lock_field(&w, session_id, value.field_value_id, true).unwrap();
+        assert!(unchanged.events.is_empty());
+
+        let err = lock_field(&w, session_id, value.field_value_id, false).unwrap_err();
+        assert!(matches!(err.code, ErrorCode::ValueLocked));
+        assert!(w.b.mapping.field_values(session_id).unwrap()[0].locked);
+
+        let correction_id = correction_session(&w, session_id);
+        let copy = FieldValue {
+            field_value_id: Uuid::now_v7(),
+            session_id: correction_id,
+            locked: true,
+            ..value
+        };
+        w.b.mapping.put_field_value(&copy).unwrap();
+        let unlocked = lock_field(&w, correction_id, copy.field_value_id, false).unwrap();
+        assert_eq!(unlocked.events[0].event_type, "FieldUnlocked");
+        assert!(!w.b.mapping.field_values(correction_id).unwrap()[0].locked);
+    }
+
+    #[test]
+    fn item_rows_are_unlocked_only_in_correction_sessions() {
+        let w = World::new(&[("description", "string", "item", false)]);
+        let session_id = w.session(SessionStatus::Review);
+        let (document_id, _) = w.document(session_id);
+        let row = item_row(&w, session_id, document_id);
+
+        let locked = lock_row(&w, session_id, row.item_id, true).unwrap();
+        assert_eq!(locked.events[0].event_type, "ItemRowLocked");
+        assert_eq!(
+            locked_item_rows(&w.b.mapping, session_id).unwrap(),
+            HashSet::from([row.item_id])
+        );
+
+        let err = lock_row(&w, session_id, row.item_id, false).unwrap_err();
+        assert!(matches!(err.code, ErrorCode::ValueLocked));
+        assert!(w.b.mapping.item_rows(session_id).unwrap()[0].locked);
+
+        let correction_id = correction_session(&w, session_id);
+        let copy = ItemRow {
+            item_id: Uuid::now_v7(),
+            session_id: correction_id,
+            locked: true,
+            ..row
+        };
+        w.b.mapping.put_item_row(&copy).unwrap();
+        let unlocked = lock_row(&w, correction_id, copy.item_id, false).unwrap();
+        assert_eq!(unlocked.events[0].event_type, "ItemRowUnlocked");
+        assert!(locked_item_rows(&w.b.mapping, correction_id)
+            .unwrap()
+            .is_empty());
+    }
+}
@@ -0,0 +1,676 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::commands::{AnyCommand, AssignFieldValue, AssignItemValue};
+use crate::dictionary::DictionaryNormalizer;
+use crate::errors::{unsupported_command, value_locked, DomainError, DomainResult, ErrorCode};
+use crate::interfaces::{
+    CommandContext, CommandOutcome, GenericCommandHandler, MappingStore, ReviewAction, SchemaStore,
+    SessionReader, StateDelta, ValidationTrigger,
+};
+use crate::provenance::{Provenance, ProvenanceChecker};
+use crate::review_tasks::{ReviewCategory, ReviewTarget};
+use crate::schema::session_schema;
+use crate::types::{FieldScope, SessionStatus, SessionStatusTransition, SourceType};
+use crate::value_parsing::{parse_value, unparseable_review_action};
+
+/// `field_values` row.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FieldValue {
+    pub field_value_id: Uuid,
+    pub session_id: Uuid,
+    pub document_id: Uuid,
+    pub schema_field_id: Uuid,
+    pub raw_value: String,
+    pub normalized_value: Option<String>,
+    pub provenance: Provenance,
+    pub locked: bool,
+}
+
+impl FieldValue {
+    /// Extraction, anchors and learning rules may replace this value: it is neither
+    /// locked nor entered by hand.
+    pub fn accepts_automatic_update(&self) -> bool {
+        !self.locked && self.provenance.source() != SourceType::Manual
+    }
+}
+
+/// A document field written by a command. Handlers that store values list these
+/// under `changed_values` in their state delta; incremental validation reads them
+/// back from the event log to decide which rules to re-run. Adding, removing or
+/// reordering an item row changes every item field of its document.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash)]
+pub struct ValueChange {
+    pub document_id: Uuid,
+    pub schema_field_id: Uuid,
+}
+
+impl From<&FieldValue> for ValueChange {
+    fn from(value: &FieldValue) -> Self {
+        Self {
+            document_id: value.document_id,
+            schema_field_id: value.schema_field_id,
+        }
+    }
+}
+
+/// A value as a command stored it. Handlers list these under `written_values` in
+/// their state delta, and the values a deleted item row took with it under
+/// `removed_values`; value history is rebuilt from them.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum WrittenValue {
+    Field(FieldValue),
+    Item(ItemValue),
+    Extra(ExtraValue),
+}
+
+/// `items` row: one line item of a document.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ItemRow {
+    pub item_id: Uuid,
+    pub session_id: Uuid,
+    pub document_id: Uuid,
+    pub row_index: u32,
+    pub locked: bool,
+}
+
+/// `item_row_tombstones` row: the position a deleted item row keeps when the project
+/// deletes rows with tombstones.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ItemRowTombstone {
+    pub item_id: Uuid,
+    pub session_id: Uuid,
+    pub document_id: Uuid,
+    pub row_index: u32,
+    pub deleted_by: String,
+    pub deleted_at: DateTime<Utc>,
+}
+
+/// `item_values` row.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ItemValue {
+    pub item_value_id: Uuid,
+    pub session_id: Uuid,
+    pub item_id: Uuid,
+    pub schema_field_id: Uuid,
+    pub raw_value: String,
+    pub normalized_value: Option<String>,
+    pub provenance: Provenance,
+    pub locked: bool,
+}
+
+/// `extra_rows` row: one row of a document's extra table.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExtraRow {
+    pub extra_row_id: Uuid,
+    pub session_id: Uuid,
+    pub document_id: Uuid,
+    pub table_name: String,
+    pub row_index: i32,
+}
+
+/// `extra_values` row.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExtraValue {
+    pub extra_value_id: Uuid,
+    pub session_id: Uuid,
+    pub extra_row_id: Uuid,
+    pub schema_field_id: Uuid,
+    pub raw_value: String,
+    pub normalized_value: Option<String>,
+    pub provenance: Provenance,
+    pub locked: bool,
+}
+
+/// Stores a document-level value and derives `normalized_value` from the field type
+/// of the session's pinned schema version. Manual values that do not parse are
+/// rejected; anchor and zone values are stored unparsed and raise a review task.
+/// The provenance is checked against the document before anything is stored. Locked
+/// values are rejected with `VALUE_LOCKED`.
+///
+/// The raw value is first run through the project's dictionary rules; `raw_value`
+/// keeps what was entered, the rewritten value is parsed into `normalized_value` and
+/// the fired rules are recorded in the provenance.
+pub struct AssignFieldValueHandler<'a> {
+    mapping: &'a dyn MappingStore,
+    sessions: &'a dyn SessionReader,
+    schemas: &'a dyn SchemaStore,
+    provenance: ProvenanceChecker<'a>,
+    dictionary: DictionaryNormalizer<'a>,
+}
+
+impl<'a> AssignFieldValueHandler<'a> {
+    pub fn new(
+        mapping: &'a dyn MappingStore,
+        sessions: &'a dyn SessionReader,
+        schemas: &'a dyn SchemaStore,
+        provenance: ProvenanceChecker<'a>,
+        dictionary: DictionaryNormalizer<'a>,
+    ) -> Self {
+        Self {
+            mapping,
+            sessions,
+            schemas,
+            provenance,
+            dictionary,
+        }
+    }
+
+    fn assign(&self, cmd: &AssignFieldValue) -> DomainResult<CommandOutcome> {
+        let payload = &cmd.payload;
+        let session = self.sessions.get_session(payload.session_id)?;
+        let status = self.sessions.get_status(payload.session_id)?;
+        let schema = session_schema(self.sessions, self.schemas, payload.session_id)?;
+        let field = schema.writable_field(payload.schema_field_id)?;
+        if field.scope != FieldScope::Document {
+            return Err(DomainError {
+                code: ErrorCode::PreconditionFailed,
+                message: "Item fields are assigned with AssignItemValue".to_string(),
+                details: Some(serde_json::json!({
+                    "schema_field_id": field.schema_field_id,
+                    "field_key": field.field_key,
+                })),
+            });
+        }
+        self.provenance.check(
+            &payload.provenance,
+            &cmd.actor,
+            &session,
+            payload.document_id,
+        )?;
+        let locale = self.schemas.get_project(session.project_id)?.locale;
+
+        let current = self
+            .mapping
+            .field_values(payload.session_id)?
+            .into_iter()
+            .find(|v| {
+                v.document_id == payload.document_id && v.schema_field_id == payload.schema_field_id
+            });
+        if let Some(current) = current.as_ref().filter(|v| v.locked) {
+            return Err(value_locked(
+                "Field value is locked",
+                serde_json::json!({
+                    "field_value_id": current.field_value_id,
+                    "field_key": field.field_key,
+                }),
+            ));
+        }
+        let mut value = FieldValue {
+            field_value_id: current
+                .as_ref()
+                .map_or_else(Uuid::now_v7, |v| v.field_value_id),
+            session_id: payload.session_id,
+            document_id: payload.document_id,
+            schema_field_id: payload.schema_field_id,
+            raw_value: payload.raw_value.clone(),
+            normalized_value: None,
+            provenance: payload.provenance.clone(),
+            locked: false,
+        };
+
+        let normalized = self.dictionary.normalize(
+            session.project_id,
+            payload.session_id,
+            payload.document_id,
+            payload.schema_field_id,
+            &payload.raw_value,
+        )?;
+        normalized.record_in(&mut value.provenance);
+        let input = normalized.value;
+
+        let parsed = parse_value(field, &input, &locale);
+        if let (Err(failure), SourceType::Manual) = (&parsed, payload.provenance.source()) {
+            return Err(DomainError {
+                code: ErrorCode::PreconditionFailed,
+                message: format!("Value does not parse as {:?}", failure.field_type),
+                details: Some(serde_json::json!({
+                    "schema_field_id": field.schema_field_id,
+                    "field_key": field.field_key,
+                    "raw_value": payload.raw_value,
+                    "normalized_input": input,
+                    "reason": failure.reason,
+                })),
+            });
+        }
+        value.normalized_value = parsed.clone().unwrap_or(None);
+        self.mapping.put_field_value(&value)?;
+
+        let review_actions = match &parsed {
+            Err(failure) => vec![unparseable_review_action(&value, field, failure)],
+            Ok(_) => Vec::new(),
+        };
+        let transition = (status == SessionStatus::Validated).then_some(SessionStatusTransition {
+            from: status,
+            to: SessionStatus::Review,
+        });
+
+        Ok(CommandOutcome {
+            state_delta: StateDelta {
+                summary: if current.is_some() {
+                    "Field value updated".to_string()
+                } else {
+                    "Field value assigned".to_string()
+                },
+                data: serde_json::json!({
+                    "field_value": value,
+                    "field_type": field.field_type,
+                    "parse_error": parsed.err().map(|f| f.reason),
+                    "changed_values": [ValueChange::from(&value)],
+                    "written_values": [WrittenValue::Field(value.clone())],
+                }),
+            },
+            transition,
+            review_actions,
+            validation_trigger: ValidationTrigger::Async,
+            events: Vec::new(),
+        })
+    }
+}
+
+impl<'a> GenericCommandHandler for AssignFieldValueHandler<'a> {
+    fn can_handle(&self, command_type: &str) -> bool {
+        command_type == "AssignFieldValue"
+    }
+
+    fn handle(&self, _ctx: &mut CommandContext, cmd: &AnyCommand) -> DomainResult<CommandOutcome> {
+        match cmd {
+            AnyCommand::AssignFieldValue(c) => self.assign(c),
+            _ => Err(unsupported_command("AssignFieldValueHandler")),
+        }
+    }
+}
+
+/// Stores an item-level value. Dictionary normalization, parsing and provenance checks
+/// follow `AssignFieldValue`; a locked row or value is rejected with `VALUE_LOCKED`.
+pub struct AssignItemValueHandler<'a> {
+    mapping: &'a dyn MappingStore,
+    sessions: &'a dyn SessionReader,
+    schemas: &'a dyn SchemaStore,
+    provenance: ProvenanceChecker<'a>,
+    dictionary: DictionaryNormalizer<'a>,
+}
+
+impl<'a> AssignItemValueHandler<'a> {
+    pub fn new(
+        mapping: &'a dyn MappingStore,
+        sessions: &'a dyn SessionReader,
+        schemas: &'a dyn SchemaStore,
+        provenance: ProvenanceChecker<'a>,
+        dictionary: DictionaryNormalizer<'a>,
+    ) -> Self {
+        Self {
+            mapping,
+            sessions,
+            schemas,
+            provenance,
+            dictionary,
+        }
+    }
+
+    fn assign(&self, cmd: &AssignItemValue) -> DomainResult<CommandOutcome> {
+        let payload = &cmd.payload;
+        let session = self.sessions.get_session(payload.session_id)?;
+        let status = self.sessions.get_status(payload.session_id)?;
+        let schema = session_schema(self.sessions, self.schemas, payload.session_id)?;
+        let field = schema.writable_field(payload.schema_field_id)?;
+        if field.scope != FieldScope::Item {
+            return Err(DomainError {
+                code: ErrorCode::PreconditionFailed,
+                message: "Document fields are assigned with AssignFieldValue".to_string(),
+                details: Some(serde_json::json!({
+                    "schema_field_id": field.schema_field_id,
+                    "field_key": field.field_key,
+                })),
+            });
+        }
+        let row = item_row(self.mapping, payload.session_id, payload.item_id)?;
+        if row.locked {
+            return Err(value_locked(
+                "Item row is locked",
+                serde_json::json!({ "item_id": row.item_id }),
+            ));
+        }
+        self.provenance
+            .check(&payload.provenance, &cmd.actor, &session, row.document_id)?;
+        let locale = self.schemas.get_project(session.project_id)?.locale;
+
+        let normalized = self.dictionary.normalize(
+            session.project_id,
+            payload.session_id,
+            row.document_id,
+            field.schema_field_id,
+            &payload.raw_value,
+        )?;
+        let mut provenance = payload.provenance.clone();
+        normalized.record_in(&mut provenance);
+        let parsed = parse_value(field, &normalized.value, &locale);
+        if let (Err(failure), SourceType::Manual) = (&parsed, payload.provenance.source()) {
+            return Err(DomainError {
+                code: ErrorCode::PreconditionFailed,
+                message: format!("Value does not parse as {:?}", failure.field_type),
+                details: Some(serde_json::json!({
+                    "schema_field_id": field.schema_field_id,
+                    "field_key": field.field_key,
+                    "raw_value": payload.raw_value,
+                    "normalized_input": normalized.value,
+                    "reason": failure.reason,
+                })),
+            });
+        }
+
+        let current = self
+            .mapping
+            .item_values(payload.session_id)?
+            .into_iter()
+            .find(|v| v.item_id == row.item_id && v.schema_field_id == field.schema_field_id);
+        if let Some(current) = current.as_ref().filter(|v| v.locked) {
+            return Err(value_locked(
+                "Item value is locked",
+                serde_json::json!({
+                    "item_value_id": current.item_value_id,
+                    "item_id": row.item_id,
+                    "field_key": field.field_key,
+                }),
+            ));
+        }
+        let value = ItemValue {
+            item_value_id: current
+                .as_ref()
+                .map_or_else(Uuid::now_v7, |v| v.item_value_id),
+            session_id: payload.session_id,
+            item_id: row.item_id,
+            schema_field_id: field.schema_field_id,
+            raw_value: payload.raw_value.clone(),
+            normalized_value: parsed.clone().unwrap_or(None),
+            provenance,
+            locked: false,
+        };
+        self.mapping.put_item_value(&value)?;
+
+        let review_actions = match &parsed {
+            Err(failure) => vec![ReviewAction::new(
+                payload.session_id,
+                ReviewCategory::UnparseableValue,
+                ReviewTarget::item(row.document_id, row.item_id, Some(field.schema_field_id)),
+                serde_json::json!({
+                    "item_value_id": value.item_value_id,
+                    "field_key": field.field_key,
+                    "raw_value": value.raw_value,
+                    "field_type": failure.field_type,
+                    "reason": failure.reason,
+                }),
+            )],
+            Ok(_) => Vec::new(),
+        };
+        let transition = (status == SessionStatus::Validated).then_some(SessionStatusTransition {
+            from: status,
+            to: SessionStatus::Review,
+        });
+
+        Ok(CommandOutcome {
+            state_delta: StateDelta {
+                summary: if current.is_some() {
+                    "Item value updated".to_string()
+                } else {
+                    "Item value assigned".to_string()
+                },
+                data: serde_json::json!({
+                    "item_value": value,
+                    "field_type": field.field_type,
+                    "parse_error": parsed.err().map(|f| f.reason),
+                    "changed_values": [ValueChange {
+                        document_id: row.document_id,
+                        schema_field_id: field.schema_field_id,
+                    }],
+                    "written_values": [WrittenValue::Item(value.clone())],
+                }),
+            },
+            transition,
+            review_actions,
+            validation_trigger: ValidationTrigger::Async,
+            events: Vec::new(),
+        })
+    }
+}
+
+impl<'a> GenericCommandHandler for AssignItemValueHandler<'a> {
+    fn can_handle(&self, command_type: &str) -> bool {
+        command_type == "AssignItemValue"
+    }
+
+    fn handle(&self, _ctx: &mut CommandContext, cmd: &AnyCommand) -> DomainResult<CommandOutcome> {
+        match cmd {
+            AnyCommand::AssignItemValue(c) => self.assign(c),
+            _ => Err(unsupported_command("AssignItemValueHandler")),
+        }
+    }
+}
+
+pub(crate) fn item_row(
+    mapping: &dyn MappingStore,
+    session_id: Uuid,
+    item_id: Uuid,
+) -> DomainResult<ItemRow> {
+    mapping
+        .item_rows(session_id)?
+        .into_iter()
+        .find(|r| r.item_id == item_id)
+        .ok_or_else(|| DomainError {
+            code: ErrorCode::NotFound,
+            message: "Item row not found".to_string(),
+            details: Some(serde_json::json!({ "item_id": item_id })),
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dictionary::AddDictionaryRuleHandler;
+    use crate::test_support::{command, ctx, World};
+
+    #[test]
+    fn manual_values_are_normalized_through_the_dictionary() {
+        let w = World::new(&[
+            ("quantity", "integer", "document", false),
+            ("units", "integer", "item", false),
+        ]);
+        let session_id = w.session(SessionStatus::Review);
+        let (document_id, _) = w.document(session_id);
+        AddDictionaryRuleHandler::new(&w.b.dictionary)
+            .handle(
+                &mut ctx(),
+                &command(serde_json::json!({
+                    "type": "AddDictionaryRule",
+                    "payload": {
+                        "project_id": w.project_id,
+                        "scope": "global",
+                        "match_type": "exact",
+                        "match_value": "none",
+                        "replace_value": "0"
+                    }
+                })),
+            )
+            .unwrap();
+        let row = ItemRow {
+            item_id: Uuid::now_v7(),
+            session_id,
+            document_id,
+            row_index: 0,
+            locked: false,
+        };
+        w.b.mapping.put_item_row(&row).unwrap();
+
+        AssignFieldValueHandler::new(
+            &w.b.mapping,
+            &w.b.sessions,
+            &w.b.schemas,
+            w.provenance(),
+            w.dictionary(),
+        )
+        .handle(
+            &mut ctx(),
+            &command(serde_json::json!({
+                "type": "AssignFieldValue",
+                "payload": {
+                    "session_id": session_id,
+                    "document_id": document_id,
+                    "schema_field_id": w.field_id("quantity"),
+                    "raw_value": "none",
+                    "provenance": { "source": "manual", "actor": "tester" }
+                }
+            })),
+        )
+        .unwrap();
+        AssignItemValueHandler::new(
+            &w.b.mapping,
+            &w.b.sessions,
+            &w.b.schemas,
+            w.provenance(),
+            w.dictionary(),
+        )
+        .handle(
+            &mut ctx(),
+            &command(serde_json::json!({
+                "type": "AssignItemValue",
+                "payload": {
+                    "session_id": session_id,
+                    "item_id": row.item_id,
+                    "schema_field_id": w.field_id("units"),
+                    "raw_value": "none",
+                    "provenance": { "source": "manual", "actor": "tester" }
+                }
+            })),
+        )
+        .unwrap();
+
+        let field = w.b.mapping.field_values(session_id).unwrap().remove(0);
+        let item = w.b.mapping.item_values(session_id).unwrap().remove(0);
+        for (raw_value, normalized_value, provenance) in [
+            (field.raw_value, field.normalized_value, field.provenance),
+            (item.raw_value, item.normalized_value, item.provenance),
+        ] {
+            assert_eq!(raw_value, "none");
+            assert_eq!(normalized_value.as_deref(), Some("0"));
+            assert_eq!(provenance.dictionary_rules.len(), 1);
+            assert_eq!(provenance.dictionary_rules[0].after, "0");
+        }
+    }
+
+    fn assign_field(
+        w: &World,
+        session_id: Uuid,
+        document_id: Uuid,
+        raw_value: &str,
+    ) -> DomainResult<CommandOutcome> {
+        AssignFieldValueHandler::new(
+            &w.b.mapping,
+            &w.b.sessions,
+            &w.b.schemas,
+            w.provenance(),
+            w.dictionary(),
+        )
+        .handle(
+            &mut ctx(),
+            &command(serde_json::json!({
+                "type": "AssignFieldValue",
+                "payload": {
+                    "session_id": session_id,
+                    "document_id": document_id,
+                    "schema_field_id": w.field_id("vendor"),
+                    "raw_value": raw_value,
+                    "provenance": { "source": "manual", "actor": "tester" }
+                }
+            })),
+        )
+    }
+
+    fn assign_item(
+        w: &World,
+        session_id: Uuid,
+        item_id: Uuid,
+        raw_value: &str,
+    ) -> DomainResult<CommandOutcome> {
+        AssignItemValueHandler::new(
+            &w.b.mapping,
+            &w.b.sessions,
+            &w.b.schemas,
+            w.provenance(),
+            w.dictionary(),
+        )
+        .handle(
+            &mut ctx(),
+            &command(serde_json::json!({
+                "type": "AssignItemValue",
+                "payload": {
+                    "session_id": session_id,
+                    "item_id": item_id,
+                    "schema_field_id": w.field_id("description"),
+                    "raw_value": raw_value,
+                    "provenance": { "source": "manual", "actor": "tester" }
+                }
+            })),
+        )
+    }
+
+    #[test]
+    fn locked_field_values_reject_assignments() {
+        let w = World::new(&[("vendor", "string", "document", false)]);
+        let session_id = w.session(SessionStatus::Review);
+        let (document_id, _) = w.document(session_id);
+        assign_field(&w, session_id, document_id, "Acme").unwrap();
+        let mut value = w.b.mapping.field_values(session_id).unwrap().remove(0);
+        value.locked = true;
+        w.b.mapping.put_field_value(&value).unwrap();
+
+        let err = assign_field(&w, session_id, document_id, "Globex").unwrap_err();
+        assert!(matches!(err.code, ErrorCode::ValueLocked));
+        let stored = w.b.mapping.field_values(session_id).unwrap().remove(0);
+        assert_eq!(stored.raw_value, "Acme");
+
+        value.locked = false;
+        w.b.mapping.put_field_value(&value).unwrap();
+        assign_field(&w, session_id, document_id, "Globex").unwrap();
+        let stored = w.b.mapping.field_values(session_id).unwrap().remove(0);
+        assert_eq!(stored.raw_value, "Globex");
+    }
+
+    #[test]
+    fn locked_item_rows_and_values_reject_assignments() {
+        let w = World::new(&[("description", "string", "item", false)]);
+        let session_id = w.session(SessionStatus::Review);
+        let (document_id, _) = w.document(session_id);
+        let mut row = ItemRow {
+            item_id: Uuid::now_v7(),
+            session_id,
+            document_id,
+            row_index: 0,
+            locked: true,
+        };
+        w.b.mapping.put_item_row(&row).unwrap();
+
+        let err = assign_item(&w, session_id, row.item_id, "Widget").unwrap_err();
+        assert!(matches!(err.code, ErrorCode::ValueLocked));
+        assert!(w.b.mapping.item_values(session_id).unwrap().is_empty());
+
+        row.locked = false;
+        w.b.mapping.put_item_row(&row).unwrap();
+        assign_item(&w, session_id, row.item_id, "Widget").unwrap();
+        let mut value = w.b.mapping.item_values(session_id).unwrap().remove(0);
+        value.locked = true;
+        w.b.mapping.put_item_value(&value).unwrap();
+
+        let err = assign_item(&w, session_id, row.item_id, "Gadget").unwrap_err();
+        assert!(matches!(err.code, ErrorCode::ValueLocked));
+        let stored = w.b.mapping.item_values(session_id).unwrap().remove(0);
+        assert_eq!(stored.raw_value, "Widget");
+
+        value.locked = false;
+        w.b.mapping.put_item_value(&value).unwrap();
+        assign_item(&w, session_id, row.item_id, "Gadget").unwrap();
+        let stored = w.b.mapping.item_values(session_id).unwrap().remove(0);
+        assert_eq!(stored.raw_value, "Gadget");
+    }
+}
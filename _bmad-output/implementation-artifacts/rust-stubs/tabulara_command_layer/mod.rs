@@ -1,8 +1,37 @@
+pub mod anchors;
+pub mod batch_resolve;
 pub mod commands;
 pub mod command_router;
+pub mod dictionary;
 pub mod dispatcher_impl;
 pub mod errors;
+pub mod export;
+pub mod extra_tables;
+pub mod extraction;
 pub mod in_memory_reference_impl;
 pub mod interfaces;
+pub mod item_rows;
+pub mod jobs;
+pub mod locks;
+pub mod mapping;
+pub mod overrides;
+pub mod provenance;
+pub mod rerun_extraction;
+pub mod retroactive;
+pub mod review_tasks;
+pub mod run_extraction;
+pub mod schema;
+pub mod sessions;
+pub mod table_mapping;
+pub mod templates;
 pub mod transition_policy;
 pub mod types;
+pub mod unknown_bucket;
+pub mod validation;
+pub mod validation_dsl;
+pub mod value_history;
+pub mod value_parsing;
+pub mod zones;
+
+#[cfg(test)]
+mod test_support;
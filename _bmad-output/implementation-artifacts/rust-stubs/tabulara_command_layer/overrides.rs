@@ -0,0 +1,585 @@
+use std::collections::{BTreeSet, HashMap};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::commands::{
+    AnyCommand, ApproveValidationOverride, ConfigureValidationOverrides, OverrideValidation,
+};
+use crate::errors::{unsupported_command, DomainError, DomainResult, ErrorCode};
+use crate::interfaces::{
+    CommandContext, CommandOutcome, GenericCommandHandler, MappingStore, OutcomeEvent,
+    OverrideStore, SchemaStore, SessionReader, StateDelta, ValidationStore, ValidationTrigger,
+};
+use crate::types::Severity;
+use crate::validation::{Finding, SessionSnapshot};
+
+/// A reason an override may be filed under.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct OverrideReasonCode {
+    pub code: String,
+    pub label: String,
+}
+
+/// `validation_override_policies` row: how a project's reviewers may override
+/// validation results.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OverridePolicy {
+    pub project_id: Uuid,
+    pub reason_codes: Vec<OverrideReasonCode>,
+    /// Overrides of blocking results stay pending until a second actor approves them.
+    pub blocking_requires_approval: bool,
+}
+
+impl OverridePolicy {
+    /// Applies until the project configures its own list.
+    pub fn default_for(project_id: Uuid) -> Self {
+        let code = |code: &str, label: &str| OverrideReasonCode {
+            code: code.to_string(),
+            label: label.to_string(),
+        };
+        Self {
+            project_id,
+            reason_codes: vec![
+                code(
+                    "confirmed_with_source",
+                    "Confirmed against the source document",
+                ),
+                code(
+                    "known_exception",
+                    "Known exception for this vendor or document",
+                ),
+                code(
+                    "rule_not_applicable",
+                    "Rule does not apply to this document",
+                ),
+                code("other", "Other (explained in the reason)"),
+            ],
+            blocking_requires_approval: false,
+        }
+    }
+
+    pub fn validate(&self) -> DomainResult<()> {
+        let invalid = |message: &str| DomainError {
+            code: ErrorCode::PreconditionFailed,
+            message: message.to_string(),
+            details: Some(serde_json::json!({ "reason_codes": self.reason_codes })),
+        };
+        if self.reason_codes.is_empty() {
+            return Err(invalid("At least one override reason code is required"));
+        }
+        let mut codes = BTreeSet::new();
+        for reason in &self.reason_codes {
+            let code_ok = !reason.code.is_empty()
+                && reason
+                    .code
+                    .chars()
+                    .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_');
+            if !code_ok {
+                return Err(invalid(
+                    "Override reason codes must be snake_case ([a-z0-9_])",
+                ));
+            }
+            if reason.label.trim().is_empty() {
+                return Err(invalid("Override reason labels must not be empty"));
+            }
+            if !codes.insert(reason.code.as_str()) {
+                return Err(invalid("Override reason codes must be unique"));
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum OverrideStatus {
+    PendingApproval,
+    Active,
+    /// A covered value changed; the finding must be reviewed again.
+    Expired,
+}
+
+/// A value as it was when the override was filed.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct CoveredValue {
+    pub value_id: Uuid,
+    pub raw_value: String,
+    pub normalized_value: Option<String>,
+}
+
+/// `validation_overrides` row.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ValidationOverride {
+    pub override_id: Uuid,
+    pub session_id: Uuid,
+    /// The result the override was filed against. Later runs produce new result
+    /// rows; the override carries over to those with the same rule, document, item
+    /// and covered values.
+    pub validation_result_id: Uuid,
+    pub validation_run_id: Uuid,
+    pub finding: Finding,
+    pub covered_values: Vec<CoveredValue>,
+    pub reason_code: String,
+    pub reason: String,
+    pub actor: String,
+    pub created_at: DateTime<Utc>,
+    pub caused_by: Uuid,
+    pub status: OverrideStatus,
+    pub approved_by: Option<String>,
+    pub approved_at: Option<DateTime<Utc>>,
+    pub expired_at: Option<DateTime<Utc>>,
+    /// The validation run that found the covered values changed.
+    pub expired_by: Option<Uuid>,
+}
+
+impl ValidationOverride {
+    pub fn is_open(&self) -> bool {
+        self.status != OverrideStatus::Expired
+    }
+
+    fn covers_target(&self, finding: &Finding) -> bool {
+        self.finding.rule_key == finding.rule_key
+            && self.finding.document_id == finding.document_id
+            && self.finding.item_id == finding.item_id
+            && self.finding.extra_row_id == finding.extra_row_id
+    }
+
+    fn covered_ids(&self) -> BTreeSet<Uuid> {
+        self.covered_values.iter().map(|v| v.value_id).collect()
+    }
+}
+
+/// Open overrides checked against a new run's findings.
+#[derive(Debug, Default)]
+pub struct ReconciledOverrides {
+    /// Overrides whose covered values changed, already marked expired.
+    pub expired: Vec<ValidationOverride>,
+    /// Active override per finding index.
+    pub applied: HashMap<usize, Uuid>,
+}
+
+impl ReconciledOverrides {
+    /// An open override expires when a covered value changed or disappeared, or when
+    /// its rule now reads a different set of values for the same document and item.
+    /// The remaining active overrides apply to their findings.
+    pub fn reconcile(
+        overrides: Vec<ValidationOverride>,
+        snapshot: &SessionSnapshot,
+        findings: &[Finding],
+        now: DateTime<Utc>,
+        validation_run_id: Uuid,
+    ) -> Self {
+        let observed = snapshot.observed_by_id();
+        let mut reconciled = Self::default();
+        for mut record in overrides.into_iter().filter(ValidationOverride::is_open) {
+            let values_changed = record.covered_values.iter().any(|covered| {
+                observed.get(&covered.value_id).is_none_or(|current| {
+                    current.raw_value != covered.raw_value
+                        || current.normalized_value != covered.normalized_value
+                })
+            });
+            let covered_ids = record.covered_ids();
+            let matching = findings.iter().position(|f| record.covers_target(f));
+            let reads_other_values = matching.is_some_and(|i| {
+                findings[i]
+                    .value_ids
+                    .iter()
+                    .copied()
+                    .collect::<BTreeSet<_>>()
+                    != covered_ids
+            });
+            if values_changed || reads_other_values {
+                record.status = OverrideStatus::Expired;
+                record.expired_at = Some(now);
+                record.expired_by = Some(validation_run_id);
+                reconciled.expired.push(record);
+            } else if let (Some(index), OverrideStatus::Active) = (matching, record.status) {
+                reconciled.applied.insert(index, record.override_id);
+            }
+        }
+        reconciled
+    }
+
+    /// One `ValidationOverrideExpired` event per expired override.
+    pub fn events(&self) -> Vec<OutcomeEvent> {
+        self.expired
+            .iter()
+            .map(|record| OutcomeEvent {
+                event_type: "ValidationOverrideExpired".to_string(),
+                session_id: Some(record.session_id),
+                data: serde_json::json!({
+                    "override_id": record.override_id,
+                    "validation_result_id": record.validation_result_id,
+                    "rule_key": record.finding.rule_key,
+                    "document_id": record.finding.document_id,
+                    "item_id": record.finding.item_id,
+                    "extra_row_id": record.finding.extra_row_id,
+                    "expired_by": record.expired_by,
+                }),
+            })
+            .collect()
+    }
+}
+
+/// Overrides section of the export manifest. Expired and pending overrides are
+/// listed too so the manifest shows every exception taken during review.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OverrideManifest {
+    pub active: usize,
+    pub pending_approval: usize,
+    pub expired: usize,
+    pub overrides: Vec<ValidationOverride>,
+}
+
+impl OverrideManifest {
+    pub fn load(store: &dyn OverrideStore, session_id: Uuid) -> DomainResult<Self> {
+        let mut overrides = store.overrides(session_id)?;
+        overrides.sort_by_key(|o| (o.created_at, o.override_id));
+        let count =
+            |status: OverrideStatus| overrides.iter().filter(|o| o.status == status).count();
+        Ok(Self {
+            active: count(OverrideStatus::Active),
+            pending_approval: count(OverrideStatus::PendingApproval),
+            expired: count(OverrideStatus::Expired),
+            overrides,
+        })
+    }
+}
+
+fn covered_values(
+    mapping: &dyn MappingStore,
+    session_id: Uuid,
+    value_ids: &[Uuid],
+) -> DomainResult<Vec<CoveredValue>> {
+    let mut current: HashMap<Uuid, CoveredValue> = HashMap::new();
+    for value in mapping.field_values(session_id)? {
+        current.insert(
+            value.field_value_id,
+            CoveredValue {
+                value_id: value.field_value_id,
+                raw_value: value.raw_value,
+                normalized_value: value.normalized_value,
+            },
+        );
+    }
+    for value in mapping.item_values(session_id)? {
+        current.insert(
+            value.item_value_id,
+            CoveredValue {
+                value_id: value.item_value_id,
+                raw_value: value.raw_value,
+                normalized_value: value.normalized_value,
+            },
+        );
+    }
+    for value in mapping.extra_values(session_id)? {
+        current.insert(
+            value.extra_value_id,
+            CoveredValue {
+                value_id: value.extra_value_id,
+                raw_value: value.raw_value,
+                normalized_value: value.normalized_value,
+            },
+        );
+    }
+    Ok(value_ids
+        .iter()
+        .filter_map(|id| current.remove(id))
+        .collect())
+}
+
+pub struct OverrideDeps<'a> {
+    pub validation: &'a dyn ValidationStore,
+    pub overrides: &'a dyn OverrideStore,
+    pub mapping: &'a dyn MappingStore,
+    pub sessions: &'a dyn SessionReader,
+}
+
+/// Files an override against a result of the session's latest validation run. The
+/// reason code must be on the project's list; blocking results may need approval.
+pub struct OverrideValidationHandler<'a> {
+    deps: OverrideDeps<'a>,
+}
+
+impl<'a> OverrideValidationHandler<'a> {
+    pub fn new(deps: OverrideDeps<'a>) -> Self {
+        Self { deps }
+    }
+
+    fn file(&self, ctx: &CommandContext, cmd: &OverrideValidation) -> DomainResult<CommandOutcome> {
+        let payload = &cmd.payload;
+        let session = self.deps.sessions.get_session(payload.session_id)?;
+        let reason = payload.reason.trim();
+        if reason.is_empty() {
+            return Err(DomainError {
+                code: ErrorCode::PreconditionFailed,
+                message: "Override reason must not be empty".to_string(),
+                details: None,
+            });
+        }
+        let policy = self
+            .deps
+            .overrides
+            .policy(session.project_id)?
+            .unwrap_or_else(|| OverridePolicy::default_for(session.project_id));
+        if !policy
+            .reason_codes
+            .iter()
+            .any(|r| r.code == payload.reason_code)
+        {
+            return Err(DomainError {
+                code: ErrorCode::PreconditionFailed,
+                message: "Override reason code is not on the project's list".to_string(),
+                details: Some(serde_json::json!({
+                    "reason_code": payload.reason_code,
+                    "allowed": policy.reason_codes,
+                })),
+            });
+        }
+
+        let latest = self.deps.validation.latest_run(payload.session_id)?;
+        let result = match &latest {
+            Some(run) => self
+                .deps
+                .validation
+                .results(run.validation_run_id)?
+                .into_iter()
+                .find(|r| r.validation_result_id == payload.validation_result_id),
+            None => None,
+        };
+        let Some(result) = result else {
+            return Err(DomainError {
+                code: ErrorCode::PreconditionFailed,
+                message: "Only results of the session's latest validation run can be overridden"
+                    .to_string(),
+                details: Some(serde_json::json!({
+                    "validation_result_id": payload.validation_result_id,
+                    "latest_validation_run_id": latest.map(|r| r.validation_run_id),
+                })),
+            });
+        };
+        if let Some(open) = self
+            .deps
+            .overrides
+            .overrides(payload.session_id)?
+            .into_iter()
+            .find(|o| o.is_open() && o.covers_target(&result.finding))
+        {
+            return Err(DomainError {
+                code: ErrorCode::PreconditionFailed,
+                message: "Validation result is already overridden".to_string(),
+                details: Some(serde_json::json!({
+                    "validation_result_id": payload.validation_result_id,
+                    "override_id": open.override_id,
+                    "status": open.status,
+                })),
+            });
+        }
+
+        let status =
+            if result.finding.severity == Severity::Blocking && policy.blocking_requires_approval {
+                OverrideStatus::PendingApproval
+            } else {
+                OverrideStatus::Active
+            };
+        let record = ValidationOverride {
+            override_id: Uuid::now_v7(),
+            session_id: payload.session_id,
+            validation_result_id: result.validation_result_id,
+            validation_run_id: result.validation_run_id,
+            covered_values: covered_values(
+                self.deps.mapping,
+                payload.session_id,
+                &result.finding.value_ids,
+            )?,
+            finding: result.finding,
+            reason_code: payload.reason_code.clone(),
+            reason: reason.to_string(),
+            actor: ctx.actor.clone(),
+            created_at: ctx.now,
+            caused_by: cmd.command_id,
+            status,
+            approved_by: None,
+            approved_at: None,
+            expired_at: None,
+            expired_by: None,
+        };
+        self.deps.overrides.put_override(&record)?;
+
+        Ok(CommandOutcome {
+            state_delta: StateDelta {
+                summary: match status {
+                    OverrideStatus::PendingApproval => {
+                        "Validation override filed; awaiting approval".to_string()
+                    }
+                    _ => "Validation result overridden".to_string(),
+                },
+                data: serde_json::json!({ "validation_override": record }),
+            },
+            transition: None,
+            review_actions: Vec::new(),
+            validation_trigger: match status {
+                OverrideStatus::Active => ValidationTrigger::Async,
+                _ => ValidationTrigger::None,
+            },
+            events: Vec::new(),
+        })
+    }
+}
+
+impl<'a> GenericCommandHandler for OverrideValidationHandler<'a> {
+    fn can_handle(&self, command_type: &str) -> bool {
+        command_type == "OverrideValidation"
+    }
+
+    fn handle(&self, ctx: &mut CommandContext, cmd: &AnyCommand) -> DomainResult<CommandOutcome> {
+        match cmd {
+            AnyCommand::OverrideValidation(c) => self.file(ctx, c),
+            _ => Err(unsupported_command("OverrideValidationHandler")),
+        }
+    }
+}
+
+/// Activates a pending override. The approver must differ from the actor who
+/// filed it.
+pub struct ApproveValidationOverrideHandler<'a> {
+    overrides: &'a dyn OverrideStore,
+}
+
+impl<'a> ApproveValidationOverrideHandler<'a> {
+    pub fn new(overrides: &'a dyn OverrideStore) -> Self {
+        Self { overrides }
+    }
+
+    fn approve(
+        &self,
+        ctx: &CommandContext,
+        cmd: &ApproveValidationOverride,
+    ) -> DomainResult<CommandOutcome> {
+        let payload = &cmd.payload;
+        let Some(mut record) = self
+            .overrides
+            .overrides(payload.session_id)?
+            .into_iter()
+            .find(|o| o.override_id == payload.override_id)
+        else {
+            return Err(DomainError {
+                code: ErrorCode::NotFound,
+                message: "Validation override not found".to_string(),
+                details: Some(serde_json::json!({
+                    "session_id": payload.session_id,
+                    "override_id": payload.override_id,
+                })),
+            });
+        };
+        if record.status != OverrideStatus::PendingApproval {
+            return Err(DomainError {
+                code: ErrorCode::PreconditionFailed,
+                message: "Validation override is not awaiting approval".to_string(),
+                details: Some(serde_json::json!({
+                    "override_id": record.override_id,
+                    "status": record.status,
+                })),
+            });
+        }
+        if record.actor == ctx.actor {
+            return Err(DomainError {
+                code: ErrorCode::PreconditionFailed,
+                message: "Overrides must be approved by a second actor".to_string(),
+                details: Some(serde_json::json!({
+                    "override_id": record.override_id,
+                    "actor": record.actor,
+                })),
+            });
+        }
+
+        record.status = OverrideStatus::Active;
+        record.approved_by = Some(ctx.actor.clone());
+        record.approved_at = Some(ctx.now);
+        self.overrides.put_override(&record)?;
+
+        Ok(CommandOutcome {
+            state_delta: StateDelta {
+                summary: "Validation override approved".to_string(),
+                data: serde_json::json!({ "validation_override": record }),
+            },
+            transition: None,
+            review_actions: Vec::new(),
+            validation_trigger: ValidationTrigger::Async,
+            events: Vec::new(),
+        })
+    }
+}
+
+impl<'a> GenericCommandHandler for ApproveValidationOverrideHandler<'a> {
+    fn can_handle(&self, command_type: &str) -> bool {
+        command_type == "ApproveValidationOverride"
+    }
+
+    fn handle(&self, ctx: &mut CommandContext, cmd: &AnyCommand) -> DomainResult<CommandOutcome> {
+        match cmd {
+            AnyCommand::ApproveValidationOverride(c) => self.approve(ctx, c),
+            _ => Err(unsupported_command("ApproveValidationOverrideHandler")),
+        }
+    }
+}
+
+/// Replaces a project's override policy. Existing overrides keep their reason code.
+pub struct ConfigureValidationOverridesHandler<'a> {
+    overrides: &'a dyn OverrideStore,
+    schemas: &'a dyn SchemaStore,
+}
+
+impl<'a> ConfigureValidationOverridesHandler<'a> {
+    pub fn new(overrides: &'a dyn OverrideStore, schemas: &'a dyn SchemaStore) -> Self {
+        Self { overrides, schemas }
+    }
+
+    fn configure(&self, cmd: &ConfigureValidationOverrides) -> DomainResult<CommandOutcome> {
+        let payload = &cmd.payload;
+        self.schemas.get_project(payload.project_id)?;
+        let policy = OverridePolicy {
+            project_id: payload.project_id,
+            reason_codes: payload
+                .reason_codes
+                .iter()
+                .map(|r| OverrideReasonCode {
+                    code: r.code.trim().to_string(),
+                    label: r.label.trim().to_string(),
+                })
+                .collect(),
+            blocking_requires_approval: payload.blocking_requires_approval,
+        };
+        policy.validate()?;
+        self.overrides.set_policy(&policy)?;
+
+        Ok(CommandOutcome {
+            state_delta: StateDelta {
+                summary: format!(
+                    "Override policy set with {} reason code(s)",
+                    policy.reason_codes.len()
+                ),
+                data: serde_json::json!({ "override_policy": policy }),
+            },
+            transition: None,
+            review_actions: Vec::new(),
+            validation_trigger: ValidationTrigger::None,
+            events: Vec::new(),
+        })
+    }
+}
+
+impl<'a> GenericCommandHandler for ConfigureValidationOverridesHandler<'a> {
+    fn can_handle(&self, command_type: &str) -> bool {
+        command_type == "ConfigureValidationOverrides"
+    }
+
+    fn handle(&self, _ctx: &mut CommandContext, cmd: &AnyCommand) -> DomainResult<CommandOutcome> {
+        match cmd {
+            AnyCommand::ConfigureValidationOverrides(c) => self.configure(c),
+            _ => Err(unsupported_command("ConfigureValidationOverridesHandler")),
+        }
+    }
+}
@@ -0,0 +1,574 @@
+use std::collections::HashSet;
+
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::dictionary::DictionaryTraceStep;
+use crate::errors::{DomainError, DomainResult, ErrorCode};
+use crate::extraction::BoundingBox;
+use crate::interfaces::{
+    AnchorStore, DictionaryStore, ExtractionStore, PageReader, TemplateStore, ZoneStore,
+};
+use crate::sessions::SessionRecord;
+use crate::types::SourceType;
+
+/// How a value was produced. Serialized inline with its `Provenance`, tagged by `source`.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "source", rename_all = "snake_case")]
+pub enum Origin {
+    /// Typed or corrected by a person.
+    Manual {
+        actor: String,
+        /// Set when the value was promoted from the unknown bucket.
+        #[serde(default)]
+        unknown_fragment_id: Option<Uuid>,
+        /// Set when the value was written by `BatchResolveField`.
+        #[serde(default)]
+        batch_command_id: Option<Uuid>,
+    },
+    Anchor {
+        anchor_id: Uuid,
+        #[serde(default)]
+        label_token_ids: Vec<Uuid>,
+    },
+    Zone {
+        zone_id: Uuid,
+        /// `(dx, dy)` applied to the template rectangle; zero when unregistered.
+        #[serde(default)]
+        offset: (f64, f64),
+        #[serde(default)]
+        registration_token_id: Option<Uuid>,
+    },
+    /// A cell of a detected table, mapped by `MapTableColumns`. `row` and `column`
+    /// index the recognized grid, which matches the engine's cells when it reported any.
+    Table {
+        table_id: Uuid,
+        row: u32,
+        column: u32,
+    },
+}
+
+/// Where a mapped value came from. Every automatic origin carries the extraction run,
+/// page and bounding box it was read from, so the UI can highlight the source region
+/// and an exported cell can be traced back to its tokens. Manual values may carry a
+/// box the user drew.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Provenance {
+    #[serde(flatten)]
+    pub origin: Origin,
+    #[serde(default)]
+    pub extraction_run_id: Option<Uuid>,
+    #[serde(default)]
+    pub page_id: Option<Uuid>,
+    #[serde(default)]
+    pub bbox: Option<BoundingBox>,
+    #[serde(default)]
+    pub token_ids: Vec<Uuid>,
+    #[serde(default)]
+    pub confidence: Option<f32>,
+    /// Template the document matched when the value was extracted.
+    #[serde(default)]
+    pub template_id: Option<Uuid>,
+    /// Dictionary rules that rewrote the raw value, in firing order.
+    #[serde(default)]
+    pub dictionary_rules: Vec<DictionaryTraceStep>,
+}
+
+impl Provenance {
+    /// Provenance with only an origin; the location fields are left empty.
+    pub fn new(origin: Origin) -> Self {
+        Self {
+            origin,
+            extraction_run_id: None,
+            page_id: None,
+            bbox: None,
+            token_ids: Vec::new(),
+            confidence: None,
+            template_id: None,
+            dictionary_rules: Vec::new(),
+        }
+    }
+
+    pub fn manual(actor: &str) -> Self {
+        Self::new(Origin::Manual {
+            actor: actor.to_string(),
+            unknown_fragment_id: None,
+            batch_command_id: None,
+        })
+    }
+
+    /// Provenance of a value read from extraction tokens of one page.
+    pub fn extracted(
+        origin: Origin,
+        extraction_run_id: Uuid,
+        page_id: Uuid,
+        bbox: BoundingBox,
+        token_ids: Vec<Uuid>,
+        confidence: f32,
+    ) -> Self {
+        Self {
+            extraction_run_id: Some(extraction_run_id),
+            page_id: Some(page_id),
+            bbox: Some(bbox),
+            token_ids,
+            confidence: Some(confidence),
+            ..Self::new(origin)
+        }
+    }
+
+    pub fn source(&self) -> SourceType {
+        match self.origin {
+            Origin::Manual { .. } => SourceType::Manual,
+            Origin::Anchor { .. } => SourceType::Anchor,
+            Origin::Zone { .. } => SourceType::Zone,
+            Origin::Table { .. } => SourceType::Table,
+        }
+    }
+
+    /// Checks the provenance is self-consistent for a command issued by `actor`:
+    /// manual values name that actor, automatic values locate their source region,
+    /// and tokens are only listed together with the run and page they belong to.
+    pub fn validate_shape(&self, actor: &str) -> DomainResult<()> {
+        match &self.origin {
+            Origin::Manual { actor: author, .. } => {
+                if author.trim().is_empty() || author != actor {
+                    return Err(invalid(
+                        "Manual provenance must name the command actor",
+                        serde_json::json!({ "actor": author, "command_actor": actor }),
+                    ));
+                }
+            }
+            _ => {
+                if self.extraction_run_id.is_none() || self.page_id.is_none() || self.bbox.is_none()
+                {
+                    return Err(invalid(
+                        "Extracted provenance requires extraction_run_id, page_id and bbox",
+                        serde_json::json!({ "source": self.source() }),
+                    ));
+                }
+            }
+        }
+        if self.extraction_run_id.is_some() && self.page_id.is_none() {
+            return Err(invalid(
+                "Provenance naming an extraction run requires page_id",
+                serde_json::json!({ "extraction_run_id": self.extraction_run_id }),
+            ));
+        }
+        if !self.token_ids.is_empty() && self.extraction_run_id.is_none() {
+            return Err(invalid(
+                "Provenance tokens require extraction_run_id",
+                serde_json::json!({ "token_ids": self.token_ids }),
+            ));
+        }
+        if let Some(bbox) = self.bbox.filter(|b| !b.is_normalized()) {
+            return Err(invalid(
+                "Provenance bbox must lie within the page in normalized coordinates",
+                serde_json::json!({ "bbox": bbox }),
+            ));
+        }
+        if let Some(confidence) = self.confidence.filter(|c| !(0.0..=1.0).contains(c)) {
+            return Err(invalid(
+                "Provenance confidence must be between 0 and 1",
+                serde_json::json!({ "confidence": confidence }),
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Validates the references in a command's provenance against the stores: the page
+/// belongs to the value's document, the run covers that page, the tokens come from
+/// that run and page, and anchors, zones, templates, tables and dictionary rules exist
+/// in the session's project.
+#[derive(Clone, Copy)]
+pub struct ProvenanceChecker<'a> {
+    pub pages: &'a dyn PageReader,
+    pub extraction: &'a dyn ExtractionStore,
+    pub anchors: &'a dyn AnchorStore,
+    pub zones: &'a dyn ZoneStore,
+    pub templates: &'a dyn TemplateStore,
+    pub dictionary: &'a dyn DictionaryStore,
+}
+
+impl<'a> ProvenanceChecker<'a> {
+    pub fn check(
+        &self,
+        provenance: &Provenance,
+        actor: &str,
+        session: &SessionRecord,
+        document_id: Uuid,
+    ) -> DomainResult<()> {
+        provenance.validate_shape(actor)?;
+        let project_id = session.project_id;
+        let foreign = |kind: &str, id: Uuid| {
+            invalid(
+                &format!("Provenance {kind} does not belong to the session's project"),
+                serde_json::json!({ kind: id, "project_id": project_id }),
+            )
+        };
+
+        if let Some(page_id) = provenance.page_id {
+            let on_document = self
+                .pages
+                .session_pages(session.session_id)?
+                .iter()
+                .any(|p| p.page_id == page_id && p.document_id == document_id);
+            if !on_document {
+                return Err(invalid(
+                    "Provenance page is not a page of the value's document",
+                    serde_json::json!({ "page_id": page_id, "document_id": document_id }),
+                ));
+            }
+        }
+        if let (Some(run_id), Some(page_id)) = (provenance.extraction_run_id, provenance.page_id) {
+            let run = referenced(self.extraction.get_run(run_id), "extraction_run_id", run_id)?;
+            if run.session_id != session.session_id || !run.covers_page(page_id) {
+                return Err(invalid(
+                    "Provenance extraction run does not cover the page",
+                    serde_json::json!({ "extraction_run_id": run_id, "page_id": page_id }),
+                ));
+            }
+            self.check_tokens(&provenance.token_ids, run_id, page_id)?;
+        }
+
+        match &provenance.origin {
+            Origin::Manual { .. } => {}
+            Origin::Anchor {
+                anchor_id,
+                label_token_ids,
+            } => {
+                let anchor = referenced(self.anchors.get(*anchor_id), "anchor_id", *anchor_id)?;
+                if anchor.project_id != project_id {
+                    return Err(foreign("anchor_id", *anchor_id));
+                }
+                if let (Some(run_id), Some(page_id)) =
+                    (provenance.extraction_run_id, provenance.page_id)
+                {
+                    self.check_tokens(label_token_ids, run_id, page_id)?;
+                }
+            }
+            Origin::Zone {
+                zone_id,
+                registration_token_id,
+                ..
+            } => {
+                let zone = referenced(self.zones.get(*zone_id), "zone_id", *zone_id)?;
+                if zone.project_id != project_id {
+                    return Err(foreign("zone_id", *zone_id));
+                }
+                if provenance
+                    .template_id
+                    .is_some_and(|t| t != zone.template_id)
+                {
+                    return Err(invalid(
+                        "Provenance zone belongs to a different template",
+                        serde_json::json!({
+                            "zone_id": zone_id,
+                            "template_id": provenance.template_id,
+                        }),
+                    ));
+                }
+                if let (Some(token_id), Some(run_id), Some(page_id)) = (
+                    registration_token_id,
+                    provenance.extraction_run_id,
+                    provenance.page_id,
+                ) {
+                    self.check_tokens(&[*token_id], run_id, page_id)?;
+                }
+            }
+            Origin::Table {
+                table_id,
+                row,
+                column,
+            } => {
+                let table =
+                    referenced(self.extraction.get_table(*table_id), "table_id", *table_id)?;
+                if Some(table.extraction_run_id) != provenance.extraction_run_id
+                    || Some(table.page_id) != provenance.page_id
+                {
+                    return Err(invalid(
+                        "Provenance table was not detected on the referenced run and page",
+                        serde_json::json!({ "table_id": table_id }),
+                    ));
+                }
+                let has_cell = table
+                    .cells
+                    .iter()
+                    .any(|c| c.row == *row && c.column == *column);
+                if !table.cells.is_empty() && !has_cell {
+                    return Err(invalid(
+                        "Provenance table cell does not exist",
+                        serde_json::json!({ "table_id": table_id, "row": row, "column": column }),
+                    ));
+                }
+            }
+        }
+
+        if let Some(template_id) = provenance.template_id {
+            let template = referenced(self.templates.get(template_id), "template_id", template_id)?;
+            if template.project_id != project_id {
+                return Err(foreign("template_id", template_id));
+            }
+        }
+        for step in &provenance.dictionary_rules {
+            let id = step.dictionary_rule_id;
+            let rule = referenced(self.dictionary.get(id), "dictionary_rule_id", id)?;
+            if rule.project_id != project_id {
+                return Err(foreign("dictionary_rule_id", id));
+            }
+        }
+        Ok(())
+    }
+
+    fn check_tokens(&self, token_ids: &[Uuid], run_id: Uuid, page_id: Uuid) -> DomainResult<()> {
+        if token_ids.is_empty() {
+            return Ok(());
+        }
+        let unique: HashSet<Uuid> = token_ids.iter().copied().collect();
+        let ids: Vec<Uuid> = unique.iter().copied().collect();
+        let tokens = match self.extraction.get_tokens(&ids) {
+            Err(e) if matches!(e.code, ErrorCode::NotFound) => Vec::new(),
+            other => other?,
+        };
+        let on_page = tokens
+            .iter()
+            .filter(|t| t.extraction_run_id == run_id && t.page_id == page_id)
+            .count();
+        if on_page != unique.len() {
+            return Err(invalid(
+                "Provenance tokens are not tokens of the referenced run and page",
+                serde_json::json!({
+                    "token_ids": token_ids,
+                    "extraction_run_id": run_id,
+                    "page_id": page_id,
+                }),
+            ));
+        }
+        Ok(())
+    }
+}
+
+fn invalid(message: &str, details: serde_json::Value) -> DomainError {
+    DomainError {
+        code: ErrorCode::PreconditionFailed,
+        message: message.to_string(),
+        details: Some(details),
+    }
+}
+
+/// Reports a dangling reference as a rejected payload rather than a missing resource.
+fn referenced<T>(found: DomainResult<T>, key: &str, id: Uuid) -> DomainResult<T> {
+    match found {
+        Err(e) if matches!(e.code, ErrorCode::NotFound) => Err(invalid(
+            &format!("Provenance {key} does not exist"),
+            serde_json::json!({ key: id }),
+        )),
+        other => other,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::anchors::AnchorRule;
+    use crate::extraction::{DetectedTable, DetectedTableCell};
+    use crate::interfaces::SessionReader;
+    use crate::test_support::{token, World};
+    use crate::types::SessionStatus;
+
+    /// A session with one document whose page was extracted twice.
+    struct Fixture {
+        w: World,
+        session: SessionRecord,
+        document_id: Uuid,
+        page_id: Uuid,
+        run_id: Uuid,
+        /// A token of the first run.
+        token: Uuid,
+        table_id: Uuid,
+        /// A token of the later run on the same page.
+        rerun_token: Uuid,
+        /// A token of another document's page.
+        other_page_token: Uuid,
+        anchor_id: Uuid,
+    }
+
+    fn bbox() -> BoundingBox {
+        BoundingBox::new(0.1, 0.1, 0.2, 0.05)
+    }
+
+    fn fixture() -> Fixture {
+        let w = World::new(&[("total", "decimal", "document", false)]);
+        let session_id = w.session(SessionStatus::Review);
+        let (document_id, page_id) = w.document(session_id);
+        let table_id = Uuid::now_v7();
+        let cell = |column| DetectedTableCell {
+            row: 0,
+            column,
+            row_span: 1,
+            column_span: 1,
+            bbox: bbox(),
+            confidence: 0.9,
+            token_ids: Vec::new(),
+        };
+        let table = DetectedTable {
+            table_id,
+            extraction_run_id: Uuid::nil(),
+            page_id: Uuid::nil(),
+            bbox: bbox(),
+            confidence: 0.9,
+            row_count: 1,
+            column_count: 2,
+            cells: vec![cell(0), cell(1)],
+        };
+        let first = token("12.00", bbox());
+        let run_id = w.run(
+            session_id,
+            document_id,
+            page_id,
+            vec![first.clone()],
+            vec![table],
+        );
+        let rerun = token("12.00", bbox());
+        w.run(
+            session_id,
+            document_id,
+            page_id,
+            vec![rerun.clone()],
+            Vec::new(),
+        );
+        let (other_document, other_page) = w.document(session_id);
+        let other = token("12.00", bbox());
+        w.run(
+            session_id,
+            other_document,
+            other_page,
+            vec![other.clone()],
+            Vec::new(),
+        );
+        let anchor_id = Uuid::now_v7();
+        w.b.anchors
+            .add(&AnchorRule {
+                anchor_id,
+                project_id: w.project_id,
+                schema_field_id: w.field_id("total"),
+                spec: serde_json::from_value(serde_json::json!({
+                    "label": { "kind": "text", "text": "Total" },
+                    "direction": "right",
+                    "max_offset": 0.2
+                }))
+                .unwrap(),
+                enabled: true,
+                created_by: "tester".to_string(),
+            })
+            .unwrap();
+        Fixture {
+            session: w.b.sessions.get_session(session_id).unwrap(),
+            document_id,
+            page_id,
+            run_id,
+            token: first.token_id,
+            table_id,
+            rerun_token: rerun.token_id,
+            other_page_token: other.token_id,
+            anchor_id,
+            w,
+        }
+    }
+
+    impl Fixture {
+        fn check(&self, provenance: &Provenance) -> DomainResult<()> {
+            self.w
+                .provenance()
+                .check(provenance, "tester", &self.session, self.document_id)
+        }
+
+        /// Read off the first run's page with `token_ids`.
+        fn extracted(&self, origin: Origin, token_ids: Vec<Uuid>) -> Provenance {
+            Provenance::extracted(origin, self.run_id, self.page_id, bbox(), token_ids, 0.9)
+        }
+
+        fn anchor(&self, label_token_ids: Vec<Uuid>) -> Origin {
+            Origin::Anchor {
+                anchor_id: self.anchor_id,
+                label_token_ids,
+            }
+        }
+
+        fn rejected(&self, provenance: &Provenance, message: &str) {
+            match self.check(provenance) {
+                Err(DomainError {
+                    code: ErrorCode::PreconditionFailed,
+                    message: actual,
+                    ..
+                }) => assert!(actual.contains(message), "{actual}"),
+                other => panic!("expected '{message}', got {other:?}"),
+            }
+        }
+    }
+
+    #[test]
+    fn manual_provenance_names_the_command_actor() {
+        let f = fixture();
+        f.check(&Provenance::manual("tester")).unwrap();
+        f.rejected(&Provenance::manual("someone else"), "command actor");
+        f.rejected(&Provenance::manual(" "), "command actor");
+    }
+
+    #[test]
+    fn anchors_and_zones_must_exist() {
+        let f = fixture();
+        f.check(&f.extracted(f.anchor(Vec::new()), Vec::new()))
+            .unwrap();
+
+        let missing = Origin::Anchor {
+            anchor_id: Uuid::now_v7(),
+            label_token_ids: Vec::new(),
+        };
+        f.rejected(
+            &f.extracted(missing, Vec::new()),
+            "anchor_id does not exist",
+        );
+        let missing = Origin::Zone {
+            zone_id: Uuid::now_v7(),
+            offset: (0.0, 0.0),
+            registration_token_id: None,
+        };
+        f.rejected(&f.extracted(missing, Vec::new()), "zone_id does not exist");
+    }
+
+    #[test]
+    fn tokens_must_come_from_the_referenced_run_and_page() {
+        let f = fixture();
+        let ok = f.extracted(f.anchor(vec![f.token]), vec![f.token]);
+        f.check(&ok).unwrap();
+
+        let message = "not tokens of the referenced run and page";
+        for token_id in [f.rerun_token, f.other_page_token, Uuid::now_v7()] {
+            f.rejected(
+                &f.extracted(f.anchor(Vec::new()), vec![f.token, token_id]),
+                message,
+            );
+            f.rejected(&f.extracted(f.anchor(vec![token_id]), Vec::new()), message);
+        }
+    }
+
+    #[test]
+    fn table_provenance_names_an_existing_cell() {
+        let f = fixture();
+        let cell = |table_id, row, column| {
+            f.extracted(
+                Origin::Table {
+                    table_id,
+                    row,
+                    column,
+                },
+                Vec::new(),
+            )
+        };
+        f.check(&cell(f.table_id, 0, 1)).unwrap();
+        f.rejected(&cell(f.table_id, 1, 0), "cell does not exist");
+        f.rejected(&cell(f.table_id, 0, 2), "cell does not exist");
+        f.rejected(&cell(Uuid::now_v7(), 0, 0), "table_id does not exist");
+    }
+}
@@ -0,0 +1,796 @@
+use std::cmp::Ordering;
+use std::collections::{HashMap, HashSet};
+
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::commands::{AnyCommand, ReRunExtraction};
+use crate::errors::{unsupported_command, DomainError, DomainResult, ErrorCode};
+use crate::extraction::{
+    BoundingBox, DetectedTable, DetectedTableCell, EngineOutput, ExtractionArtifacts,
+    ExtractionLine, ExtractionRequest, ExtractionRun, ExtractionRunStatus, ExtractionScope,
+    ExtractionToken, PageCoverage,
+};
+use crate::interfaces::{
+    CommandContext, CommandOutcome, ExtractionEngine, ExtractionStore, GenericCommandHandler,
+    MappingStore, ReviewAction, StateDelta, UnknownBucketStore, ValidationTrigger,
+};
+use crate::mapping::{ExtraRow, WrittenValue};
+use crate::provenance::{Origin, Provenance};
+use crate::review_tasks::{ReviewCategory, ReviewTarget};
+use crate::unknown_bucket::retain_unknown_text;
+
+/// Minimum overlap for a re-read token to count as the same token as before.
+const TOKEN_MATCH_IOU: f64 = 0.5;
+
+/// Token-level comparison between the baseline run and a scoped re-run.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct TokenDiff {
+    /// `(previous, next)` pairs at the same position with the same text.
+    pub unchanged: Vec<(Uuid, Uuid)>,
+    /// `(previous, next)` pairs at the same position whose text differs.
+    pub changed: Vec<(Uuid, Uuid)>,
+    pub removed: Vec<Uuid>,
+    pub added: Vec<Uuid>,
+}
+
+impl TokenDiff {
+    /// Previous token ids whose content can no longer be trusted.
+    pub fn invalidated(&self) -> HashSet<Uuid> {
+        self.changed
+            .iter()
+            .map(|(previous, _)| *previous)
+            .chain(self.removed.iter().copied())
+            .collect()
+    }
+
+    fn extend(&mut self, other: TokenDiff) {
+        self.unchanged.extend(other.unchanged);
+        self.changed.extend(other.changed);
+        self.removed.extend(other.removed);
+        self.added.extend(other.added);
+    }
+}
+
+/// Greedily pairs tokens by overlap (best IoU first) and classifies each pair by text.
+pub fn diff_tokens(previous: &[ExtractionToken], next: &[ExtractionToken]) -> TokenDiff {
+    let mut candidates = Vec::new();
+    for (i, old) in previous.iter().enumerate() {
+        for (j, new) in next.iter().enumerate() {
+            if old.page_id != new.page_id {
+                continue;
+            }
+            let iou = old.bbox.iou(&new.bbox);
+            if iou >= TOKEN_MATCH_IOU {
+                candidates.push((i, j, iou));
+            }
+        }
+    }
+    candidates.sort_by(|a, b| {
+        b.2.partial_cmp(&a.2)
+            .unwrap_or(Ordering::Equal)
+            .then(a.0.cmp(&b.0))
+            .then(a.1.cmp(&b.1))
+    });
+
+    let mut diff = TokenDiff::default();
+    let mut used_previous = HashSet::new();
+    let mut used_next = HashSet::new();
+    for (i, j, _) in candidates {
+        if used_previous.contains(&i) || used_next.contains(&j) {
+            continue;
+        }
+        used_previous.insert(i);
+        used_next.insert(j);
+        let pair = (previous[i].token_id, next[j].token_id);
+        if previous[i].text.trim() == next[j].text.trim() {
+            diff.unchanged.push(pair);
+        } else {
+            diff.changed.push(pair);
+        }
+    }
+
+    diff.removed = previous
+        .iter()
+        .enumerate()
+        .filter(|(i, _)| !used_previous.contains(i))
+        .map(|(_, t)| t.token_id)
+        .collect();
+    diff.added = next
+        .iter()
+        .enumerate()
+        .filter(|(j, _)| !used_next.contains(j))
+        .map(|(_, t)| t.token_id)
+        .collect();
+    diff
+}
+
+struct ScopedPage {
+    coverage: PageCoverage,
+    baseline: ExtractionRun,
+}
+
+struct ResolvedScope {
+    pages: Vec<ScopedPage>,
+    region: Option<BoundingBox>,
+}
+
+pub struct ReRunExtractionHandler<'a> {
+    engine: &'a dyn ExtractionEngine,
+    extraction: &'a dyn ExtractionStore,
+    mapping: &'a dyn MappingStore,
+    unknown: &'a dyn UnknownBucketStore,
+}
+
+impl<'a> ReRunExtractionHandler<'a> {
+    pub fn new(
+        engine: &'a dyn ExtractionEngine,
+        extraction: &'a dyn ExtractionStore,
+        mapping: &'a dyn MappingStore,
+        unknown: &'a dyn UnknownBucketStore,
+    ) -> Self {
+        Self {
+            engine,
+            extraction,
+            mapping,
+            unknown,
+        }
+    }
+
+    fn rerun(&self, ctx: &CommandContext, cmd: &ReRunExtraction) -> DomainResult<CommandOutcome> {
+        let session_id = cmd.payload.session_id;
+        let scope = self.resolve_scope(session_id, &cmd.payload.scope)?;
+
+        let baseline = scope
+            .pages
+            .iter()
+            .map(|p| &p.baseline)
+            .max_by_key(|run| run.started_at)
+            .ok_or_else(|| no_baseline(&cmd.payload.scope))?;
+        let params = if cmd.payload.params.is_null() {
+            baseline.params.clone()
+        } else {
+            cmd.payload.params.clone()
+        };
+
+        let extraction_run_id = Uuid::now_v7();
+        let coverage: Vec<PageCoverage> = scope.pages.iter().map(|p| p.coverage.clone()).collect();
+        let request = ExtractionRequest {
+            extraction_run_id,
+            session_id,
+            engine: baseline.engine.clone(),
+            params: params.clone(),
+            pages: coverage.clone(),
+            region: scope.region,
+        };
+        let output = self.engine.extract(&request)?;
+
+        let in_scope = |page_id: Uuid, bbox: &BoundingBox| {
+            coverage.iter().any(|p| p.page_id == page_id)
+                && scope.region.is_none_or(|region| {
+                    let (x, y) = bbox.center();
+                    region.contains_point(x, y)
+                })
+        };
+
+        let mut tokens: Vec<ExtractionToken> = output
+            .tokens
+            .into_iter()
+            .filter(|t| in_scope(t.page_id, &t.bbox))
+            .collect();
+        let mut lines: Vec<ExtractionLine> = output
+            .lines
+            .into_iter()
+            .filter(|l| in_scope(l.page_id, &l.bbox))
+            .collect();
+        let mut tables: Vec<DetectedTable> = output
+            .tables
+            .into_iter()
+            .filter(|t| in_scope(t.page_id, &t.bbox))
+            .collect();
+
+        let mut diff = TokenDiff::default();
+        for page in &scope.pages {
+            let page_id = page.coverage.page_id;
+            let previous = self
+                .extraction
+                .tokens_for_page(page.baseline.extraction_run_id, page_id)?;
+            let (inside, outside): (Vec<_>, Vec<_>) = previous.into_iter().partition(|t| {
+                scope.region.is_none_or(|region| {
+                    let (x, y) = t.bbox.center();
+                    region.contains_point(x, y)
+                })
+            });
+
+            let next: Vec<ExtractionToken> = tokens
+                .iter()
+                .filter(|t| t.page_id == page_id)
+                .cloned()
+                .collect();
+            diff.extend(diff_tokens(&inside, &next));
+
+            if let Some(region) = scope.region {
+                let carried = self.carry_forward(
+                    extraction_run_id,
+                    &page.baseline,
+                    page_id,
+                    &region,
+                    outside,
+                    &mut diff,
+                )?;
+                tokens.extend(carried.tokens);
+                lines.extend(carried.lines);
+                tables.extend(carried.tables);
+            }
+        }
+
+        let artifacts = ExtractionArtifacts {
+            run: ExtractionRun {
+                extraction_run_id,
+                session_id,
+                caused_by: cmd.command_id,
+                engine: baseline.engine.clone(),
+                params,
+                started_at: ctx.now,
+                finished_at: Some(Utc::now()),
+                status: ExtractionRunStatus::Completed,
+                pages: coverage.clone(),
+            },
+            tokens,
+            lines,
+            tables,
+        };
+        self.extraction.record_run(&artifacts)?;
+        retain_unknown_text(self.unknown, session_id, &artifacts)?;
+
+        let token_remap: HashMap<Uuid, Uuid> = diff.unchanged.iter().copied().collect();
+        let invalidated = diff.invalidated();
+        let carried = |provenance: &Provenance| {
+            carried_provenance(
+                provenance,
+                &token_remap,
+                extraction_run_id,
+                &artifacts.tables,
+            )
+        };
+        let flag =
+            |target: ReviewTarget, provenance: &Provenance, mut payload: serde_json::Value| {
+                let stale: Vec<Uuid> = provenance
+                    .token_ids
+                    .iter()
+                    .copied()
+                    .filter(|id| invalidated.contains(id))
+                    .collect();
+                if stale.is_empty() {
+                    return None;
+                }
+                payload["extraction_run_id"] = serde_json::json!(extraction_run_id);
+                payload["token_ids"] = serde_json::json!(stale);
+                Some(ReviewAction::new(
+                    session_id,
+                    ReviewCategory::ExtractionChanged,
+                    target,
+                    payload,
+                ))
+            };
+
+        let mut review_actions = Vec::new();
+        let mut affected = Vec::new();
+        let mut affected_items = Vec::new();
+        let mut affected_extras = Vec::new();
+        // Carried values keep their text; they are listed so value history sees the
+        // provenance move to the new run.
+        let mut written = Vec::new();
+
+        for mut value in self.mapping.field_values(session_id)? {
+            if let Some(provenance) = carried(&value.provenance) {
+                value.provenance = provenance;
+                self.mapping.put_field_value(&value)?;
+                written.push(WrittenValue::Field(value));
+            } else if !value.locked {
+                let target = ReviewTarget::field(value.document_id, value.schema_field_id);
+                let payload = serde_json::json!({ "field_value_id": value.field_value_id });
+                if let Some(action) = flag(target, &value.provenance, payload) {
+                    affected.push(value.field_value_id);
+                    review_actions.push(action);
+                }
+            }
+        }
+
+        let item_documents: HashMap<Uuid, Uuid> = self
+            .mapping
+            .item_rows(session_id)?
+            .into_iter()
+            .map(|row| (row.item_id, row.document_id))
+            .collect();
+        for mut value in self.mapping.item_values(session_id)? {
+            if let Some(provenance) = carried(&value.provenance) {
+                value.provenance = provenance;
+                self.mapping.put_item_value(&value)?;
+                written.push(WrittenValue::Item(value));
+            } else if !value.locked {
+                let Some(document_id) = item_documents.get(&value.item_id).copied() else {
+                    continue;
+                };
+                let target =
+                    ReviewTarget::item(document_id, value.item_id, Some(value.schema_field_id));
+                let payload = serde_json::json!({ "item_value_id": value.item_value_id });
+                if let Some(action) = flag(target, &value.provenance, payload) {
+                    affected_items.push(value.item_value_id);
+                    review_actions.push(action);
+                }
+            }
+        }
+
+        let extra_rows: HashMap<Uuid, ExtraRow> = self
+            .mapping
+            .extra_rows(session_id)?
+            .into_iter()
+            .map(|row| (row.extra_row_id, row))
+            .collect();
+        for mut value in self.mapping.extra_values(session_id)? {
+            if let Some(provenance) = carried(&value.provenance) {
+                value.provenance = provenance;
+                self.mapping.put_extra_value(&value)?;
+                written.push(WrittenValue::Extra(value));
+            } else if !value.locked {
+                let Some(row) = extra_rows.get(&value.extra_row_id) else {
+                    continue;
+                };
+                let target = ReviewTarget::field(row.document_id, value.schema_field_id);
+                let payload = serde_json::json!({
+                    "extra_value_id": value.extra_value_id,
+                    "extra_row_id": row.extra_row_id,
+                    "table_name": row.table_name,
+                });
+                if let Some(action) = flag(target, &value.provenance, payload) {
+                    affected_extras.push(value.extra_value_id);
+                    review_actions.push(action);
+                }
+            }
+        }
+
+        let flagged = affected.len() + affected_items.len() + affected_extras.len();
+        let validation_trigger = if flagged == 0 {
+            ValidationTrigger::None
+        } else {
+            ValidationTrigger::Async
+        };
+
+        Ok(CommandOutcome {
+            state_delta: StateDelta {
+                summary: format!(
+                    "Re-extracted {} page(s); {} value(s) need review",
+                    coverage.len(),
+                    flagged
+                ),
+                data: serde_json::json!({
+                    "extraction_run_id": extraction_run_id,
+                    "scope": cmd.payload.scope,
+                    "pages": coverage,
+                    "token_diff": {
+                        "unchanged": diff.unchanged.len(),
+                        "changed": diff.changed.len(),
+                        "removed": diff.removed.len(),
+                        "added": diff.added.len(),
+                    },
+                    "token_remap": diff.unchanged,
+                    "remapped_values": written.len(),
+                    "affected_field_value_ids": affected,
+                    "affected_item_value_ids": affected_items,
+                    "affected_extra_value_ids": affected_extras,
+                    "written_values": written,
+                }),
+            },
+            transition: None,
+            review_actions,
+            validation_trigger,
+            events: Vec::new(),
+        })
+    }
+
+    fn resolve_scope(&self, session_id: Uuid, scope: &ExtractionScope) -> DomainResult<ResolvedScope> {
+        let runs = self.extraction.runs_for_session(session_id)?;
+        let known_page = |page_id: Uuid| {
+            runs.iter()
+                .flat_map(|r| r.pages.iter())
+                .find(|p| p.page_id == page_id)
+                .cloned()
+        };
+
+        let (coverage, region) = match scope {
+            ExtractionScope::Document { document_id } => {
+                let mut pages: Vec<PageCoverage> = Vec::new();
+                for page in runs.iter().flat_map(|r| r.pages.iter()) {
+                    if page.document_id == *document_id && !pages.iter().any(|p| p.page_id == page.page_id) {
+                        pages.push(page.clone());
+                    }
+                }
+                pages.sort_by_key(|p| p.page_number);
+                (pages, None)
+            }
+            ExtractionScope::Page { page_id } => (known_page(*page_id).into_iter().collect(), None),
+            ExtractionScope::Region { page_id, bbox } => {
+                if !bbox.is_normalized() || bbox.area() <= 0.0 {
+                    return Err(DomainError {
+                        code: ErrorCode::PreconditionFailed,
+                        message: "Re-extraction region must be a non-empty page-normalized box".to_string(),
+                        details: Some(serde_json::json!({ "scope": scope })),
+                    });
+                }
+                (known_page(*page_id).into_iter().collect(), Some(*bbox))
+            }
+            ExtractionScope::Table { table_id } => {
+                let table = self.extraction.get_table(*table_id)?;
+                let owner = self.extraction.get_run(table.extraction_run_id)?;
+                if owner.session_id != session_id {
+                    return Err(DomainError {
+                        code: ErrorCode::PreconditionFailed,
+                        message: "Detected table does not belong to session".to_string(),
+                        details: Some(serde_json::json!({ "table_id": table_id, "session_id": session_id })),
+                    });
+                }
+                (known_page(table.page_id).into_iter().collect(), Some(table.bbox))
+            }
+        };
+
+        let mut pages = Vec::new();
+        for page in coverage {
+            let baseline = self
+                .extraction
+                .latest_run_for_page(session_id, page.page_id)?
+                .ok_or_else(|| no_baseline(scope))?;
+            pages.push(ScopedPage {
+                coverage: page,
+                baseline,
+            });
+        }
+        if pages.is_empty() {
+            return Err(no_baseline(scope));
+        }
+
+        Ok(ResolvedScope { pages, region })
+    }
+
+    /// Copies baseline artifacts outside the re-read region into the new run so the
+    /// new run remains a complete reading of the page.
+    fn carry_forward(
+        &self,
+        extraction_run_id: Uuid,
+        baseline: &ExtractionRun,
+        page_id: Uuid,
+        region: &BoundingBox,
+        outside: Vec<ExtractionToken>,
+        diff: &mut TokenDiff,
+    ) -> DomainResult<EngineOutput> {
+        let mut remap = HashMap::new();
+        let mut carried = EngineOutput::default();
+
+        for token in outside {
+            let token_id = Uuid::now_v7();
+            remap.insert(token.token_id, token_id);
+            diff.unchanged.push((token.token_id, token_id));
+            carried.tokens.push(ExtractionToken {
+                token_id,
+                extraction_run_id,
+                ..token
+            });
+        }
+
+        let remap_all = |ids: &[Uuid]| -> Option<Vec<Uuid>> {
+            ids.iter().map(|id| remap.get(id).copied()).collect()
+        };
+
+        for line in self
+            .extraction
+            .lines_for_page(baseline.extraction_run_id, page_id)?
+        {
+            if line.bbox.intersects(region) {
+                continue;
+            }
+            if let Some(token_ids) = remap_all(&line.token_ids) {
+                carried.lines.push(ExtractionLine {
+                    line_id: Uuid::now_v7(),
+                    extraction_run_id,
+                    token_ids,
+                    ..line
+                });
+            }
+        }
+
+        for table in self
+            .extraction
+            .tables_for_page(baseline.extraction_run_id, page_id)?
+        {
+            if table.bbox.intersects(region) {
+                continue;
+            }
+            let cells: Option<Vec<DetectedTableCell>> = table
+                .cells
+                .iter()
+                .map(|cell| {
+                    remap_all(&cell.token_ids).map(|token_ids| DetectedTableCell {
+                        token_ids,
+                        ..cell.clone()
+                    })
+                })
+                .collect();
+            let Some(cells) = cells else {
+                continue;
+            };
+            carried.tables.push(DetectedTable {
+                table_id: Uuid::now_v7(),
+                extraction_run_id,
+                cells,
+                ..table
+            });
+        }
+
+        Ok(carried)
+    }
+}
+
+impl<'a> GenericCommandHandler for ReRunExtractionHandler<'a> {
+    fn can_handle(&self, command_type: &str) -> bool {
+        command_type == "ReRunExtraction"
+    }
+
+    fn handle(&self, ctx: &mut CommandContext, cmd: &AnyCommand) -> DomainResult<CommandOutcome> {
+        match cmd {
+            AnyCommand::ReRunExtraction(c) => self.rerun(ctx, c),
+            _ => Err(unsupported_command("ReRunExtractionHandler")),
+        }
+    }
+}
+
+/// Provenance re-pointed at the new run, for values read only from tokens the re-run
+/// carried over unchanged. Table provenance also needs the same cell, holding the same
+/// tokens, in one of the new run's tables. `None` leaves the value on its old run.
+fn carried_provenance(
+    provenance: &Provenance,
+    remap: &HashMap<Uuid, Uuid>,
+    extraction_run_id: Uuid,
+    tables: &[DetectedTable],
+) -> Option<Provenance> {
+    if provenance.token_ids.is_empty() {
+        return None;
+    }
+    let map = |ids: &[Uuid]| -> Option<Vec<Uuid>> {
+        ids.iter().map(|id| remap.get(id).copied()).collect()
+    };
+    let mut next = provenance.clone();
+    next.token_ids = map(&provenance.token_ids)?;
+    next.extraction_run_id = Some(extraction_run_id);
+    let cell_tokens: HashSet<Uuid> = next.token_ids.iter().copied().collect();
+    match &mut next.origin {
+        Origin::Manual { .. } => {}
+        Origin::Anchor {
+            label_token_ids, ..
+        } => *label_token_ids = map(label_token_ids)?,
+        Origin::Zone {
+            registration_token_id,
+            ..
+        } => {
+            if let Some(token_id) = registration_token_id {
+                *token_id = *remap.get(token_id)?;
+            }
+        }
+        Origin::Table {
+            table_id,
+            row,
+            column,
+        } => {
+            let table = tables.iter().find(|t| {
+                Some(t.page_id) == provenance.page_id
+                    && t.cells.iter().any(|c| {
+                        c.row == *row
+                            && c.column == *column
+                            && c.token_ids.iter().copied().collect::<HashSet<_>>() == cell_tokens
+                    })
+            })?;
+            *table_id = table.table_id;
+        }
+    }
+    Some(next)
+}
+
+fn no_baseline(scope: &ExtractionScope) -> DomainError {
+    DomainError {
+        code: ErrorCode::PreconditionFailed,
+        message: "No extraction baseline exists for re-extraction scope".to_string(),
+        details: Some(serde_json::json!({ "scope": scope })),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use super::*;
+    use crate::in_memory_reference_impl::InMemoryReferenceBundle;
+    use crate::mapping::{FieldValue, ItemRow, ItemValue};
+    use crate::test_support::{command, ctx};
+
+    const LABEL: BoundingBox = BoundingBox {
+        x: 0.1,
+        y: 0.1,
+        width: 0.2,
+        height: 0.05,
+    };
+    const AMOUNT: BoundingBox = BoundingBox {
+        x: 0.6,
+        y: 0.1,
+        width: 0.2,
+        height: 0.05,
+    };
+
+    /// Answers each request with the next reading, as `(text, bbox)` tokens.
+    struct ScriptedEngine(Mutex<Vec<Vec<(&'static str, BoundingBox)>>>);
+
+    impl ExtractionEngine for ScriptedEngine {
+        fn extract(&self, request: &ExtractionRequest) -> DomainResult<EngineOutput> {
+            let reading = self.0.lock().unwrap().remove(0);
+            let page_id = request.pages[0].page_id;
+            let tokens = reading
+                .into_iter()
+                .enumerate()
+                .map(|(i, (text, bbox))| token(request.extraction_run_id, page_id, text, bbox, i))
+                .collect();
+            Ok(EngineOutput {
+                tokens,
+                ..EngineOutput::default()
+            })
+        }
+    }
+
+    fn token(run: Uuid, page_id: Uuid, text: &str, bbox: BoundingBox, i: usize) -> ExtractionToken {
+        ExtractionToken {
+            token_id: Uuid::now_v7(),
+            extraction_run_id: run,
+            page_id,
+            text: text.to_string(),
+            bbox,
+            confidence: 0.9,
+            reading_order: i as u32,
+        }
+    }
+
+    fn read_from(tokens: &[&ExtractionToken]) -> Provenance {
+        Provenance {
+            extraction_run_id: Some(tokens[0].extraction_run_id),
+            page_id: Some(tokens[0].page_id),
+            token_ids: tokens.iter().map(|t| t.token_id).collect(),
+            ..Provenance::manual("tester")
+        }
+    }
+
+    #[test]
+    fn consecutive_reruns_track_values_through_carried_tokens() {
+        let b = InMemoryReferenceBundle::new();
+        let (session_id, document_id, page_id) = (Uuid::now_v7(), Uuid::now_v7(), Uuid::now_v7());
+        let coverage = PageCoverage {
+            document_id,
+            page_id,
+            page_number: 1,
+        };
+        let baseline_id = Uuid::now_v7();
+        let label = token(baseline_id, page_id, "Total", LABEL, 0);
+        let amount = token(baseline_id, page_id, "100.00", AMOUNT, 1);
+        b.extraction
+            .record_run(&ExtractionArtifacts {
+                run: ExtractionRun {
+                    extraction_run_id: baseline_id,
+                    session_id,
+                    caused_by: Uuid::nil(),
+                    engine: "scripted".to_string(),
+                    params: serde_json::json!({}),
+                    started_at: Utc::now(),
+                    finished_at: None,
+                    status: ExtractionRunStatus::Completed,
+                    pages: vec![coverage],
+                },
+                tokens: vec![label.clone(), amount.clone()],
+                lines: Vec::new(),
+                tables: Vec::new(),
+            })
+            .unwrap();
+
+        let field_value_id = Uuid::now_v7();
+        b.mapping
+            .put_field_value(&FieldValue {
+                field_value_id,
+                session_id,
+                document_id,
+                schema_field_id: Uuid::now_v7(),
+                raw_value: "100.00".to_string(),
+                normalized_value: None,
+                provenance: read_from(&[&amount]),
+                locked: false,
+            })
+            .unwrap();
+        let item_id = Uuid::now_v7();
+        b.mapping
+            .put_item_row(&ItemRow {
+                item_id,
+                session_id,
+                document_id,
+                row_index: 0,
+                locked: false,
+            })
+            .unwrap();
+        let item_value_id = Uuid::now_v7();
+        b.mapping
+            .put_item_value(&ItemValue {
+                item_value_id,
+                session_id,
+                item_id,
+                schema_field_id: Uuid::now_v7(),
+                raw_value: "Total".to_string(),
+                normalized_value: None,
+                provenance: read_from(&[&label]),
+                locked: false,
+            })
+            .unwrap();
+
+        let engine = ScriptedEngine(Mutex::new(vec![
+            vec![("Tota1", LABEL)],
+            vec![("Tota1", LABEL), ("900.00", AMOUNT)],
+        ]));
+        let handler =
+            ReRunExtractionHandler::new(&engine, &b.extraction, &b.mapping, &b.unknown_bucket);
+        let rerun = |scope: serde_json::Value| {
+            handler
+                .handle(
+                    &mut ctx(),
+                    &command(serde_json::json!({
+                        "type": "ReRunExtraction",
+                        "payload": { "session_id": session_id, "scope": scope, "params": null }
+                    })),
+                )
+                .unwrap()
+        };
+
+        // The left half is re-read: the label changed, the amount is carried over.
+        let first = rerun(serde_json::json!({
+            "kind": "region",
+            "page_id": page_id,
+            "bbox": { "x": 0.0, "y": 0.0, "width": 0.5, "height": 1.0 }
+        }));
+        assert_eq!(
+            first.state_delta.data["affected_item_value_ids"],
+            serde_json::json!([item_value_id])
+        );
+        assert_eq!(
+            first.state_delta.data["affected_field_value_ids"],
+            serde_json::json!([])
+        );
+        let first_run = first.state_delta.data["extraction_run_id"].clone();
+        let value = &b.mapping.field_values(session_id).unwrap()[0];
+        assert_eq!(
+            serde_json::json!(value.provenance.extraction_run_id),
+            first_run
+        );
+        assert_ne!(value.provenance.token_ids, vec![amount.token_id]);
+        // The carried value is listed with its new provenance for value history.
+        let written: Vec<WrittenValue> =
+            serde_json::from_value(first.state_delta.data["written_values"].clone()).unwrap();
+        assert!(matches!(
+            &written[..],
+            [WrittenValue::Field(v)]
+                if v.field_value_id == field_value_id && v.provenance == value.provenance
+        ));
+
+        // The whole page is re-read and the amount now differs.
+        let second = rerun(serde_json::json!({ "kind": "page", "page_id": page_id }));
+        assert_eq!(
+            second.state_delta.data["affected_field_value_ids"],
+            serde_json::json!([field_value_id])
+        );
+        assert_eq!(second.review_actions.len(), 1);
+        assert_eq!(
+            second.review_actions[0].category,
+            ReviewCategory::ExtractionChanged
+        );
+    }
+}
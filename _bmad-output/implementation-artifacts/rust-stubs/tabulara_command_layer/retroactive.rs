@@ -0,0 +1,492 @@
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::anchors::{AnchorEvaluator, AnchorRule, AnchorRuleSpec, CompiledAnchor};
+use crate::dictionary::{check_new_rule, DictionaryEngine, DictionaryRule};
+use crate::errors::{DomainError, DomainResult, ErrorCode};
+use crate::interfaces::{
+    AnchorStore, DictionaryStore, ExtractionStore, MappingStore, NormalizationContextResolver,
+    OutcomeEvent, ReviewAction, SchemaStore, SessionReader,
+};
+use crate::mapping::FieldValue;
+use crate::provenance::Provenance;
+use crate::review_tasks::{ReviewCategory, ReviewTarget};
+use crate::schema::{session_schema, SchemaVersion};
+use crate::types::{DictionaryScope, MatchType, SessionStatus};
+use crate::value_parsing::{parse_value, ValueLocale};
+
+/// A learning rule being re-applied to existing sessions.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum LearningRule {
+    Anchor(AnchorRule),
+    Dictionary(DictionaryRule),
+}
+
+impl LearningRule {
+    pub fn project_id(&self) -> Uuid {
+        match self {
+            LearningRule::Anchor(rule) => rule.project_id,
+            LearningRule::Dictionary(rule) => rule.project_id,
+        }
+    }
+
+    pub fn rule_id(&self) -> Uuid {
+        match self {
+            LearningRule::Anchor(rule) => rule.anchor_id,
+            LearningRule::Dictionary(rule) => rule.dictionary_rule_id,
+        }
+    }
+}
+
+/// One field value the rule would change. `field_value_id` is `None` when an anchor
+/// finds a value for a field that has none yet. The proposed value is normalized the
+/// way extraction normalizes it: dictionary rules, then the field type's parser;
+/// `proposed_normalized_value` is `None` when the result does not parse.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RetroactiveChange {
+    pub session_id: Uuid,
+    pub document_id: Uuid,
+    pub schema_field_id: Uuid,
+    pub field_value_id: Option<Uuid>,
+    pub current_raw_value: Option<String>,
+    pub current_normalized_value: Option<String>,
+    pub proposed_raw_value: String,
+    pub proposed_normalized_value: Option<String>,
+    pub provenance: Provenance,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SessionReapplyCount {
+    pub session_id: Uuid,
+    pub values_changed: usize,
+}
+
+/// Dry-run result: how many values a rule would change, without recording anything.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReapplyPreview {
+    pub sessions_scanned: usize,
+    pub sessions_affected: usize,
+    pub values_changed: usize,
+    pub per_session: Vec<SessionReapplyCount>,
+}
+
+/// Changes grouped per session, in session order.
+#[derive(Debug, Clone, Default)]
+pub struct ReapplyPlan {
+    pub sessions_scanned: usize,
+    pub sessions: Vec<(Uuid, Vec<RetroactiveChange>)>,
+}
+
+impl ReapplyPlan {
+    pub fn preview(&self) -> ReapplyPreview {
+        let per_session: Vec<SessionReapplyCount> = self
+            .sessions
+            .iter()
+            .map(|(session_id, changes)| SessionReapplyCount {
+                session_id: *session_id,
+                values_changed: changes.len(),
+            })
+            .collect();
+        ReapplyPreview {
+            sessions_scanned: self.sessions_scanned,
+            sessions_affected: per_session.len(),
+            values_changed: per_session.iter().map(|s| s.values_changed).sum(),
+            per_session,
+        }
+    }
+
+    /// One `rule_proposal` review action per change; values are never written directly.
+    pub fn review_actions(&self, rule: &LearningRule) -> Vec<ReviewAction> {
+        self.sessions
+            .iter()
+            .flat_map(|(_, changes)| changes.iter())
+            .map(|change| {
+                ReviewAction::new(
+                    change.session_id,
+                    ReviewCategory::RuleProposal,
+                    ReviewTarget::field(change.document_id, change.schema_field_id),
+                    serde_json::json!({
+                        "rule_id": rule.rule_id(),
+                        "change": change,
+                    }),
+                )
+                .with_subject(rule.rule_id().to_string())
+            })
+            .collect()
+    }
+
+    /// One `LearningRuleReapplied` event per affected session.
+    pub fn events(&self, rule: &LearningRule) -> Vec<OutcomeEvent> {
+        self.sessions
+            .iter()
+            .map(|(session_id, changes)| OutcomeEvent {
+                event_type: "LearningRuleReapplied".to_string(),
+                session_id: Some(*session_id),
+                data: serde_json::json!({
+                    "rule": rule,
+                    "changes": changes,
+                }),
+            })
+            .collect()
+    }
+}
+
+/// Sessions that can still take mapping changes; `Created`, `Exported` and `Locked`
+/// sessions are left alone.
+pub fn accepts_reapplication(status: SessionStatus) -> bool {
+    matches!(
+        status,
+        SessionStatus::Processing | SessionStatus::Review | SessionStatus::Validated
+    )
+}
+
+/// Re-evaluates a learning rule across a project's open sessions.
+pub struct RetroactiveReapplier<'a> {
+    sessions: &'a dyn SessionReader,
+    extraction: &'a dyn ExtractionStore,
+    mapping: &'a dyn MappingStore,
+    anchors: &'a dyn AnchorStore,
+    dictionary: &'a dyn DictionaryStore,
+    contexts: &'a dyn NormalizationContextResolver,
+    schemas: &'a dyn SchemaStore,
+}
+
+/// What a session's proposals are normalized against.
+struct Normalization<'e> {
+    engine: &'e DictionaryEngine,
+    schema: SchemaVersion,
+    locale: ValueLocale,
+}
+
+impl<'a> RetroactiveReapplier<'a> {
+    pub fn new(
+        sessions: &'a dyn SessionReader,
+        extraction: &'a dyn ExtractionStore,
+        mapping: &'a dyn MappingStore,
+        anchors: &'a dyn AnchorStore,
+        dictionary: &'a dyn DictionaryStore,
+        contexts: &'a dyn NormalizationContextResolver,
+        schemas: &'a dyn SchemaStore,
+    ) -> Self {
+        Self {
+            sessions,
+            extraction,
+            mapping,
+            anchors,
+            dictionary,
+            contexts,
+            schemas,
+        }
+    }
+
+    /// Dry run for an anchor rule that has not been added yet.
+    pub fn preview_anchor_rule(
+        &self,
+        project_id: Uuid,
+        schema_field_id: Uuid,
+        spec: &AnchorRuleSpec,
+    ) -> DomainResult<ReapplyPreview> {
+        let candidate = CompiledAnchor::compile(AnchorRule {
+            anchor_id: Uuid::now_v7(),
+            project_id,
+            schema_field_id,
+            spec: spec.clone(),
+            enabled: true,
+            created_by: String::new(),
+        })?;
+        Ok(self.plan(&LearningRule::Anchor(candidate.rule))?.preview())
+    }
+
+    /// Dry run for a dictionary rule that has not been added yet.
+    pub fn preview_dictionary_rule(
+        &self,
+        project_id: Uuid,
+        scope: DictionaryScope,
+        scope_key: Option<String>,
+        match_type: MatchType,
+        match_value: &str,
+        replace_value: &str,
+    ) -> DomainResult<ReapplyPreview> {
+        let candidate = DictionaryRule {
+            dictionary_rule_id: Uuid::now_v7(),
+            project_id,
+            scope,
+            scope_key,
+            match_type,
+            match_value: match_value.to_string(),
+            replace_value: replace_value.to_string(),
+            enabled: true,
+            created_by: String::new(),
+        };
+        check_new_rule(&candidate, &self.dictionary.rules(project_id)?)?;
+        Ok(self.plan(&LearningRule::Dictionary(candidate))?.preview())
+    }
+
+    /// Changes `rule` would make across the project's open sessions.
+    pub fn plan(&self, rule: &LearningRule) -> DomainResult<ReapplyPlan> {
+        let mut plan = ReapplyPlan::default();
+        let engine = match rule {
+            LearningRule::Dictionary(candidate) => self.dictionary_engine(candidate)?,
+            LearningRule::Anchor(anchor) => {
+                DictionaryEngine::new(self.dictionary.rules(anchor.project_id)?)?
+            }
+        };
+        let locale = self.schemas.get_project(rule.project_id())?.locale;
+
+        for (session_id, status) in self.sessions.project_sessions(rule.project_id())? {
+            if !accepts_reapplication(status) {
+                continue;
+            }
+            plan.sessions_scanned += 1;
+            let values = self.mapping.field_values(session_id)?;
+            let norm = Normalization {
+                engine: &engine,
+                schema: session_schema(self.sessions, self.schemas, session_id)?,
+                locale: locale.clone(),
+            };
+            let changes = match rule {
+                LearningRule::Anchor(anchor) => {
+                    self.anchor_changes(session_id, anchor, &values, &norm)?
+                }
+                LearningRule::Dictionary(candidate) => {
+                    self.dictionary_changes(candidate, &values, &norm)?
+                }
+            };
+            if !changes.is_empty() {
+                plan.sessions.push((session_id, changes));
+            }
+        }
+        Ok(plan)
+    }
+
+    fn dictionary_engine(&self, candidate: &DictionaryRule) -> DomainResult<DictionaryEngine> {
+        let mut rules: Vec<DictionaryRule> = self
+            .dictionary
+            .rules(candidate.project_id)?
+            .into_iter()
+            .filter(|r| r.dictionary_rule_id != candidate.dictionary_rule_id)
+            .collect();
+        rules.push(DictionaryRule {
+            enabled: true,
+            ..candidate.clone()
+        });
+        DictionaryEngine::new(rules)
+    }
+
+    fn anchor_changes(
+        &self,
+        session_id: Uuid,
+        rule: &AnchorRule,
+        values: &[FieldValue],
+        norm: &Normalization,
+    ) -> DomainResult<Vec<RetroactiveChange>> {
+        let Some(field) = norm.schema.field(rule.schema_field_id) else {
+            return Ok(Vec::new());
+        };
+        let mut documents: Vec<Uuid> = Vec::new();
+        for page in self
+            .extraction
+            .runs_for_session(session_id)?
+            .iter()
+            .flat_map(|r| r.pages.iter())
+        {
+            if !documents.contains(&page.document_id) {
+                documents.push(page.document_id);
+            }
+        }
+
+        let evaluator = AnchorEvaluator::new(self.extraction, self.anchors);
+        let mut changes = Vec::new();
+        for document_id in documents {
+            for proposal in evaluator.evaluate_rules(session_id, document_id, vec![rule.clone()])? {
+                let current = values.iter().find(|v| {
+                    v.document_id == document_id && v.schema_field_id == rule.schema_field_id
+                });
+                if current.is_some_and(|v| {
+                    !v.accepts_automatic_update() || v.raw_value == proposal.raw_value
+                }) {
+                    continue;
+                }
+                let mut provenance = proposal.provenance();
+                let mut probe = FieldValue {
+                    field_value_id: current.map_or_else(Uuid::nil, |v| v.field_value_id),
+                    session_id,
+                    document_id,
+                    schema_field_id: rule.schema_field_id,
+                    raw_value: proposal.raw_value.clone(),
+                    normalized_value: None,
+                    provenance: provenance.clone(),
+                    locked: false,
+                };
+                let ctx =
+                    self.contexts
+                        .context_for(session_id, document_id, rule.schema_field_id)?;
+                let outcome = norm.engine.apply(&proposal.raw_value, &ctx);
+                if outcome.changed() {
+                    outcome.record_in(&mut provenance);
+                }
+                probe.normalized_value =
+                    parse_value(field, &outcome.value, &norm.locale).unwrap_or(None);
+                changes.push(RetroactiveChange {
+                    session_id,
+                    document_id,
+                    schema_field_id: rule.schema_field_id,
+                    field_value_id: current.map(|v| v.field_value_id),
+                    current_raw_value: current.map(|v| v.raw_value.clone()),
+                    current_normalized_value: current.and_then(|v| v.normalized_value.clone()),
+                    proposed_raw_value: proposal.raw_value.clone(),
+                    proposed_normalized_value: probe.normalized_value,
+                    provenance,
+                });
+            }
+        }
+        Ok(changes)
+    }
+
+    fn dictionary_changes(
+        &self,
+        candidate: &DictionaryRule,
+        values: &[FieldValue],
+        norm: &Normalization,
+    ) -> DomainResult<Vec<RetroactiveChange>> {
+        let mut changes = Vec::new();
+        for value in values.iter().filter(|v| v.accepts_automatic_update()) {
+            let Some(field) = norm.schema.field(value.schema_field_id) else {
+                continue;
+            };
+            let ctx = self.contexts.context_for(
+                value.session_id,
+                value.document_id,
+                value.schema_field_id,
+            )?;
+            let outcome = norm.engine.apply(&value.raw_value, &ctx);
+            // Only values the new rule actually touches count as changed by it.
+            let fired = outcome
+                .trace
+                .iter()
+                .any(|step| step.dictionary_rule_id == candidate.dictionary_rule_id);
+            let proposed = parse_value(field, &outcome.value, &norm.locale).unwrap_or(None);
+            if !fired || proposed == value.normalized_value {
+                continue;
+            }
+            let mut provenance = value.provenance.clone();
+            outcome.record_in(&mut provenance);
+            changes.push(RetroactiveChange {
+                session_id: value.session_id,
+                document_id: value.document_id,
+                schema_field_id: value.schema_field_id,
+                field_value_id: Some(value.field_value_id),
+                current_raw_value: Some(value.raw_value.clone()),
+                current_normalized_value: value.normalized_value.clone(),
+                proposed_raw_value: value.raw_value.clone(),
+                proposed_normalized_value: proposed,
+                provenance,
+            });
+        }
+        Ok(changes)
+    }
+}
+
+pub fn reapplier_missing(command_type: &str) -> DomainError {
+    DomainError {
+        code: ErrorCode::PreconditionFailed,
+        message: "Retroactive re-application is not configured for this handler".to_string(),
+        details: Some(serde_json::json!({ "command_type": command_type })),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dictionary::AddDictionaryRuleHandler;
+    use crate::interfaces::{GenericCommandHandler, ReviewTaskStore};
+    use crate::provenance::Origin;
+    use crate::review_tasks::{open_review_tasks, ResolveReviewTaskHandler};
+    use crate::test_support::{command, ctx, World};
+
+    #[test]
+    fn accepted_dictionary_proposals_are_parsed_and_applied() {
+        let w = World::new(&[("quantity", "integer", "document", false)]);
+        let session_id = w.session(SessionStatus::Review);
+        let (document_id, page_id) = w.document(session_id);
+        let extracted = FieldValue {
+            field_value_id: Uuid::now_v7(),
+            session_id,
+            document_id,
+            schema_field_id: w.field_id("quantity"),
+            raw_value: "none".to_string(),
+            normalized_value: None,
+            provenance: Provenance {
+                page_id: Some(page_id),
+                ..Provenance::new(Origin::Anchor {
+                    anchor_id: Uuid::now_v7(),
+                    label_token_ids: Vec::new(),
+                })
+            },
+            locked: false,
+        };
+        w.b.mapping.put_field_value(&extracted).unwrap();
+
+        let reapplier = RetroactiveReapplier::new(
+            &w.b.sessions,
+            &w.b.extraction,
+            &w.b.mapping,
+            &w.b.anchors,
+            &w.b.dictionary,
+            &w.b.contexts,
+            &w.b.schemas,
+        );
+        let added = AddDictionaryRuleHandler::new(&w.b.dictionary)
+            .with_reapplier(&reapplier)
+            .handle(
+                &mut ctx(),
+                &command(serde_json::json!({
+                    "type": "AddDictionaryRule",
+                    "payload": {
+                        "project_id": w.project_id,
+                        "scope": "global",
+                        "match_type": "exact",
+                        "match_value": "none",
+                        "replace_value": "0",
+                        "retroactive": true
+                    }
+                })),
+            )
+            .unwrap();
+        let change = &added.review_actions[0].payload["change"];
+        assert_eq!(change["proposed_raw_value"], "none");
+        assert_eq!(change["proposed_normalized_value"], "0");
+        open_review_tasks(&w.b.review_tasks, &added.review_actions, ctx().now).unwrap();
+        let task = w.b.review_tasks.tasks(session_id).unwrap().remove(0);
+
+        let resolved = ResolveReviewTaskHandler::new(&w.b.review_tasks)
+            .with_mapping(&w.b.mapping, &w.b.sessions)
+            .handle(
+                &mut ctx(),
+                &command(serde_json::json!({
+                    "type": "ResolveReviewTask",
+                    "payload": {
+                        "session_id": session_id,
+                        "review_task_id": task.review_task_id,
+                        "resolution": "accepted"
+                    }
+                })),
+            )
+            .unwrap();
+        assert_eq!(
+            resolved.state_delta.data["written_values"]
+                .as_array()
+                .unwrap()
+                .len(),
+            1
+        );
+        let value = w.b.mapping.field_values(session_id).unwrap().remove(0);
+        assert_eq!(value.field_value_id, extracted.field_value_id);
+        assert_eq!(value.raw_value, "none");
+        assert_eq!(value.normalized_value.as_deref(), Some("0"));
+        assert_eq!(value.provenance.origin, extracted.provenance.origin);
+        assert_eq!(value.provenance.dictionary_rules.len(), 1);
+        assert!(!w.b.review_tasks.tasks(session_id).unwrap()[0].is_open());
+    }
+}
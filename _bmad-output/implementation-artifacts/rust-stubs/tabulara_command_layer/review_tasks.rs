@@ -0,0 +1,821 @@
+use std::cmp::Reverse;
+use std::collections::hash_map::Entry;
+use std::collections::{HashMap, HashSet};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::commands::{AnyCommand, ResolveReviewTask, SkipReviewTask};
+use crate::errors::{unsupported_command, value_locked, DomainError, DomainResult, ErrorCode};
+use crate::extraction::BoundingBox;
+use crate::interfaces::{
+    CommandContext, CommandOutcome, GenericCommandHandler, MappingStore, OutcomeEvent, PageReader,
+    ReviewAction, ReviewTaskStore, SessionReader, StateDelta, ValidationTrigger,
+};
+use crate::mapping::{FieldValue, ValueChange, WrittenValue};
+use crate::provenance::Provenance;
+use crate::retroactive::RetroactiveChange;
+use crate::types::{SessionStatus, SessionStatusTransition};
+
+/// Height of one reading line in page-normalized units; positions within a band
+/// read left to right.
+const READING_LINE_HEIGHT: f64 = 0.01;
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum ReviewCategory {
+    RequiredMissing,
+    LowConfidence,
+    ValidationError,
+    NewPattern,
+    Duplicate,
+    /// Anchor or zone text stored without a typed value.
+    UnparseableValue,
+    /// Re-extraction changed the tokens a value was read from.
+    ExtractionChanged,
+    /// A learning rule would change an existing value.
+    RuleProposal,
+}
+
+impl ReviewCategory {
+    pub fn default_priority(self) -> ReviewPriority {
+        match self {
+            ReviewCategory::RequiredMissing
+            | ReviewCategory::ValidationError
+            | ReviewCategory::Duplicate
+            | ReviewCategory::UnparseableValue => ReviewPriority::High,
+            ReviewCategory::LowConfidence
+            | ReviewCategory::NewPattern
+            | ReviewCategory::ExtractionChanged => ReviewPriority::Normal,
+            ReviewCategory::RuleProposal => ReviewPriority::Low,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum ReviewPriority {
+    Low,
+    Normal,
+    High,
+}
+
+/// `review_tasks.status`; only `open -> resolved|skipped` is allowed.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ReviewTaskStatus {
+    Open,
+    Resolved,
+    Skipped,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ReviewResolution {
+    Accepted,
+    Edited,
+    Confirmed,
+    /// The issue no longer applies: a later run stopped raising it, or its item row
+    /// was deleted.
+    Superseded,
+}
+
+/// What a task is about: a document, one of its fields, an item row, or one field
+/// of an item row.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash)]
+pub struct ReviewTarget {
+    pub document_id: Uuid,
+    pub schema_field_id: Option<Uuid>,
+    pub item_id: Option<Uuid>,
+}
+
+impl ReviewTarget {
+    pub fn document(document_id: Uuid) -> Self {
+        Self {
+            document_id,
+            schema_field_id: None,
+            item_id: None,
+        }
+    }
+
+    pub fn field(document_id: Uuid, schema_field_id: Uuid) -> Self {
+        Self {
+            document_id,
+            schema_field_id: Some(schema_field_id),
+            item_id: None,
+        }
+    }
+
+    pub fn item(document_id: Uuid, item_id: Uuid, schema_field_id: Option<Uuid>) -> Self {
+        Self {
+            document_id,
+            schema_field_id,
+            item_id: Some(item_id),
+        }
+    }
+}
+
+impl ReviewAction {
+    /// Task at the category's default priority.
+    pub fn new(
+        session_id: Uuid,
+        category: ReviewCategory,
+        target: ReviewTarget,
+        payload: serde_json::Value,
+    ) -> Self {
+        Self {
+            session_id,
+            category,
+            target,
+            priority: category.default_priority(),
+            subject: None,
+            payload,
+        }
+    }
+
+    pub fn with_subject(mut self, subject: impl Into<String>) -> Self {
+        self.subject = Some(subject.into());
+        self
+    }
+}
+
+/// `review_tasks` row.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReviewTask {
+    pub review_task_id: Uuid,
+    pub session_id: Uuid,
+    pub category: ReviewCategory,
+    pub priority: ReviewPriority,
+    pub target: ReviewTarget,
+    pub subject: Option<String>,
+    pub payload: serde_json::Value,
+    pub status: ReviewTaskStatus,
+    pub opened_at: DateTime<Utc>,
+    pub closed_at: Option<DateTime<Utc>>,
+    pub closed_by: Option<String>,
+    pub resolution: Option<ReviewResolution>,
+    pub skip_reason: Option<String>,
+}
+
+impl ReviewTask {
+    pub fn open(action: &ReviewAction, now: DateTime<Utc>) -> Self {
+        Self {
+            review_task_id: Uuid::now_v7(),
+            session_id: action.session_id,
+            category: action.category,
+            priority: action.priority,
+            target: action.target,
+            subject: action.subject.clone(),
+            payload: action.payload.clone(),
+            status: ReviewTaskStatus::Open,
+            opened_at: now,
+            closed_at: None,
+            closed_by: None,
+            resolution: None,
+            skip_reason: None,
+        }
+    }
+
+    pub fn is_open(&self) -> bool {
+        self.status == ReviewTaskStatus::Open
+    }
+
+    pub fn resolve(&mut self, resolution: ReviewResolution, actor: &str, now: DateTime<Utc>) {
+        self.status = ReviewTaskStatus::Resolved;
+        self.resolution = Some(resolution);
+        self.closed_at = Some(now);
+        self.closed_by = Some(actor.to_string());
+    }
+
+    pub fn skip(&mut self, reason: &str, actor: &str, now: DateTime<Utc>) {
+        self.status = ReviewTaskStatus::Skipped;
+        self.skip_reason = Some(reason.to_string());
+        self.closed_at = Some(now);
+        self.closed_by = Some(actor.to_string());
+    }
+
+    /// `ReviewTaskResolved` or `ReviewTaskSkipped` for a closed task.
+    pub fn closed_event(&self) -> OutcomeEvent {
+        let event_type = match self.status {
+            ReviewTaskStatus::Skipped => "ReviewTaskSkipped",
+            _ => "ReviewTaskResolved",
+        };
+        OutcomeEvent {
+            event_type: event_type.to_string(),
+            session_id: Some(self.session_id),
+            data: serde_json::json!({
+                "review_task_id": self.review_task_id,
+                "category": self.category,
+                "target": self.target,
+                "resolution": self.resolution,
+                "skip_reason": self.skip_reason,
+            }),
+        }
+    }
+
+    fn raised_by(&self, action: &ReviewAction) -> bool {
+        self.session_id == action.session_id
+            && self.category == action.category
+            && self.target == action.target
+            && self.subject == action.subject
+    }
+}
+
+/// Opens a task per action. An action matching an open task (same category, target
+/// and subject) refreshes that task instead, so repeated runs do not queue the same
+/// issue twice.
+pub fn open_review_tasks(
+    store: &dyn ReviewTaskStore,
+    actions: &[ReviewAction],
+    now: DateTime<Utc>,
+) -> DomainResult<()> {
+    let mut sessions: HashMap<Uuid, Vec<ReviewTask>> = HashMap::new();
+    for action in actions {
+        let open = match sessions.entry(action.session_id) {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => entry.insert(
+                store
+                    .tasks(action.session_id)?
+                    .into_iter()
+                    .filter(ReviewTask::is_open)
+                    .collect(),
+            ),
+        };
+        let task = match open.iter_mut().find(|t| t.raised_by(action)) {
+            Some(existing) => {
+                existing.priority = action.priority;
+                existing.payload = action.payload.clone();
+                existing.clone()
+            }
+            None => {
+                let task = ReviewTask::open(action, now);
+                open.push(task.clone());
+                task
+            }
+        };
+        store.put_task(&task)?;
+    }
+    Ok(())
+}
+
+/// Resolves open tasks that no longer apply, as `superseded`: tasks of the `owned`
+/// categories that `actions` do not raise again, `actions` being the complete set
+/// the caller raises for those categories, and tasks about an item row that no longer
+/// exists. Returns the closed tasks.
+pub fn close_stale_review_tasks(
+    store: &dyn ReviewTaskStore,
+    mapping: &dyn MappingStore,
+    session_id: Uuid,
+    owned: &[ReviewCategory],
+    actions: &[ReviewAction],
+    actor: &str,
+    now: DateTime<Utc>,
+) -> DomainResult<Vec<ReviewTask>> {
+    let rows: HashSet<Uuid> = mapping
+        .item_rows(session_id)?
+        .into_iter()
+        .map(|r| r.item_id)
+        .collect();
+    let mut closed = Vec::new();
+    for mut task in store.tasks(session_id)? {
+        let row_gone = task.target.item_id.is_some_and(|id| !rows.contains(&id));
+        let no_longer_raised =
+            owned.contains(&task.category) && !actions.iter().any(|a| task.raised_by(a));
+        if !task.is_open() || !(row_gone || no_longer_raised) {
+            continue;
+        }
+        task.resolve(ReviewResolution::Superseded, actor, now);
+        store.put_task(&task)?;
+        closed.push(task);
+    }
+    Ok(closed)
+}
+
+/// Where a task's value sits, read from the value's provenance.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+pub struct ReviewLocation {
+    pub page_id: Uuid,
+    pub page_number: u32,
+    pub bbox: Option<BoundingBox>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ReviewQueueFilter {
+    /// Empty means every category.
+    #[serde(default)]
+    pub categories: Vec<ReviewCategory>,
+    #[serde(default)]
+    pub min_priority: Option<ReviewPriority>,
+}
+
+impl ReviewQueueFilter {
+    fn accepts(&self, task: &ReviewTask) -> bool {
+        (self.categories.is_empty() || self.categories.contains(&task.category))
+            && self.min_priority.is_none_or(|p| task.priority >= p)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QueuedTask {
+    /// Index in the session's full queue, closed tasks included, so positions do not
+    /// shift as tasks are resolved.
+    pub position: usize,
+    pub task: ReviewTask,
+    pub location: Option<ReviewLocation>,
+}
+
+/// Sort key: document, then document-level tasks, header fields in reading order,
+/// and item rows by `row_index`; ties go to the higher priority, then category, then
+/// the older task.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
+struct QueueKey {
+    document: usize,
+    region: u8,
+    row_index: u32,
+    page_number: u32,
+    line: i64,
+    x: i64,
+    schema_field_id: Option<Uuid>,
+    priority: Reverse<ReviewPriority>,
+    category: ReviewCategory,
+    review_task_id: Uuid,
+}
+
+/// Orders a session's review tasks by document, page and reading position, so the
+/// next task is always the one after the current task in that order.
+pub struct ReviewQueue<'a> {
+    tasks: &'a dyn ReviewTaskStore,
+    pages: &'a dyn PageReader,
+    mapping: &'a dyn MappingStore,
+}
+
+impl<'a> ReviewQueue<'a> {
+    pub fn new(
+        tasks: &'a dyn ReviewTaskStore,
+        pages: &'a dyn PageReader,
+        mapping: &'a dyn MappingStore,
+    ) -> Self {
+        Self {
+            tasks,
+            pages,
+            mapping,
+        }
+    }
+
+    /// Every task of the session, closed ones included, in queue order.
+    pub fn ordered(&self, session_id: Uuid) -> DomainResult<Vec<QueuedTask>> {
+        let mut documents: Vec<Uuid> = Vec::new();
+        let mut page_numbers: HashMap<Uuid, u32> = HashMap::new();
+        for page in self.pages.session_pages(session_id)? {
+            if !documents.contains(&page.document_id) {
+                documents.push(page.document_id);
+            }
+            page_numbers.insert(page.page_id, page.page_number);
+        }
+        let rows: HashMap<Uuid, u32> = self
+            .mapping
+            .item_rows(session_id)?
+            .into_iter()
+            .map(|row| (row.item_id, row.row_index))
+            .collect();
+        let mut sources: HashMap<(Uuid, Uuid), Provenance> = HashMap::new();
+        for value in self.mapping.field_values(session_id)? {
+            sources.insert((value.document_id, value.schema_field_id), value.provenance);
+        }
+        for value in self.mapping.item_values(session_id)? {
+            sources.insert((value.item_id, value.schema_field_id), value.provenance);
+        }
+
+        let locate = |target: &ReviewTarget| {
+            let field = target.schema_field_id?;
+            let owner = target.item_id.unwrap_or(target.document_id);
+            let source = sources.get(&(owner, field))?;
+            let page_id = source.page_id?;
+            Some(ReviewLocation {
+                page_id,
+                page_number: *page_numbers.get(&page_id)?,
+                bbox: source.bbox,
+            })
+        };
+
+        let mut keyed: Vec<(QueueKey, ReviewTask, Option<ReviewLocation>)> = self
+            .tasks
+            .tasks(session_id)?
+            .into_iter()
+            .map(|task| {
+                let target = task.target;
+                let location = locate(&target);
+                let (line, x) =
+                    location
+                        .and_then(|l| l.bbox)
+                        .map_or((i64::MAX, i64::MAX), |bbox| {
+                            let (_, center_y) = bbox.center();
+                            (
+                                (center_y / READING_LINE_HEIGHT).floor() as i64,
+                                (bbox.x * 10_000.0).round() as i64,
+                            )
+                        });
+                let key = QueueKey {
+                    document: documents
+                        .iter()
+                        .position(|d| *d == target.document_id)
+                        .unwrap_or(usize::MAX),
+                    region: match (target.item_id, target.schema_field_id) {
+                        (None, None) => 0,
+                        (None, Some(_)) => 1,
+                        (Some(_), _) => 2,
+                    },
+                    row_index: target
+                        .item_id
+                        .map_or(0, |id| rows.get(&id).copied().unwrap_or(u32::MAX)),
+                    page_number: location.map_or(u32::MAX, |l| l.page_number),
+                    line,
+                    x,
+                    schema_field_id: target.schema_field_id,
+                    priority: Reverse(task.priority),
+                    category: task.category,
+                    review_task_id: task.review_task_id,
+                };
+                (key, task, location)
+            })
+            .collect();
+        keyed.sort_by(|a, b| a.0.cmp(&b.0));
+
+        Ok(keyed
+            .into_iter()
+            .enumerate()
+            .map(|(position, (_, task, location))| QueuedTask {
+                position,
+                task,
+                location,
+            })
+            .collect())
+    }
+
+    /// Open tasks the filter accepts, in queue order.
+    pub fn open(
+        &self,
+        session_id: Uuid,
+        filter: &ReviewQueueFilter,
+    ) -> DomainResult<Vec<QueuedTask>> {
+        Ok(self
+            .ordered(session_id)?
+            .into_iter()
+            .filter(|q| q.task.is_open() && filter.accepts(&q.task))
+            .collect())
+    }
+
+    /// First open task after `current` in queue order, wrapping to the start of the
+    /// queue; `current` may already be closed. Without `current`, the first open task.
+    pub fn next(
+        &self,
+        session_id: Uuid,
+        current: Option<Uuid>,
+        filter: &ReviewQueueFilter,
+    ) -> DomainResult<Option<QueuedTask>> {
+        let ordered = self.ordered(session_id)?;
+        let start = current
+            .and_then(|id| ordered.iter().position(|q| q.task.review_task_id == id))
+            .map_or(0, |position| position + 1);
+        let count = ordered.len();
+        Ok((0..count)
+            .map(|offset| &ordered[(start + offset) % count])
+            .find(|q| {
+                q.task.is_open()
+                    && filter.accepts(&q.task)
+                    && Some(q.task.review_task_id) != current
+            })
+            .cloned())
+    }
+}
+
+fn open_task(
+    tasks: &dyn ReviewTaskStore,
+    session_id: Uuid,
+    review_task_id: Uuid,
+) -> DomainResult<ReviewTask> {
+    let task = tasks
+        .get_task(review_task_id)?
+        .filter(|t| t.session_id == session_id)
+        .ok_or_else(|| DomainError {
+            code: ErrorCode::NotFound,
+            message: "Review task not found".to_string(),
+            details: Some(serde_json::json!({
+                "session_id": session_id,
+                "review_task_id": review_task_id,
+            })),
+        })?;
+    if !task.is_open() {
+        return Err(DomainError {
+            code: ErrorCode::PreconditionFailed,
+            message: "Review task is not open".to_string(),
+            details: Some(serde_json::json!({
+                "review_task_id": review_task_id,
+                "status": task.status,
+            })),
+        });
+    }
+    Ok(task)
+}
+
+fn closed_outcome(summary: &str, task: ReviewTask) -> CommandOutcome {
+    CommandOutcome {
+        state_delta: StateDelta {
+            summary: summary.to_string(),
+            data: serde_json::json!({ "review_task": task }),
+        },
+        transition: None,
+        review_actions: Vec::new(),
+        validation_trigger: ValidationTrigger::None,
+        events: vec![task.closed_event()],
+    }
+}
+
+/// Closes a task. Accepting a `rule_proposal` task also writes the proposed value,
+/// with the rule's provenance, when the handler has a mapping store attached.
+pub struct ResolveReviewTaskHandler<'a> {
+    tasks: &'a dyn ReviewTaskStore,
+    proposals: Option<(&'a dyn MappingStore, &'a dyn SessionReader)>,
+}
+
+impl<'a> ResolveReviewTaskHandler<'a> {
+    pub fn new(tasks: &'a dyn ReviewTaskStore) -> Self {
+        Self {
+            tasks,
+            proposals: None,
+        }
+    }
+
+    /// Enables applying accepted rule proposals.
+    pub fn with_mapping(
+        mut self,
+        mapping: &'a dyn MappingStore,
+        sessions: &'a dyn SessionReader,
+    ) -> Self {
+        self.proposals = Some((mapping, sessions));
+        self
+    }
+
+    fn resolve(
+        &self,
+        ctx: &CommandContext,
+        cmd: &ResolveReviewTask,
+    ) -> DomainResult<CommandOutcome> {
+        let payload = &cmd.payload;
+        if payload.resolution == ReviewResolution::Superseded {
+            return Err(DomainError {
+                code: ErrorCode::PreconditionFailed,
+                message: "Tasks are superseded by validation, not resolved as superseded"
+                    .to_string(),
+                details: Some(serde_json::json!({ "review_task_id": payload.review_task_id })),
+            });
+        }
+        let mut task = open_task(self.tasks, payload.session_id, payload.review_task_id)?;
+        let applies_proposal = task.category == ReviewCategory::RuleProposal
+            && payload.resolution == ReviewResolution::Accepted;
+        let Some((mapping, sessions)) = self.proposals.filter(|_| applies_proposal) else {
+            task.resolve(payload.resolution, &ctx.actor, ctx.now);
+            self.tasks.put_task(&task)?;
+            return Ok(closed_outcome("Review task resolved", task));
+        };
+
+        let value = apply_proposal(mapping, &task)?;
+        let status = sessions.get_status(payload.session_id)?;
+        task.resolve(payload.resolution, &ctx.actor, ctx.now);
+        self.tasks.put_task(&task)?;
+
+        let mut outcome = closed_outcome("Rule proposal applied", task);
+        outcome.state_delta.data["field_value"] = serde_json::json!(value);
+        outcome.state_delta.data["changed_values"] = serde_json::json!([ValueChange::from(&value)]);
+        outcome.state_delta.data["written_values"] =
+            serde_json::json!([WrittenValue::Field(value)]);
+        outcome.transition =
+            (status == SessionStatus::Validated).then_some(SessionStatusTransition {
+                from: status,
+                to: SessionStatus::Review,
+            });
+        outcome.validation_trigger = ValidationTrigger::Async;
+        Ok(outcome)
+    }
+}
+
+/// Writes the value a `rule_proposal` task proposes. The proposal is rejected when the
+/// value has since been locked or changed, since it was computed against the old value.
+fn apply_proposal(mapping: &dyn MappingStore, task: &ReviewTask) -> DomainResult<FieldValue> {
+    let change: RetroactiveChange = serde_json::from_value(task.payload["change"].clone())
+        .map_err(|e| DomainError {
+            code: ErrorCode::PreconditionFailed,
+            message: "Review task does not carry a rule proposal".to_string(),
+            details: Some(serde_json::json!({
+                "review_task_id": task.review_task_id,
+                "error": e.to_string(),
+            })),
+        })?;
+    let current = mapping
+        .field_values(change.session_id)?
+        .into_iter()
+        .find(|v| {
+            v.document_id == change.document_id && v.schema_field_id == change.schema_field_id
+        });
+    if let Some(current) = current.as_ref().filter(|v| v.locked) {
+        return Err(value_locked(
+            "Field value is locked",
+            serde_json::json!({ "field_value_id": current.field_value_id }),
+        ));
+    }
+    let unchanged = current.as_ref().map(|v| v.field_value_id) == change.field_value_id
+        && current.as_ref().map(|v| &v.raw_value) == change.current_raw_value.as_ref()
+        && current.as_ref().and_then(|v| v.normalized_value.as_ref())
+            == change.current_normalized_value.as_ref();
+    if !unchanged {
+        return Err(DomainError {
+            code: ErrorCode::PreconditionFailed,
+            message: "Field value changed since the rule proposal was raised".to_string(),
+            details: Some(serde_json::json!({
+                "review_task_id": task.review_task_id,
+                "field_value_id": change.field_value_id,
+            })),
+        });
+    }
+
+    let value = FieldValue {
+        field_value_id: change.field_value_id.unwrap_or_else(Uuid::now_v7),
+        session_id: change.session_id,
+        document_id: change.document_id,
+        schema_field_id: change.schema_field_id,
+        raw_value: change.proposed_raw_value,
+        normalized_value: change.proposed_normalized_value,
+        provenance: change.provenance,
+        locked: false,
+    };
+    mapping.put_field_value(&value)?;
+    Ok(value)
+}
+
+impl<'a> GenericCommandHandler for ResolveReviewTaskHandler<'a> {
+    fn can_handle(&self, command_type: &str) -> bool {
+        command_type == "ResolveReviewTask"
+    }
+
+    fn handle(&self, ctx: &mut CommandContext, cmd: &AnyCommand) -> DomainResult<CommandOutcome> {
+        match cmd {
+            AnyCommand::ResolveReviewTask(c) => self.resolve(ctx, c),
+            _ => Err(unsupported_command("ResolveReviewTaskHandler")),
+        }
+    }
+}
+
+/// Closes a task without acting on it. Skipping does not clear the validation
+/// result behind a task; blocking results still hold the session in review.
+pub struct SkipReviewTaskHandler<'a> {
+    tasks: &'a dyn ReviewTaskStore,
+}
+
+impl<'a> SkipReviewTaskHandler<'a> {
+    pub fn new(tasks: &'a dyn ReviewTaskStore) -> Self {
+        Self { tasks }
+    }
+
+    fn skip(&self, ctx: &CommandContext, cmd: &SkipReviewTask) -> DomainResult<CommandOutcome> {
+        let payload = &cmd.payload;
+        let reason = payload.reason.trim();
+        if reason.is_empty() {
+            return Err(DomainError {
+                code: ErrorCode::PreconditionFailed,
+                message: "Skipping a review task requires a reason".to_string(),
+                details: Some(serde_json::json!({ "review_task_id": payload.review_task_id })),
+            });
+        }
+        let mut task = open_task(self.tasks, payload.session_id, payload.review_task_id)?;
+        task.skip(reason, &ctx.actor, ctx.now);
+        self.tasks.put_task(&task)?;
+        Ok(closed_outcome("Review task skipped", task))
+    }
+}
+
+impl<'a> GenericCommandHandler for SkipReviewTaskHandler<'a> {
+    fn can_handle(&self, command_type: &str) -> bool {
+        command_type == "SkipReviewTask"
+    }
+
+    fn handle(&self, ctx: &mut CommandContext, cmd: &AnyCommand) -> DomainResult<CommandOutcome> {
+        match cmd {
+            AnyCommand::SkipReviewTask(c) => self.skip(ctx, c),
+            _ => Err(unsupported_command("SkipReviewTaskHandler")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mapping::ItemRow;
+    use crate::test_support::{command, ctx, World};
+    use crate::types::SessionStatus;
+    use crate::validation::{RunValidationHandler, ValidationDeps};
+
+    #[test]
+    fn validation_runs_supersede_tasks_that_no_longer_apply() {
+        let w = World::new(&[
+            ("invoice_number", "string", "document", true),
+            ("amount", "currency", "item", false),
+        ]);
+        let session_id = w.session(SessionStatus::Review);
+        let (document_id, _) = w.document(session_id);
+        let item_id = Uuid::now_v7();
+        w.b.mapping
+            .put_item_row(&ItemRow {
+                item_id,
+                session_id,
+                document_id,
+                row_index: 0,
+                locked: false,
+            })
+            .unwrap();
+        let validate = || {
+            let outcome = RunValidationHandler::new(ValidationDeps {
+                mapping: &w.b.mapping,
+                sessions: &w.b.sessions,
+                schemas: &w.b.schemas,
+                pages: &w.b.pages,
+                validation: &w.b.validation,
+                events: &w.b.events,
+                overrides: &w.b.overrides,
+            })
+            .with_review_tasks(&w.b.review_tasks)
+            .handle(
+                &mut ctx(),
+                &command(serde_json::json!({
+                    "type": "RunValidation",
+                    "payload": { "session_id": session_id, "rule_scope": "all" }
+                })),
+            )
+            .unwrap();
+            open_review_tasks(&w.b.review_tasks, &outcome.review_actions, ctx().now).unwrap();
+            outcome
+        };
+        let open_categories = || -> Vec<ReviewCategory> {
+            w.b.review_tasks
+                .tasks(session_id)
+                .unwrap()
+                .into_iter()
+                .filter(ReviewTask::is_open)
+                .map(|t| t.category)
+                .collect()
+        };
+
+        validate();
+        open_review_tasks(
+            &w.b.review_tasks,
+            &[ReviewAction::new(
+                session_id,
+                ReviewCategory::LowConfidence,
+                ReviewTarget::item(document_id, item_id, Some(w.field_id("amount"))),
+                serde_json::json!({}),
+            )],
+            ctx().now,
+        )
+        .unwrap();
+        let mut open = open_categories();
+        open.sort();
+        assert_eq!(
+            open,
+            [
+                ReviewCategory::RequiredMissing,
+                ReviewCategory::LowConfidence
+            ]
+        );
+
+        w.b.mapping
+            .put_field_value(&crate::mapping::FieldValue {
+                field_value_id: Uuid::now_v7(),
+                session_id,
+                document_id,
+                schema_field_id: w.field_id("invoice_number"),
+                raw_value: "A-1".to_string(),
+                normalized_value: Some("A-1".to_string()),
+                provenance: Provenance::manual("tester"),
+                locked: false,
+            })
+            .unwrap();
+        w.b.mapping.delete_item_row(item_id).unwrap();
+        let outcome = validate();
+
+        assert!(open_categories().is_empty());
+        assert_eq!(
+            outcome.state_delta.data["superseded_review_task_ids"]
+                .as_array()
+                .unwrap()
+                .len(),
+            2
+        );
+        assert!(w
+            .b
+            .review_tasks
+            .tasks(session_id)
+            .unwrap()
+            .iter()
+            .all(|t| t.resolution == Some(ReviewResolution::Superseded)));
+    }
+}
@@ -0,0 +1,499 @@
+use chrono::Utc;
+use uuid::Uuid;
+
+use crate::anchors::{AnchorEvaluator, AnchorProposal};
+use crate::commands::{AnyCommand, CompleteExtractionJob, RunExtraction};
+use crate::dictionary::DictionaryEngine;
+use crate::errors::{unsupported_command, DomainError, DomainResult, ErrorCode};
+use crate::extraction::{
+    EngineOutput, ExtractionArtifacts, ExtractionRequest, ExtractionRun, ExtractionRunStatus,
+    PageCoverage,
+};
+use crate::interfaces::{
+    AnchorStore, CommandContext, CommandOutcome, DictionaryStore, ExtractionEngine,
+    ExtractionStore, GenericCommandHandler, JobStore, MappingStore, NormalizationContextResolver,
+    OutcomeEvent, PageReader, ReviewAction, SchemaStore, SessionReader, StateDelta, TemplateStore,
+    UnknownBucketStore, ValidationTrigger, ZoneStore,
+};
+use crate::jobs::{ensure_no_active_job, session_job, ExtractionJob, JobStatus};
+use crate::mapping::{FieldValue, ValueChange, WrittenValue};
+use crate::provenance::Provenance;
+use crate::review_tasks::{ReviewCategory, ReviewTarget};
+use crate::schema::{session_schema, SchemaVersion};
+use crate::templates::{
+    classify, fingerprint_document, DocumentTemplate, Template, TemplateRuleSet,
+};
+use crate::types::{SessionStatus, SessionStatusTransition};
+use crate::unknown_bucket::retain_unknown_text;
+use crate::value_parsing::{parse_value, unparseable_review_action, ValueLocale};
+use crate::zones::{ZoneEvaluator, ZoneProposal};
+
+pub struct RunExtractionDeps<'a> {
+    pub engine: &'a dyn ExtractionEngine,
+    pub extraction: &'a dyn ExtractionStore,
+    pub mapping: &'a dyn MappingStore,
+    pub sessions: &'a dyn SessionReader,
+    pub pages: &'a dyn PageReader,
+    pub templates: &'a dyn TemplateStore,
+    pub anchors: &'a dyn AnchorStore,
+    pub dictionary: &'a dyn DictionaryStore,
+    pub contexts: &'a dyn NormalizationContextResolver,
+    pub zones: &'a dyn ZoneStore,
+    pub schemas: &'a dyn SchemaStore,
+    pub unknown: &'a dyn UnknownBucketStore,
+}
+
+/// Everything a proposal is checked and normalized against within one document.
+struct ApplyContext<'r> {
+    session_id: Uuid,
+    schema: &'r SchemaVersion,
+    locale: &'r ValueLocale,
+    template: Option<&'r Template>,
+    rules: &'r TemplateRuleSet,
+    engine: &'r DictionaryEngine,
+}
+
+/// A value found by an anchor or a zone, before dictionary normalization.
+struct FieldProposal {
+    document_id: Uuid,
+    schema_field_id: Uuid,
+    raw_value: String,
+    provenance: Provenance,
+}
+
+impl FieldProposal {
+    fn from_anchor(proposal: &AnchorProposal) -> Self {
+        Self {
+            document_id: proposal.document_id,
+            schema_field_id: proposal.schema_field_id,
+            raw_value: proposal.raw_value.clone(),
+            provenance: proposal.provenance(),
+        }
+    }
+
+    fn from_zone(proposal: &ZoneProposal) -> Self {
+        Self {
+            document_id: proposal.document_id,
+            schema_field_id: proposal.schema_field_id,
+            raw_value: proposal.raw_value.clone(),
+            provenance: proposal.provenance(),
+        }
+    }
+}
+
+/// Extracts every imported page of a session, clusters each document to a template and
+/// applies the matching anchors, zones and dictionary rules. With a job store the
+/// engine runs in the background instead (see `jobs`), and the results are applied
+/// by `CompleteExtractionJob`.
+pub struct RunExtractionHandler<'a> {
+    deps: RunExtractionDeps<'a>,
+    jobs: Option<&'a dyn JobStore>,
+}
+
+impl<'a> RunExtractionHandler<'a> {
+    pub fn new(deps: RunExtractionDeps<'a>) -> Self {
+        Self { deps, jobs: None }
+    }
+
+    /// Queues `RunExtraction` as a background job and enables `CompleteExtractionJob`.
+    pub fn with_jobs(mut self, jobs: &'a dyn JobStore) -> Self {
+        self.jobs = Some(jobs);
+        self
+    }
+
+    fn run(&self, ctx: &CommandContext, cmd: &RunExtraction) -> DomainResult<CommandOutcome> {
+        let session_id = cmd.payload.session_id;
+        let coverage: Vec<PageCoverage> = self
+            .deps
+            .pages
+            .session_pages(session_id)?
+            .iter()
+            .map(|p| PageCoverage {
+                document_id: p.document_id,
+                page_id: p.page_id,
+                page_number: p.page_number,
+            })
+            .collect();
+        if coverage.is_empty() {
+            return Err(DomainError {
+                code: ErrorCode::PreconditionFailed,
+                message: "Session has no imported pages to extract".to_string(),
+                details: Some(serde_json::json!({ "session_id": session_id })),
+            });
+        }
+
+        let request = ExtractionRequest {
+            extraction_run_id: Uuid::now_v7(),
+            session_id,
+            engine: cmd.payload.engine.clone(),
+            params: cmd.payload.params.clone(),
+            pages: coverage,
+            region: None,
+        };
+        if let Some(jobs) = self.jobs {
+            return self.enqueue(jobs, ctx, cmd, request);
+        }
+        let output = self.deps.engine.extract(&request)?;
+        self.apply_run(
+            ExtractionRun {
+                extraction_run_id: request.extraction_run_id,
+                session_id,
+                caused_by: cmd.command_id,
+                engine: request.engine,
+                params: request.params,
+                started_at: ctx.now,
+                finished_at: Some(Utc::now()),
+                status: ExtractionRunStatus::Completed,
+                pages: request.pages,
+            },
+            output,
+        )
+    }
+
+    fn enqueue(
+        &self,
+        jobs: &dyn JobStore,
+        ctx: &CommandContext,
+        cmd: &RunExtraction,
+        request: ExtractionRequest,
+    ) -> DomainResult<CommandOutcome> {
+        let session_id = cmd.payload.session_id;
+        ensure_no_active_job(jobs, session_id)?;
+        let job = ExtractionJob::new(session_id, cmd.command_id, &cmd.actor, request, ctx.now);
+        jobs.put_job(&job)?;
+
+        let data = serde_json::json!({
+            "job_id": job.job_id,
+            "extraction_run_id": job.request.extraction_run_id,
+            "pages": job.request.pages,
+            "status": job.status,
+        });
+        Ok(CommandOutcome {
+            state_delta: StateDelta {
+                summary: format!(
+                    "Extraction of {} page(s) queued as job {}",
+                    job.progress.pages_total, job.job_id
+                ),
+                data: data.clone(),
+            },
+            transition: None,
+            review_actions: Vec::new(),
+            validation_trigger: ValidationTrigger::None,
+            events: vec![OutcomeEvent {
+                event_type: "ExtractionJobAccepted".to_string(),
+                session_id: Some(session_id),
+                data,
+            }],
+        })
+    }
+
+    /// Applies a finished job: a successful run like a synchronous one, a failed or
+    /// cancelled job as an event only, leaving the session status unchanged.
+    fn complete(&self, cmd: &CompleteExtractionJob) -> DomainResult<CommandOutcome> {
+        let jobs = self.jobs.ok_or_else(|| DomainError {
+            code: ErrorCode::PreconditionFailed,
+            message: "Background jobs are not configured for this handler".to_string(),
+            details: Some(serde_json::json!({ "command_type": "CompleteExtractionJob" })),
+        })?;
+        let session_id = cmd.payload.session_id;
+        let job = session_job(jobs, session_id, cmd.payload.job_id)?;
+        if !job.status.is_finished() || job.completed {
+            return Err(DomainError {
+                code: ErrorCode::PreconditionFailed,
+                message: "Job is not awaiting completion".to_string(),
+                details: Some(serde_json::json!({
+                    "job_id": job.job_id,
+                    "status": job.status,
+                    "completed": job.completed,
+                })),
+            });
+        }
+
+        let data = serde_json::json!({
+            "job_id": job.job_id,
+            "extraction_run_id": job.request.extraction_run_id,
+            "status": job.status,
+            "progress": job.progress,
+            "error": job.error,
+        });
+        let event = |event_type: &str| OutcomeEvent {
+            event_type: event_type.to_string(),
+            session_id: Some(session_id),
+            data: data.clone(),
+        };
+        if job.status == JobStatus::Succeeded {
+            let output = ExtractionJob::merged_output(jobs.page_outputs(job.job_id)?);
+            let mut outcome = self.apply_run(
+                ExtractionRun {
+                    extraction_run_id: job.request.extraction_run_id,
+                    session_id,
+                    caused_by: job.caused_by,
+                    engine: job.request.engine.clone(),
+                    params: job.request.params.clone(),
+                    started_at: job.started_at.unwrap_or(job.accepted_at),
+                    finished_at: job.finished_at,
+                    status: ExtractionRunStatus::Completed,
+                    pages: job.request.pages.clone(),
+                },
+                output,
+            )?;
+            outcome.events.push(event("ExtractionCompleted"));
+            return Ok(outcome);
+        }
+
+        let (summary, event_type) = match job.status {
+            JobStatus::Cancelled => (
+                format!(
+                    "Extraction job cancelled after {} of {} page(s)",
+                    job.progress.pages_done, job.progress.pages_total
+                ),
+                "ExtractionCancelled",
+            ),
+            _ => (
+                format!(
+                    "Extraction job failed: {}",
+                    job.error
+                        .as_ref()
+                        .map_or("unknown error", |e| e.message.as_str())
+                ),
+                "ExtractionFailed",
+            ),
+        };
+        Ok(CommandOutcome {
+            state_delta: StateDelta {
+                summary,
+                data: data.clone(),
+            },
+            transition: None,
+            review_actions: Vec::new(),
+            validation_trigger: ValidationTrigger::None,
+            events: vec![event(event_type)],
+        })
+    }
+
+    /// Records a completed run and proposes values from it.
+    fn apply_run(&self, run: ExtractionRun, output: EngineOutput) -> DomainResult<CommandOutcome> {
+        let session_id = run.session_id;
+        let extraction_run_id = run.extraction_run_id;
+        let status = self.deps.sessions.get_status(session_id)?;
+        let project_id = self.deps.sessions.project_of(session_id)?;
+        let schema = session_schema(self.deps.sessions, self.deps.schemas, session_id)?;
+        let locale = self.deps.schemas.get_project(project_id)?.locale;
+        let layouts = self.deps.pages.session_pages(session_id)?;
+        let coverage = run.pages.clone();
+
+        let artifacts = ExtractionArtifacts {
+            run,
+            tokens: output.tokens,
+            lines: output.lines,
+            tables: output.tables,
+        };
+        self.deps.extraction.record_run(&artifacts)?;
+        retain_unknown_text(self.deps.unknown, session_id, &artifacts)?;
+
+        let templates = self.deps.templates.templates(project_id)?;
+        let anchors = self.deps.anchors.rules(project_id)?;
+        let dictionary_rules = self.deps.dictionary.rules(project_id)?;
+        let evaluator = AnchorEvaluator::new(self.deps.extraction, self.deps.anchors);
+        let zones = ZoneEvaluator::new(self.deps.extraction, self.deps.zones);
+
+        let mut documents: Vec<Uuid> = Vec::new();
+        for page in &coverage {
+            if !documents.contains(&page.document_id) {
+                documents.push(page.document_id);
+            }
+        }
+
+        let mut assignments = Vec::new();
+        let mut written = Vec::new();
+        let mut changed = Vec::new();
+        let mut values = Vec::new();
+        let mut review_actions = Vec::new();
+        for document_id in documents {
+            let Some(fingerprint) =
+                fingerprint_document(self.deps.extraction, session_id, &layouts, document_id)?
+            else {
+                continue;
+            };
+            let matched = classify(&fingerprint, &templates);
+            let template =
+                matched.and_then(|m| templates.iter().find(|t| t.template_id == m.template_id));
+            let assignment = DocumentTemplate {
+                session_id,
+                document_id,
+                extraction_run_id,
+                fingerprint,
+                matched,
+            };
+            self.deps.templates.record_match(&assignment)?;
+            if matched.is_none() {
+                review_actions.push(ReviewAction::new(
+                    session_id,
+                    ReviewCategory::NewPattern,
+                    ReviewTarget::document(document_id),
+                    serde_json::json!({
+                        "extraction_run_id": extraction_run_id,
+                        "fingerprint": assignment.fingerprint,
+                    }),
+                ));
+            }
+
+            let rules = TemplateRuleSet::select(
+                template,
+                &templates,
+                anchors.clone(),
+                dictionary_rules.clone(),
+            );
+            let engine = DictionaryEngine::new(rules.dictionary_rules.clone())?;
+            let mut proposals: Vec<FieldProposal> = evaluator
+                .evaluate_rules(session_id, document_id, rules.anchors.clone())?
+                .iter()
+                .map(FieldProposal::from_anchor)
+                .collect();
+            // Zones fill the fields the anchors did not find.
+            if let Some(template) = template {
+                let pages: Vec<PageCoverage> = coverage
+                    .iter()
+                    .filter(|p| p.document_id == document_id)
+                    .cloned()
+                    .collect();
+                for zone in zones.evaluate_document(template.template_id, session_id, &pages)? {
+                    if !proposals
+                        .iter()
+                        .any(|p| p.schema_field_id == zone.schema_field_id)
+                    {
+                        proposals.push(FieldProposal::from_zone(&zone));
+                    }
+                }
+            }
+            let apply = ApplyContext {
+                session_id,
+                schema: &schema,
+                locale: &locale,
+                template,
+                rules: &rules,
+                engine: &engine,
+            };
+            for proposal in proposals {
+                if let Some(value) = self.apply_proposal(&apply, proposal, &mut review_actions)? {
+                    written.push(value.field_value_id);
+                    changed.push(ValueChange::from(&value));
+                    values.push(WrittenValue::Field(value));
+                }
+            }
+            assignments.push(assignment);
+        }
+
+        // A job can finish after the session was validated; new values demote it.
+        let demote = status == SessionStatus::Processing
+            || (status == SessionStatus::Validated && !written.is_empty());
+        let transition = demote.then_some(SessionStatusTransition {
+            from: status,
+            to: SessionStatus::Review,
+        });
+        let validation_trigger = if written.is_empty() {
+            ValidationTrigger::None
+        } else {
+            ValidationTrigger::Async
+        };
+
+        Ok(CommandOutcome {
+            state_delta: StateDelta {
+                summary: format!(
+                    "Extracted {} page(s); {} field value(s) proposed",
+                    coverage.len(),
+                    written.len()
+                ),
+                data: serde_json::json!({
+                    "extraction_run_id": extraction_run_id,
+                    "pages": coverage,
+                    "document_templates": assignments,
+                    "field_value_ids": written,
+                    "changed_values": changed,
+                    "written_values": values,
+                }),
+            },
+            transition,
+            review_actions,
+            validation_trigger,
+            events: Vec::new(),
+        })
+    }
+
+    /// Writes a proposal unless the current value must not be replaced automatically
+    /// or the field is not writable in the session's schema version. Values that do
+    /// not parse as the field type are stored unnormalized with a review task.
+    fn apply_proposal(
+        &self,
+        apply: &ApplyContext<'_>,
+        proposal: FieldProposal,
+        review_actions: &mut Vec<ReviewAction>,
+    ) -> DomainResult<Option<FieldValue>> {
+        let Ok(field) = apply.schema.writable_field(proposal.schema_field_id) else {
+            return Ok(None);
+        };
+        let current = self
+            .deps
+            .mapping
+            .field_values(apply.session_id)?
+            .into_iter()
+            .find(|v| {
+                v.document_id == proposal.document_id
+                    && v.schema_field_id == proposal.schema_field_id
+            });
+        if current
+            .as_ref()
+            .is_some_and(|v| !v.accepts_automatic_update())
+        {
+            return Ok(None);
+        }
+
+        let mut provenance = proposal.provenance;
+        provenance.template_id = apply.template.map(|t| t.template_id);
+        let mut value = FieldValue {
+            field_value_id: current.map_or_else(Uuid::now_v7, |v| v.field_value_id),
+            session_id: apply.session_id,
+            document_id: proposal.document_id,
+            schema_field_id: proposal.schema_field_id,
+            raw_value: proposal.raw_value,
+            normalized_value: None,
+            provenance,
+            locked: false,
+        };
+
+        let mut norm_ctx = self.deps.contexts.context_for(
+            value.session_id,
+            value.document_id,
+            value.schema_field_id,
+        )?;
+        if norm_ctx.vendor.is_none() {
+            norm_ctx.vendor = apply.rules.vendor.clone();
+        }
+        let normalized = apply.engine.apply(&value.raw_value, &norm_ctx);
+        if normalized.changed() {
+            normalized.record_in(&mut value.provenance);
+        }
+        match parse_value(field, &normalized.value, apply.locale) {
+            Ok(typed) => value.normalized_value = typed,
+            Err(failure) => review_actions.push(unparseable_review_action(&value, field, &failure)),
+        }
+
+        self.deps.mapping.put_field_value(&value)?;
+        Ok(Some(value))
+    }
+}
+
+impl<'a> GenericCommandHandler for RunExtractionHandler<'a> {
+    fn can_handle(&self, command_type: &str) -> bool {
+        command_type == "RunExtraction"
+            || (command_type == "CompleteExtractionJob" && self.jobs.is_some())
+    }
+
+    fn handle(&self, ctx: &mut CommandContext, cmd: &AnyCommand) -> DomainResult<CommandOutcome> {
+        match cmd {
+            AnyCommand::RunExtraction(c) => self.run(ctx, c),
+            AnyCommand::CompleteExtractionJob(c) => self.complete(c),
+            _ => Err(unsupported_command("RunExtractionHandler")),
+        }
+    }
+}
@@ -0,0 +1,936 @@
+use std::collections::{BTreeMap, HashSet};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::commands::{
+    AddExtraTable, AddSchemaField, AnyCommand, ChangeSchemaFieldType, CreateProject, CreateSchema,
+    RenameSchemaField, RetireSchemaField,
+};
+use crate::errors::{unsupported_command, DomainError, DomainResult, ErrorCode};
+use crate::interfaces::{
+    CommandContext, CommandOutcome, GenericCommandHandler, SchemaStore, SessionReader, StateDelta,
+    ValidationTrigger,
+};
+use crate::item_rows::ItemRowDeletion;
+use crate::types::{FieldScope, FieldType};
+use crate::value_parsing::ValueLocale;
+
+/// `projects` row.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Project {
+    pub project_id: Uuid,
+    pub name: String,
+    /// Decimal separator, date order and default currency used to parse values.
+    #[serde(default)]
+    pub locale: ValueLocale,
+    /// Whether deleted item rows give up their position or leave a tombstone.
+    #[serde(default)]
+    pub item_row_deletion: ItemRowDeletion,
+    /// Actor that created the project.
+    pub created_by: String,
+}
+
+/// `schemas` row. Field definitions live in [`SchemaVersion`]s.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Schema {
+    pub schema_id: Uuid,
+    pub project_id: Uuid,
+    pub name: String,
+    /// Actor that created the schema.
+    pub created_by: String,
+}
+
+/// Field definition as submitted by a command.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SchemaFieldSpec {
+    pub field_key: String,
+    pub label: String,
+    pub field_type: FieldType,
+    #[serde(default = "default_scope")]
+    pub scope: FieldScope,
+    #[serde(default)]
+    pub required: bool,
+    /// Allowed values; only for `enum` fields.
+    #[serde(default)]
+    pub enum_values: Vec<String>,
+    /// Alternative spellings mapped to one of `enum_values`.
+    #[serde(default)]
+    pub enum_synonyms: BTreeMap<String, String>,
+    /// Extra table the column belongs to; only for `extra_table` fields.
+    #[serde(default)]
+    pub table_name: Option<String>,
+}
+
+fn default_scope() -> FieldScope {
+    FieldScope::Document
+}
+
+/// `schema_fields` row as of one schema version. `schema_field_id` is stable across
+/// versions; retired fields stay listed so older values keep their definition.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct SchemaField {
+    pub schema_field_id: Uuid,
+    pub field_key: String,
+    pub label: String,
+    pub field_type: FieldType,
+    pub scope: FieldScope,
+    pub required: bool,
+    pub enum_values: Vec<String>,
+    pub enum_synonyms: BTreeMap<String, String>,
+    /// Table of an `extra_table` column.
+    #[serde(default)]
+    pub extra_table_id: Option<Uuid>,
+    pub retired: bool,
+}
+
+impl SchemaField {
+    /// Resolves the spec's `table_name` against the tables declared so far.
+    fn from_spec(spec: &SchemaFieldSpec, tables: &[ExtraTable]) -> DomainResult<Self> {
+        let extra_table_id = match (spec.scope, &spec.table_name) {
+            (FieldScope::ExtraTable, Some(name)) => Some(
+                tables
+                    .iter()
+                    .find(|t| t.table_name == name.trim())
+                    .map(|t| t.extra_table_id)
+                    .ok_or_else(|| DomainError {
+                        code: ErrorCode::NotFound,
+                        message: "Extra table not declared in the schema".to_string(),
+                        details: Some(serde_json::json!({
+                            "field_key": spec.field_key,
+                            "table_name": name,
+                        })),
+                    })?,
+            ),
+            (FieldScope::ExtraTable, None) => {
+                return Err(spec_error("Extra-table fields name their table", spec))
+            }
+            (_, Some(_)) => return Err(spec_error("Only extra-table fields name a table", spec)),
+            (_, None) => None,
+        };
+        Ok(Self {
+            schema_field_id: Uuid::now_v7(),
+            field_key: spec.field_key.trim().to_string(),
+            label: spec.label.trim().to_string(),
+            field_type: spec.field_type,
+            scope: spec.scope,
+            required: spec.required,
+            enum_values: spec
+                .enum_values
+                .iter()
+                .map(|v| v.trim().to_string())
+                .collect(),
+            enum_synonyms: trimmed_synonyms(&spec.enum_synonyms),
+            extra_table_id,
+            retired: false,
+        })
+    }
+}
+
+fn spec_error(message: &str, spec: &SchemaFieldSpec) -> DomainError {
+    DomainError {
+        code: ErrorCode::PreconditionFailed,
+        message: message.to_string(),
+        details: Some(serde_json::json!({ "field_key": spec.field_key })),
+    }
+}
+
+/// Extra table as submitted by a command, with its columns.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExtraTableSpec {
+    pub table_name: String,
+    pub label: String,
+    /// Column definitions; their `scope` and `table_name` are implied.
+    #[serde(default)]
+    pub columns: Vec<SchemaFieldSpec>,
+}
+
+impl ExtraTableSpec {
+    fn column_specs(&self) -> impl Iterator<Item = SchemaFieldSpec> + '_ {
+        self.columns.iter().map(|column| SchemaFieldSpec {
+            scope: FieldScope::ExtraTable,
+            table_name: Some(self.table_name.clone()),
+            ..column.clone()
+        })
+    }
+}
+
+/// `schema_extra_tables` row as of one schema version: a document-level table beside
+/// the line items, whose columns are the version's `extra_table` fields.
+/// `extra_table_id` is stable across versions.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ExtraTable {
+    pub extra_table_id: Uuid,
+    pub table_name: String,
+    pub label: String,
+}
+
+impl ExtraTable {
+    fn from_spec(spec: &ExtraTableSpec) -> Self {
+        Self {
+            extra_table_id: Uuid::now_v7(),
+            table_name: spec.table_name.trim().to_string(),
+            label: spec.label.trim().to_string(),
+        }
+    }
+}
+
+fn trimmed_synonyms(synonyms: &BTreeMap<String, String>) -> BTreeMap<String, String> {
+    synonyms
+        .iter()
+        .map(|(synonym, value)| (synonym.trim().to_string(), value.trim().to_string()))
+        .collect()
+}
+
+/// Immutable snapshot of a schema's fields. Every field change appends a new version.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SchemaVersion {
+    pub schema_id: Uuid,
+    pub version: u32,
+    pub fields: Vec<SchemaField>,
+    #[serde(default)]
+    pub extra_tables: Vec<ExtraTable>,
+    pub created_at: DateTime<Utc>,
+    pub caused_by: Uuid,
+}
+
+impl SchemaVersion {
+    pub fn field(&self, schema_field_id: Uuid) -> Option<&SchemaField> {
+        self.fields
+            .iter()
+            .find(|f| f.schema_field_id == schema_field_id)
+    }
+
+    /// Document and item fields by key; extra-table columns as `<table>.<column>`.
+    pub fn field_by_key(&self, field_key: &str) -> Option<&SchemaField> {
+        if let Some((table_name, column)) = field_key.split_once('.') {
+            return self.column_by_key(table_name, column);
+        }
+        self.fields
+            .iter()
+            .find(|f| !f.retired && f.scope != FieldScope::ExtraTable && f.field_key == field_key)
+    }
+
+    /// The key [`Self::field_by_key`] resolves to the field.
+    pub fn qualified_key(&self, field: &SchemaField) -> String {
+        match field.extra_table_id.and_then(|id| self.extra_table(id)) {
+            Some(table) => format!("{}.{}", table.table_name, field.field_key),
+            None => field.field_key.clone(),
+        }
+    }
+
+    pub fn extra_table(&self, extra_table_id: Uuid) -> Option<&ExtraTable> {
+        self.extra_tables
+            .iter()
+            .find(|t| t.extra_table_id == extra_table_id)
+    }
+
+    pub fn extra_table_by_name(&self, table_name: &str) -> Option<&ExtraTable> {
+        self.extra_tables
+            .iter()
+            .find(|t| t.table_name == table_name)
+    }
+
+    /// Active columns of an extra table in declaration order.
+    pub fn columns(&self, extra_table_id: Uuid) -> impl Iterator<Item = &SchemaField> {
+        self.active_fields()
+            .filter(move |f| f.extra_table_id == Some(extra_table_id))
+    }
+
+    pub fn column_by_key(&self, table_name: &str, field_key: &str) -> Option<&SchemaField> {
+        let table = self.extra_table_by_name(table_name)?;
+        self.columns(table.extra_table_id)
+            .find(|f| f.field_key == field_key)
+    }
+
+    pub fn active_fields(&self) -> impl Iterator<Item = &SchemaField> {
+        self.fields.iter().filter(|f| !f.retired)
+    }
+
+    /// Field a value may be written to in a session pinned to this version.
+    pub fn writable_field(&self, schema_field_id: Uuid) -> DomainResult<&SchemaField> {
+        match self.field(schema_field_id) {
+            Some(field) if !field.retired => Ok(field),
+            Some(_) => Err(DomainError {
+                code: ErrorCode::PreconditionFailed,
+                message: "Schema field is retired".to_string(),
+                details: Some(serde_json::json!({
+                    "schema_id": self.schema_id,
+                    "version": self.version,
+                    "schema_field_id": schema_field_id,
+                })),
+            }),
+            None => Err(DomainError {
+                code: ErrorCode::NotFound,
+                message: "Schema field not found in session schema version".to_string(),
+                details: Some(serde_json::json!({
+                    "schema_id": self.schema_id,
+                    "version": self.version,
+                    "schema_field_id": schema_field_id,
+                })),
+            }),
+        }
+    }
+
+    /// Field keys are unique among active document and item fields, and column keys
+    /// within their table; table names are unique. Enum fields list their values and
+    /// only enum fields do.
+    pub fn validate(&self) -> DomainResult<()> {
+        let mut tables = HashSet::new();
+        for table in &self.extra_tables {
+            if !is_snake_case(&table.table_name) || table.label.is_empty() {
+                return Err(table_error(
+                    "Extra table name must be snake_case and its label non-empty",
+                    table,
+                ));
+            }
+            if !tables.insert(table.table_name.as_str()) {
+                return Err(table_error("Duplicate extra table name", table));
+            }
+        }
+        let mut keys = HashSet::new();
+        for field in self.active_fields() {
+            validate_field(field)?;
+            let table = field.extra_table_id.and_then(|id| self.extra_table(id));
+            if (field.scope == FieldScope::ExtraTable) != table.is_some() {
+                return Err(field_error(
+                    "Extra-table fields, and only they, belong to a declared table",
+                    field,
+                ));
+            }
+            if !keys.insert((field.extra_table_id, field.field_key.as_str())) {
+                return Err(field_error("Duplicate schema field key", field));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Schema and version a session was created against.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub struct SchemaRef {
+    pub schema_id: Uuid,
+    pub version: u32,
+}
+
+fn is_snake_case(key: &str) -> bool {
+    !key.is_empty()
+        && key
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_')
+        && !key.starts_with(|c: char| c.is_ascii_digit())
+}
+
+fn validate_field(field: &SchemaField) -> DomainResult<()> {
+    if !is_snake_case(&field.field_key) {
+        return Err(field_error(
+            "Schema field key must be snake_case ([a-z0-9_], not starting with a digit)",
+            field,
+        ));
+    }
+    if field.label.is_empty() {
+        return Err(field_error("Schema field label must not be empty", field));
+    }
+    match field.field_type {
+        FieldType::Enum => {
+            let distinct: HashSet<&str> = field.enum_values.iter().map(String::as_str).collect();
+            if field.enum_values.is_empty()
+                || distinct.len() != field.enum_values.len()
+                || distinct.contains("")
+            {
+                return Err(field_error(
+                    "Enum field needs distinct, non-empty values",
+                    field,
+                ));
+            }
+            if field
+                .enum_synonyms
+                .iter()
+                .any(|(synonym, value)| synonym.is_empty() || !distinct.contains(value.as_str()))
+            {
+                return Err(field_error(
+                    "Enum synonyms must map to one of the enum values",
+                    field,
+                ));
+            }
+        }
+        _ if !field.enum_values.is_empty() || !field.enum_synonyms.is_empty() => {
+            return Err(field_error("Only enum fields take enum values", field));
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+fn field_error(message: &str, field: &SchemaField) -> DomainError {
+    DomainError {
+        code: ErrorCode::PreconditionFailed,
+        message: message.to_string(),
+        details: Some(serde_json::json!({
+            "schema_field_id": field.schema_field_id,
+            "field_key": field.field_key,
+        })),
+    }
+}
+
+fn table_error(message: &str, table: &ExtraTable) -> DomainError {
+    DomainError {
+        code: ErrorCode::PreconditionFailed,
+        message: message.to_string(),
+        details: Some(serde_json::json!({
+            "extra_table_id": table.extra_table_id,
+            "table_name": table.table_name,
+        })),
+    }
+}
+
+/// The exact schema version a session validates and exports against.
+pub fn session_schema(
+    sessions: &dyn SessionReader,
+    schemas: &dyn SchemaStore,
+    session_id: Uuid,
+) -> DomainResult<SchemaVersion> {
+    let pinned = sessions.get_session(session_id)?.schema;
+    schemas.version(pinned.schema_id, pinned.version)
+}
+
+pub struct CreateProjectHandler<'a> {
+    schemas: &'a dyn SchemaStore,
+}
+
+impl<'a> CreateProjectHandler<'a> {
+    pub fn new(schemas: &'a dyn SchemaStore) -> Self {
+        Self { schemas }
+    }
+
+    fn create(&self, cmd: &CreateProject) -> DomainResult<CommandOutcome> {
+        let name = cmd.payload.name.trim();
+        if name.is_empty() {
+            return Err(DomainError {
+                code: ErrorCode::PreconditionFailed,
+                message: "Project name must not be empty".to_string(),
+                details: None,
+            });
+        }
+        let project = Project {
+            project_id: Uuid::now_v7(),
+            name: name.to_string(),
+            locale: cmd.payload.locale.clone(),
+            item_row_deletion: cmd.payload.item_row_deletion,
+            created_by: cmd.actor.clone(),
+        };
+        self.schemas.create_project(&project)?;
+
+        Ok(CommandOutcome {
+            state_delta: StateDelta {
+                summary: format!("Project '{}' created", project.name),
+                data: serde_json::json!({ "project": project }),
+            },
+            transition: None,
+            review_actions: Vec::new(),
+            validation_trigger: ValidationTrigger::None,
+            events: Vec::new(),
+        })
+    }
+}
+
+impl<'a> GenericCommandHandler for CreateProjectHandler<'a> {
+    fn can_handle(&self, command_type: &str) -> bool {
+        command_type == "CreateProject"
+    }
+
+    fn handle(&self, _ctx: &mut CommandContext, cmd: &AnyCommand) -> DomainResult<CommandOutcome> {
+        match cmd {
+            AnyCommand::CreateProject(c) => self.create(c),
+            _ => Err(unsupported_command("CreateProjectHandler")),
+        }
+    }
+}
+
+pub struct CreateSchemaHandler<'a> {
+    schemas: &'a dyn SchemaStore,
+}
+
+impl<'a> CreateSchemaHandler<'a> {
+    pub fn new(schemas: &'a dyn SchemaStore) -> Self {
+        Self { schemas }
+    }
+
+    fn create(&self, ctx: &CommandContext, cmd: &CreateSchema) -> DomainResult<CommandOutcome> {
+        self.schemas.get_project(cmd.payload.project_id)?;
+        let name = cmd.payload.name.trim();
+        if name.is_empty() {
+            return Err(DomainError {
+                code: ErrorCode::PreconditionFailed,
+                message: "Schema name must not be empty".to_string(),
+                details: None,
+            });
+        }
+
+        let schema = Schema {
+            schema_id: Uuid::now_v7(),
+            project_id: cmd.payload.project_id,
+            name: name.to_string(),
+            created_by: cmd.actor.clone(),
+        };
+        let extra_tables: Vec<ExtraTable> = cmd
+            .payload
+            .extra_tables
+            .iter()
+            .map(ExtraTable::from_spec)
+            .collect();
+        let column_specs: Vec<SchemaFieldSpec> = cmd
+            .payload
+            .extra_tables
+            .iter()
+            .flat_map(ExtraTableSpec::column_specs)
+            .collect();
+        let fields = cmd
+            .payload
+            .fields
+            .iter()
+            .chain(&column_specs)
+            .map(|spec| SchemaField::from_spec(spec, &extra_tables))
+            .collect::<DomainResult<Vec<_>>>()?;
+        let version = SchemaVersion {
+            schema_id: schema.schema_id,
+            version: 1,
+            fields,
+            extra_tables,
+            created_at: ctx.now,
+            caused_by: cmd.command_id,
+        };
+        version.validate()?;
+        self.schemas.create_schema(&schema)?;
+        self.schemas.append_version(&version)?;
+
+        Ok(CommandOutcome {
+            state_delta: StateDelta {
+                summary: format!("Schema '{}' created", schema.name),
+                data: serde_json::json!({ "schema": schema, "schema_version": version }),
+            },
+            transition: None,
+            review_actions: Vec::new(),
+            validation_trigger: ValidationTrigger::None,
+            events: Vec::new(),
+        })
+    }
+}
+
+impl<'a> GenericCommandHandler for CreateSchemaHandler<'a> {
+    fn can_handle(&self, command_type: &str) -> bool {
+        command_type == "CreateSchema"
+    }
+
+    fn handle(&self, ctx: &mut CommandContext, cmd: &AnyCommand) -> DomainResult<CommandOutcome> {
+        match cmd {
+            AnyCommand::CreateSchema(c) => self.create(ctx, c),
+            _ => Err(unsupported_command("CreateSchemaHandler")),
+        }
+    }
+}
+
+/// What a schema edit changed, reported in the delta by id.
+enum Revised {
+    Field(Uuid),
+    ExtraTable(Uuid),
+}
+
+/// Handles field and extra-table edits. Each accepted edit appends a new schema
+/// version; earlier versions, and sessions pinned to them, are untouched.
+pub struct SchemaFieldHandler<'a> {
+    schemas: &'a dyn SchemaStore,
+}
+
+impl<'a> SchemaFieldHandler<'a> {
+    pub fn new(schemas: &'a dyn SchemaStore) -> Self {
+        Self { schemas }
+    }
+
+    /// `edit` changes a copy of the latest version and says what it changed.
+    fn revise<F>(
+        &self,
+        ctx: &CommandContext,
+        command_id: Uuid,
+        project_id: Uuid,
+        schema_id: Uuid,
+        summary: &str,
+        edit: F,
+    ) -> DomainResult<CommandOutcome>
+    where
+        F: FnOnce(&mut SchemaVersion) -> DomainResult<Revised>,
+    {
+        let schema = self.schemas.get_schema(schema_id)?;
+        if schema.project_id != project_id {
+            return Err(DomainError {
+                code: ErrorCode::PreconditionFailed,
+                message: "Schema belongs to a different project".to_string(),
+                details: Some(serde_json::json!({
+                    "project_id": project_id,
+                    "schema_id": schema_id,
+                })),
+            });
+        }
+        let current = self.schemas.latest_version(schema_id)?;
+        let mut next = SchemaVersion {
+            version: current.version + 1,
+            created_at: ctx.now,
+            caused_by: command_id,
+            ..current.clone()
+        };
+        let revised = edit(&mut next)?;
+        next.validate()?;
+        self.schemas.append_version(&next)?;
+
+        let mut data = serde_json::json!({
+            "schema_id": schema_id,
+            "previous_version": current.version,
+            "schema_version": next,
+        });
+        match revised {
+            Revised::Field(id) => data["schema_field_id"] = serde_json::json!(id),
+            Revised::ExtraTable(id) => data["extra_table_id"] = serde_json::json!(id),
+        }
+        Ok(CommandOutcome {
+            state_delta: StateDelta {
+                summary: format!("{summary}; schema now at version {}", next.version),
+                data,
+            },
+            transition: None,
+            review_actions: Vec::new(),
+            validation_trigger: ValidationTrigger::None,
+            events: Vec::new(),
+        })
+    }
+
+    fn add(&self, ctx: &CommandContext, cmd: &AddSchemaField) -> DomainResult<CommandOutcome> {
+        let p = &cmd.payload;
+        self.revise(
+            ctx,
+            cmd.command_id,
+            p.project_id,
+            p.schema_id,
+            "Schema field added",
+            |next| {
+                let field = SchemaField::from_spec(&p.field, &next.extra_tables)?;
+                let id = field.schema_field_id;
+                next.fields.push(field);
+                Ok(Revised::Field(id))
+            },
+        )
+    }
+
+    /// Declares a table with its columns in one version.
+    fn add_table(&self, ctx: &CommandContext, cmd: &AddExtraTable) -> DomainResult<CommandOutcome> {
+        let p = &cmd.payload;
+        self.revise(
+            ctx,
+            cmd.command_id,
+            p.project_id,
+            p.schema_id,
+            "Extra table added",
+            |next| {
+                let table = ExtraTable::from_spec(&p.table);
+                let id = table.extra_table_id;
+                next.extra_tables.push(table);
+                for spec in p.table.column_specs() {
+                    let column = SchemaField::from_spec(&spec, &next.extra_tables)?;
+                    next.fields.push(column);
+                }
+                Ok(Revised::ExtraTable(id))
+            },
+        )
+    }
+
+    fn rename(
+        &self,
+        ctx: &CommandContext,
+        cmd: &RenameSchemaField,
+    ) -> DomainResult<CommandOutcome> {
+        let p = &cmd.payload;
+        self.revise(
+            ctx,
+            cmd.command_id,
+            p.project_id,
+            p.schema_id,
+            "Schema field renamed",
+            |next| {
+                let field = active_field(&mut next.fields, p.schema_field_id)?;
+                field.label = p.label.trim().to_string();
+                if let Some(key) = &p.field_key {
+                    field.field_key = key.trim().to_string();
+                }
+                Ok(Revised::Field(field.schema_field_id))
+            },
+        )
+    }
+
+    fn retire(
+        &self,
+        ctx: &CommandContext,
+        cmd: &RetireSchemaField,
+    ) -> DomainResult<CommandOutcome> {
+        let p = &cmd.payload;
+        self.revise(
+            ctx,
+            cmd.command_id,
+            p.project_id,
+            p.schema_id,
+            "Schema field retired",
+            |next| {
+                let field = active_field(&mut next.fields, p.schema_field_id)?;
+                field.retired = true;
+                Ok(Revised::Field(field.schema_field_id))
+            },
+        )
+    }
+
+    fn change_type(
+        &self,
+        ctx: &CommandContext,
+        cmd: &ChangeSchemaFieldType,
+    ) -> DomainResult<CommandOutcome> {
+        let p = &cmd.payload;
+        self.revise(
+            ctx,
+            cmd.command_id,
+            p.project_id,
+            p.schema_id,
+            "Schema field type changed",
+            |next| {
+                let field = active_field(&mut next.fields, p.schema_field_id)?;
+                let enum_values: Vec<String> =
+                    p.enum_values.iter().map(|v| v.trim().to_string()).collect();
+                let enum_synonyms = trimmed_synonyms(&p.enum_synonyms);
+                if field.field_type == p.field_type
+                    && field.enum_values == enum_values
+                    && field.enum_synonyms == enum_synonyms
+                {
+                    return Err(field_error("Schema field already has this type", field));
+                }
+                field.field_type = p.field_type;
+                field.enum_values = enum_values;
+                field.enum_synonyms = enum_synonyms;
+                if let Some(required) = p.required {
+                    field.required = required;
+                }
+                Ok(Revised::Field(field.schema_field_id))
+            },
+        )
+    }
+}
+
+fn active_field(
+    fields: &mut [SchemaField],
+    schema_field_id: Uuid,
+) -> DomainResult<&mut SchemaField> {
+    match fields
+        .iter_mut()
+        .find(|f| f.schema_field_id == schema_field_id)
+    {
+        Some(field) if field.retired => Err(field_error("Schema field is retired", field)),
+        Some(field) => Ok(field),
+        None => Err(DomainError {
+            code: ErrorCode::NotFound,
+            message: "Schema field not found".to_string(),
+            details: Some(serde_json::json!({ "schema_field_id": schema_field_id })),
+        }),
+    }
+}
+
+impl<'a> GenericCommandHandler for SchemaFieldHandler<'a> {
+    fn can_handle(&self, command_type: &str) -> bool {
+        matches!(
+            command_type,
+            "AddSchemaField"
+                | "AddExtraTable"
+                | "RenameSchemaField"
+                | "RetireSchemaField"
+                | "ChangeSchemaFieldType"
+        )
+    }
+
+    fn handle(&self, ctx: &mut CommandContext, cmd: &AnyCommand) -> DomainResult<CommandOutcome> {
+        match cmd {
+            AnyCommand::AddSchemaField(c) => self.add(ctx, c),
+            AnyCommand::AddExtraTable(c) => self.add_table(ctx, c),
+            AnyCommand::RenameSchemaField(c) => self.rename(ctx, c),
+            AnyCommand::RetireSchemaField(c) => self.retire(ctx, c),
+            AnyCommand::ChangeSchemaFieldType(c) => self.change_type(ctx, c),
+            _ => Err(unsupported_command("SchemaFieldHandler")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sessions::CreateSessionHandler;
+    use crate::test_support::{command, ctx, World};
+
+    fn rejected(version: &SchemaVersion) -> bool {
+        matches!(
+            version.validate(),
+            Err(DomainError {
+                code: ErrorCode::PreconditionFailed,
+                ..
+            })
+        )
+    }
+
+    /// Handles a field edit on the world's schema; `payload` omits the ids.
+    fn revise(w: &World, command_type: &str, mut payload: serde_json::Value) -> CommandOutcome {
+        payload["project_id"] = serde_json::json!(w.project_id);
+        payload["schema_id"] = serde_json::json!(w.schema.schema_id);
+        SchemaFieldHandler::new(&w.b.schemas)
+            .handle(
+                &mut ctx(),
+                &command(serde_json::json!({ "type": command_type, "payload": payload })),
+            )
+            .unwrap()
+    }
+
+    fn create_session(w: &World) -> Uuid {
+        let outcome = CreateSessionHandler::new(&w.b.sessions, &w.b.schemas)
+            .handle(
+                &mut ctx(),
+                &command(serde_json::json!({
+                    "type": "CreateSession",
+                    "payload": {
+                        "project_id": w.project_id,
+                        "schema_id": w.schema.schema_id,
+                        "source": "manual"
+                    }
+                })),
+            )
+            .unwrap();
+        serde_json::from_value(outcome.state_delta.data["session"]["session_id"].clone()).unwrap()
+    }
+
+    #[test]
+    fn versions_reject_malformed_fields() {
+        let w = World::new(&[
+            ("vendor", "string", "document", false),
+            ("currency", "string", "document", false),
+        ]);
+        w.schema.validate().unwrap();
+
+        let mut version = w.schema.clone();
+        version.fields[1].field_type = FieldType::Enum;
+        assert!(rejected(&version), "enum without values");
+        version.fields[1].enum_values = vec!["EUR".to_string(), "USD".to_string()];
+        version.validate().unwrap();
+
+        let mut bad = version.clone();
+        bad.fields[0].field_key = "Vendor Name".to_string();
+        assert!(rejected(&bad), "key not snake_case");
+
+        let mut bad = version.clone();
+        bad.fields[1].field_key = "vendor".to_string();
+        assert!(rejected(&bad), "duplicate key");
+        bad.fields[1].retired = true;
+        bad.validate().unwrap();
+
+        let mut bad = version.clone();
+        bad.fields[0].enum_values = vec!["x".to_string()];
+        assert!(rejected(&bad), "enum values on a string field");
+
+        let mut bad = version.clone();
+        bad.fields[1]
+            .enum_synonyms
+            .insert("euro".to_string(), "GBP".to_string());
+        assert!(rejected(&bad), "synonym for an unknown value");
+
+        let mut bad = version;
+        bad.fields[0].scope = FieldScope::ExtraTable;
+        assert!(rejected(&bad), "extra-table field without a table");
+    }
+
+    #[test]
+    fn revisions_append_a_version_and_keep_the_previous_one() {
+        let w = World::new(&[("vendor", "string", "document", false)]);
+        assert_eq!(w.schema.version, 1);
+        let schema = w.b.schemas.get_schema(w.schema.schema_id).unwrap();
+        assert_eq!(schema.created_by, "tester");
+        assert_eq!(
+            w.b.schemas.get_project(w.project_id).unwrap().created_by,
+            "tester"
+        );
+
+        let added = revise(
+            &w,
+            "AddSchemaField",
+            serde_json::json!({
+                "field": { "field_key": "total", "label": "Total", "field_type": "decimal" }
+            }),
+        );
+        assert_eq!(added.state_delta.data["previous_version"], 1);
+        revise(
+            &w,
+            "RenameSchemaField",
+            serde_json::json!({ "schema_field_id": w.field_id("vendor"), "label": "Supplier" }),
+        );
+
+        let latest = w.b.schemas.latest_version(w.schema.schema_id).unwrap();
+        assert_eq!(latest.version, 3);
+        assert_eq!(
+            latest.field(w.field_id("vendor")).unwrap().label,
+            "Supplier"
+        );
+        assert!(latest.field_by_key("total").is_some());
+
+        let first = w.b.schemas.version(w.schema.schema_id, 1).unwrap();
+        assert_eq!(first.fields.len(), 1);
+        assert_eq!(first.field(w.field_id("vendor")).unwrap().label, "vendor");
+        let second = w.b.schemas.version(w.schema.schema_id, 2).unwrap();
+        assert!(second.field_by_key("total").is_some());
+        assert_eq!(second.field(w.field_id("vendor")).unwrap().label, "vendor");
+    }
+
+    #[test]
+    fn sessions_stay_pinned_to_their_version() {
+        let w = World::new(&[
+            ("vendor", "string", "document", false),
+            ("note", "string", "document", false),
+        ]);
+        let pinned = create_session(&w);
+        revise(
+            &w,
+            "RetireSchemaField",
+            serde_json::json!({ "schema_field_id": w.field_id("note") }),
+        );
+        let added = revise(
+            &w,
+            "AddSchemaField",
+            serde_json::json!({
+                "field": { "field_key": "total", "label": "Total", "field_type": "decimal" }
+            }),
+        );
+        let total: Uuid =
+            serde_json::from_value(added.state_delta.data["schema_field_id"].clone()).unwrap();
+
+        let old = session_schema(&w.b.sessions, &w.b.schemas, pinned).unwrap();
+        assert_eq!(old.version, 1);
+        old.writable_field(w.field_id("note")).unwrap();
+        assert!(matches!(
+            old.writable_field(total),
+            Err(DomainError {
+                code: ErrorCode::NotFound,
+                ..
+            })
+        ));
+
+        let current = create_session(&w);
+        let new = session_schema(&w.b.sessions, &w.b.schemas, current).unwrap();
+        assert_eq!(new.version, 3);
+        new.writable_field(total).unwrap();
+        assert!(matches!(
+            new.writable_field(w.field_id("note")),
+            Err(DomainError {
+                code: ErrorCode::PreconditionFailed,
+                ..
+            })
+        ));
+    }
+}
@@ -0,0 +1,248 @@
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::commands::{AnyCommand, CreateCorrectionSession, CreateSession};
+use crate::errors::{unsupported_command, DomainError, DomainResult, ErrorCode};
+use crate::interfaces::{
+    CommandContext, CommandOutcome, GenericCommandHandler, OutcomeEvent, SchemaStore,
+    SessionReader, SessionWriter, StateDelta, ValidationTrigger,
+};
+use crate::schema::SchemaRef;
+use crate::types::SessionStatus;
+
+/// `sessions` row, minus the lifecycle status tracked by projections.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SessionRecord {
+    pub session_id: Uuid,
+    pub project_id: Uuid,
+    /// Schema version fixed at creation; validation and export always use it.
+    pub schema: SchemaRef,
+    pub source: String,
+    /// Set for correction sessions.
+    pub base_session_id: Option<Uuid>,
+    /// Actor that created the session.
+    pub created_by: String,
+}
+
+pub struct CreateSessionHandler<'a> {
+    sessions: &'a dyn SessionWriter,
+    schemas: &'a dyn SchemaStore,
+}
+
+impl<'a> CreateSessionHandler<'a> {
+    pub fn new(sessions: &'a dyn SessionWriter, schemas: &'a dyn SchemaStore) -> Self {
+        Self { sessions, schemas }
+    }
+
+    fn create(&self, cmd: &CreateSession) -> DomainResult<CommandOutcome> {
+        let payload = &cmd.payload;
+        self.schemas.get_project(payload.project_id)?;
+        let schema = self.schemas.get_schema(payload.schema_id)?;
+        if schema.project_id != payload.project_id {
+            return Err(DomainError {
+                code: ErrorCode::PreconditionFailed,
+                message: "Schema belongs to a different project".to_string(),
+                details: Some(serde_json::json!({
+                    "project_id": payload.project_id,
+                    "schema_id": payload.schema_id,
+                })),
+            });
+        }
+        let version = match payload.schema_version {
+            Some(version) => self.schemas.version(payload.schema_id, version)?,
+            None => self.schemas.latest_version(payload.schema_id)?,
+        };
+
+        let record = SessionRecord {
+            session_id: Uuid::now_v7(),
+            project_id: payload.project_id,
+            schema: SchemaRef {
+                schema_id: version.schema_id,
+                version: version.version,
+            },
+            source: payload.source.clone(),
+            base_session_id: None,
+            created_by: cmd.actor.clone(),
+        };
+        self.sessions
+            .create_session(&record, SessionStatus::Created)?;
+
+        Ok(created_outcome(
+            "Session created",
+            "SessionCreated",
+            &record,
+        ))
+    }
+}
+
+impl<'a> GenericCommandHandler for CreateSessionHandler<'a> {
+    fn can_handle(&self, command_type: &str) -> bool {
+        command_type == "CreateSession"
+    }
+
+    fn handle(&self, _ctx: &mut CommandContext, cmd: &AnyCommand) -> DomainResult<CommandOutcome> {
+        match cmd {
+            AnyCommand::CreateSession(c) => self.create(c),
+            _ => Err(unsupported_command("CreateSessionHandler")),
+        }
+    }
+}
+
+/// Correction sessions inherit the base session's pinned schema version so the
+/// corrected export stays comparable with the original.
+pub struct CreateCorrectionSessionHandler<'a> {
+    reader: &'a dyn SessionReader,
+    sessions: &'a dyn SessionWriter,
+}
+
+impl<'a> CreateCorrectionSessionHandler<'a> {
+    pub fn new(reader: &'a dyn SessionReader, sessions: &'a dyn SessionWriter) -> Self {
+        Self { reader, sessions }
+    }
+
+    fn create(&self, cmd: &CreateCorrectionSession) -> DomainResult<CommandOutcome> {
+        let payload = &cmd.payload;
+        let base = self.reader.get_session(payload.base_session_id)?;
+        if base.project_id != payload.project_id || base.schema.schema_id != payload.schema_id {
+            return Err(DomainError {
+                code: ErrorCode::PreconditionFailed,
+                message: "Base session belongs to a different project or schema".to_string(),
+                details: Some(serde_json::json!({
+                    "base_session_id": payload.base_session_id,
+                    "project_id": payload.project_id,
+                    "schema_id": payload.schema_id,
+                })),
+            });
+        }
+        let status = self.reader.get_status(payload.base_session_id)?;
+        if status != SessionStatus::Locked {
+            return Err(DomainError {
+                code: ErrorCode::PreconditionFailed,
+                message: "Correction sessions can only be based on a locked session".to_string(),
+                details: Some(serde_json::json!({
+                    "base_session_id": payload.base_session_id,
+                    "status": status,
+                })),
+            });
+        }
+
+        let record = SessionRecord {
+            session_id: Uuid::now_v7(),
+            project_id: base.project_id,
+            schema: base.schema,
+            source: base.source.clone(),
+            base_session_id: Some(base.session_id),
+            created_by: cmd.actor.clone(),
+        };
+        self.sessions
+            .create_session(&record, SessionStatus::Created)?;
+
+        Ok(created_outcome(
+            "Correction session created",
+            "CorrectionSessionCreated",
+            &record,
+        ))
+    }
+}
+
+impl<'a> GenericCommandHandler for CreateCorrectionSessionHandler<'a> {
+    fn can_handle(&self, command_type: &str) -> bool {
+        command_type == "CreateCorrectionSession"
+    }
+
+    fn handle(&self, _ctx: &mut CommandContext, cmd: &AnyCommand) -> DomainResult<CommandOutcome> {
+        match cmd {
+            AnyCommand::CreateCorrectionSession(c) => self.create(c),
+            _ => Err(unsupported_command("CreateCorrectionSessionHandler")),
+        }
+    }
+}
+
+/// The command has no session of its own, so the creation event carries the new
+/// session's id; it is the session's first event and counts as version 1.
+fn created_outcome(summary: &str, event_type: &str, record: &SessionRecord) -> CommandOutcome {
+    let data = serde_json::json!({
+        "session": record,
+        "status": SessionStatus::Created,
+    });
+    CommandOutcome {
+        state_delta: StateDelta {
+            summary: summary.to_string(),
+            data: data.clone(),
+        },
+        transition: None,
+        review_actions: Vec::new(),
+        validation_trigger: ValidationTrigger::None,
+        events: vec![OutcomeEvent {
+            event_type: event_type.to_string(),
+            session_id: Some(record.session_id),
+            data,
+        }],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dispatcher_impl::DefaultCommandDispatcher;
+    use crate::interfaces::{CommandDispatcher, DispatcherDeps, EventReader};
+    use crate::test_support::{command, World};
+    use crate::transition_policy::MatrixTransitionPolicy;
+    use crate::value_history::ValueHistory;
+
+    #[test]
+    fn the_creation_event_is_the_sessions_first_version() {
+        let w = World::new(&[("vendor", "string", "document", false)]);
+        let policy = MatrixTransitionPolicy::new();
+        let create = CreateSessionHandler::new(&w.b.sessions, &w.b.schemas);
+        let dispatcher = DefaultCommandDispatcher::new(DispatcherDeps {
+            handlers: vec![&create],
+            transitions: &policy,
+            idempotency: &w.b.idempotency,
+            events: &w.b.events,
+            event_reader: &w.b.events,
+            event_factory: &w.b.event_factory,
+            invariants: &w.b.invariants,
+            sessions: &w.b.sessions,
+            projections: &w.b.projections,
+            locks: &w.b.locks,
+            uow: &w.b.uow,
+        });
+        let command_id = Uuid::now_v7();
+
+        dispatcher
+            .dispatch(command(serde_json::json!({
+                "type": "CreateSession",
+                "command_id": command_id,
+                "payload": {
+                    "project_id": w.project_id,
+                    "schema_id": w.schema.schema_id,
+                    "source": "manual"
+                }
+            })))
+            .unwrap();
+
+        let log = w.b.events.all_events().unwrap();
+        let created = log
+            .iter()
+            .find(|e| e.event_type == "SessionCreated")
+            .unwrap();
+        assert_eq!(created.caused_by, command_id);
+        let session_id: Uuid = serde_json::from_value(created.data["session_id"].clone()).unwrap();
+        assert_eq!(
+            w.b.sessions.get_status(session_id).unwrap(),
+            SessionStatus::Created
+        );
+        assert_eq!(w.b.events.session_events(session_id).unwrap().len(), 1);
+        assert_eq!(
+            w.b.sessions.get_session(session_id).unwrap().created_by,
+            "tester"
+        );
+        assert_eq!(
+            ValueHistory::new(&w.b.events)
+                .session_version(session_id)
+                .unwrap(),
+            1
+        );
+    }
+}
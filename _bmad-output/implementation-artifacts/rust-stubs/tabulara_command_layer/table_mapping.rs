@@ -0,0 +1,880 @@
+use std::cmp::Ordering;
+use std::collections::HashSet;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::commands::{
+    AddItemRow, AddItemRowPayload, AnyCommand, AssignItemValue, AssignItemValuePayload,
+    DeleteItemRow, DeleteItemRowPayload, MapTableColumns,
+};
+use crate::dictionary::DictionaryNormalizer;
+use crate::errors::{unsupported_command, DomainError, DomainResult, ErrorCode};
+use crate::extraction::{BoundingBox, DetectedTable, ExtractionToken};
+use crate::interfaces::{
+    CommandContext, CommandOutcome, ExtractionStore, GenericCommandHandler, HeaderSynonymStore,
+    MappingStore, PageReader, ReviewAction, SchemaStore, SessionReader, StateDelta,
+    ValidationTrigger,
+};
+use crate::item_rows::{AddItemRowHandler, DeleteItemRowHandler, ItemRowDeps};
+use crate::mapping::AssignItemValueHandler;
+use crate::provenance::{Origin, Provenance, ProvenanceChecker};
+use crate::review_tasks::{ReviewCategory, ReviewTarget};
+use crate::schema::session_schema;
+use crate::types::FieldScope;
+
+/// Minimum header score for a column to be mapped to an item field.
+const HEADER_MATCH_THRESHOLD: f64 = 0.5;
+
+/// Cells mapped with less confidence than this are queued for review.
+const LOW_CONFIDENCE_THRESHOLD: f32 = 0.6;
+
+/// Item schema field a table column can be mapped to.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ItemFieldTarget {
+    pub schema_field_id: Uuid,
+    pub field_key: String,
+    pub label: String,
+}
+
+/// Header text a reviewer previously confirmed for an item field.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HeaderSynonym {
+    pub project_id: Uuid,
+    pub schema_field_id: Uuid,
+    pub header_text: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GridCell {
+    pub text: String,
+    pub bbox: Option<BoundingBox>,
+    pub confidence: f32,
+    pub token_ids: Vec<Uuid>,
+}
+
+impl GridCell {
+    fn empty() -> Self {
+        Self {
+            text: String::new(),
+            bbox: None,
+            confidence: 0.0,
+            token_ids: Vec::new(),
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.text.trim().is_empty()
+    }
+}
+
+/// Row/column structure recognized from a detected table candidate.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TableGrid {
+    pub table_id: Uuid,
+    pub extraction_run_id: Uuid,
+    pub page_id: Uuid,
+    pub rows: Vec<Vec<GridCell>>,
+}
+
+impl TableGrid {
+    pub fn column_count(&self) -> usize {
+        self.rows.iter().map(Vec::len).max().unwrap_or(0)
+    }
+
+    /// First row whose non-empty cells are mostly free of digits.
+    pub fn header_row(&self) -> Option<usize> {
+        self.rows.iter().position(|row| {
+            let filled: Vec<&GridCell> = row.iter().filter(|c| !c.is_empty()).collect();
+            let textual = filled
+                .iter()
+                .filter(|c| !c.text.chars().any(|ch| ch.is_ascii_digit()))
+                .count();
+            !filled.is_empty() && textual * 2 >= filled.len() && filled.len() * 2 >= row.len()
+        })
+    }
+}
+
+/// Builds a grid from the candidate's cells, or infers rows and columns from token
+/// geometry when the engine only reported the table outline.
+pub fn recognize_table(table: &DetectedTable, page_tokens: &[ExtractionToken]) -> TableGrid {
+    let rows = if table.cells.is_empty() {
+        infer_grid(table, page_tokens)
+    } else {
+        grid_from_cells(table, page_tokens)
+    };
+
+    TableGrid {
+        table_id: table.table_id,
+        extraction_run_id: table.extraction_run_id,
+        page_id: table.page_id,
+        rows,
+    }
+}
+
+fn grid_from_cells(table: &DetectedTable, page_tokens: &[ExtractionToken]) -> Vec<Vec<GridCell>> {
+    let mut rows =
+        vec![vec![GridCell::empty(); table.column_count as usize]; table.row_count as usize];
+    for cell in &table.cells {
+        let mut tokens: Vec<&ExtractionToken> = page_tokens
+            .iter()
+            .filter(|t| cell.token_ids.contains(&t.token_id))
+            .collect();
+        tokens.sort_by_key(|t| t.reading_order);
+        rows[cell.row as usize][cell.column as usize] = GridCell {
+            text: join_text(&tokens),
+            bbox: Some(cell.bbox),
+            confidence: cell.confidence.min(min_confidence(&tokens)),
+            token_ids: tokens.iter().map(|t| t.token_id).collect(),
+        };
+    }
+    rows
+}
+
+fn infer_grid(table: &DetectedTable, page_tokens: &[ExtractionToken]) -> Vec<Vec<GridCell>> {
+    let mut tokens: Vec<&ExtractionToken> = page_tokens
+        .iter()
+        .filter(|t| {
+            let (x, y) = t.bbox.center();
+            t.page_id == table.page_id && table.bbox.contains_point(x, y)
+        })
+        .collect();
+    if tokens.is_empty() {
+        return Vec::new();
+    }
+    tokens.sort_by(|a, b| cmp_f64(a.bbox.center().1, b.bbox.center().1));
+
+    // Rows: a token starts a new row when its center falls below the current row band.
+    let mut bands: Vec<Vec<&ExtractionToken>> = Vec::new();
+    for token in tokens {
+        match bands.last_mut() {
+            Some(band) if token.bbox.center().1 <= band_bottom(band) => band.push(token),
+            _ => bands.push(vec![token]),
+        }
+    }
+
+    // Columns: gaps in the horizontal projection of every token inside the table.
+    let mut spans: Vec<(f64, f64)> = bands
+        .iter()
+        .flatten()
+        .map(|t| (t.bbox.x, t.bbox.right()))
+        .collect();
+    spans.sort_by(|a, b| cmp_f64(a.0, b.0));
+    let mut columns: Vec<(f64, f64)> = Vec::new();
+    for (start, end) in spans {
+        match columns.last_mut() {
+            Some(last) if start <= last.1 => last.1 = last.1.max(end),
+            _ => columns.push((start, end)),
+        }
+    }
+
+    bands
+        .into_iter()
+        .map(|band| {
+            columns
+                .iter()
+                .map(|(start, end)| {
+                    let mut members: Vec<&ExtractionToken> = band
+                        .iter()
+                        .copied()
+                        .filter(|t| t.bbox.x >= *start && t.bbox.right() <= *end)
+                        .collect();
+                    if members.is_empty() {
+                        return GridCell::empty();
+                    }
+                    members.sort_by(|a, b| cmp_f64(a.bbox.x, b.bbox.x));
+                    let bbox = members
+                        .iter()
+                        .skip(1)
+                        .fold(members[0].bbox, |acc, t| acc.union(&t.bbox));
+                    GridCell {
+                        text: join_text(&members),
+                        bbox: Some(bbox),
+                        confidence: min_confidence(&members),
+                        token_ids: members.iter().map(|t| t.token_id).collect(),
+                    }
+                })
+                .collect()
+        })
+        .collect()
+}
+
+fn band_bottom(band: &[&ExtractionToken]) -> f64 {
+    band.iter().map(|t| t.bbox.bottom()).fold(0.0, f64::max)
+}
+
+fn join_text(tokens: &[&ExtractionToken]) -> String {
+    tokens
+        .iter()
+        .map(|t| t.text.trim())
+        .filter(|t| !t.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn min_confidence(tokens: &[&ExtractionToken]) -> f32 {
+    tokens.iter().map(|t| t.confidence).fold(1.0, f32::min)
+}
+
+fn cmp_f64(a: f64, b: f64) -> Ordering {
+    a.partial_cmp(&b).unwrap_or(Ordering::Equal)
+}
+
+/// Case- and punctuation-insensitive form used for header comparison.
+pub fn normalize_header(text: &str) -> String {
+    text.chars()
+        .map(|c| if c.is_alphanumeric() { c.to_ascii_lowercase() } else { ' ' })
+        .collect::<String>()
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Scores how well a header cell names `field`: exact key/label/synonym match is 1.0,
+/// otherwise word overlap (Jaccard) against the label and synonyms.
+pub fn header_score(header: &str, field: &ItemFieldTarget, synonyms: &[HeaderSynonym]) -> f64 {
+    let header = normalize_header(header);
+    if header.is_empty() {
+        return 0.0;
+    }
+
+    let candidates: Vec<String> = [field.label.as_str(), field.field_key.as_str()]
+        .into_iter()
+        .chain(
+            synonyms
+                .iter()
+                .filter(|s| s.schema_field_id == field.schema_field_id)
+                .map(|s| s.header_text.as_str()),
+        )
+        .map(normalize_header)
+        .collect();
+
+    let header_words: HashSet<&str> = header.split(' ').collect();
+    candidates
+        .iter()
+        .map(|candidate| {
+            if *candidate == header {
+                return 1.0;
+            }
+            let words: HashSet<&str> = candidate.split(' ').collect();
+            let shared = header_words.intersection(&words).count() as f64;
+            let total = header_words.union(&words).count() as f64;
+            if total == 0.0 {
+                0.0
+            } else {
+                shared / total
+            }
+        })
+        .fold(0.0, f64::max)
+}
+
+/// A reviewer's choice of item field for one column of a detected table.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct ColumnAssignment {
+    pub column: u32,
+    pub schema_field_id: Uuid,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ColumnMapping {
+    pub column: usize,
+    pub header_text: String,
+    pub schema_field_id: Uuid,
+    pub score: f64,
+}
+
+/// Assigns each column to at most one field and each field to at most one column,
+/// best score first.
+pub fn map_columns(
+    header: &[GridCell],
+    fields: &[ItemFieldTarget],
+    synonyms: &[HeaderSynonym],
+) -> Vec<ColumnMapping> {
+    let mut candidates = Vec::new();
+    for (column, cell) in header.iter().enumerate() {
+        for field in fields {
+            let score = header_score(&cell.text, field, synonyms);
+            if score >= HEADER_MATCH_THRESHOLD {
+                candidates.push((column, field.schema_field_id, score));
+            }
+        }
+    }
+    candidates.sort_by(|a, b| cmp_f64(b.2, a.2).then(a.0.cmp(&b.0)));
+
+    let mut used_columns = HashSet::new();
+    let mut used_fields = HashSet::new();
+    let mut mappings = Vec::new();
+    for (column, schema_field_id, score) in candidates {
+        if used_columns.contains(&column) || used_fields.contains(&schema_field_id) {
+            continue;
+        }
+        used_columns.insert(column);
+        used_fields.insert(schema_field_id);
+        mappings.push(ColumnMapping {
+            column,
+            header_text: header[column].text.clone(),
+            schema_field_id,
+            score,
+        });
+    }
+    mappings.sort_by_key(|m| m.column);
+    mappings
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LineItemMappingRequest {
+    pub project_id: Uuid,
+    pub session_id: Uuid,
+    pub document_id: Uuid,
+    pub table_id: Uuid,
+    pub item_fields: Vec<ItemFieldTarget>,
+    /// `row_index` given to the first proposed row.
+    pub first_row_index: i32,
+    /// Cells below this confidence are queued for review.
+    pub review_threshold: f32,
+    /// Reviewer corrections; each replaces the header match for its column and field.
+    #[serde(default)]
+    pub column_overrides: Vec<ColumnAssignment>,
+    pub actor: String,
+    pub now: DateTime<Utc>,
+}
+
+/// Proposed `AddItemRow`/`AssignItemValue` commands for one table. Nothing is applied
+/// until the commands are dispatched.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LineItemProposal {
+    pub table_id: Uuid,
+    pub columns: Vec<ColumnMapping>,
+    pub unmapped_columns: Vec<usize>,
+    pub commands: Vec<AnyCommand>,
+    pub review_actions: Vec<ReviewAction>,
+}
+
+pub struct LineItemMapper<'a> {
+    extraction: &'a dyn ExtractionStore,
+    synonyms: &'a dyn HeaderSynonymStore,
+}
+
+impl<'a> LineItemMapper<'a> {
+    pub fn new(extraction: &'a dyn ExtractionStore, synonyms: &'a dyn HeaderSynonymStore) -> Self {
+        Self {
+            extraction,
+            synonyms,
+        }
+    }
+
+    pub fn propose(&self, request: &LineItemMappingRequest) -> DomainResult<LineItemProposal> {
+        let table = self.extraction.get_table(request.table_id)?;
+        let tokens = self
+            .extraction
+            .tokens_for_page(table.extraction_run_id, table.page_id)?;
+        let grid = recognize_table(&table, &tokens);
+
+        let header_row = grid.header_row().ok_or_else(|| DomainError {
+            code: ErrorCode::PreconditionFailed,
+            message: "Detected table has no recognizable header row".to_string(),
+            details: Some(serde_json::json!({ "table_id": request.table_id })),
+        })?;
+
+        let synonyms = self.synonyms.synonyms(request.project_id)?;
+        let header = &grid.rows[header_row];
+        let mut columns = map_columns(header, &request.item_fields, &synonyms);
+        for assignment in &request.column_overrides {
+            let column = assignment.column as usize;
+            let cell = header.get(column).ok_or_else(|| DomainError {
+                code: ErrorCode::PreconditionFailed,
+                message: "Column is not part of the detected table".to_string(),
+                details: Some(serde_json::json!({
+                    "table_id": request.table_id,
+                    "column": assignment.column,
+                })),
+            })?;
+            if !request
+                .item_fields
+                .iter()
+                .any(|f| f.schema_field_id == assignment.schema_field_id)
+            {
+                return Err(DomainError {
+                    code: ErrorCode::PreconditionFailed,
+                    message: "Columns can only be mapped to item fields".to_string(),
+                    details: Some(serde_json::json!({
+                        "schema_field_id": assignment.schema_field_id,
+                    })),
+                });
+            }
+            columns
+                .retain(|m| m.column != column && m.schema_field_id != assignment.schema_field_id);
+            columns.push(ColumnMapping {
+                column,
+                header_text: cell.text.clone(),
+                schema_field_id: assignment.schema_field_id,
+                score: 1.0,
+            });
+        }
+        columns.sort_by_key(|m| m.column);
+        let unmapped_columns = (0..grid.column_count())
+            .filter(|c| !columns.iter().any(|m| m.column == *c))
+            .collect();
+
+        let mut commands = Vec::new();
+        let mut review_actions = Vec::new();
+        let mut row_index = request.first_row_index;
+
+        for (grid_row, row) in grid.rows.iter().enumerate().skip(header_row + 1) {
+            let mapped: Vec<(&ColumnMapping, &GridCell)> = columns
+                .iter()
+                .filter_map(|m| row.get(m.column).map(|cell| (m, cell)))
+                .filter(|(_, cell)| !cell.is_empty())
+                .collect();
+            if mapped.is_empty() {
+                continue;
+            }
+
+            let item_id = Uuid::now_v7();
+            commands.push(AnyCommand::AddItemRow(AddItemRow {
+                command_id: Uuid::now_v7(),
+                actor: request.actor.clone(),
+                timestamp: request.now,
+                expected_version: None,
+                payload: AddItemRowPayload {
+                    session_id: request.session_id,
+                    document_id: request.document_id,
+                    row_index,
+                    item_id: Some(item_id),
+                },
+            }));
+
+            for (mapping, cell) in mapped {
+                let confidence = cell.confidence * mapping.score as f32;
+                commands.push(AnyCommand::AssignItemValue(AssignItemValue {
+                    command_id: Uuid::now_v7(),
+                    actor: request.actor.clone(),
+                    timestamp: request.now,
+                    expected_version: None,
+                    payload: AssignItemValuePayload {
+                        session_id: request.session_id,
+                        item_id,
+                        schema_field_id: mapping.schema_field_id,
+                        raw_value: cell.text.clone(),
+                        normalized_value: None,
+                        provenance: Provenance {
+                            extraction_run_id: Some(grid.extraction_run_id),
+                            page_id: Some(grid.page_id),
+                            bbox: cell.bbox,
+                            token_ids: cell.token_ids.clone(),
+                            confidence: Some(confidence),
+                            ..Provenance::new(Origin::Table {
+                                table_id: grid.table_id,
+                                row: grid_row as u32,
+                                column: mapping.column as u32,
+                            })
+                        },
+                    },
+                }));
+
+                if confidence < request.review_threshold {
+                    review_actions.push(ReviewAction::new(
+                        request.session_id,
+                        ReviewCategory::LowConfidence,
+                        ReviewTarget::item(
+                            request.document_id,
+                            item_id,
+                            Some(mapping.schema_field_id),
+                        ),
+                        serde_json::json!({
+                            "table_id": grid.table_id,
+                            "row": grid_row,
+                            "column": mapping.column,
+                            "confidence": confidence,
+                        }),
+                    ));
+                }
+            }
+
+            row_index += 1;
+        }
+
+        Ok(LineItemProposal {
+            table_id: grid.table_id,
+            columns,
+            unmapped_columns,
+            commands,
+            review_actions,
+        })
+    }
+}
+
+pub struct MapTableColumnsDeps<'a> {
+    pub extraction: &'a dyn ExtractionStore,
+    pub synonyms: &'a dyn HeaderSynonymStore,
+    pub mapping: &'a dyn MappingStore,
+    pub sessions: &'a dyn SessionReader,
+    pub schemas: &'a dyn SchemaStore,
+    pub pages: &'a dyn PageReader,
+    pub provenance: ProvenanceChecker<'a>,
+    pub dictionary: DictionaryNormalizer<'a>,
+}
+
+/// Maps a detected table into item rows of its document: the line item proposal is
+/// applied through the `AddItemRow` and `AssignItemValue` handlers, so row ordering,
+/// parsing and lock checks are theirs. Rows mapped from the table before are deleted
+/// first. A column correction whose header did not already name its field exactly is
+/// learned as a header synonym of the project.
+pub struct MapTableColumnsHandler<'a> {
+    deps: MapTableColumnsDeps<'a>,
+}
+
+impl<'a> MapTableColumnsHandler<'a> {
+    pub fn new(deps: MapTableColumnsDeps<'a>) -> Self {
+        Self { deps }
+    }
+
+    fn row_deps(&self) -> ItemRowDeps<'a> {
+        ItemRowDeps {
+            mapping: self.deps.mapping,
+            sessions: self.deps.sessions,
+            schemas: self.deps.schemas,
+            pages: self.deps.pages,
+        }
+    }
+
+    fn map(&self, ctx: &mut CommandContext, cmd: &MapTableColumns) -> DomainResult<CommandOutcome> {
+        let payload = &cmd.payload;
+        let session_id = payload.session_id;
+        let session = self.deps.sessions.get_session(session_id)?;
+        let table = self.deps.extraction.get_table(payload.table_id)?;
+        let run = self.deps.extraction.get_run(table.extraction_run_id)?;
+        let on_document = self
+            .deps
+            .pages
+            .session_pages(session_id)?
+            .iter()
+            .any(|p| p.page_id == table.page_id && p.document_id == payload.document_id);
+        if run.session_id != session_id || !on_document {
+            return Err(DomainError {
+                code: ErrorCode::PreconditionFailed,
+                message: "Detected table is not on a page of the document".to_string(),
+                details: Some(serde_json::json!({
+                    "table_id": payload.table_id,
+                    "document_id": payload.document_id,
+                })),
+            });
+        }
+
+        let schema = session_schema(self.deps.sessions, self.deps.schemas, session_id)?;
+        let item_fields: Vec<ItemFieldTarget> = schema
+            .active_fields()
+            .filter(|f| f.scope == FieldScope::Item)
+            .map(|f| ItemFieldTarget {
+                schema_field_id: f.schema_field_id,
+                field_key: f.field_key.clone(),
+                label: f.label.clone(),
+            })
+            .collect();
+        let synonyms = self.deps.synonyms.synonyms(session.project_id)?;
+
+        let mut replaced: Vec<Uuid> = self
+            .deps
+            .mapping
+            .item_values(session_id)?
+            .into_iter()
+            .filter(|v| match v.provenance.origin {
+                Origin::Table { table_id, .. } => table_id == payload.table_id,
+                _ => false,
+            })
+            .map(|v| v.item_id)
+            .collect();
+        replaced.sort();
+        replaced.dedup();
+
+        let mut outcomes = Vec::new();
+        let delete = DeleteItemRowHandler::new(self.row_deps());
+        for item_id in &replaced {
+            let command = AnyCommand::DeleteItemRow(DeleteItemRow {
+                command_id: cmd.command_id,
+                actor: cmd.actor.clone(),
+                timestamp: cmd.timestamp,
+                expected_version: None,
+                payload: DeleteItemRowPayload {
+                    session_id,
+                    item_id: *item_id,
+                },
+            });
+            outcomes.push(delete.handle(ctx, &command)?);
+        }
+
+        let first_row_index = self
+            .deps
+            .mapping
+            .item_rows(session_id)?
+            .iter()
+            .filter(|r| r.document_id == payload.document_id)
+            .count()
+            + self
+                .deps
+                .mapping
+                .item_row_tombstones(session_id)?
+                .iter()
+                .filter(|t| t.document_id == payload.document_id)
+                .count();
+        let proposal = LineItemMapper::new(self.deps.extraction, self.deps.synonyms).propose(
+            &LineItemMappingRequest {
+                project_id: session.project_id,
+                session_id,
+                document_id: payload.document_id,
+                table_id: payload.table_id,
+                item_fields: item_fields.clone(),
+                first_row_index: first_row_index as i32,
+                review_threshold: LOW_CONFIDENCE_THRESHOLD,
+                column_overrides: payload.columns.clone(),
+                actor: cmd.actor.clone(),
+                now: ctx.now,
+            },
+        )?;
+
+        let add_row = AddItemRowHandler::new(self.row_deps());
+        let assign = AssignItemValueHandler::new(
+            self.deps.mapping,
+            self.deps.sessions,
+            self.deps.schemas,
+            self.deps.provenance,
+            self.deps.dictionary,
+        );
+        let mut item_ids = Vec::new();
+        for command in &proposal.commands {
+            if let AnyCommand::AddItemRow(c) = command {
+                item_ids.extend(c.payload.item_id);
+                outcomes.push(add_row.handle(ctx, command)?);
+            } else {
+                outcomes.push(assign.handle(ctx, command)?);
+            }
+        }
+
+        let mut learned = Vec::new();
+        for assignment in &payload.columns {
+            let (Some(column), Some(field)) = (
+                proposal
+                    .columns
+                    .iter()
+                    .find(|m| m.column == assignment.column as usize),
+                item_fields
+                    .iter()
+                    .find(|f| f.schema_field_id == assignment.schema_field_id),
+            ) else {
+                continue;
+            };
+            if normalize_header(&column.header_text).is_empty()
+                || header_score(&column.header_text, field, &synonyms) >= 1.0
+            {
+                continue;
+            }
+            let synonym = HeaderSynonym {
+                project_id: session.project_id,
+                schema_field_id: field.schema_field_id,
+                header_text: column.header_text.trim().to_string(),
+            };
+            self.deps.synonyms.learn(&synonym)?;
+            learned.push(synonym);
+        }
+
+        let mut transition = None;
+        let mut review_actions = Vec::new();
+        let mut events = Vec::new();
+        let mut collected: [(&str, Vec<serde_json::Value>); 3] = [
+            ("written_values", Vec::new()),
+            ("removed_values", Vec::new()),
+            ("changed_values", Vec::new()),
+        ];
+        for outcome in outcomes {
+            transition = transition.or(outcome.transition);
+            review_actions.extend(outcome.review_actions);
+            events.extend(outcome.events);
+            for (key, values) in collected.iter_mut() {
+                if let Some(serde_json::Value::Array(items)) = outcome.state_delta.data.get(*key) {
+                    values.extend(items.iter().cloned());
+                }
+            }
+        }
+        review_actions.extend(proposal.review_actions);
+
+        let mut data = serde_json::json!({
+            "table_id": payload.table_id,
+            "columns": proposal.columns,
+            "unmapped_columns": proposal.unmapped_columns,
+            "item_ids": item_ids,
+            "replaced_item_ids": replaced,
+            "learned_synonyms": learned,
+        });
+        for (key, values) in collected {
+            data[key] = serde_json::Value::Array(values);
+        }
+
+        Ok(CommandOutcome {
+            state_delta: StateDelta {
+                summary: format!("Mapped {} item row(s) from the table", item_ids.len()),
+                data,
+            },
+            transition,
+            review_actions,
+            validation_trigger: if item_ids.is_empty() && replaced.is_empty() {
+                ValidationTrigger::None
+            } else {
+                ValidationTrigger::Async
+            },
+            events,
+        })
+    }
+}
+
+impl<'a> GenericCommandHandler for MapTableColumnsHandler<'a> {
+    fn can_handle(&self, command_type: &str) -> bool {
+        command_type == "MapTableColumns"
+    }
+
+    fn handle(&self, ctx: &mut CommandContext, cmd: &AnyCommand) -> DomainResult<CommandOutcome> {
+        match cmd {
+            AnyCommand::MapTableColumns(c) => self.map(ctx, c),
+            _ => Err(unsupported_command("MapTableColumnsHandler")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{command, ctx, token, World};
+    use crate::types::SessionStatus;
+
+    fn handler(w: &World) -> MapTableColumnsHandler<'_> {
+        MapTableColumnsHandler::new(MapTableColumnsDeps {
+            extraction: &w.b.extraction,
+            synonyms: &w.b.header_synonyms,
+            mapping: &w.b.mapping,
+            sessions: &w.b.sessions,
+            schemas: &w.b.schemas,
+            pages: &w.b.pages,
+            provenance: w.provenance(),
+            dictionary: w.dictionary(),
+        })
+    }
+
+    /// A two-column table without engine cells, headed `Description | <amount_header>`.
+    fn invoice_table(w: &World, session_id: Uuid, amount_header: &str) -> (Uuid, Uuid) {
+        let (document_id, page_id) = w.document(session_id);
+        let rows = [
+            ("Description", amount_header),
+            ("Widget", "10.00"),
+            ("Gadget", "5.50"),
+        ];
+        let mut tokens = Vec::new();
+        for (i, (left, right)) in rows.iter().enumerate() {
+            let y = 0.2 + i as f64 * 0.05;
+            tokens.push(token(left, BoundingBox::new(0.1, y, 0.2, 0.03)));
+            tokens.push(token(right, BoundingBox::new(0.6, y, 0.1, 0.03)));
+        }
+        let table = DetectedTable {
+            table_id: Uuid::now_v7(),
+            extraction_run_id: Uuid::nil(),
+            page_id,
+            bbox: BoundingBox::new(0.05, 0.15, 0.9, 0.2),
+            confidence: 0.9,
+            row_count: 3,
+            column_count: 2,
+            cells: Vec::new(),
+        };
+        let table_id = table.table_id;
+        w.run(session_id, document_id, page_id, tokens, vec![table]);
+        (document_id, table_id)
+    }
+
+    fn map(
+        w: &World,
+        session_id: Uuid,
+        document_id: Uuid,
+        table_id: Uuid,
+        columns: serde_json::Value,
+    ) -> CommandOutcome {
+        handler(w)
+            .handle(
+                &mut ctx(),
+                &command(serde_json::json!({
+                    "type": "MapTableColumns",
+                    "payload": {
+                        "session_id": session_id,
+                        "document_id": document_id,
+                        "table_id": table_id,
+                        "columns": columns,
+                    }
+                })),
+            )
+            .unwrap()
+    }
+
+    #[test]
+    fn corrected_header_is_learned_and_reused() {
+        let w = World::new(&[
+            ("description", "string", "item", false),
+            ("amount", "currency", "item", false),
+        ]);
+        let (description, amount) = (w.field_id("description"), w.field_id("amount"));
+        let session_id = w.session(SessionStatus::Review);
+        let (document_id, table_id) = invoice_table(&w, session_id, "Amt");
+
+        // "Amt" names no field yet, so only descriptions are mapped.
+        let first = map(&w, session_id, document_id, table_id, serde_json::json!([]));
+        assert_eq!(
+            first.state_delta.data["item_ids"].as_array().unwrap().len(),
+            2
+        );
+        assert_eq!(
+            first.state_delta.data["unmapped_columns"],
+            serde_json::json!([1])
+        );
+        let values = w.b.mapping.item_values(session_id).unwrap();
+        assert!(values.iter().all(|v| v.schema_field_id == description));
+
+        // The reviewer maps the column: the rows are replaced and the header is learned.
+        let corrected = map(
+            &w,
+            session_id,
+            document_id,
+            table_id,
+            serde_json::json!([{ "column": 1, "schema_field_id": amount }]),
+        );
+        assert_eq!(
+            corrected.state_delta.data["replaced_item_ids"],
+            first.state_delta.data["item_ids"]
+        );
+        assert_eq!(w.b.mapping.item_rows(session_id).unwrap().len(), 2);
+        let mut amounts: Vec<String> =
+            w.b.mapping
+                .item_values(session_id)
+                .unwrap()
+                .into_iter()
+                .filter(|v| v.schema_field_id == amount)
+                .map(|v| v.raw_value)
+                .collect();
+        amounts.sort();
+        assert_eq!(amounts, ["10.00", "5.50"]);
+        let learned = w.b.header_synonyms.synonyms(w.project_id).unwrap();
+        assert_eq!(learned.len(), 1);
+        assert_eq!(
+            (learned[0].schema_field_id, learned[0].header_text.as_str()),
+            (amount, "Amt")
+        );
+
+        // The next table with the same header maps without a correction.
+        let (document_id, table_id) = invoice_table(&w, session_id, "Amt");
+        let next = map(&w, session_id, document_id, table_id, serde_json::json!([]));
+        assert_eq!(
+            next.state_delta.data["unmapped_columns"],
+            serde_json::json!([])
+        );
+        assert_eq!(
+            next.state_delta.data["learned_synonyms"],
+            serde_json::json!([])
+        );
+        assert_eq!(w.b.header_synonyms.synonyms(w.project_id).unwrap().len(), 1);
+    }
+}
@@ -0,0 +1,476 @@
+use std::collections::{BTreeSet, HashSet};
+
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::anchors::AnchorRule;
+use crate::commands::{AnyCommand, RegisterTemplate};
+use crate::dictionary::DictionaryRule;
+use crate::errors::{unsupported_command, DomainError, DomainResult, ErrorCode};
+use crate::extraction::ExtractionToken;
+use crate::interfaces::{
+    AnchorStore, CommandContext, CommandOutcome, DictionaryStore, ExtractionStore,
+    GenericCommandHandler, PageReader, SessionReader, StateDelta, TemplateStore, ValidationTrigger,
+};
+
+/// Minimum fingerprint similarity for a document to be assigned to a template.
+pub const TEMPLATE_MATCH_THRESHOLD: f64 = 0.75;
+
+/// Top band of the first page whose words make up the header fingerprint.
+const HEADER_BAND: f64 = 0.2;
+const MAX_HEADER_TOKENS: usize = 32;
+
+const HEADER_WEIGHT: f64 = 0.5;
+const LOGO_WEIGHT: f64 = 0.3;
+const GEOMETRY_WEIGHT: f64 = 0.2;
+
+/// Page geometry and logo hash recorded for a derivative page at import/preprocessing.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PageLayout {
+    pub document_id: Uuid,
+    pub page_id: Uuid,
+    pub page_number: u32,
+    pub width_pt: f64,
+    pub height_pt: f64,
+    /// 64-bit perceptual hash of the logo region, when the page has one.
+    pub logo_hash: Option<u64>,
+}
+
+/// Layout signature of a document's first page.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct LayoutFingerprint {
+    /// Normalized header words, sorted and de-duplicated. Words with digits are left out
+    /// because they change per document (invoice numbers, dates).
+    pub header_tokens: Vec<String>,
+    pub logo_hash: Option<u64>,
+    pub page_width_pt: f64,
+    pub page_height_pt: f64,
+}
+
+impl LayoutFingerprint {
+    /// Fingerprints `page` from the tokens extracted on it.
+    pub fn from_page(page: &PageLayout, tokens: &[ExtractionToken]) -> Self {
+        let mut ordered: Vec<&ExtractionToken> = tokens
+            .iter()
+            .filter(|t| t.page_id == page.page_id && t.bbox.bottom() <= HEADER_BAND)
+            .collect();
+        ordered.sort_by_key(|t| t.reading_order);
+
+        let header_tokens: BTreeSet<String> = ordered
+            .iter()
+            .filter_map(|t| header_word(&t.text))
+            .take(MAX_HEADER_TOKENS)
+            .collect();
+
+        Self {
+            header_tokens: header_tokens.into_iter().collect(),
+            logo_hash: page.logo_hash,
+            page_width_pt: page.width_pt,
+            page_height_pt: page.height_pt,
+        }
+    }
+
+    /// Weighted similarity in `0.0..=1.0`. The logo signal only counts when both sides
+    /// have a logo hash; the remaining weights are rescaled otherwise.
+    pub fn similarity(&self, other: &LayoutFingerprint) -> f64 {
+        let header = jaccard(&self.header_tokens, &other.header_tokens);
+        let geometry = ratio(self.page_width_pt, other.page_width_pt)
+            * ratio(self.page_height_pt, other.page_height_pt);
+
+        match (self.logo_hash, other.logo_hash) {
+            (Some(a), Some(b)) => {
+                let logo = 1.0 - f64::from((a ^ b).count_ones()) / 64.0;
+                HEADER_WEIGHT * header + LOGO_WEIGHT * logo + GEOMETRY_WEIGHT * geometry
+            }
+            _ => {
+                (HEADER_WEIGHT * header + GEOMETRY_WEIGHT * geometry)
+                    / (HEADER_WEIGHT + GEOMETRY_WEIGHT)
+            }
+        }
+    }
+}
+
+fn header_word(text: &str) -> Option<String> {
+    let word: String = text
+        .chars()
+        .filter(|c| c.is_alphanumeric())
+        .flat_map(char::to_lowercase)
+        .collect();
+    (word.chars().count() >= 2 && !word.chars().any(|c| c.is_numeric())).then_some(word)
+}
+
+fn jaccard(a: &[String], b: &[String]) -> f64 {
+    let a: HashSet<&String> = a.iter().collect();
+    let b: HashSet<&String> = b.iter().collect();
+    let union = a.union(&b).count();
+    if union == 0 {
+        0.0
+    } else {
+        a.intersection(&b).count() as f64 / union as f64
+    }
+}
+
+fn ratio(a: f64, b: f64) -> f64 {
+    if a <= 0.0 || b <= 0.0 {
+        0.0
+    } else {
+        a.min(b) / a.max(b)
+    }
+}
+
+/// `templates` row: a known document layout and the rule set that goes with it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Template {
+    pub template_id: Uuid,
+    pub project_id: Uuid,
+    pub name: String,
+    /// Vendor used as dictionary context for documents of this template.
+    pub vendor: Option<String>,
+    pub fingerprint: LayoutFingerprint,
+    pub anchor_ids: Vec<Uuid>,
+    pub dictionary_rule_ids: Vec<Uuid>,
+    /// Actor that added the template.
+    pub created_by: String,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+pub struct TemplateMatch {
+    pub template_id: Uuid,
+    pub score: f64,
+}
+
+/// Best template at or above [`TEMPLATE_MATCH_THRESHOLD`]; ties go to the oldest template.
+pub fn classify(fingerprint: &LayoutFingerprint, templates: &[Template]) -> Option<TemplateMatch> {
+    templates
+        .iter()
+        .map(|t| TemplateMatch {
+            template_id: t.template_id,
+            score: fingerprint.similarity(&t.fingerprint),
+        })
+        .filter(|m| m.score >= TEMPLATE_MATCH_THRESHOLD)
+        .min_by(|a, b| {
+            b.score
+                .total_cmp(&a.score)
+                .then(a.template_id.cmp(&b.template_id))
+        })
+}
+
+/// Which template (if any) a document was clustered to by an extraction run.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DocumentTemplate {
+    pub session_id: Uuid,
+    pub document_id: Uuid,
+    pub extraction_run_id: Uuid,
+    pub fingerprint: LayoutFingerprint,
+    pub matched: Option<TemplateMatch>,
+}
+
+/// Rules that apply to one document.
+#[derive(Debug, Clone, Default)]
+pub struct TemplateRuleSet {
+    pub anchors: Vec<AnchorRule>,
+    pub dictionary_rules: Vec<DictionaryRule>,
+    pub vendor: Option<String>,
+}
+
+impl TemplateRuleSet {
+    /// Rules owned by a template only apply to that template's documents; rules no
+    /// template owns apply project-wide. Disabled anchors are dropped here, disabled
+    /// dictionary rules by the engine.
+    pub fn select(
+        template: Option<&Template>,
+        templates: &[Template],
+        anchors: Vec<AnchorRule>,
+        dictionary_rules: Vec<DictionaryRule>,
+    ) -> Self {
+        let owned_anchors: HashSet<Uuid> = templates
+            .iter()
+            .flat_map(|t| t.anchor_ids.iter().copied())
+            .collect();
+        let owned_rules: HashSet<Uuid> = templates
+            .iter()
+            .flat_map(|t| t.dictionary_rule_ids.iter().copied())
+            .collect();
+
+        let anchors = anchors
+            .into_iter()
+            .filter(|a| a.enabled)
+            .filter(|a| {
+                !owned_anchors.contains(&a.anchor_id)
+                    || template.is_some_and(|t| t.anchor_ids.contains(&a.anchor_id))
+            })
+            .collect();
+        let dictionary_rules = dictionary_rules
+            .into_iter()
+            .filter(|r| {
+                !owned_rules.contains(&r.dictionary_rule_id)
+                    || template
+                        .is_some_and(|t| t.dictionary_rule_ids.contains(&r.dictionary_rule_id))
+            })
+            .collect();
+
+        Self {
+            anchors,
+            dictionary_rules,
+            vendor: template.and_then(|t| t.vendor.clone()),
+        }
+    }
+}
+
+/// Fingerprints a document from the latest extraction of its first page.
+pub fn fingerprint_document(
+    extraction: &dyn ExtractionStore,
+    session_id: Uuid,
+    pages: &[PageLayout],
+    document_id: Uuid,
+) -> DomainResult<Option<LayoutFingerprint>> {
+    let Some(first) = pages
+        .iter()
+        .filter(|p| p.document_id == document_id)
+        .min_by_key(|p| p.page_number)
+    else {
+        return Ok(None);
+    };
+    let Some(run) = extraction.latest_run_for_page(session_id, first.page_id)? else {
+        return Ok(None);
+    };
+    let tokens = extraction.tokens_for_page(run.extraction_run_id, first.page_id)?;
+    Ok(Some(LayoutFingerprint::from_page(first, &tokens)))
+}
+
+pub struct RegisterTemplateHandler<'a> {
+    templates: &'a dyn TemplateStore,
+    sessions: &'a dyn SessionReader,
+    pages: &'a dyn PageReader,
+    extraction: &'a dyn ExtractionStore,
+    anchors: &'a dyn AnchorStore,
+    dictionary: &'a dyn DictionaryStore,
+}
+
+impl<'a> RegisterTemplateHandler<'a> {
+    pub fn new(
+        templates: &'a dyn TemplateStore,
+        sessions: &'a dyn SessionReader,
+        pages: &'a dyn PageReader,
+        extraction: &'a dyn ExtractionStore,
+        anchors: &'a dyn AnchorStore,
+        dictionary: &'a dyn DictionaryStore,
+    ) -> Self {
+        Self {
+            templates,
+            sessions,
+            pages,
+            extraction,
+            anchors,
+            dictionary,
+        }
+    }
+
+    fn register(&self, cmd: &RegisterTemplate) -> DomainResult<CommandOutcome> {
+        let payload = &cmd.payload;
+        if payload.name.trim().is_empty() {
+            return Err(DomainError {
+                code: ErrorCode::PreconditionFailed,
+                message: "Template name must not be empty".to_string(),
+                details: None,
+            });
+        }
+        if self.sessions.project_of(payload.session_id)? != payload.project_id {
+            return Err(DomainError {
+                code: ErrorCode::PreconditionFailed,
+                message: "Sample session does not belong to the project".to_string(),
+                details: Some(serde_json::json!({
+                    "project_id": payload.project_id,
+                    "session_id": payload.session_id,
+                })),
+            });
+        }
+        for anchor_id in &payload.anchor_ids {
+            if self.anchors.get(*anchor_id)?.project_id != payload.project_id {
+                return Err(foreign_rule("anchor_id", *anchor_id));
+            }
+        }
+        for rule_id in &payload.dictionary_rule_ids {
+            if self.dictionary.get(*rule_id)?.project_id != payload.project_id {
+                return Err(foreign_rule("dictionary_rule_id", *rule_id));
+            }
+        }
+
+        let pages = self.pages.session_pages(payload.session_id)?;
+        let fingerprint = fingerprint_document(
+            self.extraction,
+            payload.session_id,
+            &pages,
+            payload.document_id,
+        )?
+        .ok_or_else(|| DomainError {
+            code: ErrorCode::PreconditionFailed,
+            message: "Sample document has no extracted pages to fingerprint".to_string(),
+            details: Some(serde_json::json!({ "document_id": payload.document_id })),
+        })?;
+
+        let template = Template {
+            template_id: Uuid::now_v7(),
+            project_id: payload.project_id,
+            name: payload.name.trim().to_string(),
+            vendor: payload.vendor.clone(),
+            fingerprint,
+            anchor_ids: payload.anchor_ids.clone(),
+            dictionary_rule_ids: payload.dictionary_rule_ids.clone(),
+            created_by: cmd.actor.clone(),
+        };
+        self.templates.add(&template)?;
+
+        Ok(CommandOutcome {
+            state_delta: StateDelta {
+                summary: format!("Template '{}' registered", template.name),
+                data: serde_json::json!({ "template": template }),
+            },
+            transition: None,
+            review_actions: Vec::new(),
+            validation_trigger: ValidationTrigger::None,
+            events: Vec::new(),
+        })
+    }
+}
+
+impl<'a> GenericCommandHandler for RegisterTemplateHandler<'a> {
+    fn can_handle(&self, command_type: &str) -> bool {
+        command_type == "RegisterTemplate"
+    }
+
+    fn handle(&self, _ctx: &mut CommandContext, cmd: &AnyCommand) -> DomainResult<CommandOutcome> {
+        match cmd {
+            AnyCommand::RegisterTemplate(c) => self.register(c),
+            _ => Err(unsupported_command("RegisterTemplateHandler")),
+        }
+    }
+}
+
+fn foreign_rule(key: &str, id: Uuid) -> DomainError {
+    DomainError {
+        code: ErrorCode::PreconditionFailed,
+        message: "Template rule belongs to a different project".to_string(),
+        details: Some(serde_json::json!({ key: id })),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::anchors::AddAnchorRuleHandler;
+    use crate::dictionary::AddDictionaryRuleHandler;
+    use crate::extraction::BoundingBox;
+    use crate::interfaces::MappingStore;
+    use crate::review_tasks::ReviewCategory;
+    use crate::test_support::{command, ctx, token, StaticEngine, World};
+    use crate::types::SessionStatus;
+
+    fn invoice(header: [&str; 2]) -> StaticEngine {
+        StaticEngine(vec![
+            token(header[0], BoundingBox::new(0.1, 0.05, 0.1, 0.03)),
+            token(header[1], BoundingBox::new(0.3, 0.05, 0.15, 0.03)),
+            token("Vendor:", BoundingBox::new(0.1, 0.3, 0.1, 0.03)),
+            token("ACME-CO", BoundingBox::new(0.25, 0.3, 0.15, 0.03)),
+        ])
+    }
+
+    fn created_id(outcome: &CommandOutcome, entity: &str, key: &str) -> Uuid {
+        serde_json::from_value(outcome.state_delta.data[entity][key].clone()).unwrap()
+    }
+
+    #[test]
+    fn matched_documents_get_the_template_rule_set() {
+        let w = World::new(&[("vendor", "string", "document", false)]);
+        let acme = invoice(["Acme", "Invoice"]);
+        let sample = w.session(SessionStatus::Processing);
+        let (sample_doc, _) = w.document(sample);
+        w.extract(sample, &acme);
+
+        let anchor = AddAnchorRuleHandler::new(&w.b.anchors)
+            .handle(
+                &mut ctx(),
+                &command(serde_json::json!({
+                    "type": "AddAnchorRule",
+                    "payload": {
+                        "project_id": w.project_id,
+                        "schema_field_id": w.field_id("vendor"),
+                        "rule": {
+                            "label": { "kind": "text", "text": "Vendor:" },
+                            "direction": "right",
+                            "max_offset": 0.2,
+                        }
+                    }
+                })),
+            )
+            .unwrap();
+        let rule = AddDictionaryRuleHandler::new(&w.b.dictionary)
+            .handle(
+                &mut ctx(),
+                &command(serde_json::json!({
+                    "type": "AddDictionaryRule",
+                    "payload": {
+                        "project_id": w.project_id,
+                        "scope": "vendor",
+                        "scope_key": "Acme",
+                        "match_type": "exact",
+                        "match_value": "ACME-CO",
+                        "replace_value": "Acme Corporation"
+                    }
+                })),
+            )
+            .unwrap();
+        let registered = RegisterTemplateHandler::new(
+            &w.b.templates,
+            &w.b.sessions,
+            &w.b.pages,
+            &w.b.extraction,
+            &w.b.anchors,
+            &w.b.dictionary,
+        )
+        .handle(
+            &mut ctx(),
+            &command(serde_json::json!({
+                "type": "RegisterTemplate",
+                "payload": {
+                    "project_id": w.project_id,
+                    "session_id": sample,
+                    "document_id": sample_doc,
+                    "name": "Acme invoice",
+                    "vendor": "Acme",
+                    "anchor_ids": [created_id(&anchor, "anchor", "anchor_id")],
+                    "dictionary_rule_ids": [
+                        created_id(&rule, "dictionary_rule", "dictionary_rule_id")
+                    ]
+                }
+            })),
+        )
+        .unwrap();
+        let template_id = created_id(&registered, "template", "template_id");
+
+        // The anchor and the vendor-scoped rule only apply through the template.
+        let session = w.session(SessionStatus::Processing);
+        w.document(session);
+        let outcome = w.extract(session, &acme);
+        assert!(outcome.review_actions.is_empty());
+        let values = w.b.mapping.field_values(session).unwrap();
+        assert_eq!(values.len(), 1);
+        assert_eq!(values[0].raw_value, "ACME-CO");
+        assert_eq!(
+            values[0].normalized_value,
+            Some("Acme Corporation".to_string())
+        );
+        assert_eq!(values[0].provenance.template_id, Some(template_id));
+
+        let other = w.session(SessionStatus::Processing);
+        let (other_doc, _) = w.document(other);
+        let outcome = w.extract(other, &invoice(["Globex", "Statement"]));
+        assert!(w.b.mapping.field_values(other).unwrap().is_empty());
+        assert_eq!(outcome.review_actions.len(), 1);
+        assert_eq!(
+            outcome.review_actions[0].category,
+            ReviewCategory::NewPattern
+        );
+        assert_eq!(outcome.review_actions[0].target.document_id, other_doc);
+    }
+}
@@ -0,0 +1,295 @@
+//! Fixtures shared by the module tests.
+
+use chrono::Utc;
+use uuid::Uuid;
+
+use crate::commands::AnyCommand;
+use crate::dictionary::DictionaryNormalizer;
+use crate::errors::DomainResult;
+use crate::extraction::{
+    BoundingBox, DetectedTable, EngineOutput, ExtractionArtifacts, ExtractionLine,
+    ExtractionRequest, ExtractionRun, ExtractionRunStatus, ExtractionToken, PageCoverage,
+};
+use crate::in_memory_reference_impl::InMemoryReferenceBundle;
+use crate::interfaces::{
+    CommandContext, CommandOutcome, ExtractionEngine, ExtractionStore, GenericCommandHandler,
+};
+use crate::provenance::ProvenanceChecker;
+use crate::run_extraction::{RunExtractionDeps, RunExtractionHandler};
+use crate::schema::{CreateProjectHandler, CreateSchemaHandler, SchemaRef, SchemaVersion};
+use crate::sessions::SessionRecord;
+use crate::templates::PageLayout;
+use crate::types::SessionStatus;
+
+pub fn ctx() -> CommandContext {
+    CommandContext {
+        now: Utc::now(),
+        actor: "tester".to_string(),
+    }
+}
+
+/// Parses a command from its wire form; `command_id`, `actor` and `timestamp` are
+/// filled in when missing.
+pub fn command(mut value: serde_json::Value) -> AnyCommand {
+    let fields = value.as_object_mut().expect("command object");
+    fields
+        .entry("command_id")
+        .or_insert_with(|| serde_json::json!(Uuid::now_v7()));
+    fields
+        .entry("actor")
+        .or_insert_with(|| serde_json::json!("tester"));
+    fields
+        .entry("timestamp")
+        .or_insert_with(|| serde_json::json!(Utc::now()));
+    serde_json::from_value(value).expect("valid command")
+}
+
+/// A project with one schema, backed by a fresh in-memory bundle.
+pub struct World {
+    pub b: InMemoryReferenceBundle,
+    pub project_id: Uuid,
+    pub schema: SchemaVersion,
+}
+
+impl World {
+    /// `fields` are `(field_key, field_type, scope, required)`.
+    pub fn new(fields: &[(&str, &str, &str, bool)]) -> Self {
+        Self::with_extra_tables(fields, serde_json::json!([]))
+    }
+
+    /// Like [`World::new`]; `extra_tables` is the `CreateSchema` payload's list.
+    pub fn with_extra_tables(
+        fields: &[(&str, &str, &str, bool)],
+        extra_tables: serde_json::Value,
+    ) -> Self {
+        let b = InMemoryReferenceBundle::new();
+        let created = CreateProjectHandler::new(&b.schemas)
+            .handle(
+                &mut ctx(),
+                &command(serde_json::json!({
+                    "type": "CreateProject",
+                    "payload": { "name": "Invoices" }
+                })),
+            )
+            .unwrap();
+        let project_id: Uuid =
+            serde_json::from_value(created.state_delta.data["project"]["project_id"].clone())
+                .unwrap();
+        let fields: Vec<serde_json::Value> = fields
+            .iter()
+            .map(|(key, field_type, scope, required)| {
+                serde_json::json!({
+                    "field_key": key,
+                    "label": key.replace('_', " "),
+                    "field_type": field_type,
+                    "scope": scope,
+                    "required": required,
+                })
+            })
+            .collect();
+        let created = CreateSchemaHandler::new(&b.schemas)
+            .handle(
+                &mut ctx(),
+                &command(serde_json::json!({
+                    "type": "CreateSchema",
+                    "payload": {
+                        "project_id": project_id,
+                        "name": "Invoice",
+                        "fields": fields,
+                        "extra_tables": extra_tables
+                    }
+                })),
+            )
+            .unwrap();
+        let schema =
+            serde_json::from_value(created.state_delta.data["schema_version"].clone()).unwrap();
+        Self {
+            b,
+            project_id,
+            schema,
+        }
+    }
+
+    pub fn field_id(&self, field_key: &str) -> Uuid {
+        self.schema.field_by_key(field_key).unwrap().schema_field_id
+    }
+
+    pub fn provenance(&self) -> ProvenanceChecker<'_> {
+        ProvenanceChecker {
+            pages: &self.b.pages,
+            extraction: &self.b.extraction,
+            anchors: &self.b.anchors,
+            zones: &self.b.zones,
+            templates: &self.b.templates,
+            dictionary: &self.b.dictionary,
+        }
+    }
+
+    pub fn dictionary(&self) -> DictionaryNormalizer<'_> {
+        DictionaryNormalizer::new(&self.b.dictionary, &self.b.contexts)
+    }
+
+    /// A session pinned to the schema's first version.
+    pub fn session(&self, status: SessionStatus) -> Uuid {
+        let session_id = Uuid::now_v7();
+        self.b
+            .sessions
+            .register_session(
+                SessionRecord {
+                    session_id,
+                    project_id: self.project_id,
+                    schema: SchemaRef {
+                        schema_id: self.schema.schema_id,
+                        version: self.schema.version,
+                    },
+                    source: "manual".to_string(),
+                    base_session_id: None,
+                    created_by: "tester".to_string(),
+                },
+                status,
+            )
+            .unwrap();
+        session_id
+    }
+
+    /// A one-page document; returns `(document_id, page_id)`.
+    pub fn document(&self, session_id: Uuid) -> (Uuid, Uuid) {
+        let (document_id, page_id) = (Uuid::now_v7(), Uuid::now_v7());
+        self.b
+            .pages
+            .add_page(
+                session_id,
+                PageLayout {
+                    document_id,
+                    page_id,
+                    page_number: 1,
+                    width_pt: 612.0,
+                    height_pt: 792.0,
+                    logo_hash: None,
+                },
+            )
+            .unwrap();
+        (document_id, page_id)
+    }
+
+    /// Records a completed run of one page. `tokens` and `tables` are re-stamped with
+    /// the run and page.
+    pub fn run(
+        &self,
+        session_id: Uuid,
+        document_id: Uuid,
+        page_id: Uuid,
+        tokens: Vec<ExtractionToken>,
+        tables: Vec<DetectedTable>,
+    ) -> Uuid {
+        let extraction_run_id = Uuid::now_v7();
+        let stamp = |t: ExtractionToken| ExtractionToken {
+            extraction_run_id,
+            page_id,
+            ..t
+        };
+        self.b
+            .extraction
+            .record_run(&ExtractionArtifacts {
+                run: ExtractionRun {
+                    extraction_run_id,
+                    session_id,
+                    caused_by: Uuid::nil(),
+                    engine: "test".to_string(),
+                    params: serde_json::json!({}),
+                    started_at: Utc::now(),
+                    finished_at: None,
+                    status: ExtractionRunStatus::Completed,
+                    pages: vec![PageCoverage {
+                        document_id,
+                        page_id,
+                        page_number: 1,
+                    }],
+                },
+                tokens: tokens.into_iter().map(stamp).collect(),
+                lines: Vec::new(),
+                tables: tables
+                    .into_iter()
+                    .map(|t| DetectedTable {
+                        extraction_run_id,
+                        page_id,
+                        ..t
+                    })
+                    .collect(),
+            })
+            .unwrap();
+        extraction_run_id
+    }
+
+    /// Handles `RunExtraction` for the session synchronously with `engine`.
+    pub fn extract(&self, session_id: Uuid, engine: &dyn ExtractionEngine) -> CommandOutcome {
+        let b = &self.b;
+        RunExtractionHandler::new(RunExtractionDeps {
+            engine,
+            extraction: &b.extraction,
+            mapping: &b.mapping,
+            sessions: &b.sessions,
+            pages: &b.pages,
+            templates: &b.templates,
+            anchors: &b.anchors,
+            dictionary: &b.dictionary,
+            contexts: &b.contexts,
+            zones: &b.zones,
+            schemas: &b.schemas,
+            unknown: &b.unknown_bucket,
+        })
+        .handle(
+            &mut ctx(),
+            &command(serde_json::json!({
+                "type": "RunExtraction",
+                "payload": { "session_id": session_id, "engine": "test", "params": {} }
+            })),
+        )
+        .unwrap()
+    }
+}
+
+/// Reads the same tokens off every requested page, each token on a line of its own.
+pub struct StaticEngine(pub Vec<ExtractionToken>);
+
+impl ExtractionEngine for StaticEngine {
+    fn extract(&self, request: &ExtractionRequest) -> DomainResult<EngineOutput> {
+        let mut output = EngineOutput::default();
+        for page in &request.pages {
+            for (order, t) in (0u32..).zip(&self.0) {
+                let t = ExtractionToken {
+                    token_id: Uuid::now_v7(),
+                    extraction_run_id: request.extraction_run_id,
+                    page_id: page.page_id,
+                    reading_order: order,
+                    ..t.clone()
+                };
+                output.lines.push(ExtractionLine {
+                    line_id: Uuid::now_v7(),
+                    extraction_run_id: t.extraction_run_id,
+                    page_id: t.page_id,
+                    text: t.text.clone(),
+                    bbox: t.bbox,
+                    confidence: t.confidence,
+                    reading_order: order,
+                    token_ids: vec![t.token_id],
+                });
+                output.tokens.push(t);
+            }
+        }
+        Ok(output)
+    }
+}
+
+/// A token with a fresh id; its run and page are set by [`World::run`].
+pub fn token(text: &str, bbox: BoundingBox) -> ExtractionToken {
+    ExtractionToken {
+        token_id: Uuid::now_v7(),
+        extraction_run_id: Uuid::nil(),
+        page_id: Uuid::nil(),
+        text: text.to_string(),
+        bbox,
+        confidence: 0.95,
+        reading_order: 0,
+    }
+}
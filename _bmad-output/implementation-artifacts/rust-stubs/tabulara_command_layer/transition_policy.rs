@@ -24,6 +24,17 @@ impl MatrixTransitionPolicy {
                 "DisableAnchorRule",
                 "AddDictionaryRule",
                 "DisableDictionaryRule",
+                "RegisterTemplate",
+                "AddZone",
+                "CreateProject",
+                "CreateSchema",
+                "AddSchemaField",
+                "AddExtraTable",
+                "RenameSchemaField",
+                "RetireSchemaField",
+                "ChangeSchemaFieldType",
+                "AddValidationRule",
+                "ConfigureValidationOverrides",
             ]),
         );
 
@@ -37,20 +48,36 @@ impl MatrixTransitionPolicy {
                 "ReprocessDocument",
                 "RunExtraction",
                 "ReRunExtraction",
+                "CompleteExtractionJob",
+                "CancelExtractionJob",
                 "AssignFieldValue",
                 "LockField",
                 "AddItemRow",
                 "DeleteItemRow",
+                "MoveItemRow",
                 "AssignItemValue",
                 "LockItemRow",
+                "MapTableColumns",
                 "AddExtraRow",
                 "AssignExtraValue",
+                "PromoteUnknownFragment",
                 "ResolveReviewTask",
                 "RunValidation",
                 "AddAnchorRule",
                 "DisableAnchorRule",
                 "AddDictionaryRule",
                 "DisableDictionaryRule",
+                "RegisterTemplate",
+                "AddZone",
+                "CreateProject",
+                "CreateSchema",
+                "AddSchemaField",
+                "AddExtraTable",
+                "RenameSchemaField",
+                "RetireSchemaField",
+                "ChangeSchemaFieldType",
+                "AddValidationRule",
+                "ConfigureValidationOverrides",
             ]),
         );
 
@@ -64,23 +91,40 @@ impl MatrixTransitionPolicy {
                 "ReprocessDocument",
                 "RunExtraction",
                 "ReRunExtraction",
+                "CompleteExtractionJob",
+                "CancelExtractionJob",
                 "AssignFieldValue",
                 "LockField",
                 "AddItemRow",
                 "DeleteItemRow",
+                "MoveItemRow",
                 "AssignItemValue",
                 "LockItemRow",
+                "MapTableColumns",
                 "AddExtraRow",
                 "AssignExtraValue",
+                "PromoteUnknownFragment",
                 "ResolveReviewTask",
                 "SkipReviewTask",
                 "BatchResolveField",
                 "RunValidation",
                 "OverrideValidation",
+                "ApproveValidationOverride",
                 "AddAnchorRule",
                 "DisableAnchorRule",
                 "AddDictionaryRule",
                 "DisableDictionaryRule",
+                "RegisterTemplate",
+                "AddZone",
+                "CreateProject",
+                "CreateSchema",
+                "AddSchemaField",
+                "AddExtraTable",
+                "RenameSchemaField",
+                "RetireSchemaField",
+                "ChangeSchemaFieldType",
+                "AddValidationRule",
+                "ConfigureValidationOverrides",
             ]),
         );
 
@@ -89,20 +133,36 @@ impl MatrixTransitionPolicy {
             set(&[
                 "PinSession",
                 "ConfirmDuplicate",
+                "CompleteExtractionJob",
                 "AssignFieldValue",
                 "AddItemRow",
                 "DeleteItemRow",
+                "MoveItemRow",
                 "AssignItemValue",
+                "MapTableColumns",
                 "AddExtraRow",
                 "AssignExtraValue",
+                "PromoteUnknownFragment",
                 "ResolveReviewTask",
                 "RunValidation",
                 "OverrideValidation",
+                "ApproveValidationOverride",
                 "ExportSession",
                 "AddAnchorRule",
                 "DisableAnchorRule",
                 "AddDictionaryRule",
                 "DisableDictionaryRule",
+                "RegisterTemplate",
+                "AddZone",
+                "CreateProject",
+                "CreateSchema",
+                "AddSchemaField",
+                "AddExtraTable",
+                "RenameSchemaField",
+                "RetireSchemaField",
+                "ChangeSchemaFieldType",
+                "AddValidationRule",
+                "ConfigureValidationOverrides",
             ]),
         );
 
@@ -116,6 +176,17 @@ impl MatrixTransitionPolicy {
                 "DisableAnchorRule",
                 "AddDictionaryRule",
                 "DisableDictionaryRule",
+                "RegisterTemplate",
+                "AddZone",
+                "CreateProject",
+                "CreateSchema",
+                "AddSchemaField",
+                "AddExtraTable",
+                "RenameSchemaField",
+                "RetireSchemaField",
+                "ChangeSchemaFieldType",
+                "AddValidationRule",
+                "ConfigureValidationOverrides",
             ]),
         );
 
@@ -128,6 +199,17 @@ impl MatrixTransitionPolicy {
                 "DisableAnchorRule",
                 "AddDictionaryRule",
                 "DisableDictionaryRule",
+                "RegisterTemplate",
+                "AddZone",
+                "CreateProject",
+                "CreateSchema",
+                "AddSchemaField",
+                "AddExtraTable",
+                "RenameSchemaField",
+                "RetireSchemaField",
+                "ChangeSchemaFieldType",
+                "AddValidationRule",
+                "ConfigureValidationOverrides",
             ]),
         );
 
@@ -213,5 +295,16 @@ fn is_mutating_session_command(command_type: &str) -> bool {
             | "DisableAnchorRule"
             | "AddDictionaryRule"
             | "DisableDictionaryRule"
+            | "RegisterTemplate"
+            | "AddZone"
+            | "CreateProject"
+            | "CreateSchema"
+            | "AddSchemaField"
+            | "AddExtraTable"
+            | "RenameSchemaField"
+            | "RetireSchemaField"
+            | "ChangeSchemaFieldType"
+            | "AddValidationRule"
+            | "ConfigureValidationOverrides"
     )
 }
@@ -2,7 +2,7 @@ use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
-#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash)]
 #[serde(rename_all = "snake_case")]
 pub enum SessionStatus {
     Created,
@@ -13,22 +13,44 @@ pub enum SessionStatus {
     Locked,
 }
 
-#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
 #[serde(rename_all = "snake_case")]
 pub enum SourceType {
     Manual,
     Anchor,
     Zone,
+    Table,
 }
 
-#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum FieldType {
+    String,
+    Integer,
+    Decimal,
+    Currency,
+    Date,
+    Enum,
+    Boolean,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum FieldScope {
+    Document,
+    Item,
+    /// A column of an extra table declared by the schema.
+    ExtraTable,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
 #[serde(rename_all = "snake_case")]
 pub enum MatchType {
     Exact,
     Regex,
 }
 
-#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash)]
 #[serde(rename_all = "snake_case")]
 pub enum DictionaryScope {
     Global,
@@ -45,6 +67,14 @@ pub enum ExportFormat {
     Json,
 }
 
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum Severity {
+    /// Keeps the session from moving to `validated`.
+    Blocking,
+    Warning,
+}
+
 #[derive(Debug, Clone, Copy, Serialize, Deserialize)]
 #[serde(rename_all = "snake_case")]
 pub enum ValidationRuleScope {
@@ -73,5 +103,8 @@ pub struct DispatchResult {
     pub command_id: Uuid,
     pub event_ids: Vec<Uuid>,
     pub session_status: Option<SessionStatus>,
+    /// Session version after the command, to send as the next `expected_version`.
+    #[serde(default)]
+    pub session_version: Option<u64>,
     pub idempotent_replay: bool,
 }
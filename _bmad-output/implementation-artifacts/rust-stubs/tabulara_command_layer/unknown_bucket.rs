@@ -0,0 +1,545 @@
+use std::collections::{HashMap, HashSet};
+
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::commands::{AnyCommand, PromoteUnknownFragment};
+use crate::errors::{unsupported_command, value_locked, DomainError, DomainResult, ErrorCode};
+use crate::extra_tables::{extra_row, writable_column};
+use crate::extraction::{BoundingBox, ExtractionArtifacts};
+use crate::interfaces::{
+    CommandContext, CommandOutcome, GenericCommandHandler, MappingStore, PageReader, SchemaStore,
+    SessionReader, StateDelta, UnknownBucketStore, ValidationTrigger,
+};
+use crate::mapping::{ExtraValue, FieldValue, ValueChange, WrittenValue};
+use crate::provenance::{Origin, Provenance};
+use crate::schema::session_schema;
+use crate::types::{FieldScope, SessionStatus, SessionStatusTransition};
+use crate::value_parsing::parse_value;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UnknownToken {
+    pub token_id: Uuid,
+    pub text: String,
+    pub bbox: BoundingBox,
+    pub confidence: f32,
+}
+
+/// `unknown_fragments` row: one extracted line, or a token outside every line.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UnknownFragment {
+    /// The line id, or the token id for a token outside every line.
+    pub fragment_id: Uuid,
+    pub session_id: Uuid,
+    pub document_id: Uuid,
+    pub page_id: Uuid,
+    pub extraction_run_id: Uuid,
+    pub reading_order: u32,
+    pub tokens: Vec<UnknownToken>,
+}
+
+impl UnknownFragment {
+    pub fn text(&self) -> String {
+        self.tokens
+            .iter()
+            .map(|t| t.text.as_str())
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    pub fn bbox(&self) -> Option<BoundingBox> {
+        let (first, rest) = self.tokens.split_first()?;
+        Some(rest.iter().fold(first.bbox, |acc, t| acc.union(&t.bbox)))
+    }
+
+    pub fn confidence(&self) -> f32 {
+        self.tokens.iter().map(|t| t.confidence).fold(1.0, f32::min)
+    }
+
+    pub fn token_ids(&self) -> Vec<Uuid> {
+        self.tokens.iter().map(|t| t.token_id).collect()
+    }
+
+    /// Provenance of a value promoted from this fragment by `actor`.
+    pub fn provenance(&self, actor: &str) -> Provenance {
+        Provenance {
+            extraction_run_id: Some(self.extraction_run_id),
+            page_id: Some(self.page_id),
+            bbox: self.bbox(),
+            token_ids: self.token_ids(),
+            confidence: Some(self.confidence()),
+            ..Provenance::new(Origin::Manual {
+                actor: actor.to_string(),
+                unknown_fragment_id: Some(self.fragment_id),
+                batch_command_id: None,
+            })
+        }
+    }
+
+    fn retain_tokens(&mut self, keep: impl Fn(&UnknownToken) -> bool) {
+        self.tokens.retain(keep);
+    }
+}
+
+/// Fragments for every page an extraction run covered, in reading order.
+pub fn fragments_for_run(
+    session_id: Uuid,
+    artifacts: &ExtractionArtifacts,
+) -> Vec<UnknownFragment> {
+    let run_id = artifacts.run.extraction_run_id;
+    let tokens: HashMap<Uuid, UnknownToken> = artifacts
+        .tokens
+        .iter()
+        .map(|t| {
+            (
+                t.token_id,
+                UnknownToken {
+                    token_id: t.token_id,
+                    text: t.text.clone(),
+                    bbox: t.bbox,
+                    confidence: t.confidence,
+                },
+            )
+        })
+        .collect();
+
+    let mut fragments = Vec::new();
+    for page in &artifacts.run.pages {
+        let mut lines: Vec<_> = artifacts
+            .lines
+            .iter()
+            .filter(|l| l.page_id == page.page_id)
+            .collect();
+        lines.sort_by_key(|l| l.reading_order);
+        let in_lines: HashSet<Uuid> = lines.iter().flat_map(|l| l.token_ids.clone()).collect();
+        let last_order = lines.last().map_or(0, |l| l.reading_order);
+
+        let fragment =
+            |fragment_id: Uuid, reading_order: u32, tokens: Vec<UnknownToken>| UnknownFragment {
+                fragment_id,
+                session_id,
+                document_id: page.document_id,
+                page_id: page.page_id,
+                extraction_run_id: run_id,
+                reading_order,
+                tokens,
+            };
+        for line in &lines {
+            let line_tokens = line
+                .token_ids
+                .iter()
+                .filter_map(|id| tokens.get(id).cloned())
+                .collect();
+            fragments.push(fragment(line.line_id, line.reading_order, line_tokens));
+        }
+        let mut loose: Vec<_> = artifacts
+            .tokens
+            .iter()
+            .filter(|t| t.page_id == page.page_id && !in_lines.contains(&t.token_id))
+            .collect();
+        loose.sort_by(|a, b| {
+            a.bbox
+                .y
+                .total_cmp(&b.bbox.y)
+                .then(a.bbox.x.total_cmp(&b.bbox.x))
+        });
+        for (offset, token) in loose.into_iter().enumerate() {
+            fragments.push(fragment(
+                token.token_id,
+                last_order + 1 + offset as u32,
+                vec![tokens[&token.token_id].clone()],
+            ));
+        }
+    }
+    fragments
+}
+
+/// Replaces the bucket of every page the run covered. The bucket keeps all text of
+/// the page's latest run; reads leave out what a value consumes, so text returns to
+/// the bucket when the value that used it is replaced.
+pub fn retain_unknown_text(
+    store: &dyn UnknownBucketStore,
+    session_id: Uuid,
+    artifacts: &ExtractionArtifacts,
+) -> DomainResult<()> {
+    let fragments = fragments_for_run(session_id, artifacts);
+    for page in &artifacts.run.pages {
+        let on_page: Vec<UnknownFragment> = fragments
+            .iter()
+            .filter(|f| f.page_id == page.page_id)
+            .cloned()
+            .collect();
+        store.replace_page(session_id, page.page_id, &on_page)?;
+    }
+    Ok(())
+}
+
+/// Tokens referenced by a field, item or extra value. A value also consumes every
+/// token whose center lies in its provenance `bbox`, which covers values read
+/// from an earlier extraction run of the page.
+struct Consumed {
+    token_ids: HashSet<Uuid>,
+    regions: Vec<(Uuid, BoundingBox)>,
+}
+
+impl Consumed {
+    fn load(mapping: &dyn MappingStore, session_id: Uuid) -> DomainResult<Self> {
+        let mut consumed = Self {
+            token_ids: HashSet::new(),
+            regions: Vec::new(),
+        };
+        let field_refs = mapping
+            .field_values(session_id)?
+            .into_iter()
+            .map(|v| v.provenance);
+        let item_refs = mapping
+            .item_values(session_id)?
+            .into_iter()
+            .map(|v| v.provenance);
+        let extra_refs = mapping
+            .extra_values(session_id)?
+            .into_iter()
+            .map(|v| v.provenance);
+        for provenance in field_refs.chain(item_refs).chain(extra_refs) {
+            consumed.token_ids.extend(provenance.token_ids);
+            if let (Some(page_id), Some(bbox)) = (provenance.page_id, provenance.bbox) {
+                consumed.regions.push((page_id, bbox));
+            }
+        }
+        Ok(consumed)
+    }
+
+    fn contains(&self, page_id: Uuid, token: &UnknownToken) -> bool {
+        let (x, y) = token.bbox.center();
+        self.token_ids.contains(&token.token_id)
+            || self
+                .regions
+                .iter()
+                .any(|(page, bbox)| *page == page_id && bbox.contains_point(x, y))
+    }
+}
+
+/// Narrows a bucket read; every field is optional.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct UnknownBucketQuery {
+    #[serde(default)]
+    pub document_id: Option<Uuid>,
+    #[serde(default)]
+    pub page_id: Option<Uuid>,
+    /// Keeps tokens whose center lies in the region.
+    #[serde(default)]
+    pub region: Option<BoundingBox>,
+}
+
+/// Extracted text no value has consumed, per document.
+pub struct UnknownBucket<'a> {
+    store: &'a dyn UnknownBucketStore,
+    mapping: &'a dyn MappingStore,
+    pages: &'a dyn PageReader,
+}
+
+impl<'a> UnknownBucket<'a> {
+    pub fn new(
+        store: &'a dyn UnknownBucketStore,
+        mapping: &'a dyn MappingStore,
+        pages: &'a dyn PageReader,
+    ) -> Self {
+        Self {
+            store,
+            mapping,
+            pages,
+        }
+    }
+
+    /// Fragments with their unconsumed tokens, ordered by document, page and reading
+    /// order. Fragments left without tokens are dropped.
+    pub fn unassigned(
+        &self,
+        session_id: Uuid,
+        query: &UnknownBucketQuery,
+    ) -> DomainResult<Vec<UnknownFragment>> {
+        let consumed = Consumed::load(self.mapping, session_id)?;
+        let mut order: HashMap<Uuid, (usize, u32)> = HashMap::new();
+        let mut documents: Vec<Uuid> = Vec::new();
+        for page in self.pages.session_pages(session_id)? {
+            if !documents.contains(&page.document_id) {
+                documents.push(page.document_id);
+            }
+            let rank = documents.len() - 1;
+            order.insert(page.page_id, (rank, page.page_number));
+        }
+
+        let mut fragments: Vec<UnknownFragment> = self
+            .store
+            .fragments(session_id)?
+            .into_iter()
+            .filter(|f| query.document_id.is_none_or(|d| f.document_id == d))
+            .filter(|f| query.page_id.is_none_or(|p| f.page_id == p))
+            .filter_map(|mut f| {
+                let page_id = f.page_id;
+                f.retain_tokens(|t| {
+                    let (x, y) = t.bbox.center();
+                    !consumed.contains(page_id, t)
+                        && query.region.is_none_or(|r| r.contains_point(x, y))
+                });
+                (!f.tokens.is_empty()).then_some(f)
+            })
+            .collect();
+        fragments.sort_by_key(|f| {
+            let (document, page_number) = order
+                .get(&f.page_id)
+                .copied()
+                .unwrap_or((usize::MAX, u32::MAX));
+            (document, page_number, f.reading_order, f.fragment_id)
+        });
+        Ok(fragments)
+    }
+}
+
+/// Rows of the optional `unknown_text` export table.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UnknownTextRow {
+    pub document_id: Uuid,
+    pub page_id: Uuid,
+    pub fragment_id: Uuid,
+    pub text: String,
+    pub bbox: Option<BoundingBox>,
+    pub confidence: f32,
+    pub extraction_run_id: Uuid,
+}
+
+impl From<&UnknownFragment> for UnknownTextRow {
+    fn from(fragment: &UnknownFragment) -> Self {
+        Self {
+            document_id: fragment.document_id,
+            page_id: fragment.page_id,
+            fragment_id: fragment.fragment_id,
+            text: fragment.text(),
+            bbox: fragment.bbox(),
+            confidence: fragment.confidence(),
+            extraction_run_id: fragment.extraction_run_id,
+        }
+    }
+}
+
+/// Where a promoted fragment is written.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum PromotionTarget {
+    /// A document field of the fragment's document.
+    Field { schema_field_id: Uuid },
+    /// A cell of an extra-table row of the fragment's document.
+    Extra {
+        extra_row_id: Uuid,
+        schema_field_id: Uuid,
+    },
+}
+
+pub struct UnknownBucketDeps<'a> {
+    pub bucket: &'a dyn UnknownBucketStore,
+    pub mapping: &'a dyn MappingStore,
+    pub pages: &'a dyn PageReader,
+    pub sessions: &'a dyn SessionReader,
+    pub schemas: &'a dyn SchemaStore,
+}
+
+/// Writes an unknown fragment, or some of its tokens, into a field or extra cell as
+/// a manual value whose provenance keeps the fragment's tokens and box. Like manual
+/// entry, text that does not parse as the field type is rejected.
+pub struct PromoteUnknownFragmentHandler<'a> {
+    deps: UnknownBucketDeps<'a>,
+}
+
+impl<'a> PromoteUnknownFragmentHandler<'a> {
+    pub fn new(deps: UnknownBucketDeps<'a>) -> Self {
+        Self { deps }
+    }
+
+    fn promote(&self, cmd: &PromoteUnknownFragment) -> DomainResult<CommandOutcome> {
+        let payload = &cmd.payload;
+        let session_id = payload.session_id;
+        let session = self.deps.sessions.get_session(session_id)?;
+        let status = self.deps.sessions.get_status(session_id)?;
+        let bucket = UnknownBucket::new(self.deps.bucket, self.deps.mapping, self.deps.pages);
+        let not_found = || DomainError {
+            code: ErrorCode::NotFound,
+            message: "Unknown fragment is not in the session's bucket".to_string(),
+            details: Some(serde_json::json!({ "fragment_id": payload.fragment_id })),
+        };
+        let stored = self
+            .deps
+            .bucket
+            .fragments(session_id)?
+            .into_iter()
+            .find(|f| f.fragment_id == payload.fragment_id)
+            .ok_or_else(not_found)?;
+        let mut fragment = bucket
+            .unassigned(
+                session_id,
+                &UnknownBucketQuery {
+                    page_id: Some(stored.page_id),
+                    ..UnknownBucketQuery::default()
+                },
+            )?
+            .into_iter()
+            .find(|f| f.fragment_id == payload.fragment_id)
+            .ok_or_else(|| DomainError {
+                code: ErrorCode::PreconditionFailed,
+                message: "Unknown fragment is already assigned".to_string(),
+                details: Some(serde_json::json!({ "fragment_id": payload.fragment_id })),
+            })?;
+        if !payload.token_ids.is_empty() {
+            let available = fragment.token_ids();
+            if let Some(missing) = payload.token_ids.iter().find(|id| !available.contains(id)) {
+                return Err(DomainError {
+                    code: ErrorCode::PreconditionFailed,
+                    message: "Token is not unassigned text of the fragment".to_string(),
+                    details: Some(serde_json::json!({
+                        "fragment_id": payload.fragment_id,
+                        "token_id": missing,
+                    })),
+                });
+            }
+            fragment.retain_tokens(|t| payload.token_ids.contains(&t.token_id));
+        }
+
+        let schema = session_schema(self.deps.sessions, self.deps.schemas, session_id)?;
+        let locale = self.deps.schemas.get_project(session.project_id)?.locale;
+        let schema_field_id = match &payload.target {
+            PromotionTarget::Field { schema_field_id } => *schema_field_id,
+            PromotionTarget::Extra {
+                schema_field_id, ..
+            } => *schema_field_id,
+        };
+        let field = schema.writable_field(schema_field_id)?;
+        let raw_value = fragment.text();
+        let normalized_value =
+            parse_value(field, &raw_value, &locale).map_err(|failure| DomainError {
+                code: ErrorCode::PreconditionFailed,
+                message: format!("Value does not parse as {:?}", failure.field_type),
+                details: Some(serde_json::json!({
+                    "schema_field_id": field.schema_field_id,
+                    "field_key": field.field_key,
+                    "raw_value": raw_value,
+                    "reason": failure.reason,
+                })),
+            })?;
+        let provenance = fragment.provenance(&cmd.actor);
+        let locked = || {
+            value_locked(
+                "Target value is locked",
+                serde_json::json!({ "schema_field_id": field.schema_field_id }),
+            )
+        };
+
+        let written = match &payload.target {
+            PromotionTarget::Field { .. } => {
+                if field.scope != FieldScope::Document {
+                    return Err(DomainError {
+                        code: ErrorCode::PreconditionFailed,
+                        message: "Only document fields take promoted text".to_string(),
+                        details: Some(serde_json::json!({ "field_key": field.field_key })),
+                    });
+                }
+                let current = self
+                    .deps
+                    .mapping
+                    .field_values(session_id)?
+                    .into_iter()
+                    .find(|v| {
+                        v.document_id == fragment.document_id
+                            && v.schema_field_id == field.schema_field_id
+                    });
+                if current.as_ref().is_some_and(|v| v.locked) {
+                    return Err(locked());
+                }
+                let value = FieldValue {
+                    field_value_id: current.map_or_else(Uuid::now_v7, |v| v.field_value_id),
+                    session_id,
+                    document_id: fragment.document_id,
+                    schema_field_id: field.schema_field_id,
+                    raw_value,
+                    normalized_value,
+                    provenance,
+                    locked: false,
+                };
+                self.deps.mapping.put_field_value(&value)?;
+                WrittenValue::Field(value)
+            }
+            PromotionTarget::Extra { extra_row_id, .. } => {
+                let row = extra_row(self.deps.mapping, session_id, *extra_row_id)?;
+                if row.document_id != fragment.document_id {
+                    return Err(DomainError {
+                        code: ErrorCode::PreconditionFailed,
+                        message: "Extra row belongs to another document".to_string(),
+                        details: Some(serde_json::json!({
+                            "extra_row_id": extra_row_id,
+                            "document_id": fragment.document_id,
+                        })),
+                    });
+                }
+                writable_column(&schema, &row, field.schema_field_id)?;
+                let current = self
+                    .deps
+                    .mapping
+                    .extra_values(session_id)?
+                    .into_iter()
+                    .find(|v| {
+                        v.extra_row_id == *extra_row_id
+                            && v.schema_field_id == field.schema_field_id
+                    });
+                if current.as_ref().is_some_and(|v| v.locked) {
+                    return Err(locked());
+                }
+                let value = ExtraValue {
+                    extra_value_id: current.map_or_else(Uuid::now_v7, |v| v.extra_value_id),
+                    session_id,
+                    extra_row_id: *extra_row_id,
+                    schema_field_id: field.schema_field_id,
+                    raw_value,
+                    normalized_value,
+                    provenance,
+                    locked: false,
+                };
+                self.deps.mapping.put_extra_value(&value)?;
+                WrittenValue::Extra(value)
+            }
+        };
+        let transition = (status == SessionStatus::Validated).then_some(SessionStatusTransition {
+            from: status,
+            to: SessionStatus::Review,
+        });
+
+        Ok(CommandOutcome {
+            state_delta: StateDelta {
+                summary: format!("Promoted unknown text to {}", field.field_key),
+                data: serde_json::json!({
+                    "fragment_id": fragment.fragment_id,
+                    "token_ids": fragment.token_ids(),
+                    "changed_values": [ValueChange {
+                        document_id: fragment.document_id,
+                        schema_field_id: field.schema_field_id,
+                    }],
+                    "written_values": [written],
+                }),
+            },
+            transition,
+            review_actions: Vec::new(),
+            validation_trigger: ValidationTrigger::Async,
+            events: Vec::new(),
+        })
+    }
+}
+
+impl<'a> GenericCommandHandler for PromoteUnknownFragmentHandler<'a> {
+    fn can_handle(&self, command_type: &str) -> bool {
+        command_type == "PromoteUnknownFragment"
+    }
+
+    fn handle(&self, _ctx: &mut CommandContext, cmd: &AnyCommand) -> DomainResult<CommandOutcome> {
+        match cmd {
+            AnyCommand::PromoteUnknownFragment(c) => self.promote(c),
+            _ => Err(unsupported_command("PromoteUnknownFragmentHandler")),
+        }
+    }
+}
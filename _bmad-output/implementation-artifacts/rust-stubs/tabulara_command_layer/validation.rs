@@ -0,0 +1,1965 @@
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use chrono::{DateTime, NaiveDate, Utc};
+use regex::{Regex, RegexBuilder};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::commands::{AddValidationRule, AnyCommand, RunValidation};
+use crate::errors::{unsupported_command, DomainError, DomainResult, ErrorCode};
+use crate::interfaces::{
+    CommandContext, CommandOutcome, EventReader, GenericCommandHandler, MappingStore,
+    OverrideStore, PageReader, ReviewAction, ReviewTaskStore, SchemaStore, SessionReader,
+    StateDelta, ValidationStore, ValidationTrigger,
+};
+use crate::mapping::ValueChange;
+use crate::overrides::ReconciledOverrides;
+use crate::review_tasks::{close_stale_review_tasks, ReviewCategory, ReviewTarget};
+use crate::schema::{session_schema, SchemaField, SchemaRef, SchemaVersion};
+use crate::types::{
+    EventEnvelope, FieldScope, FieldType, SessionStatus, SessionStatusTransition, Severity,
+    ValidationRuleScope,
+};
+use crate::validation_dsl::{DslErrorKind, Expression};
+
+/// Compiled size limit for rule patterns, bounding their memory and matching cost.
+pub(crate) const PATTERN_SIZE_LIMIT: usize = 1 << 20;
+
+/// Allowed difference between a computed sum and the stated total.
+pub const DEFAULT_SUM_TOLERANCE: f64 = 0.01;
+
+/// Review task categories raised only by validation runs.
+const VALIDATION_CATEGORIES: [ReviewCategory; 3] = [
+    ReviewCategory::RequiredMissing,
+    ReviewCategory::Duplicate,
+    ReviewCategory::ValidationError,
+];
+
+fn default_tolerance() -> f64 {
+    DEFAULT_SUM_TOLERANCE
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum RuleLayer {
+    /// One value at a time.
+    Field,
+    /// Several values of one document.
+    CrossField,
+    /// Values across the documents of a project.
+    Dataset,
+}
+
+/// What a rule checks. Fields are addressed by key and resolved against the
+/// session's schema version; a rule naming a key the version lacks does not apply.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "check", rename_all = "snake_case")]
+pub enum ValidationCheck {
+    Required {
+        field_key: String,
+    },
+    /// Non-blank values parse as the field type, which covers enum membership.
+    Typed {
+        field_key: String,
+    },
+    Regex {
+        field_key: String,
+        pattern: String,
+    },
+    /// Inclusive bounds for numeric, currency and date fields.
+    Range {
+        field_key: String,
+        #[serde(default)]
+        min: Option<String>,
+        #[serde(default)]
+        max: Option<String>,
+    },
+    Enum {
+        field_key: String,
+        values: Vec<String>,
+    },
+    /// An item field summed over a document's rows equals a document field.
+    ItemsSum {
+        item_field_key: String,
+        field_key: String,
+        #[serde(default = "default_tolerance")]
+        tolerance: f64,
+    },
+    /// Document fields add up to a total; missing addends count as zero, but at
+    /// least one must be present.
+    Sum {
+        addend_keys: Vec<String>,
+        field_key: String,
+        #[serde(default = "default_tolerance")]
+        tolerance: f64,
+    },
+    /// `field_key` is a later date than `after_field_key`.
+    DateAfter {
+        field_key: String,
+        after_field_key: String,
+        #[serde(default)]
+        allow_equal: bool,
+    },
+    /// No two documents in the project share a value, optionally per group (e.g. vendor).
+    Unique {
+        field_key: String,
+        #[serde(default)]
+        group_key: Option<String>,
+    },
+    /// A condition written in the rule language of `validation_dsl`, e.g.
+    /// `sum(items.amount) == fields.subtotal ± 0.01`.
+    Expression {
+        expression: String,
+    },
+}
+
+impl ValidationCheck {
+    pub fn layer(&self) -> RuleLayer {
+        match self {
+            ValidationCheck::Required { .. }
+            | ValidationCheck::Typed { .. }
+            | ValidationCheck::Regex { .. }
+            | ValidationCheck::Range { .. }
+            | ValidationCheck::Enum { .. } => RuleLayer::Field,
+            ValidationCheck::ItemsSum { .. }
+            | ValidationCheck::Sum { .. }
+            | ValidationCheck::DateAfter { .. } => RuleLayer::CrossField,
+            ValidationCheck::Unique { .. } => RuleLayer::Dataset,
+            ValidationCheck::Expression { expression } => {
+                Expression::parse(expression).map_or(RuleLayer::CrossField, |e| e.layer())
+            }
+        }
+    }
+
+    /// Field a finding is reported against: the checked field, or the total of a sum.
+    /// Expressions have none; their findings target the document or row.
+    pub fn target_field_key(&self) -> Option<&str> {
+        match self {
+            ValidationCheck::Required { field_key }
+            | ValidationCheck::Typed { field_key }
+            | ValidationCheck::Regex { field_key, .. }
+            | ValidationCheck::Range { field_key, .. }
+            | ValidationCheck::Enum { field_key, .. }
+            | ValidationCheck::ItemsSum { field_key, .. }
+            | ValidationCheck::Sum { field_key, .. }
+            | ValidationCheck::DateAfter { field_key, .. }
+            | ValidationCheck::Unique { field_key, .. } => Some(field_key),
+            ValidationCheck::Expression { .. } => None,
+        }
+    }
+
+    pub fn review_category(&self) -> ReviewCategory {
+        match self {
+            ValidationCheck::Required { .. } => ReviewCategory::RequiredMissing,
+            ValidationCheck::Unique { .. } => ReviewCategory::Duplicate,
+            _ => ReviewCategory::ValidationError,
+        }
+    }
+
+    /// Keys the rule reads, in the order they appear.
+    pub fn field_keys(&self) -> Vec<String> {
+        let keys: Vec<&str> = match self {
+            ValidationCheck::Required { field_key }
+            | ValidationCheck::Typed { field_key }
+            | ValidationCheck::Regex { field_key, .. }
+            | ValidationCheck::Range { field_key, .. }
+            | ValidationCheck::Enum { field_key, .. } => vec![field_key.as_str()],
+            ValidationCheck::ItemsSum {
+                item_field_key,
+                field_key,
+                ..
+            } => vec![item_field_key.as_str(), field_key.as_str()],
+            ValidationCheck::Sum {
+                addend_keys,
+                field_key,
+                ..
+            } => addend_keys
+                .iter()
+                .map(String::as_str)
+                .chain([field_key.as_str()])
+                .collect(),
+            ValidationCheck::DateAfter {
+                field_key,
+                after_field_key,
+                ..
+            } => vec![after_field_key.as_str(), field_key.as_str()],
+            ValidationCheck::Unique {
+                field_key,
+                group_key,
+            } => [Some(field_key.as_str()), group_key.as_deref()]
+                .into_iter()
+                .flatten()
+                .collect(),
+            ValidationCheck::Expression { expression } => {
+                return Expression::parse(expression)
+                    .map(|e| e.field_keys())
+                    .unwrap_or_default();
+            }
+        };
+        keys.into_iter().map(str::to_string).collect()
+    }
+
+    /// Structural checks done when a rule is added; schema fit is checked per session.
+    pub fn validate(&self) -> DomainResult<()> {
+        let invalid = |message: &str| DomainError {
+            code: ErrorCode::PreconditionFailed,
+            message: message.to_string(),
+            details: Some(serde_json::json!({ "check": self })),
+        };
+        if self.field_keys().iter().any(|k| k.trim().is_empty()) {
+            return Err(invalid("Validation rule field keys must not be empty"));
+        }
+        match self {
+            ValidationCheck::Regex { pattern, .. } => {
+                compile_pattern(pattern)?;
+            }
+            ValidationCheck::Expression { expression } => {
+                Expression::parse(expression).map_err(|e| e.to_domain_error(expression))?;
+            }
+            ValidationCheck::Range { min, max, .. } => {
+                if min.is_none() && max.is_none() {
+                    return Err(invalid("Range rule needs a minimum or a maximum"));
+                }
+                let bounds: Vec<&str> = [min, max]
+                    .into_iter()
+                    .flatten()
+                    .map(String::as_str)
+                    .collect();
+                if bounds.iter().any(|b| Bound::parse(b).is_none()) {
+                    return Err(invalid("Range bounds must be numbers or YYYY-MM-DD dates"));
+                }
+                if let (Some(min), Some(max)) = (min, max) {
+                    if Bound::parse(min).and_then(|lo| lo.compare(max)) == Some(Ordering::Greater) {
+                        return Err(invalid("Range minimum is above its maximum"));
+                    }
+                }
+            }
+            ValidationCheck::Enum { values, .. } if values.is_empty() => {
+                return Err(invalid("Enum rule needs at least one value"));
+            }
+            ValidationCheck::ItemsSum { tolerance, .. }
+            | ValidationCheck::Sum { tolerance, .. }
+                if !(tolerance.is_finite() && *tolerance >= 0.0) =>
+            {
+                return Err(invalid("Sum tolerance must be a non-negative number"));
+            }
+            ValidationCheck::Sum { addend_keys, .. } if addend_keys.is_empty() => {
+                return Err(invalid("Sum rule needs at least one addend"));
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+}
+
+fn compile_pattern(pattern: &str) -> DomainResult<Regex> {
+    RegexBuilder::new(pattern)
+        .size_limit(PATTERN_SIZE_LIMIT)
+        .build()
+        .map_err(|err| DomainError {
+            code: ErrorCode::PreconditionFailed,
+            message: "Invalid validation rule pattern".to_string(),
+            details: Some(serde_json::json!({ "pattern": pattern, "error": err.to_string() })),
+        })
+}
+
+/// A range bound: numbers compare numerically, dates chronologically.
+enum Bound {
+    Number(f64),
+    Date(NaiveDate),
+}
+
+impl Bound {
+    fn parse(text: &str) -> Option<Self> {
+        let text = text.trim();
+        if let Ok(date) = NaiveDate::parse_from_str(text, "%Y-%m-%d") {
+            return Some(Bound::Date(date));
+        }
+        text.parse::<f64>()
+            .ok()
+            .filter(|n| n.is_finite())
+            .map(Bound::Number)
+    }
+
+    /// Orders the bound against a canonical value of the same kind.
+    fn compare(&self, canonical: &str) -> Option<Ordering> {
+        match self {
+            Bound::Number(n) => n.partial_cmp(&amount(canonical)?.0),
+            Bound::Date(d) => NaiveDate::parse_from_str(canonical, "%Y-%m-%d")
+                .ok()
+                .map(|v| d.cmp(&v)),
+        }
+    }
+}
+
+/// Amount and currency code of a canonical integer, decimal or currency value.
+fn amount(canonical: &str) -> Option<(f64, Option<&str>)> {
+    let mut parts = canonical.split(' ');
+    let number = parts.next()?.parse::<f64>().ok()?;
+    Some((number, parts.next()))
+}
+
+/// `validation_rules` row.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ValidationRule {
+    pub validation_rule_id: Uuid,
+    pub project_id: Uuid,
+    pub name: String,
+    pub check: ValidationCheck,
+    pub severity: Severity,
+    pub enabled: bool,
+    /// Actor that added the rule.
+    pub created_by: String,
+}
+
+/// A rule as evaluated: a stored project rule or one implied by the schema.
+#[derive(Debug, Clone)]
+pub struct RuleInstance {
+    /// Stable identity across runs: the rule id, or `schema:<check>:<schema_field_id>`.
+    pub rule_key: String,
+    pub validation_rule_id: Option<Uuid>,
+    pub name: String,
+    pub check: ValidationCheck,
+    pub severity: Severity,
+}
+
+impl RuleInstance {
+    pub fn from_rule(rule: &ValidationRule) -> Self {
+        Self {
+            rule_key: rule.validation_rule_id.to_string(),
+            validation_rule_id: Some(rule.validation_rule_id),
+            name: rule.name.clone(),
+            check: rule.check.clone(),
+            severity: rule.severity,
+        }
+    }
+}
+
+/// Blocking rules every schema version implies: required fields are present and
+/// typed fields parse.
+pub fn schema_rules(schema: &SchemaVersion) -> Vec<RuleInstance> {
+    let mut rules = Vec::new();
+    for field in schema.active_fields() {
+        if field.required {
+            rules.push(RuleInstance {
+                rule_key: format!("schema:required:{}", field.schema_field_id),
+                validation_rule_id: None,
+                name: format!("{} is required", field.label),
+                check: ValidationCheck::Required {
+                    field_key: schema.qualified_key(field),
+                },
+                severity: Severity::Blocking,
+            });
+        }
+        if field.field_type != FieldType::String {
+            rules.push(RuleInstance {
+                rule_key: format!("schema:typed:{}", field.schema_field_id),
+                validation_rule_id: None,
+                name: format!("{} must be a valid {:?}", field.label, field.field_type),
+                check: ValidationCheck::Typed {
+                    field_key: schema.qualified_key(field),
+                },
+                severity: Severity::Blocking,
+            });
+        }
+    }
+    rules
+}
+
+/// A field or item value as the rules see it.
+#[derive(Debug, Clone)]
+pub struct Observed {
+    pub value_id: Uuid,
+    pub raw_value: String,
+    pub normalized_value: Option<String>,
+}
+
+impl Observed {
+    pub(crate) fn is_blank(&self) -> bool {
+        self.raw_value.trim().is_empty()
+    }
+
+    /// Normalized value, falling back to the trimmed raw value.
+    pub(crate) fn text(&self) -> &str {
+        self.normalized_value
+            .as_deref()
+            .unwrap_or_else(|| self.raw_value.trim())
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ItemValues {
+    pub item_id: Uuid,
+    pub row_index: u32,
+    pub values: HashMap<String, Observed>,
+}
+
+/// One row of an extra table, values keyed by column key.
+#[derive(Debug, Clone)]
+pub struct ExtraRowValues {
+    pub extra_row_id: Uuid,
+    pub row_index: i32,
+    pub values: HashMap<String, Observed>,
+}
+
+/// One document's values keyed by field key.
+#[derive(Debug, Clone, Default)]
+pub struct DocumentValues {
+    pub fields: HashMap<String, Observed>,
+    /// Rows in `row_index` order.
+    pub items: Vec<ItemValues>,
+    /// Extra-table rows by table name, in `row_index` order.
+    pub extras: BTreeMap<String, Vec<ExtraRowValues>>,
+}
+
+/// Everything the rules read from one session.
+#[derive(Debug, Clone)]
+pub struct SessionSnapshot {
+    pub session_id: Uuid,
+    pub schema: SchemaVersion,
+    pub documents: BTreeMap<Uuid, DocumentValues>,
+}
+
+impl SessionSnapshot {
+    /// Every field, item and extra value of the session by id.
+    pub fn observed_by_id(&self) -> HashMap<Uuid, &Observed> {
+        let mut observed = HashMap::new();
+        for doc in self.documents.values() {
+            let rows = doc.items.iter().map(|row| &row.values);
+            let extras = doc.extras.values().flatten().map(|row| &row.values);
+            for values in std::iter::once(&doc.fields).chain(rows).chain(extras) {
+                observed.extend(values.values().map(|o| (o.value_id, o)));
+            }
+        }
+        observed
+    }
+
+    /// Imported documents without any value are included so required rules see them.
+    pub fn load(
+        mapping: &dyn MappingStore,
+        pages: &dyn PageReader,
+        session_id: Uuid,
+        schema: SchemaVersion,
+    ) -> DomainResult<Self> {
+        let key_of =
+            |schema_field_id: Uuid| schema.field(schema_field_id).map(|f| f.field_key.clone());
+        let mut documents: BTreeMap<Uuid, DocumentValues> = BTreeMap::new();
+        for page in pages.session_pages(session_id)? {
+            documents.entry(page.document_id).or_default();
+        }
+        for value in mapping.field_values(session_id)? {
+            let Some(key) = key_of(value.schema_field_id) else {
+                continue;
+            };
+            documents
+                .entry(value.document_id)
+                .or_default()
+                .fields
+                .insert(
+                    key,
+                    Observed {
+                        value_id: value.field_value_id,
+                        raw_value: value.raw_value,
+                        normalized_value: value.normalized_value,
+                    },
+                );
+        }
+
+        let mut rows = mapping.item_rows(session_id)?;
+        rows.sort_by_key(|r| (r.document_id, r.row_index, r.item_id));
+        let item_values = mapping.item_values(session_id)?;
+        for row in rows {
+            let values = item_values
+                .iter()
+                .filter(|v| v.item_id == row.item_id)
+                .filter_map(|v| {
+                    let key = key_of(v.schema_field_id)?;
+                    Some((
+                        key,
+                        Observed {
+                            value_id: v.item_value_id,
+                            raw_value: v.raw_value.clone(),
+                            normalized_value: v.normalized_value.clone(),
+                        },
+                    ))
+                })
+                .collect();
+            documents
+                .entry(row.document_id)
+                .or_default()
+                .items
+                .push(ItemValues {
+                    item_id: row.item_id,
+                    row_index: row.row_index,
+                    values,
+                });
+        }
+
+        let mut extra_rows = mapping.extra_rows(session_id)?;
+        extra_rows.sort_by_key(|r| (r.document_id, r.row_index, r.extra_row_id));
+        let extra_values = mapping.extra_values(session_id)?;
+        for row in extra_rows {
+            let values = extra_values
+                .iter()
+                .filter(|v| v.extra_row_id == row.extra_row_id)
+                .filter_map(|v| {
+                    let key = key_of(v.schema_field_id)?;
+                    Some((
+                        key,
+                        Observed {
+                            value_id: v.extra_value_id,
+                            raw_value: v.raw_value.clone(),
+                            normalized_value: v.normalized_value.clone(),
+                        },
+                    ))
+                })
+                .collect();
+            documents
+                .entry(row.document_id)
+                .or_default()
+                .extras
+                .entry(row.table_name)
+                .or_default()
+                .push(ExtraRowValues {
+                    extra_row_id: row.extra_row_id,
+                    row_index: row.row_index,
+                    values,
+                });
+        }
+
+        Ok(Self {
+            session_id,
+            schema,
+            documents,
+        })
+    }
+}
+
+/// Document field values of the other sessions in a project, for dataset rules.
+#[derive(Debug, Clone, Default)]
+pub struct ProjectDataset {
+    pub documents: Vec<DatasetDocument>,
+}
+
+#[derive(Debug, Clone)]
+pub struct DatasetDocument {
+    pub session_id: Uuid,
+    pub document_id: Uuid,
+    /// Comparable text (normalized, lowercased) per field key.
+    pub fields: HashMap<String, String>,
+}
+
+fn comparable(observed: &Observed) -> Option<String> {
+    (!observed.is_blank()).then(|| observed.text().to_lowercase())
+}
+
+impl ProjectDataset {
+    /// Loads every other session of the project except those in the same
+    /// correction lineage, whose documents are expected to repeat.
+    pub fn load(
+        sessions: &dyn SessionReader,
+        schemas: &dyn SchemaStore,
+        mapping: &dyn MappingStore,
+        session_id: Uuid,
+    ) -> DomainResult<Self> {
+        let session = sessions.get_session(session_id)?;
+        let mut documents = Vec::new();
+        for (other_id, _) in sessions.project_sessions(session.project_id)? {
+            let other = sessions.get_session(other_id)?;
+            let related = other_id == session_id
+                || session.base_session_id == Some(other_id)
+                || other.base_session_id == Some(session_id);
+            if related {
+                continue;
+            }
+            let schema = session_schema(sessions, schemas, other_id)?;
+            let mut by_document: BTreeMap<Uuid, HashMap<String, String>> = BTreeMap::new();
+            for value in mapping.field_values(other_id)? {
+                let Some(field) = schema.field(value.schema_field_id) else {
+                    continue;
+                };
+                let observed = Observed {
+                    value_id: value.field_value_id,
+                    raw_value: value.raw_value,
+                    normalized_value: value.normalized_value,
+                };
+                if let Some(text) = comparable(&observed) {
+                    by_document
+                        .entry(value.document_id)
+                        .or_default()
+                        .insert(field.field_key.clone(), text);
+                }
+            }
+            documents.extend(by_document.into_iter().map(|(document_id, fields)| {
+                DatasetDocument {
+                    session_id: other_id,
+                    document_id,
+                    fields,
+                }
+            }));
+        }
+        Ok(Self { documents })
+    }
+}
+
+/// One rule failing for one document, or one item or extra row of it.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Finding {
+    pub rule_key: String,
+    pub validation_rule_id: Option<Uuid>,
+    pub layer: RuleLayer,
+    pub severity: Severity,
+    pub document_id: Uuid,
+    pub item_id: Option<Uuid>,
+    #[serde(default)]
+    pub extra_row_id: Option<Uuid>,
+    pub field_keys: Vec<String>,
+    /// Field and item values the finding was computed from.
+    pub value_ids: Vec<Uuid>,
+    /// Other documents involved, e.g. the duplicates found by a dataset rule.
+    #[serde(default)]
+    pub related_document_ids: Vec<Uuid>,
+    pub message: String,
+}
+
+/// `validation_results` row.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ValidationResult {
+    pub validation_result_id: Uuid,
+    pub validation_run_id: Uuid,
+    pub session_id: Uuid,
+    #[serde(flatten)]
+    pub finding: Finding,
+    /// Active override covering the result when the run was recorded.
+    #[serde(default)]
+    pub override_id: Option<Uuid>,
+}
+
+/// `validation_runs` row.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ValidationRun {
+    pub validation_run_id: Uuid,
+    pub session_id: Uuid,
+    pub caused_by: Uuid,
+    pub rule_scope: ValidationRuleScope,
+    pub schema: SchemaRef,
+    pub ran_at: DateTime<Utc>,
+    /// Results not covered by an active override.
+    pub blocking: usize,
+    pub warnings: usize,
+    pub overridden: usize,
+    /// Rules and documents the run covered; a later `ChangedOnly` run re-evaluates
+    /// anything missing from these lists.
+    pub rule_keys: Vec<String>,
+    pub document_ids: Vec<Uuid>,
+    /// (rule, document) pairs evaluated, and pairs whose findings were carried over
+    /// from the previous run.
+    pub checks_evaluated: usize,
+    pub checks_reused: usize,
+}
+
+/// Document fields written since a validation run, collected from the
+/// `changed_values` of the session events that followed the run's own events.
+#[derive(Debug, Clone, Default)]
+pub struct ChangeSet {
+    values: HashSet<ValueChange>,
+}
+
+impl ChangeSet {
+    /// `None` when the run's events are missing or a later event cannot be read, in
+    /// which case nothing is known to be unchanged.
+    pub fn since_run(events: &[EventEnvelope], run: &ValidationRun) -> Option<Self> {
+        let last = events.iter().rposition(|e| e.caused_by == run.caused_by)?;
+        let mut values = HashSet::new();
+        for event in &events[last + 1..] {
+            if let Some(changed) = event.data.pointer("/delta/changed_values") {
+                let changed: Vec<ValueChange> = serde_json::from_value(changed.clone()).ok()?;
+                values.extend(changed);
+            }
+        }
+        Some(Self { values })
+    }
+
+    fn touches(&self, document_id: Uuid, field_keys: &[String], schema: &SchemaVersion) -> bool {
+        field_keys
+            .iter()
+            .filter_map(|key| schema.field_by_key(key))
+            .any(|field| {
+                self.values.contains(&ValueChange {
+                    document_id,
+                    schema_field_id: field.schema_field_id,
+                })
+            })
+    }
+}
+
+/// The previous run of a session and what changed since, for `ChangedOnly` runs.
+pub struct Baseline {
+    rule_keys: HashSet<String>,
+    document_ids: HashSet<Uuid>,
+    findings: HashMap<(String, Uuid), Vec<Finding>>,
+    changes: ChangeSet,
+}
+
+impl Baseline {
+    pub fn new(run: &ValidationRun, results: Vec<ValidationResult>, changes: ChangeSet) -> Self {
+        let mut findings: HashMap<(String, Uuid), Vec<Finding>> = HashMap::new();
+        for result in results {
+            let key = (result.finding.rule_key.clone(), result.finding.document_id);
+            findings.entry(key).or_default().push(result.finding);
+        }
+        Self {
+            rule_keys: run.rule_keys.iter().cloned().collect(),
+            document_ids: run.document_ids.iter().copied().collect(),
+            findings,
+            changes,
+        }
+    }
+
+    /// Findings that still hold for the rule and document: the previous run checked
+    /// the pair and none of the rule's fields changed since. Dataset rules depend on
+    /// other sessions and are never reused.
+    fn reusable(
+        &self,
+        rule: &RuleInstance,
+        document_id: Uuid,
+        schema: &SchemaVersion,
+    ) -> Option<&[Finding]> {
+        let unchanged = rule.check.layer() != RuleLayer::Dataset
+            && self.rule_keys.contains(&rule.rule_key)
+            && self.document_ids.contains(&document_id)
+            && !self
+                .changes
+                .touches(document_id, &rule.check.field_keys(), schema);
+        unchanged.then(|| {
+            self.findings
+                .get(&(rule.rule_key.clone(), document_id))
+                .map_or(&[][..], Vec::as_slice)
+        })
+    }
+}
+
+/// Findings of a run with the number of checks evaluated and reused.
+pub struct Evaluation {
+    pub findings: Vec<Finding>,
+    pub checks_evaluated: usize,
+    pub checks_reused: usize,
+}
+
+struct CompiledRule {
+    rule: RuleInstance,
+    regex: Option<Regex>,
+    expression: Option<Expression>,
+}
+
+pub struct ValidationEngine {
+    rules: Vec<CompiledRule>,
+}
+
+impl ValidationEngine {
+    pub fn new(rules: Vec<RuleInstance>) -> DomainResult<Self> {
+        let rules = rules
+            .into_iter()
+            .map(|rule| {
+                let regex = match &rule.check {
+                    ValidationCheck::Regex { pattern, .. } => Some(compile_pattern(pattern)?),
+                    _ => None,
+                };
+                let expression = match &rule.check {
+                    ValidationCheck::Expression { expression } => Some(
+                        Expression::parse(expression).map_err(|e| e.to_domain_error(expression))?,
+                    ),
+                    _ => None,
+                };
+                Ok(CompiledRule {
+                    rule,
+                    regex,
+                    expression,
+                })
+            })
+            .collect::<DomainResult<Vec<_>>>()?;
+        Ok(Self { rules })
+    }
+
+    pub fn has_dataset_rules(&self) -> bool {
+        self.rules
+            .iter()
+            .any(|r| r.rule.check.layer() == RuleLayer::Dataset)
+    }
+
+    pub fn rule_keys(&self) -> Vec<String> {
+        self.rules.iter().map(|r| r.rule.rule_key.clone()).collect()
+    }
+
+    pub fn rule(&self, rule_key: &str) -> Option<&RuleInstance> {
+        self.rules
+            .iter()
+            .map(|r| &r.rule)
+            .find(|r| r.rule_key == rule_key)
+    }
+
+    /// Findings for every rule and document, ordered by document, rule and row.
+    pub fn evaluate(&self, snapshot: &SessionSnapshot, dataset: &ProjectDataset) -> Vec<Finding> {
+        self.evaluate_since(snapshot, dataset, None).findings
+    }
+
+    /// Same findings and order as [`Self::evaluate`], carrying over the baseline's
+    /// findings for every (rule, document) pair whose inputs did not change.
+    pub fn evaluate_since(
+        &self,
+        snapshot: &SessionSnapshot,
+        dataset: &ProjectDataset,
+        baseline: Option<&Baseline>,
+    ) -> Evaluation {
+        let mut evaluation = Evaluation {
+            findings: Vec::new(),
+            checks_evaluated: 0,
+            checks_reused: 0,
+        };
+        for document_id in snapshot.documents.keys() {
+            for rule in &self.rules {
+                let reused =
+                    baseline.and_then(|b| b.reusable(&rule.rule, *document_id, &snapshot.schema));
+                match reused {
+                    Some(findings) => {
+                        evaluation.findings.extend(findings.iter().cloned());
+                        evaluation.checks_reused += 1;
+                    }
+                    None => {
+                        evaluation.findings.extend(self.check(
+                            rule,
+                            snapshot,
+                            *document_id,
+                            dataset,
+                        ));
+                        evaluation.checks_evaluated += 1;
+                    }
+                }
+            }
+        }
+        evaluation
+    }
+
+    fn check(
+        &self,
+        compiled: &CompiledRule,
+        snapshot: &SessionSnapshot,
+        document_id: Uuid,
+        dataset: &ProjectDataset,
+    ) -> Vec<Finding> {
+        let Some(doc) = snapshot.documents.get(&document_id) else {
+            return Vec::new();
+        };
+        let schema = &snapshot.schema;
+        let rule = &compiled.rule;
+        let finding = |item_id: Option<Uuid>, value_ids: Vec<Uuid>, message: String| Finding {
+            rule_key: rule.rule_key.clone(),
+            validation_rule_id: rule.validation_rule_id,
+            layer: rule.check.layer(),
+            severity: rule.severity,
+            document_id,
+            item_id,
+            extra_row_id: None,
+            field_keys: rule.check.field_keys(),
+            value_ids,
+            related_document_ids: Vec::new(),
+            message,
+        };
+        let finding_at = |row: ObservedRow, value_ids: Vec<Uuid>, message: String| match row {
+            ObservedRow::Document => finding(None, value_ids, message),
+            ObservedRow::Item(item_id) => finding(Some(item_id), value_ids, message),
+            ObservedRow::Extra(extra_row_id) => Finding {
+                extra_row_id: Some(extra_row_id),
+                ..finding(None, value_ids, message)
+            },
+        };
+        let mut findings = Vec::new();
+
+        match &rule.check {
+            ValidationCheck::Required { field_key } => {
+                let Some(field) = schema.field_by_key(field_key) else {
+                    return findings;
+                };
+                for (row, observed) in observations(schema, field, doc) {
+                    if observed.is_none_or(Observed::is_blank) {
+                        findings.push(finding_at(
+                            row,
+                            observed.map(|o| o.value_id).into_iter().collect(),
+                            format!("{} is required", field.label),
+                        ));
+                    }
+                }
+            }
+            ValidationCheck::Typed { field_key } => {
+                let Some(field) = schema.field_by_key(field_key) else {
+                    return findings;
+                };
+                for (row, observed) in observations(schema, field, doc) {
+                    if let Some(o) =
+                        observed.filter(|o| !o.is_blank() && o.normalized_value.is_none())
+                    {
+                        findings.push(finding_at(
+                            row,
+                            vec![o.value_id],
+                            format!(
+                                "{} '{}' is not a valid {:?}",
+                                field.label,
+                                o.raw_value.trim(),
+                                field.field_type
+                            ),
+                        ));
+                    }
+                }
+            }
+            ValidationCheck::Regex { field_key, pattern } => {
+                let (Some(field), Some(regex)) = (schema.field_by_key(field_key), &compiled.regex)
+                else {
+                    return findings;
+                };
+                for (row, observed) in observations(schema, field, doc) {
+                    if let Some(o) = observed.filter(|o| !o.is_blank() && !regex.is_match(o.text()))
+                    {
+                        findings.push(finding_at(
+                            row,
+                            vec![o.value_id],
+                            format!("{} does not match {pattern}", field.label),
+                        ));
+                    }
+                }
+            }
+            ValidationCheck::Range {
+                field_key,
+                min,
+                max,
+            } => {
+                let Some(field) = schema.field_by_key(field_key) else {
+                    return findings;
+                };
+                let min = min.as_deref().and_then(Bound::parse);
+                let max = max.as_deref().and_then(Bound::parse);
+                for (row, observed) in observations(schema, field, doc) {
+                    let Some(o) = observed else { continue };
+                    let Some(value) = o.normalized_value.as_deref() else {
+                        continue;
+                    };
+                    let below =
+                        min.as_ref().and_then(|b| b.compare(value)) == Some(Ordering::Greater);
+                    let above = max.as_ref().and_then(|b| b.compare(value)) == Some(Ordering::Less);
+                    if below || above {
+                        findings.push(finding_at(
+                            row,
+                            vec![o.value_id],
+                            format!("{} {value} is out of range", field.label),
+                        ));
+                    }
+                }
+            }
+            ValidationCheck::Enum { field_key, values } => {
+                let Some(field) = schema.field_by_key(field_key) else {
+                    return findings;
+                };
+                for (row, observed) in observations(schema, field, doc) {
+                    let Some(o) = observed.filter(|o| !o.is_blank()) else {
+                        continue;
+                    };
+                    if !values.iter().any(|v| v.eq_ignore_ascii_case(o.text())) {
+                        findings.push(finding_at(
+                            row,
+                            vec![o.value_id],
+                            format!("{} must be one of {}", field.label, values.join(", ")),
+                        ));
+                    }
+                }
+            }
+            ValidationCheck::ItemsSum {
+                item_field_key,
+                field_key,
+                tolerance,
+            } => {
+                let (Some(item_field), Some(total_field)) = (
+                    schema.field_by_key(item_field_key),
+                    schema.field_by_key(field_key),
+                ) else {
+                    return findings;
+                };
+                let Some(total) = doc.fields.get(&total_field.field_key) else {
+                    return findings;
+                };
+                let addends: Vec<&Observed> = doc
+                    .items
+                    .iter()
+                    .filter_map(|row| row.values.get(&item_field.field_key))
+                    .filter(|o| !o.is_blank())
+                    .collect();
+                if addends.is_empty() {
+                    return findings;
+                }
+                if let Some(message) = sum_mismatch(&addends, total, *tolerance) {
+                    let value_ids = addends.iter().chain([&total]).map(|o| o.value_id).collect();
+                    findings.push(finding(
+                        None,
+                        value_ids,
+                        format!(
+                            "Sum of {} {message} {}",
+                            item_field.label, total_field.label
+                        ),
+                    ));
+                }
+            }
+            ValidationCheck::Sum {
+                addend_keys,
+                field_key,
+                tolerance,
+            } => {
+                let Some(total_field) = schema.field_by_key(field_key) else {
+                    return findings;
+                };
+                if addend_keys.iter().any(|k| schema.field_by_key(k).is_none()) {
+                    return findings;
+                }
+                let Some(total) = doc.fields.get(&total_field.field_key) else {
+                    return findings;
+                };
+                let addends: Vec<&Observed> = addend_keys
+                    .iter()
+                    .filter_map(|k| doc.fields.get(k))
+                    .filter(|o| !o.is_blank())
+                    .collect();
+                if addends.is_empty() {
+                    return findings;
+                }
+                if let Some(message) = sum_mismatch(&addends, total, *tolerance) {
+                    let value_ids = addends.iter().chain([&total]).map(|o| o.value_id).collect();
+                    findings.push(finding(
+                        None,
+                        value_ids,
+                        format!(
+                            "{} {message} {}",
+                            addend_keys.join(" + "),
+                            total_field.label
+                        ),
+                    ));
+                }
+            }
+            ValidationCheck::DateAfter {
+                field_key,
+                after_field_key,
+                allow_equal,
+            } => {
+                let (Some(later_field), Some(earlier_field)) = (
+                    schema.field_by_key(field_key),
+                    schema.field_by_key(after_field_key),
+                ) else {
+                    return findings;
+                };
+                let date = |key: &str| {
+                    let o = doc.fields.get(key)?;
+                    let date =
+                        NaiveDate::parse_from_str(o.normalized_value.as_deref()?, "%Y-%m-%d")
+                            .ok()?;
+                    Some((o, date))
+                };
+                let (Some((later, later_date)), Some((earlier, earlier_date))) =
+                    (date(&later_field.field_key), date(&earlier_field.field_key))
+                else {
+                    return findings;
+                };
+                let ok = match later_date.cmp(&earlier_date) {
+                    Ordering::Greater => true,
+                    Ordering::Equal => *allow_equal,
+                    Ordering::Less => false,
+                };
+                if !ok {
+                    findings.push(finding(
+                        None,
+                        vec![earlier.value_id, later.value_id],
+                        format!(
+                            "{} must be after {}",
+                            later_field.label, earlier_field.label
+                        ),
+                    ));
+                }
+            }
+            ValidationCheck::Unique {
+                field_key,
+                group_key,
+            } => {
+                let Some(field) = schema.field_by_key(field_key) else {
+                    return findings;
+                };
+                let Some(value) = doc.fields.get(field_key) else {
+                    return findings;
+                };
+                let Some(text) = comparable(value) else {
+                    return findings;
+                };
+                let group_of = |fields: &HashMap<String, Observed>| {
+                    group_key
+                        .as_ref()
+                        .map(|k| fields.get(k).and_then(comparable))
+                };
+                let own_group = group_of(&doc.fields);
+
+                let in_session = snapshot
+                    .documents
+                    .iter()
+                    .filter(|(id, other)| {
+                        **id != document_id
+                            && other.fields.get(field_key).and_then(comparable).as_ref()
+                                == Some(&text)
+                            && group_of(&other.fields) == own_group
+                    })
+                    .map(|(id, _)| *id);
+                let in_project = dataset
+                    .documents
+                    .iter()
+                    .filter(|other| {
+                        other.fields.get(field_key) == Some(&text)
+                            && group_key.as_ref().map(|k| other.fields.get(k).cloned()) == own_group
+                    })
+                    .map(|other| other.document_id);
+                let duplicates: Vec<Uuid> = in_session.chain(in_project).collect();
+                if !duplicates.is_empty() {
+                    let mut found = finding(
+                        None,
+                        vec![value.value_id],
+                        format!(
+                            "{} '{}' also appears in {} other document(s)",
+                            field.label,
+                            value.text(),
+                            duplicates.len()
+                        ),
+                    );
+                    found.related_document_ids = duplicates;
+                    findings.push(found);
+                }
+            }
+            ValidationCheck::Expression { .. } => {
+                let Some(expression) = &compiled.expression else {
+                    return findings;
+                };
+                match expression.typed(schema) {
+                    Ok(typed) => {
+                        for violation in typed.violations(doc) {
+                            let message = match violation.detail {
+                                Some(detail) => format!("{} failed: {detail}", rule.name),
+                                None => format!("{} failed", rule.name),
+                            };
+                            findings.push(finding(violation.item_id, violation.value_ids, message));
+                        }
+                    }
+                    // Like the built-in checks, a rule naming a key this version
+                    // lacks does not apply.
+                    Err(err) if err.kind == DslErrorKind::UnknownField => {}
+                    Err(err) => findings.push(finding(
+                        None,
+                        Vec::new(),
+                        format!(
+                            "{} does not fit schema version {}: {}",
+                            rule.name, schema.version, err.message
+                        ),
+                    )),
+                }
+            }
+        }
+        findings
+    }
+}
+
+/// Where a field-level value sits in its document.
+#[derive(Debug, Clone, Copy)]
+enum ObservedRow {
+    Document,
+    Item(Uuid),
+    Extra(Uuid),
+}
+
+/// The values a field-level rule sees in one document: the document value, or one
+/// per row for item fields and extra-table columns.
+fn observations<'d>(
+    schema: &SchemaVersion,
+    field: &SchemaField,
+    doc: &'d DocumentValues,
+) -> Vec<(ObservedRow, Option<&'d Observed>)> {
+    match field.scope {
+        FieldScope::Document => vec![(ObservedRow::Document, doc.fields.get(&field.field_key))],
+        FieldScope::Item => doc
+            .items
+            .iter()
+            .map(|row| {
+                (
+                    ObservedRow::Item(row.item_id),
+                    row.values.get(&field.field_key),
+                )
+            })
+            .collect(),
+        FieldScope::ExtraTable => field
+            .extra_table_id
+            .and_then(|id| schema.extra_table(id))
+            .and_then(|table| doc.extras.get(&table.table_name))
+            .into_iter()
+            .flatten()
+            .map(|row| {
+                (
+                    ObservedRow::Extra(row.extra_row_id),
+                    row.values.get(&field.field_key),
+                )
+            })
+            .collect(),
+    }
+}
+
+/// `None` when the addends match the total within `tolerance`, or when any value is
+/// not a parsed amount (the typed rule reports that).
+fn sum_mismatch(addends: &[&Observed], total: &Observed, tolerance: f64) -> Option<String> {
+    let (total, total_currency) = amount(total.normalized_value.as_deref()?)?;
+    let mut sum = 0.0;
+    for addend in addends {
+        let (value, currency) = amount(addend.normalized_value.as_deref()?)?;
+        if currency != total_currency {
+            return Some("mixes currencies with".to_string());
+        }
+        sum += value;
+    }
+    ((sum - total).abs() > tolerance + f64::EPSILON).then(|| format!("({sum:.2}) does not equal"))
+}
+
+pub struct ValidationDeps<'a> {
+    pub mapping: &'a dyn MappingStore,
+    pub sessions: &'a dyn SessionReader,
+    pub schemas: &'a dyn SchemaStore,
+    pub pages: &'a dyn PageReader,
+    pub validation: &'a dyn ValidationStore,
+    pub events: &'a dyn EventReader,
+    pub overrides: &'a dyn OverrideStore,
+}
+
+/// Runs the schema rules and the project's enabled rules against a session. A run
+/// without blocking findings moves `review -> validated`; blocking findings on a
+/// validated session move it back to `review`. Active overrides clear the findings
+/// they cover, and overrides whose covered values changed expire.
+///
+/// `ChangedOnly` re-evaluates the rules reading a field written since the session's
+/// latest run, dataset rules, and rules or documents that run did not cover; the
+/// other findings are carried over. It evaluates everything when there is no
+/// previous run or its events cannot be read.
+pub struct RunValidationHandler<'a> {
+    deps: ValidationDeps<'a>,
+    review_tasks: Option<&'a dyn ReviewTaskStore>,
+}
+
+impl<'a> RunValidationHandler<'a> {
+    pub fn new(deps: ValidationDeps<'a>) -> Self {
+        Self {
+            deps,
+            review_tasks: None,
+        }
+    }
+
+    /// Resolves open validation tasks the run no longer raises, and tasks about
+    /// deleted item rows, as `superseded`.
+    pub fn with_review_tasks(mut self, review_tasks: &'a dyn ReviewTaskStore) -> Self {
+        self.review_tasks = Some(review_tasks);
+        self
+    }
+
+    fn run(&self, ctx: &CommandContext, cmd: &RunValidation) -> DomainResult<CommandOutcome> {
+        let session_id = cmd.payload.session_id;
+        let status = self.deps.sessions.get_status(session_id)?;
+        let session = self.deps.sessions.get_session(session_id)?;
+        let schema = session_schema(self.deps.sessions, self.deps.schemas, session_id)?;
+
+        let mut rules = schema_rules(&schema);
+        rules.extend(
+            self.deps
+                .validation
+                .rules(session.project_id)?
+                .iter()
+                .filter(|r| r.enabled)
+                .map(RuleInstance::from_rule),
+        );
+        let engine = ValidationEngine::new(rules)?;
+        let snapshot =
+            SessionSnapshot::load(self.deps.mapping, self.deps.pages, session_id, schema)?;
+        let dataset = if engine.has_dataset_rules() {
+            ProjectDataset::load(
+                self.deps.sessions,
+                self.deps.schemas,
+                self.deps.mapping,
+                session_id,
+            )?
+        } else {
+            ProjectDataset::default()
+        };
+        let baseline = match cmd.payload.rule_scope {
+            ValidationRuleScope::All => None,
+            ValidationRuleScope::ChangedOnly => self.baseline(session_id, session.schema)?,
+        };
+        let evaluation = engine.evaluate_since(&snapshot, &dataset, baseline.as_ref());
+        let findings = evaluation.findings;
+
+        let validation_run_id = Uuid::now_v7();
+        let reconciled = ReconciledOverrides::reconcile(
+            self.deps.overrides.overrides(session_id)?,
+            &snapshot,
+            &findings,
+            ctx.now,
+            validation_run_id,
+        );
+        for expired in &reconciled.expired {
+            self.deps.overrides.put_override(expired)?;
+        }
+        let results: Vec<ValidationResult> = findings
+            .into_iter()
+            .enumerate()
+            .map(|(index, finding)| ValidationResult {
+                validation_result_id: Uuid::now_v7(),
+                validation_run_id,
+                session_id,
+                finding,
+                override_id: reconciled.applied.get(&index).copied(),
+            })
+            .collect();
+        let open = |severity: Severity| {
+            results
+                .iter()
+                .filter(|r| r.override_id.is_none() && r.finding.severity == severity)
+                .count()
+        };
+        let blocking = open(Severity::Blocking);
+        let run = ValidationRun {
+            validation_run_id,
+            session_id,
+            caused_by: cmd.command_id,
+            rule_scope: cmd.payload.rule_scope,
+            schema: session.schema,
+            ran_at: ctx.now,
+            blocking,
+            warnings: open(Severity::Warning),
+            overridden: reconciled.applied.len(),
+            rule_keys: engine.rule_keys(),
+            document_ids: snapshot.documents.keys().copied().collect(),
+            checks_evaluated: evaluation.checks_evaluated,
+            checks_reused: evaluation.checks_reused,
+        };
+        self.deps.validation.record_run(&run, &results)?;
+
+        let review_actions: Vec<ReviewAction> = results
+            .iter()
+            .filter(|r| r.override_id.is_none() && r.finding.severity == Severity::Blocking)
+            .map(|r| {
+                let finding = &r.finding;
+                let check = engine.rule(&finding.rule_key).map(|rule| &rule.check);
+                let target = ReviewTarget {
+                    document_id: finding.document_id,
+                    schema_field_id: check
+                        .and_then(ValidationCheck::target_field_key)
+                        .and_then(|key| snapshot.schema.field_by_key(key))
+                        .map(|field| field.schema_field_id),
+                    item_id: finding.item_id,
+                };
+                ReviewAction::new(
+                    session_id,
+                    check.map_or(
+                        ReviewCategory::ValidationError,
+                        ValidationCheck::review_category,
+                    ),
+                    target,
+                    serde_json::json!({
+                        "validation_result_id": r.validation_result_id,
+                        "rule_key": finding.rule_key,
+                        "extra_row_id": finding.extra_row_id,
+                        "field_keys": finding.field_keys,
+                        "related_document_ids": finding.related_document_ids,
+                        "message": finding.message,
+                    }),
+                )
+                .with_subject(finding.rule_key.clone())
+            })
+            .collect();
+        let closed = match self.review_tasks {
+            Some(store) => close_stale_review_tasks(
+                store,
+                self.deps.mapping,
+                session_id,
+                &VALIDATION_CATEGORIES,
+                &review_actions,
+                &ctx.actor,
+                ctx.now,
+            )?,
+            None => Vec::new(),
+        };
+        let mut events = reconciled.events();
+        events.extend(closed.iter().map(|task| task.closed_event()));
+
+        let next = match status {
+            SessionStatus::Review if blocking == 0 => Some(SessionStatus::Validated),
+            SessionStatus::Validated if blocking > 0 => Some(SessionStatus::Review),
+            _ => None,
+        };
+
+        Ok(CommandOutcome {
+            state_delta: StateDelta {
+                summary: format!(
+                    "Validation found {} blocking error(s) and {} warning(s); {} overridden",
+                    run.blocking, run.warnings, run.overridden
+                ),
+                data: serde_json::json!({
+                    "validation_run": run,
+                    "results": results,
+                    "expired_override_ids": reconciled
+                        .expired
+                        .iter()
+                        .map(|o| o.override_id)
+                        .collect::<Vec<_>>(),
+                    "superseded_review_task_ids": closed
+                        .iter()
+                        .map(|t| t.review_task_id)
+                        .collect::<Vec<_>>(),
+                }),
+            },
+            transition: next.map(|to| SessionStatusTransition { from: status, to }),
+            review_actions,
+            validation_trigger: ValidationTrigger::None,
+            events,
+        })
+    }
+
+    fn baseline(&self, session_id: Uuid, schema: SchemaRef) -> DomainResult<Option<Baseline>> {
+        let Some(run) = self.deps.validation.latest_run(session_id)? else {
+            return Ok(None);
+        };
+        if run.schema != schema {
+            return Ok(None);
+        }
+        let events = self.deps.events.session_events(session_id)?;
+        let Some(changes) = ChangeSet::since_run(&events, &run) else {
+            return Ok(None);
+        };
+        let results = self.deps.validation.results(run.validation_run_id)?;
+        Ok(Some(Baseline::new(&run, results, changes)))
+    }
+}
+
+impl<'a> GenericCommandHandler for RunValidationHandler<'a> {
+    fn can_handle(&self, command_type: &str) -> bool {
+        command_type == "RunValidation"
+    }
+
+    fn handle(&self, ctx: &mut CommandContext, cmd: &AnyCommand) -> DomainResult<CommandOutcome> {
+        match cmd {
+            AnyCommand::RunValidation(c) => self.run(ctx, c),
+            _ => Err(unsupported_command("RunValidationHandler")),
+        }
+    }
+}
+
+pub struct AddValidationRuleHandler<'a> {
+    validation: &'a dyn ValidationStore,
+    schemas: &'a dyn SchemaStore,
+}
+
+impl<'a> AddValidationRuleHandler<'a> {
+    pub fn new(validation: &'a dyn ValidationStore, schemas: &'a dyn SchemaStore) -> Self {
+        Self {
+            validation,
+            schemas,
+        }
+    }
+
+    fn add(&self, cmd: &AddValidationRule) -> DomainResult<CommandOutcome> {
+        let payload = &cmd.payload;
+        self.schemas.get_project(payload.project_id)?;
+        payload.check.validate()?;
+        if let ValidationCheck::Expression { expression } = &payload.check {
+            self.check_expression(payload.project_id, expression)?;
+        }
+        let name = payload.name.trim();
+        if name.is_empty() {
+            return Err(DomainError {
+                code: ErrorCode::PreconditionFailed,
+                message: "Validation rule name must not be empty".to_string(),
+                details: None,
+            });
+        }
+
+        let rule = ValidationRule {
+            validation_rule_id: Uuid::now_v7(),
+            project_id: payload.project_id,
+            name: name.to_string(),
+            check: payload.check.clone(),
+            severity: payload.severity,
+            enabled: true,
+            created_by: cmd.actor.clone(),
+        };
+        self.validation.add_rule(&rule)?;
+
+        Ok(CommandOutcome {
+            state_delta: StateDelta {
+                summary: format!("Validation rule '{}' added", rule.name),
+                data: serde_json::json!({
+                    "validation_rule": rule,
+                    "layer": rule.check.layer(),
+                }),
+            },
+            transition: None,
+            review_actions: Vec::new(),
+            validation_trigger: ValidationTrigger::None,
+            events: Vec::new(),
+        })
+    }
+
+    /// Type-checks an expression against the latest version of every schema in the
+    /// project. Schemas lacking one of its fields are skipped, but at least one must
+    /// define them all, and none may disagree with the expression's types.
+    fn check_expression(&self, project_id: Uuid, source: &str) -> DomainResult<()> {
+        let expression = Expression::parse(source).map_err(|e| e.to_domain_error(source))?;
+        let mut fits = false;
+        let mut unknown_field = None;
+        for schema in self.schemas.project_schemas(project_id)? {
+            let version = self.schemas.latest_version(schema.schema_id)?;
+            match expression.typed(&version) {
+                Ok(_) => fits = true,
+                Err(err) if err.kind == DslErrorKind::UnknownField => {
+                    unknown_field.get_or_insert(err);
+                }
+                Err(err) => return Err(err.to_domain_error(source)),
+            }
+        }
+        match (fits, unknown_field) {
+            (true, _) => Ok(()),
+            (false, Some(err)) => Err(err.to_domain_error(source)),
+            (false, None) => Err(DomainError {
+                code: ErrorCode::PreconditionFailed,
+                message: "Project has no schema to check the expression against".to_string(),
+                details: Some(serde_json::json!({ "project_id": project_id })),
+            }),
+        }
+    }
+}
+
+impl<'a> GenericCommandHandler for AddValidationRuleHandler<'a> {
+    fn can_handle(&self, command_type: &str) -> bool {
+        command_type == "AddValidationRule"
+    }
+
+    fn handle(&self, _ctx: &mut CommandContext, cmd: &AnyCommand) -> DomainResult<CommandOutcome> {
+        match cmd {
+            AnyCommand::AddValidationRule(c) => self.add(c),
+            _ => Err(unsupported_command("AddValidationRuleHandler")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::anchors::AnchorRule;
+    use crate::extraction::BoundingBox;
+    use crate::interfaces::{AnchorStore, EventStore};
+    use crate::item_rows::{AddItemRowHandler, DeleteItemRowHandler, ItemRowDeps};
+    use crate::mapping::{AssignFieldValueHandler, AssignItemValueHandler};
+    use crate::test_support::{command, ctx, World};
+
+    /// What the dispatcher appends for a command, as far as `ChangedOnly` reads it.
+    fn record(w: &World, session_id: Uuid, command_id: Uuid, outcome: &CommandOutcome) {
+        w.b.events
+            .append(&[EventEnvelope {
+                event_id: Uuid::now_v7(),
+                caused_by: command_id,
+                event_type: "Processed".to_string(),
+                timestamp: Utc::now(),
+                data: serde_json::json!({
+                    "session_id": session_id,
+                    "delta": outcome.state_delta.data,
+                }),
+            }])
+            .unwrap();
+    }
+
+    /// Deterministic linear congruential generator; `next(n)` is in `0..n`.
+    struct Lcg(u64);
+
+    impl Lcg {
+        fn next(&mut self, n: usize) -> usize {
+            self.0 = self
+                .0
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            ((self.0 >> 33) % n as u64) as usize
+        }
+    }
+
+    #[test]
+    fn changed_only_runs_match_full_runs_over_random_edits() {
+        let w = World::new(&[
+            ("invoice_number", "string", "document", true),
+            ("vendor", "string", "document", false),
+            ("subtotal", "currency", "document", false),
+            ("tax", "currency", "document", false),
+            ("total", "currency", "document", true),
+            ("invoice_date", "date", "document", false),
+            ("due_date", "date", "document", false),
+            ("amount", "currency", "item", false),
+        ]);
+        let checks = serde_json::json!({
+            "sum": { "check": "sum", "addend_keys": ["subtotal", "tax"], "field_key": "total" },
+            "items": { "check": "items_sum", "item_field_key": "amount", "field_key": "subtotal" },
+            "dates": { "check": "date_after", "field_key": "due_date", "after_field_key": "invoice_date" },
+            "unique": { "check": "unique", "field_key": "invoice_number", "group_key": "vendor" },
+        });
+        for (name, check) in checks.as_object().unwrap() {
+            AddValidationRuleHandler::new(&w.b.validation, &w.b.schemas)
+                .handle(
+                    &mut ctx(),
+                    &command(serde_json::json!({
+                        "type": "AddValidationRule",
+                        "payload": {
+                            "project_id": w.project_id,
+                            "name": name,
+                            "check": check,
+                            "severity": "blocking"
+                        }
+                    })),
+                )
+                .unwrap();
+        }
+        let anchor_id = Uuid::now_v7();
+        w.b.anchors
+            .add(&AnchorRule {
+                anchor_id,
+                project_id: w.project_id,
+                schema_field_id: w.field_id("invoice_number"),
+                spec: serde_json::from_value(serde_json::json!({
+                    "label": { "kind": "text", "text": "Invoice" },
+                    "direction": "right",
+                    "max_offset": 0.2
+                }))
+                .unwrap(),
+                enabled: true,
+                created_by: "tester".to_string(),
+            })
+            .unwrap();
+
+        let session_id = w.session(SessionStatus::Review);
+        let documents: Vec<(Uuid, serde_json::Value)> = (0..3)
+            .map(|_| {
+                let (document_id, page_id) = w.document(session_id);
+                let run_id = w.run(session_id, document_id, page_id, Vec::new(), Vec::new());
+                let provenance = serde_json::json!({
+                    "source": "anchor",
+                    "anchor_id": anchor_id,
+                    "extraction_run_id": run_id,
+                    "page_id": page_id,
+                    "bbox": BoundingBox { x: 0.1, y: 0.1, width: 0.2, height: 0.05 },
+                    "confidence": 0.9
+                });
+                (document_id, provenance)
+            })
+            .collect();
+        let other = w.session(SessionStatus::Review);
+        let (other_document, _) = w.document(other);
+        for (key, raw) in [("invoice_number", "A-1"), ("vendor", "Acme")] {
+            w.b.mapping
+                .put_field_value(&crate::mapping::FieldValue {
+                    field_value_id: Uuid::now_v7(),
+                    session_id: other,
+                    document_id: other_document,
+                    schema_field_id: w.field_id(key),
+                    raw_value: raw.to_string(),
+                    normalized_value: Some(raw.to_string()),
+                    provenance: crate::provenance::Provenance::manual("tester"),
+                    locked: false,
+                })
+                .unwrap();
+        }
+
+        let keys = [
+            "invoice_number",
+            "vendor",
+            "subtotal",
+            "tax",
+            "total",
+            "invoice_date",
+            "due_date",
+        ];
+        let pool = [
+            "",
+            "A-1",
+            "B-2",
+            "$100.00",
+            "$8.00",
+            "$108.00",
+            "$92",
+            "2024-01-01",
+            "2024-02-01",
+            "abc",
+            "Acme",
+        ];
+        let item_deps = || ItemRowDeps {
+            mapping: &w.b.mapping,
+            sessions: &w.b.sessions,
+            schemas: &w.b.schemas,
+            pages: &w.b.pages,
+        };
+        let validation = RunValidationHandler::new(ValidationDeps {
+            mapping: &w.b.mapping,
+            sessions: &w.b.sessions,
+            schemas: &w.b.schemas,
+            pages: &w.b.pages,
+            validation: &w.b.validation,
+            events: &w.b.events,
+            overrides: &w.b.overrides,
+        });
+        let mut rng = Lcg(42);
+        let (mut applied, mut reused) = (0, 0);
+
+        for round in 0..200 {
+            for _ in 0..rng.next(4) {
+                let (document_id, provenance) = &documents[rng.next(documents.len())];
+                let rows: Vec<Uuid> =
+                    w.b.mapping
+                        .item_rows(session_id)
+                        .unwrap()
+                        .into_iter()
+                        .filter(|r| r.document_id == *document_id)
+                        .map(|r| r.item_id)
+                        .collect();
+                let command_id = Uuid::now_v7();
+                let envelope = |command_type: &str, payload: serde_json::Value| {
+                    command(serde_json::json!({
+                        "type": command_type,
+                        "command_id": command_id,
+                        "payload": payload,
+                    }))
+                };
+                let outcome = match rng.next(9) {
+                    0..=5 => AssignFieldValueHandler::new(
+                        &w.b.mapping,
+                        &w.b.sessions,
+                        &w.b.schemas,
+                        w.provenance(),
+                        w.dictionary(),
+                    )
+                    .handle(
+                        &mut ctx(),
+                        &envelope(
+                            "AssignFieldValue",
+                            serde_json::json!({
+                                "session_id": session_id,
+                                "document_id": document_id,
+                                "schema_field_id": w.field_id(keys[rng.next(keys.len())]),
+                                "raw_value": pool[rng.next(pool.len())],
+                                "provenance": provenance,
+                            }),
+                        ),
+                    ),
+                    6 => AddItemRowHandler::new(item_deps()).handle(
+                        &mut ctx(),
+                        &envelope(
+                            "AddItemRow",
+                            serde_json::json!({
+                                "session_id": session_id,
+                                "document_id": document_id,
+                                "row_index": rng.next(rows.len() + 1),
+                            }),
+                        ),
+                    ),
+                    7 if !rows.is_empty() => DeleteItemRowHandler::new(item_deps()).handle(
+                        &mut ctx(),
+                        &envelope(
+                            "DeleteItemRow",
+                            serde_json::json!({
+                                "session_id": session_id,
+                                "item_id": rows[rng.next(rows.len())],
+                            }),
+                        ),
+                    ),
+                    _ if !rows.is_empty() => AssignItemValueHandler::new(
+                        &w.b.mapping,
+                        &w.b.sessions,
+                        &w.b.schemas,
+                        w.provenance(),
+                        w.dictionary(),
+                    )
+                    .handle(
+                        &mut ctx(),
+                        &envelope(
+                            "AssignItemValue",
+                            serde_json::json!({
+                                "session_id": session_id,
+                                "item_id": rows[rng.next(rows.len())],
+                                "schema_field_id": w.field_id("amount"),
+                                "raw_value": pool[rng.next(pool.len())],
+                                "provenance": { "source": "manual", "actor": "tester" },
+                            }),
+                        ),
+                    ),
+                    _ => continue,
+                };
+                // Rejected edits (unparseable manual values) change nothing.
+                if let Ok(outcome) = outcome {
+                    record(&w, session_id, command_id, &outcome);
+                    applied += 1;
+                }
+            }
+
+            let command_id = Uuid::now_v7();
+            let outcome = validation
+                .handle(
+                    &mut ctx(),
+                    &command(serde_json::json!({
+                        "type": "RunValidation",
+                        "command_id": command_id,
+                        "payload": { "session_id": session_id, "rule_scope": "changed_only" }
+                    })),
+                )
+                .unwrap();
+            record(&w, session_id, command_id, &outcome);
+            reused += outcome.state_delta.data["validation_run"]["checks_reused"]
+                .as_u64()
+                .unwrap();
+            let incremental: Vec<Finding> = serde_json::from_value::<Vec<ValidationResult>>(
+                outcome.state_delta.data["results"].clone(),
+            )
+            .unwrap()
+            .into_iter()
+            .map(|r| r.finding)
+            .collect();
+
+            let mut rules = schema_rules(&w.schema);
+            rules.extend(
+                w.b.validation
+                    .rules(w.project_id)
+                    .unwrap()
+                    .iter()
+                    .map(RuleInstance::from_rule),
+            );
+            let engine = ValidationEngine::new(rules).unwrap();
+            let snapshot =
+                SessionSnapshot::load(&w.b.mapping, &w.b.pages, session_id, w.schema.clone())
+                    .unwrap();
+            let dataset =
+                ProjectDataset::load(&w.b.sessions, &w.b.schemas, &w.b.mapping, session_id)
+                    .unwrap();
+            assert_eq!(
+                incremental,
+                engine.evaluate(&snapshot, &dataset),
+                "round {round}"
+            );
+        }
+        assert!(applied > 100, "only {applied} edits applied");
+        assert!(reused > 0, "changed_only never reused a check");
+    }
+
+    #[test]
+    fn expression_findings_are_stored_with_the_run() {
+        let w = World::new(&[
+            ("subtotal", "currency", "document", false),
+            ("amount", "currency", "item", false),
+        ]);
+        let added = AddValidationRuleHandler::new(&w.b.validation, &w.b.schemas)
+            .handle(
+                &mut ctx(),
+                &command(serde_json::json!({
+                    "type": "AddValidationRule",
+                    "payload": {
+                        "project_id": w.project_id,
+                        "name": "Items add up",
+                        "check": {
+                            "check": "expression",
+                            "expression": "sum(items.amount) == fields.subtotal ± 0.01"
+                        },
+                        "severity": "blocking"
+                    }
+                })),
+            )
+            .unwrap();
+        let rule: ValidationRule =
+            serde_json::from_value(added.state_delta.data["validation_rule"].clone()).unwrap();
+
+        let session_id = w.session(SessionStatus::Review);
+        let (document_id, _) = w.document(session_id);
+        let subtotal = crate::mapping::FieldValue {
+            field_value_id: Uuid::now_v7(),
+            session_id,
+            document_id,
+            schema_field_id: w.field_id("subtotal"),
+            raw_value: "100.02".to_string(),
+            normalized_value: Some("100.02".to_string()),
+            provenance: crate::provenance::Provenance::manual("tester"),
+            locked: false,
+        };
+        w.b.mapping.put_field_value(&subtotal).unwrap();
+        for (row_index, amount) in [(0, "40.00"), (1, "60.00")] {
+            let item_id = Uuid::now_v7();
+            w.b.mapping
+                .put_item_row(&crate::mapping::ItemRow {
+                    item_id,
+                    session_id,
+                    document_id,
+                    row_index,
+                    locked: false,
+                })
+                .unwrap();
+            w.b.mapping
+                .put_item_value(&crate::mapping::ItemValue {
+                    item_value_id: Uuid::now_v7(),
+                    session_id,
+                    item_id,
+                    schema_field_id: w.field_id("amount"),
+                    raw_value: amount.to_string(),
+                    normalized_value: Some(amount.to_string()),
+                    provenance: crate::provenance::Provenance::manual("tester"),
+                    locked: false,
+                })
+                .unwrap();
+        }
+
+        let outcome = RunValidationHandler::new(ValidationDeps {
+            mapping: &w.b.mapping,
+            sessions: &w.b.sessions,
+            schemas: &w.b.schemas,
+            pages: &w.b.pages,
+            validation: &w.b.validation,
+            events: &w.b.events,
+            overrides: &w.b.overrides,
+        })
+        .handle(
+            &mut ctx(),
+            &command(serde_json::json!({
+                "type": "RunValidation",
+                "payload": { "session_id": session_id, "rule_scope": "all" }
+            })),
+        )
+        .unwrap();
+
+        let run = w.b.validation.latest_run(session_id).unwrap().unwrap();
+        assert_eq!(run.blocking, 1);
+        let results = w.b.validation.results(run.validation_run_id).unwrap();
+        assert_eq!(results.len(), 1);
+        let result = &results[0];
+        assert_eq!(result.session_id, session_id);
+        let finding = &result.finding;
+        assert_eq!(finding.rule_key, rule.validation_rule_id.to_string());
+        assert_eq!(finding.validation_rule_id, Some(rule.validation_rule_id));
+        assert_eq!(finding.layer, RuleLayer::CrossField);
+        assert_eq!(finding.severity, Severity::Blocking);
+        assert_eq!(finding.document_id, document_id);
+        assert_eq!(finding.item_id, None);
+        assert_eq!(finding.field_keys, ["amount", "subtotal"]);
+        assert!(finding.value_ids.contains(&subtotal.field_value_id));
+        assert_eq!(finding.value_ids.len(), 3);
+        assert_eq!(
+            finding.message,
+            "Items add up failed: sum(items.amount) is 100, fields.subtotal is 100.02"
+        );
+        let reported: Vec<ValidationResult> =
+            serde_json::from_value(outcome.state_delta.data["results"].clone()).unwrap();
+        assert_eq!(
+            reported[0].validation_result_id,
+            result.validation_result_id
+        );
+    }
+}
@@ -0,0 +1,1548 @@
+use std::collections::HashMap;
+
+use chrono::{Duration, NaiveDate};
+use regex::{Regex, RegexBuilder};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::errors::{DomainError, ErrorCode};
+use crate::schema::SchemaVersion;
+use crate::types::{FieldScope, FieldType};
+use crate::validation::{DocumentValues, ItemValues, Observed, RuleLayer, PATTERN_SIZE_LIMIT};
+
+/// Longest accepted expression, in bytes.
+pub const MAX_EXPRESSION_LEN: usize = 2_000;
+/// Deepest accepted nesting of operators, calls and parentheses.
+pub const MAX_EXPRESSION_DEPTH: usize = 32;
+/// Numbers compared with `==` and no explicit tolerance may differ by this much,
+/// so that sums of decimal amounts compare as written.
+const EXACT_TOLERANCE: f64 = 1e-9;
+
+/// Byte range of a token or sub-expression in the source.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Span {
+    fn to(self, other: Span) -> Span {
+        Span {
+            start: self.start,
+            end: other.end,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DslErrorKind {
+    Syntax,
+    /// A key the schema version does not define; the rule does not apply to it.
+    UnknownField,
+    Type,
+}
+
+#[derive(Debug, Clone)]
+pub struct DslError {
+    pub kind: DslErrorKind,
+    pub message: String,
+    pub span: Span,
+}
+
+impl DslError {
+    fn new(kind: DslErrorKind, message: impl Into<String>, span: Span) -> Self {
+        Self {
+            kind,
+            message: message.into(),
+            span,
+        }
+    }
+
+    fn syntax(message: impl Into<String>, span: Span) -> Self {
+        Self::new(DslErrorKind::Syntax, message, span)
+    }
+
+    fn typing(message: impl Into<String>, span: Span) -> Self {
+        Self::new(DslErrorKind::Type, message, span)
+    }
+
+    /// 1-based line and column of the offending token.
+    pub fn position(&self, source: &str) -> (usize, usize) {
+        let before = &source[..self.span.start.min(source.len())];
+        let line = before.matches('\n').count() + 1;
+        let column = before.rsplit('\n').next().map_or(0, |l| l.chars().count()) + 1;
+        (line, column)
+    }
+
+    /// The offending line with the token underlined.
+    pub fn pointer(&self, source: &str) -> String {
+        let start = self.span.start.min(source.len());
+        let line_start = source[..start].rfind('\n').map_or(0, |i| i + 1);
+        let line_end = source[start..]
+            .find('\n')
+            .map_or(source.len(), |i| start + i);
+        let end = self.span.end.clamp(start, line_end);
+        let indent = source[line_start..start].chars().count();
+        let width = source[start..end].chars().count().max(1);
+        format!(
+            "{}\n{}{}",
+            &source[line_start..line_end],
+            " ".repeat(indent),
+            "^".repeat(width)
+        )
+    }
+
+    pub fn to_domain_error(&self, source: &str) -> DomainError {
+        let (line, column) = self.position(source);
+        let start = self.span.start.min(source.len());
+        let end = self.span.end.clamp(start, source.len());
+        DomainError {
+            code: ErrorCode::PreconditionFailed,
+            message: format!("{} (line {line}, column {column})", self.message),
+            details: Some(serde_json::json!({
+                "expression": source,
+                "kind": self.kind,
+                "offset": start,
+                "line": line,
+                "column": column,
+                "token": &source[start..end],
+                "pointer": self.pointer(source),
+            })),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Tok {
+    Number(f64),
+    Text(String),
+    Ident(String),
+    Dot,
+    Comma,
+    LParen,
+    RParen,
+    Plus,
+    Minus,
+    Star,
+    Slash,
+    PlusMinus,
+    Compare(CompareOp),
+    End,
+}
+
+#[derive(Debug, Clone)]
+struct Token {
+    tok: Tok,
+    span: Span,
+}
+
+fn lex(source: &str) -> Result<Vec<Token>, DslError> {
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while let Some(c) = source[i..].chars().next() {
+        if c.is_whitespace() {
+            i += c.len_utf8();
+            continue;
+        }
+        let rest = &source[i..];
+        let span = |len: usize| Span {
+            start: i,
+            end: i + len,
+        };
+        let (tok, len) = if c.is_ascii_digit() {
+            let len = rest
+                .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+                .unwrap_or(rest.len());
+            let number = rest[..len]
+                .parse::<f64>()
+                .ok()
+                .filter(|n| n.is_finite())
+                .ok_or_else(|| {
+                    DslError::syntax(format!("Invalid number '{}'", &rest[..len]), span(len))
+                })?;
+            (Tok::Number(number), len)
+        } else if c.is_ascii_alphabetic() || c == '_' {
+            let len = rest
+                .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+                .unwrap_or(rest.len());
+            (Tok::Ident(rest[..len].to_string()), len)
+        } else if c == '"' || c == '\'' {
+            lex_text(rest, c).ok_or_else(|| DslError::syntax("Unterminated string", span(1)))?
+        } else if rest.starts_with("+/-") {
+            (Tok::PlusMinus, 3)
+        } else {
+            let two = rest.get(..2).unwrap_or("");
+            match (c, two) {
+                (_, "==") => (Tok::Compare(CompareOp::Eq), 2),
+                (_, "!=") => (Tok::Compare(CompareOp::Ne), 2),
+                (_, "<=") => (Tok::Compare(CompareOp::Le), 2),
+                (_, ">=") => (Tok::Compare(CompareOp::Ge), 2),
+                ('<', _) => (Tok::Compare(CompareOp::Lt), 1),
+                ('>', _) => (Tok::Compare(CompareOp::Gt), 1),
+                ('±', _) => (Tok::PlusMinus, c.len_utf8()),
+                ('+', _) => (Tok::Plus, 1),
+                ('-', _) => (Tok::Minus, 1),
+                ('*', _) => (Tok::Star, 1),
+                ('/', _) => (Tok::Slash, 1),
+                ('.', _) => (Tok::Dot, 1),
+                (',', _) => (Tok::Comma, 1),
+                ('(', _) => (Tok::LParen, 1),
+                (')', _) => (Tok::RParen, 1),
+                ('=', _) => return Err(DslError::syntax("Use '==' to compare", span(1))),
+                ('&' | '|' | '!', _) => {
+                    return Err(DslError::syntax(
+                        "Use 'and', 'or' and 'not' to combine conditions",
+                        span(c.len_utf8()),
+                    ))
+                }
+                _ => {
+                    return Err(DslError::syntax(
+                        format!("Unexpected character '{c}'"),
+                        span(c.len_utf8()),
+                    ))
+                }
+            }
+        };
+        tokens.push(Token {
+            tok,
+            span: span(len),
+        });
+        i += len;
+    }
+    tokens.push(Token {
+        tok: Tok::End,
+        span: Span {
+            start: source.len(),
+            end: source.len(),
+        },
+    });
+    Ok(tokens)
+}
+
+/// String literal starting at `rest[0]`; `\` escapes the quote and itself.
+fn lex_text(rest: &str, quote: char) -> Option<(Tok, usize)> {
+    let mut text = String::new();
+    let mut chars = rest.char_indices().skip(1);
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '\\' => text.push(chars.next()?.1),
+            c if c == quote => return Some((Tok::Text(text), i + 1)),
+            c => text.push(c),
+        }
+    }
+    None
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CompareOp {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ArithOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum LogicOp {
+    And,
+    Or,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Aggregate {
+    Sum,
+    Count,
+    Min,
+    Max,
+}
+
+impl Aggregate {
+    fn name(self) -> &'static str {
+        match self {
+            Aggregate::Sum => "sum",
+            Aggregate::Count => "count",
+            Aggregate::Min => "min",
+            Aggregate::Max => "max",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Expr {
+    kind: ExprKind,
+    span: Span,
+    depth: usize,
+}
+
+#[derive(Debug, Clone)]
+enum ExprKind {
+    Number(f64),
+    Text(String),
+    Boolean(bool),
+    Date(NaiveDate),
+    /// `fields.<key>`: a document-level value.
+    Field(String),
+    /// `items.<key>`: the value of the current item row.
+    Item(String),
+    /// `extra.<table>.<column>`: the value of the current row of an extra table.
+    /// Only valid inside an aggregate.
+    Extra(String, String),
+    Neg(Box<Expr>),
+    Not(Box<Expr>),
+    Arith(ArithOp, Box<Expr>, Box<Expr>),
+    Logic(LogicOp, Box<Expr>, Box<Expr>),
+    Compare {
+        op: CompareOp,
+        left: Box<Expr>,
+        right: Box<Expr>,
+        tolerance: Option<Box<Expr>>,
+    },
+    /// Folds an item-row expression over the document's rows.
+    Aggregate(Aggregate, Box<Expr>),
+    Abs(Box<Expr>),
+    Round(Box<Expr>, u32),
+    Len(Box<Expr>),
+    Present(Box<Expr>),
+    Matches(Box<Expr>, Regex),
+}
+
+impl Expr {
+    fn new(kind: ExprKind, span: Span) -> Result<Self, DslError> {
+        let mut expr = Self {
+            kind,
+            span,
+            depth: 1,
+        };
+        expr.depth = 1 + expr.children().iter().map(|c| c.depth).max().unwrap_or(0);
+        if expr.depth > MAX_EXPRESSION_DEPTH {
+            return Err(DslError::syntax(
+                format!("Expression is nested more than {MAX_EXPRESSION_DEPTH} levels deep"),
+                span,
+            ));
+        }
+        Ok(expr)
+    }
+
+    fn children(&self) -> Vec<&Expr> {
+        match &self.kind {
+            ExprKind::Number(_)
+            | ExprKind::Text(_)
+            | ExprKind::Boolean(_)
+            | ExprKind::Date(_)
+            | ExprKind::Field(_)
+            | ExprKind::Item(_)
+            | ExprKind::Extra(..) => Vec::new(),
+            ExprKind::Neg(e)
+            | ExprKind::Not(e)
+            | ExprKind::Aggregate(_, e)
+            | ExprKind::Abs(e)
+            | ExprKind::Round(e, _)
+            | ExprKind::Len(e)
+            | ExprKind::Present(e)
+            | ExprKind::Matches(e, _) => vec![e],
+            ExprKind::Arith(_, l, r) | ExprKind::Logic(_, l, r) => vec![l, r],
+            ExprKind::Compare {
+                left,
+                right,
+                tolerance,
+                ..
+            } => [Some(left), Some(right), tolerance.as_ref()]
+                .into_iter()
+                .flatten()
+                .map(|e| &**e)
+                .collect(),
+        }
+    }
+
+    fn is_literal(&self) -> bool {
+        matches!(
+            self.kind,
+            ExprKind::Number(_) | ExprKind::Text(_) | ExprKind::Boolean(_) | ExprKind::Date(_)
+        )
+    }
+
+    /// Extra table an aggregate argument folds over; `None` for item rows.
+    fn extra_table(&self) -> Option<&str> {
+        let mut table = None;
+        self.visit(&mut |e| {
+            if let ExprKind::Extra(table_name, _) = &e.kind {
+                table.get_or_insert(table_name.as_str());
+            }
+        });
+        table
+    }
+
+    fn visit<'e>(&'e self, f: &mut impl FnMut(&'e Expr)) {
+        f(self);
+        for child in self.children() {
+            child.visit(f);
+        }
+    }
+}
+
+struct Parser<'s> {
+    source: &'s str,
+    tokens: Vec<Token>,
+    pos: usize,
+    /// Open sub-expressions, bounding recursion before any node is built.
+    depth: usize,
+}
+
+impl<'s> Parser<'s> {
+    fn peek(&self) -> &Token {
+        &self.tokens[self.pos]
+    }
+
+    fn next(&mut self) -> Token {
+        let token = self.tokens[self.pos].clone();
+        if token.tok != Tok::End {
+            self.pos += 1;
+        }
+        token
+    }
+
+    fn is_keyword(&self, word: &str) -> bool {
+        matches!(&self.peek().tok, Tok::Ident(w) if w == word)
+    }
+
+    fn expect(&mut self, tok: Tok, what: &str) -> Result<Token, DslError> {
+        if self.peek().tok == tok {
+            Ok(self.next())
+        } else {
+            Err(self.unexpected(what))
+        }
+    }
+
+    fn unexpected(&self, what: &str) -> DslError {
+        let token = self.peek();
+        let found = match token.tok {
+            Tok::End => "end of expression".to_string(),
+            _ => format!("'{}'", &self.source[token.span.start..token.span.end]),
+        };
+        DslError::syntax(format!("Expected {what}, found {found}"), token.span)
+    }
+
+    fn nested(
+        &mut self,
+        parse: impl FnOnce(&mut Self) -> Result<Expr, DslError>,
+    ) -> Result<Expr, DslError> {
+        if self.depth >= MAX_EXPRESSION_DEPTH {
+            return Err(DslError::syntax(
+                format!("Expression is nested more than {MAX_EXPRESSION_DEPTH} levels deep"),
+                self.peek().span,
+            ));
+        }
+        self.depth += 1;
+        let expr = parse(self);
+        self.depth -= 1;
+        expr
+    }
+
+    fn or(&mut self) -> Result<Expr, DslError> {
+        self.nested(Self::or_chain)
+    }
+
+    fn or_chain(&mut self) -> Result<Expr, DslError> {
+        let mut left = self.and()?;
+        while self.is_keyword("or") {
+            self.next();
+            let right = self.and()?;
+            let span = left.span.to(right.span);
+            left = Expr::new(
+                ExprKind::Logic(LogicOp::Or, Box::new(left), Box::new(right)),
+                span,
+            )?;
+        }
+        Ok(left)
+    }
+
+    fn and(&mut self) -> Result<Expr, DslError> {
+        let mut left = self.not()?;
+        while self.is_keyword("and") {
+            self.next();
+            let right = self.not()?;
+            let span = left.span.to(right.span);
+            left = Expr::new(
+                ExprKind::Logic(LogicOp::And, Box::new(left), Box::new(right)),
+                span,
+            )?;
+        }
+        Ok(left)
+    }
+
+    fn not(&mut self) -> Result<Expr, DslError> {
+        if self.is_keyword("not") {
+            let keyword = self.next();
+            let operand = self.nested(Self::not)?;
+            let span = keyword.span.to(operand.span);
+            return Expr::new(ExprKind::Not(Box::new(operand)), span);
+        }
+        self.comparison()
+    }
+
+    fn comparison(&mut self) -> Result<Expr, DslError> {
+        let left = self.additive()?;
+        let Tok::Compare(op) = self.peek().tok else {
+            if self.peek().tok == Tok::PlusMinus {
+                return Err(DslError::syntax(
+                    "A tolerance must follow a comparison, e.g. a == b ± 0.01",
+                    self.peek().span,
+                ));
+            }
+            return Ok(left);
+        };
+        self.next();
+        let right = self.additive()?;
+        let tolerance = if self.peek().tok == Tok::PlusMinus {
+            self.next();
+            Some(Box::new(self.additive()?))
+        } else {
+            None
+        };
+        if let Tok::Compare(_) = self.peek().tok {
+            return Err(DslError::syntax(
+                "Comparisons cannot be chained; combine them with 'and'",
+                self.peek().span,
+            ));
+        }
+        let end = tolerance.as_ref().map_or(right.span, |t| t.span);
+        let span = left.span.to(end);
+        Expr::new(
+            ExprKind::Compare {
+                op,
+                left: Box::new(left),
+                right: Box::new(right),
+                tolerance,
+            },
+            span,
+        )
+    }
+
+    fn additive(&mut self) -> Result<Expr, DslError> {
+        let mut left = self.multiplicative()?;
+        loop {
+            let op = match self.peek().tok {
+                Tok::Plus => ArithOp::Add,
+                Tok::Minus => ArithOp::Sub,
+                _ => return Ok(left),
+            };
+            self.next();
+            let right = self.multiplicative()?;
+            let span = left.span.to(right.span);
+            left = Expr::new(ExprKind::Arith(op, Box::new(left), Box::new(right)), span)?;
+        }
+    }
+
+    fn multiplicative(&mut self) -> Result<Expr, DslError> {
+        let mut left = self.unary()?;
+        loop {
+            let op = match self.peek().tok {
+                Tok::Star => ArithOp::Mul,
+                Tok::Slash => ArithOp::Div,
+                _ => return Ok(left),
+            };
+            self.next();
+            let right = self.unary()?;
+            let span = left.span.to(right.span);
+            left = Expr::new(ExprKind::Arith(op, Box::new(left), Box::new(right)), span)?;
+        }
+    }
+
+    fn unary(&mut self) -> Result<Expr, DslError> {
+        if self.peek().tok == Tok::Minus {
+            let minus = self.next();
+            let operand = self.nested(Self::unary)?;
+            let span = minus.span.to(operand.span);
+            return Expr::new(ExprKind::Neg(Box::new(operand)), span);
+        }
+        self.primary()
+    }
+
+    fn primary(&mut self) -> Result<Expr, DslError> {
+        let token = self.next();
+        match token.tok {
+            Tok::Number(n) => Expr::new(ExprKind::Number(n), token.span),
+            Tok::Text(text) => Expr::new(ExprKind::Text(text), token.span),
+            Tok::LParen => {
+                let inner = self.or()?;
+                let close = self.expect(Tok::RParen, "')'")?;
+                Expr::new(inner.kind, token.span.to(close.span))
+            }
+            Tok::Ident(word) => match word.as_str() {
+                "true" | "false" => Expr::new(ExprKind::Boolean(word == "true"), token.span),
+                "fields" | "items" => {
+                    self.expect(Tok::Dot, "'.' and a field key")?;
+                    let Tok::Ident(field_key) = self.peek().tok.clone() else {
+                        return Err(self.unexpected("a field key"));
+                    };
+                    let key = self.next();
+                    let kind = if word == "fields" {
+                        ExprKind::Field(field_key)
+                    } else {
+                        ExprKind::Item(field_key)
+                    };
+                    Expr::new(kind, token.span.to(key.span))
+                }
+                "extra" => {
+                    self.expect(Tok::Dot, "'.' and a table name")?;
+                    let Tok::Ident(table_name) = self.peek().tok.clone() else {
+                        return Err(self.unexpected("a table name"));
+                    };
+                    self.next();
+                    self.expect(Tok::Dot, "'.' and a column key")?;
+                    let Tok::Ident(column) = self.peek().tok.clone() else {
+                        return Err(self.unexpected("a column key"));
+                    };
+                    let key = self.next();
+                    Expr::new(ExprKind::Extra(table_name, column), token.span.to(key.span))
+                }
+                "and" | "or" | "not" => Err(DslError::syntax(
+                    format!("Expected a value before '{word}'"),
+                    token.span,
+                )),
+                _ if self.peek().tok == Tok::LParen => self.call(&word, token.span),
+                _ => Err(DslError::syntax(
+                    format!(
+                        "Unknown name '{word}'; use fields.<key>, items.<key>, \
+                         extra.<table>.<column> or a function"
+                    ),
+                    token.span,
+                )),
+            },
+            _ => {
+                self.pos -= usize::from(token.tok != Tok::End);
+                Err(self.unexpected("a value"))
+            }
+        }
+    }
+
+    fn call(&mut self, name: &str, name_span: Span) -> Result<Expr, DslError> {
+        self.next();
+        let mut args = Vec::new();
+        if self.peek().tok != Tok::RParen {
+            args.push(self.or()?);
+            while self.peek().tok == Tok::Comma {
+                self.next();
+                args.push(self.or()?);
+            }
+        }
+        let close = self.expect(Tok::RParen, "',' or ')'")?;
+        let span = name_span.to(close.span);
+        let arity = |n: usize| {
+            if args.len() == n {
+                Ok(())
+            } else {
+                Err(DslError::syntax(
+                    format!("{name}() takes {n} argument(s), found {}", args.len()),
+                    span,
+                ))
+            }
+        };
+        let kind = match name {
+            "sum" | "count" | "min" | "max" => {
+                arity(1)?;
+                let aggregate = match name {
+                    "sum" => Aggregate::Sum,
+                    "count" => Aggregate::Count,
+                    "min" => Aggregate::Min,
+                    _ => Aggregate::Max,
+                };
+                ExprKind::Aggregate(aggregate, Box::new(args.remove(0)))
+            }
+            "abs" | "len" => {
+                arity(1)?;
+                let arg = Box::new(args.remove(0));
+                if name == "abs" {
+                    ExprKind::Abs(arg)
+                } else {
+                    ExprKind::Len(arg)
+                }
+            }
+            "present" => {
+                arity(1)?;
+                let arg = args.remove(0);
+                if !matches!(
+                    arg.kind,
+                    ExprKind::Field(_) | ExprKind::Item(_) | ExprKind::Extra(..)
+                ) {
+                    return Err(DslError::syntax(
+                        "present() takes a field, e.g. present(fields.po_number)",
+                        arg.span,
+                    ));
+                }
+                ExprKind::Present(Box::new(arg))
+            }
+            "round" => {
+                arity(2)?;
+                let digits = args.remove(1);
+                let places = match digits.kind {
+                    ExprKind::Number(n) if n.fract() == 0.0 && (0.0..=6.0).contains(&n) => n as u32,
+                    _ => {
+                        return Err(DslError::syntax(
+                            "round() takes a whole number of decimal places from 0 to 6",
+                            digits.span,
+                        ))
+                    }
+                };
+                ExprKind::Round(Box::new(args.remove(0)), places)
+            }
+            "date" => {
+                arity(1)?;
+                let arg = args.remove(0);
+                let date = match &arg.kind {
+                    ExprKind::Text(text) => NaiveDate::parse_from_str(text, "%Y-%m-%d").ok(),
+                    _ => None,
+                };
+                let date = date.ok_or_else(|| {
+                    DslError::syntax("date() takes a \"YYYY-MM-DD\" string", arg.span)
+                })?;
+                ExprKind::Date(date)
+            }
+            "matches" => {
+                arity(2)?;
+                let pattern = args.remove(1);
+                let ExprKind::Text(text) = &pattern.kind else {
+                    return Err(DslError::syntax(
+                        "matches() takes a pattern string as its second argument",
+                        pattern.span,
+                    ));
+                };
+                let regex = RegexBuilder::new(text)
+                    .size_limit(PATTERN_SIZE_LIMIT)
+                    .build()
+                    .map_err(|err| {
+                        DslError::syntax(format!("Invalid pattern: {err}"), pattern.span)
+                    })?;
+                ExprKind::Matches(Box::new(args.remove(0)), regex)
+            }
+            _ => {
+                return Err(DslError::syntax(
+                    format!(
+                        "Unknown function '{name}'; available: sum, count, min, max, abs, \
+                         round, len, present, matches, date"
+                    ),
+                    name_span,
+                ))
+            }
+        };
+        Expr::new(kind, span)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ValueType {
+    Number,
+    Text,
+    Date,
+    Boolean,
+}
+
+impl ValueType {
+    fn of(field_type: FieldType) -> Self {
+        match field_type {
+            FieldType::Integer | FieldType::Decimal | FieldType::Currency => ValueType::Number,
+            FieldType::Date => ValueType::Date,
+            FieldType::Boolean => ValueType::Boolean,
+            FieldType::String | FieldType::Enum => ValueType::Text,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            ValueType::Number => "a number",
+            ValueType::Text => "text",
+            ValueType::Date => "a date",
+            ValueType::Boolean => "a condition",
+        }
+    }
+}
+
+/// A parsed rule expression: a condition over `fields.<key>` (document values),
+/// `items.<key>` (item row values) and `extra.<table>.<column>` (extra-table values,
+/// inside aggregates). An item value outside an aggregate makes the rule apply to
+/// each row. Parsing is bounded by [`MAX_EXPRESSION_LEN`] and
+/// [`MAX_EXPRESSION_DEPTH`]; evaluation only folds over the document's rows.
+#[derive(Debug, Clone)]
+pub struct Expression {
+    source: String,
+    root: Expr,
+}
+
+impl Expression {
+    pub fn parse(source: &str) -> Result<Self, DslError> {
+        if source.len() > MAX_EXPRESSION_LEN {
+            return Err(DslError::syntax(
+                format!("Expression is longer than {MAX_EXPRESSION_LEN} characters"),
+                Span {
+                    start: 0,
+                    end: source.len(),
+                },
+            ));
+        }
+        let mut parser = Parser {
+            source,
+            tokens: lex(source)?,
+            pos: 0,
+            depth: 0,
+        };
+        if parser.peek().tok == Tok::End {
+            return Err(parser.unexpected("a condition"));
+        }
+        let root = parser.or()?;
+        if parser.peek().tok != Tok::End {
+            return Err(parser.unexpected("an operator or the end of the expression"));
+        }
+        Ok(Self {
+            source: source.to_string(),
+            root,
+        })
+    }
+
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    /// Field keys in order of first use; extra-table columns as `<table>.<column>`.
+    pub fn field_keys(&self) -> Vec<String> {
+        let mut keys: Vec<String> = Vec::new();
+        self.root.visit(&mut |e| {
+            let key = match &e.kind {
+                ExprKind::Field(key) | ExprKind::Item(key) => key.clone(),
+                ExprKind::Extra(table_name, column) => format!("{table_name}.{column}"),
+                _ => return,
+            };
+            if !keys.contains(&key) {
+                keys.push(key);
+            }
+        });
+        keys
+    }
+
+    /// A single document value is a field rule; anything reading several values or
+    /// item rows is cross-field.
+    pub fn layer(&self) -> RuleLayer {
+        let mut aggregates = false;
+        self.root.visit(&mut |e| {
+            aggregates |= matches!(e.kind, ExprKind::Aggregate(..));
+        });
+        if !aggregates && self.field_keys().len() == 1 {
+            RuleLayer::Field
+        } else {
+            RuleLayer::CrossField
+        }
+    }
+
+    /// Type-checks the expression against a schema version.
+    pub fn typed(&self, schema: &SchemaVersion) -> Result<TypedExpression<'_>, DslError> {
+        let mut checker = Checker {
+            schema,
+            types: HashMap::new(),
+            row_scoped: false,
+        };
+        let root = checker.check(&self.root, false)?;
+        if root != ValueType::Boolean {
+            return Err(DslError::typing(
+                format!(
+                    "A rule must be a condition (true or false), found {}",
+                    root.name()
+                ),
+                self.root.span,
+            ));
+        }
+        Ok(TypedExpression {
+            expression: self,
+            types: checker.types,
+            row_scoped: checker.row_scoped,
+        })
+    }
+}
+
+struct Checker<'s> {
+    schema: &'s SchemaVersion,
+    types: HashMap<String, ValueType>,
+    row_scoped: bool,
+}
+
+impl<'s> Checker<'s> {
+    fn field(&mut self, key: &str, scope: FieldScope, span: Span) -> Result<ValueType, DslError> {
+        let Some(field) = self.schema.field_by_key(key) else {
+            return Err(DslError::new(
+                DslErrorKind::UnknownField,
+                format!("Unknown field '{key}'"),
+                span,
+            ));
+        };
+        if field.scope != scope {
+            let hint = match field.scope {
+                FieldScope::Document => format!("'{key}' is a document field; use fields.{key}"),
+                FieldScope::Item => format!("'{key}' is an item field; use items.{key}"),
+                FieldScope::ExtraTable => {
+                    format!("'{key}' is an extra-table column; use extra.{key}")
+                }
+            };
+            return Err(DslError::typing(hint, span));
+        }
+        let value_type = ValueType::of(field.field_type);
+        self.types.insert(key.to_string(), value_type);
+        Ok(value_type)
+    }
+
+    fn expect(
+        &mut self,
+        expr: &Expr,
+        in_aggregate: bool,
+        allowed: &[ValueType],
+        context: &str,
+    ) -> Result<ValueType, DslError> {
+        let found = self.check(expr, in_aggregate)?;
+        if allowed.contains(&found) {
+            Ok(found)
+        } else {
+            let wanted: Vec<&str> = allowed.iter().map(|t| t.name()).collect();
+            Err(DslError::typing(
+                format!(
+                    "{context} needs {}, found {}",
+                    wanted.join(" or "),
+                    found.name()
+                ),
+                expr.span,
+            ))
+        }
+    }
+
+    fn check(&mut self, expr: &Expr, in_aggregate: bool) -> Result<ValueType, DslError> {
+        use ValueType::{Boolean, Date, Number, Text};
+        Ok(match &expr.kind {
+            ExprKind::Number(_) => Number,
+            ExprKind::Text(_) => Text,
+            ExprKind::Boolean(_) => Boolean,
+            ExprKind::Date(_) => Date,
+            ExprKind::Field(key) => self.field(key, FieldScope::Document, expr.span)?,
+            ExprKind::Item(key) => {
+                self.row_scoped |= !in_aggregate;
+                self.field(key, FieldScope::Item, expr.span)?
+            }
+            ExprKind::Extra(table_name, column) => {
+                if !in_aggregate {
+                    return Err(DslError::typing(
+                        format!(
+                            "extra.{table_name}.{column} has a value per row; fold it with \
+                             sum(), count(), min() or max()"
+                        ),
+                        expr.span,
+                    ));
+                }
+                let key = format!("{table_name}.{column}");
+                self.field(&key, FieldScope::ExtraTable, expr.span)?
+            }
+            ExprKind::Neg(e) | ExprKind::Abs(e) | ExprKind::Round(e, _) => {
+                self.expect(e, in_aggregate, &[Number], "This operation")?
+            }
+            ExprKind::Len(e) => {
+                self.expect(e, in_aggregate, &[Text], "len()")?;
+                Number
+            }
+            ExprKind::Matches(e, _) => {
+                self.expect(e, in_aggregate, &[Text], "matches()")?;
+                Boolean
+            }
+            ExprKind::Present(e) => {
+                self.check(e, in_aggregate)?;
+                Boolean
+            }
+            ExprKind::Not(e) => self.expect(e, in_aggregate, &[Boolean], "'not'")?,
+            ExprKind::Logic(op, l, r) => {
+                let context = match op {
+                    LogicOp::And => "'and'",
+                    LogicOp::Or => "'or'",
+                };
+                self.expect(l, in_aggregate, &[Boolean], context)?;
+                self.expect(r, in_aggregate, &[Boolean], context)?
+            }
+            ExprKind::Arith(op, l, r) => match op {
+                ArithOp::Mul | ArithOp::Div => {
+                    self.expect(l, in_aggregate, &[Number], "Multiplication and division")?;
+                    self.expect(r, in_aggregate, &[Number], "Multiplication and division")?
+                }
+                ArithOp::Add => {
+                    let left = self.expect(l, in_aggregate, &[Number, Date], "Addition")?;
+                    self.expect(r, in_aggregate, &[Number], "Addition")?;
+                    left
+                }
+                ArithOp::Sub => {
+                    match self.expect(l, in_aggregate, &[Number, Date], "Subtraction")? {
+                        Number => self.expect(r, in_aggregate, &[Number], "Subtraction")?,
+                        _ => match self.expect(r, in_aggregate, &[Number, Date], "Subtraction")? {
+                            Date => Number,
+                            _ => Date,
+                        },
+                    }
+                }
+            },
+            ExprKind::Compare {
+                op,
+                left,
+                right,
+                tolerance,
+            } => {
+                let left_type = self.check(left, in_aggregate)?;
+                let ordered = !matches!(op, CompareOp::Eq | CompareOp::Ne);
+                if ordered && !matches!(left_type, Number | Date) {
+                    return Err(DslError::typing(
+                        format!(
+                            "Only numbers and dates can be ordered, found {}",
+                            left_type.name()
+                        ),
+                        left.span,
+                    ));
+                }
+                self.expect(right, in_aggregate, &[left_type], "This comparison")?;
+                if let Some(tolerance) = tolerance {
+                    if ordered || left_type != Number {
+                        return Err(DslError::typing(
+                            "A tolerance only applies to '==' or '!=' between numbers",
+                            tolerance.span,
+                        ));
+                    }
+                    self.expect(tolerance, in_aggregate, &[Number], "A tolerance")?;
+                }
+                Boolean
+            }
+            ExprKind::Aggregate(aggregate, e) => {
+                if in_aggregate {
+                    return Err(DslError::typing("Aggregates cannot be nested", expr.span));
+                }
+                let mut reads_items = false;
+                let mut tables: Vec<&str> = Vec::new();
+                e.visit(&mut |inner| match &inner.kind {
+                    ExprKind::Item(_) => reads_items = true,
+                    ExprKind::Extra(table_name, _) if !tables.contains(&table_name.as_str()) => {
+                        tables.push(table_name)
+                    }
+                    _ => {}
+                });
+                if !reads_items && tables.is_empty() {
+                    return Err(DslError::typing(
+                        format!(
+                            "{}() folds over item rows and needs an item field, e.g. {}(items.amount), \
+                             or over an extra table, e.g. {}(extra.fees.amount)",
+                            aggregate.name(),
+                            aggregate.name(),
+                            aggregate.name()
+                        ),
+                        e.span,
+                    ));
+                }
+                if usize::from(reads_items) + tables.len() > 1 {
+                    return Err(DslError::typing(
+                        format!(
+                            "{}() folds over one table; it cannot mix item rows and extra tables",
+                            aggregate.name()
+                        ),
+                        e.span,
+                    ));
+                }
+                let context = format!("{}()", aggregate.name());
+                match aggregate {
+                    Aggregate::Sum => self.expect(e, true, &[Number], &context)?,
+                    Aggregate::Min | Aggregate::Max => {
+                        self.expect(e, true, &[Number, Date], &context)?
+                    }
+                    Aggregate::Count => {
+                        self.check(e, true)?;
+                        Number
+                    }
+                }
+            }
+        })
+    }
+}
+
+/// An expression that type-checks against a schema version.
+pub struct TypedExpression<'e> {
+    expression: &'e Expression,
+    types: HashMap<String, ValueType>,
+    row_scoped: bool,
+}
+
+/// One document, or one item row of it, failing the expression.
+#[derive(Debug, Clone)]
+pub struct Violation {
+    pub item_id: Option<Uuid>,
+    pub value_ids: Vec<Uuid>,
+    /// Values on both sides when the expression is a comparison.
+    pub detail: Option<String>,
+}
+
+/// Missing (blank) and invalid (unparsed) values make the result unknown, and a
+/// rule only fails on a definite `false`: the required and typed rules report
+/// those values instead.
+#[derive(Debug, Clone, PartialEq)]
+enum Value {
+    Missing,
+    Invalid,
+    Number(f64),
+    Text(String),
+    Date(NaiveDate),
+    Boolean(bool),
+}
+
+impl Value {
+    fn is_known(&self) -> bool {
+        !matches!(self, Value::Missing | Value::Invalid)
+    }
+
+    fn describe(&self) -> String {
+        match self {
+            Value::Missing => "missing".to_string(),
+            Value::Invalid => "not a valid value".to_string(),
+            Value::Number(n) => format!("{}", (n * 1e6).round() / 1e6),
+            Value::Text(t) => format!("'{t}'"),
+            Value::Date(d) => d.format("%Y-%m-%d").to_string(),
+            Value::Boolean(b) => b.to_string(),
+        }
+    }
+}
+
+/// The less informative of two unknown operands.
+fn unknown(a: &Value, b: &Value) -> Value {
+    if matches!(a, Value::Invalid) || matches!(b, Value::Invalid) {
+        Value::Invalid
+    } else {
+        Value::Missing
+    }
+}
+
+struct Scope<'d> {
+    document: &'d DocumentValues,
+    /// Values of the current item or extra row.
+    row: Option<&'d HashMap<String, Observed>>,
+    value_ids: Vec<Uuid>,
+}
+
+impl<'e> TypedExpression<'e> {
+    pub fn violations(&self, document: &DocumentValues) -> Vec<Violation> {
+        let rows: Vec<Option<&ItemValues>> = if self.row_scoped {
+            document.items.iter().map(Some).collect()
+        } else {
+            vec![None]
+        };
+        let mut violations = Vec::new();
+        for row in rows {
+            let mut scope = Scope {
+                document,
+                row: row.map(|r| &r.values),
+                value_ids: Vec::new(),
+            };
+            if self.eval(&self.expression.root, &mut scope) != Value::Boolean(false) {
+                continue;
+            }
+            let detail = match &self.expression.root.kind {
+                ExprKind::Compare { left, right, .. } => {
+                    let sides: Vec<String> = [left, right]
+                        .into_iter()
+                        .filter(|side| !side.is_literal())
+                        .map(|side| {
+                            let value = self.eval(side, &mut scope).describe();
+                            format!("{} is {value}", self.snippet(side))
+                        })
+                        .collect();
+                    (!sides.is_empty()).then(|| sides.join(", "))
+                }
+                _ => None,
+            };
+            let mut value_ids = Vec::new();
+            for id in scope.value_ids {
+                if !value_ids.contains(&id) {
+                    value_ids.push(id);
+                }
+            }
+            violations.push(Violation {
+                item_id: row.map(|r| r.item_id),
+                value_ids,
+                detail,
+            });
+        }
+        violations
+    }
+
+    fn snippet(&self, expr: &Expr) -> &str {
+        &self.expression.source[expr.span.start..expr.span.end]
+    }
+
+    fn read(&self, key: &str, observed: Option<&Observed>, scope: &mut Scope) -> Value {
+        let Some(observed) = observed else {
+            return Value::Missing;
+        };
+        scope.value_ids.push(observed.value_id);
+        if observed.is_blank() {
+            return Value::Missing;
+        }
+        let normalized = observed.normalized_value.as_deref();
+        let value = match self.types.get(key) {
+            Some(ValueType::Text) | None => Some(Value::Text(observed.text().to_string())),
+            Some(ValueType::Number) => normalized
+                .and_then(|n| n.split(' ').next()?.parse::<f64>().ok())
+                .map(Value::Number),
+            Some(ValueType::Date) => normalized
+                .and_then(|n| NaiveDate::parse_from_str(n, "%Y-%m-%d").ok())
+                .map(Value::Date),
+            Some(ValueType::Boolean) => normalized
+                .and_then(|n| n.parse::<bool>().ok())
+                .map(Value::Boolean),
+        };
+        value.unwrap_or(Value::Invalid)
+    }
+
+    fn eval(&self, expr: &Expr, scope: &mut Scope) -> Value {
+        match &expr.kind {
+            ExprKind::Number(n) => Value::Number(*n),
+            ExprKind::Text(t) => Value::Text(t.clone()),
+            ExprKind::Boolean(b) => Value::Boolean(*b),
+            ExprKind::Date(d) => Value::Date(*d),
+            ExprKind::Field(key) => {
+                let document = scope.document;
+                self.read(key, document.fields.get(key), scope)
+            }
+            ExprKind::Item(key) => {
+                let row = scope.row;
+                self.read(key, row.and_then(|r| r.get(key)), scope)
+            }
+            ExprKind::Extra(table_name, column) => {
+                let row = scope.row;
+                let key = format!("{table_name}.{column}");
+                self.read(&key, row.and_then(|r| r.get(column)), scope)
+            }
+            ExprKind::Neg(e) => match self.eval(e, scope) {
+                Value::Number(n) => Value::Number(-n),
+                other => other,
+            },
+            ExprKind::Abs(e) => match self.eval(e, scope) {
+                Value::Number(n) => Value::Number(n.abs()),
+                other => other,
+            },
+            ExprKind::Round(e, places) => match self.eval(e, scope) {
+                Value::Number(n) => {
+                    let factor = 10f64.powi(*places as i32);
+                    Value::Number((n * factor).round() / factor)
+                }
+                other => other,
+            },
+            ExprKind::Len(e) => match self.eval(e, scope) {
+                Value::Text(t) => Value::Number(t.chars().count() as f64),
+                other => other,
+            },
+            ExprKind::Matches(e, regex) => match self.eval(e, scope) {
+                Value::Text(t) => Value::Boolean(regex.is_match(&t)),
+                other => other,
+            },
+            ExprKind::Present(e) => Value::Boolean(!matches!(self.eval(e, scope), Value::Missing)),
+            ExprKind::Not(e) => match self.eval(e, scope) {
+                Value::Boolean(b) => Value::Boolean(!b),
+                other => other,
+            },
+            ExprKind::Logic(op, l, r) => {
+                let (left, right) = (self.eval(l, scope), self.eval(r, scope));
+                let decisive = *op == LogicOp::Or;
+                match (&left, &right) {
+                    (Value::Boolean(a), _) if *a == decisive => Value::Boolean(decisive),
+                    (_, Value::Boolean(b)) if *b == decisive => Value::Boolean(decisive),
+                    (Value::Boolean(_), Value::Boolean(_)) => Value::Boolean(!decisive),
+                    _ => unknown(&left, &right),
+                }
+            }
+            ExprKind::Arith(op, l, r) => {
+                let (left, right) = (self.eval(l, scope), self.eval(r, scope));
+                arith(*op, &left, &right)
+            }
+            ExprKind::Compare {
+                op,
+                left,
+                right,
+                tolerance,
+            } => {
+                let (left, right) = (self.eval(left, scope), self.eval(right, scope));
+                let tolerance = match tolerance {
+                    Some(t) => self.eval(t, scope),
+                    None => Value::Number(EXACT_TOLERANCE),
+                };
+                compare(*op, &left, &right, &tolerance)
+            }
+            ExprKind::Aggregate(aggregate, e) => {
+                let document = scope.document;
+                let outer = scope.row;
+                let rows: Vec<&HashMap<String, Observed>> = match e.extra_table() {
+                    Some(table_name) => document
+                        .extras
+                        .get(table_name)
+                        .into_iter()
+                        .flatten()
+                        .map(|r| &r.values)
+                        .collect(),
+                    None => document.items.iter().map(|r| &r.values).collect(),
+                };
+                let mut values = Vec::new();
+                for row in rows {
+                    scope.row = Some(row);
+                    values.push(self.eval(e, scope));
+                }
+                scope.row = outer;
+                fold(*aggregate, values)
+            }
+        }
+    }
+}
+
+fn arith(op: ArithOp, left: &Value, right: &Value) -> Value {
+    let result = match (op, left, right) {
+        (ArithOp::Add, Value::Number(a), Value::Number(b)) => Value::Number(a + b),
+        (ArithOp::Sub, Value::Number(a), Value::Number(b)) => Value::Number(a - b),
+        (ArithOp::Mul, Value::Number(a), Value::Number(b)) => Value::Number(a * b),
+        (ArithOp::Div, Value::Number(_), Value::Number(b)) if *b == 0.0 => Value::Invalid,
+        (ArithOp::Div, Value::Number(a), Value::Number(b)) => Value::Number(a / b),
+        (ArithOp::Sub, Value::Date(a), Value::Date(b)) => {
+            Value::Number((*a - *b).num_days() as f64)
+        }
+        (ArithOp::Add | ArithOp::Sub, Value::Date(d), Value::Number(n)) => {
+            if n.fract() != 0.0 || n.abs() > 1e6 {
+                return Value::Invalid;
+            }
+            let days = Duration::days(*n as i64);
+            let shifted = if op == ArithOp::Add {
+                d.checked_add_signed(days)
+            } else {
+                d.checked_sub_signed(days)
+            };
+            shifted.map_or(Value::Invalid, Value::Date)
+        }
+        _ => return unknown(left, right),
+    };
+    match result {
+        Value::Number(n) if !n.is_finite() => Value::Invalid,
+        other => other,
+    }
+}
+
+fn compare(op: CompareOp, left: &Value, right: &Value, tolerance: &Value) -> Value {
+    let ordering = match (left, right) {
+        (Value::Number(a), Value::Number(b)) if matches!(op, CompareOp::Eq | CompareOp::Ne) => {
+            let Value::Number(tolerance) = tolerance else {
+                return unknown(tolerance, tolerance);
+            };
+            if *tolerance < 0.0 {
+                return Value::Invalid;
+            }
+            let equal = (a - b).abs() <= tolerance + f64::EPSILON;
+            return Value::Boolean(equal == (op == CompareOp::Eq));
+        }
+        (Value::Number(a), Value::Number(b)) => a.partial_cmp(b),
+        (Value::Date(a), Value::Date(b)) => Some(a.cmp(b)),
+        (Value::Text(a), Value::Text(b)) => Some(a.cmp(b)),
+        (Value::Boolean(a), Value::Boolean(b)) => Some(a.cmp(b)),
+        _ => return unknown(left, right),
+    };
+    let Some(ordering) = ordering else {
+        return Value::Invalid;
+    };
+    Value::Boolean(match op {
+        CompareOp::Eq => ordering.is_eq(),
+        CompareOp::Ne => ordering.is_ne(),
+        CompareOp::Lt => ordering.is_lt(),
+        CompareOp::Le => ordering.is_le(),
+        CompareOp::Gt => ordering.is_gt(),
+        CompareOp::Ge => ordering.is_ge(),
+    })
+}
+
+/// Missing rows are skipped; an invalid row makes sums and extremes unknown. Sums
+/// and extremes over no values are missing, so documents without rows pass.
+fn fold(aggregate: Aggregate, values: Vec<Value>) -> Value {
+    if aggregate == Aggregate::Count {
+        return Value::Number(values.iter().filter(|v| v.is_known()).count() as f64);
+    }
+    if values.contains(&Value::Invalid) {
+        return Value::Invalid;
+    }
+    let mut known = values.into_iter().filter(Value::is_known);
+    let Some(first) = known.next() else {
+        return Value::Missing;
+    };
+    known.fold(first, |acc, value| match (aggregate, &acc, &value) {
+        (Aggregate::Sum, Value::Number(a), Value::Number(b)) => Value::Number(a + b),
+        (Aggregate::Min, Value::Number(a), Value::Number(b)) => Value::Number(a.min(*b)),
+        (Aggregate::Max, Value::Number(a), Value::Number(b)) => Value::Number(a.max(*b)),
+        (Aggregate::Min, Value::Date(a), Value::Date(b)) => Value::Date(*a.min(b)),
+        (Aggregate::Max, Value::Date(a), Value::Date(b)) => Value::Date(*a.max(b)),
+        _ => Value::Invalid,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::World;
+
+    const SUMS: &str = "sum(items.amount) == fields.subtotal ± 0.01";
+
+    fn schema() -> SchemaVersion {
+        World::new(&[
+            ("subtotal", "currency", "document", false),
+            ("invoice_date", "date", "document", false),
+            ("amount", "currency", "item", false),
+        ])
+        .schema
+    }
+
+    fn observed(normalized: &str) -> Observed {
+        Observed {
+            value_id: Uuid::now_v7(),
+            raw_value: normalized.to_string(),
+            normalized_value: Some(normalized.to_string()),
+        }
+    }
+
+    fn document(subtotal: Option<&str>, amounts: &[&str]) -> DocumentValues {
+        DocumentValues {
+            fields: subtotal
+                .map(|s| ("subtotal".to_string(), observed(s)))
+                .into_iter()
+                .collect(),
+            items: (0u32..)
+                .zip(amounts)
+                .map(|(row_index, amount)| ItemValues {
+                    item_id: Uuid::now_v7(),
+                    row_index,
+                    values: HashMap::from([("amount".to_string(), observed(amount))]),
+                })
+                .collect(),
+            extras: Default::default(),
+        }
+    }
+
+    fn parse_error(source: &str) -> DslError {
+        Expression::parse(source).unwrap_err()
+    }
+
+    fn type_error(source: &str) -> DslError {
+        match Expression::parse(source).unwrap().typed(&schema()) {
+            Err(err) => err,
+            Ok(_) => panic!("'{source}' type-checked"),
+        }
+    }
+
+    #[test]
+    fn syntax_errors_carry_the_offending_span() {
+        let err = parse_error("fields.subtotal = 1");
+        assert_eq!(err.kind, DslErrorKind::Syntax);
+        assert_eq!(err.span, Span { start: 16, end: 17 });
+
+        let err = parse_error("fields.subtotal == ");
+        assert_eq!(err.span, Span { start: 19, end: 19 });
+        let err = parse_error("sum(items.amount");
+        assert_eq!(err.span, Span { start: 16, end: 16 });
+        let err = parse_error("fields.subtotal > 0 < 5");
+        assert_eq!(err.span, Span { start: 20, end: 21 });
+        let err = parse_error("fields.subtotal && true");
+        assert_eq!(err.span, Span { start: 16, end: 17 });
+
+        let source = "fields.subtotal > 0 and\n  len(fields.vendor) < 'x";
+        let err = parse_error(source);
+        assert_eq!(err.span, Span { start: 47, end: 48 });
+        assert_eq!(err.position(source), (2, 24));
+        let details = err.to_domain_error(source).details.unwrap();
+        assert_eq!(details["line"], 2);
+        assert_eq!(details["token"], "'");
+        assert_eq!(
+            details["pointer"],
+            "  len(fields.vendor) < 'x\n                       ^"
+        );
+    }
+
+    #[test]
+    fn type_errors_are_checked_against_the_schema() {
+        let err = type_error("fields.discount > 0");
+        assert_eq!(err.kind, DslErrorKind::UnknownField);
+        assert_eq!(err.span, Span { start: 0, end: 15 });
+
+        let err = type_error("fields.invoice_date == fields.subtotal");
+        assert_eq!(err.kind, DslErrorKind::Type);
+        assert_eq!(err.span, Span { start: 23, end: 38 });
+        let err = type_error("fields.invoice_date < date(\"2024-01-01\") ± 1");
+        assert_eq!(err.kind, DslErrorKind::Type);
+
+        let err = type_error("fields.amount > 0");
+        assert_eq!(err.kind, DslErrorKind::Type);
+        assert!(err.message.contains("use items.amount"), "{}", err.message);
+        let err = type_error("fields.subtotal + 1");
+        assert_eq!(err.kind, DslErrorKind::Type);
+        assert_eq!(err.span, Span { start: 0, end: 19 });
+
+        Expression::parse(SUMS).unwrap().typed(&schema()).unwrap();
+    }
+
+    #[test]
+    fn sums_compare_within_the_tolerance() {
+        let schema = schema();
+        let expression = Expression::parse(SUMS).unwrap();
+        assert_eq!(expression.field_keys(), ["amount", "subtotal"]);
+        assert_eq!(expression.layer(), RuleLayer::CrossField);
+        let typed = expression.typed(&schema).unwrap();
+
+        assert!(typed
+            .violations(&document(Some("100.00"), &["40.00", "60.005"]))
+            .is_empty());
+        assert!(typed
+            .violations(&document(None, &["40.00", "60.00"]))
+            .is_empty());
+
+        let failing = document(Some("100.02"), &["40.00", "60.005"]);
+        let violations = typed.violations(&failing);
+        assert_eq!(violations.len(), 1);
+        assert_eq!(violations[0].item_id, None);
+        assert_eq!(
+            violations[0].detail.as_deref(),
+            Some("sum(items.amount) is 100.005, fields.subtotal is 100.02")
+        );
+        assert_eq!(violations[0].value_ids.len(), 3);
+
+        let ascii = Expression::parse("sum(items.amount) == fields.subtotal +/- 0.01").unwrap();
+        assert_eq!(ascii.typed(&schema).unwrap().violations(&failing).len(), 1);
+    }
+
+    #[test]
+    fn deep_or_long_input_is_rejected() {
+        for source in [
+            format!("{}true{}", "(".repeat(500), ")".repeat(500)),
+            format!("{}true", "not ".repeat(400)),
+            format!("{}1 == 1", "-".repeat(1_000)),
+            format!("true{}", " and true".repeat(100)),
+            format!("fields.subtotal{} > 0", " + 1".repeat(100)),
+        ] {
+            let err = parse_error(&source);
+            assert_eq!(err.kind, DslErrorKind::Syntax);
+            assert!(err.message.contains("nested"), "{}", err.message);
+        }
+
+        let long = format!("true{}", " or true".repeat(300));
+        let err = parse_error(&long);
+        assert!(err.message.contains("longer"), "{}", err.message);
+        assert_eq!(
+            err.span,
+            Span {
+                start: 0,
+                end: long.len()
+            }
+        );
+    }
+}
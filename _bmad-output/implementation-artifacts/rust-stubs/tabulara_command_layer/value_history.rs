@@ -0,0 +1,419 @@
+use std::collections::BTreeMap;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::errors::{DomainError, DomainResult, ErrorCode};
+use crate::interfaces::EventReader;
+use crate::mapping::WrittenValue;
+use crate::provenance::Provenance;
+use crate::types::EventEnvelope;
+
+/// A field value, item cell or extra cell, addressed the way commands address it.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum ValueTarget {
+    Field {
+        document_id: Uuid,
+        schema_field_id: Uuid,
+    },
+    Item {
+        item_id: Uuid,
+        schema_field_id: Uuid,
+    },
+    Extra {
+        extra_row_id: Uuid,
+        schema_field_id: Uuid,
+    },
+}
+
+impl From<&WrittenValue> for ValueTarget {
+    fn from(value: &WrittenValue) -> Self {
+        match value {
+            WrittenValue::Field(v) => ValueTarget::Field {
+                document_id: v.document_id,
+                schema_field_id: v.schema_field_id,
+            },
+            WrittenValue::Item(v) => ValueTarget::Item {
+                item_id: v.item_id,
+                schema_field_id: v.schema_field_id,
+            },
+            WrittenValue::Extra(v) => ValueTarget::Extra {
+                extra_row_id: v.extra_row_id,
+                schema_field_id: v.schema_field_id,
+            },
+        }
+    }
+}
+
+/// One version of a value. `version` is the session version that produced it: the
+/// number of session events up to and including the command's event. A `removed`
+/// version records the value as it was when its item row was deleted.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ValueVersion {
+    pub version: u64,
+    pub raw_value: String,
+    pub normalized_value: Option<String>,
+    pub provenance: Provenance,
+    pub removed: bool,
+    pub actor: String,
+    pub command_id: Uuid,
+    pub event_type: String,
+    pub timestamp: DateTime<Utc>,
+}
+
+impl ValueVersion {
+    fn new(version: u64, event: &EventEnvelope, value: WrittenValue, removed: bool) -> Self {
+        let (raw_value, normalized_value, provenance) = match value {
+            WrittenValue::Field(v) => (v.raw_value, v.normalized_value, v.provenance),
+            WrittenValue::Item(v) => (v.raw_value, v.normalized_value, v.provenance),
+            WrittenValue::Extra(v) => (v.raw_value, v.normalized_value, v.provenance),
+        };
+        Self {
+            version,
+            raw_value,
+            normalized_value,
+            provenance,
+            removed,
+            actor: event
+                .data
+                .get("actor")
+                .and_then(serde_json::Value::as_str)
+                .unwrap_or_default()
+                .to_string(),
+            command_id: event.caused_by,
+            event_type: event.event_type.clone(),
+            timestamp: event.timestamp,
+        }
+    }
+
+    /// Whether `other` holds the same value; who wrote it and when is ignored.
+    fn same_state(&self, other: &ValueVersion) -> bool {
+        self.raw_value == other.raw_value
+            && self.normalized_value == other.normalized_value
+            && self.provenance == other.provenance
+    }
+}
+
+/// A value that differs between two session versions. `before` and `after` are the
+/// versions current at each point; `None` when the value did not exist or its row
+/// had been deleted.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ValueDiff {
+    pub target: ValueTarget,
+    pub before: Option<ValueVersion>,
+    pub after: Option<ValueVersion>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SessionDiff {
+    pub session_id: Uuid,
+    pub from_version: u64,
+    pub to_version: u64,
+    pub changes: Vec<ValueDiff>,
+}
+
+/// Value history rebuilt from the `written_values` and `removed_values` of the
+/// session's events, so it covers every command that stored a value.
+pub struct ValueHistory<'a> {
+    events: &'a dyn EventReader,
+}
+
+impl<'a> ValueHistory<'a> {
+    pub fn new(events: &'a dyn EventReader) -> Self {
+        Self { events }
+    }
+
+    /// Current session version: the number of events recorded for the session.
+    pub fn session_version(&self, session_id: Uuid) -> DomainResult<u64> {
+        self.events.session_version(session_id)
+    }
+
+    /// Every version of one value, oldest first; empty when it was never written.
+    pub fn history(
+        &self,
+        session_id: Uuid,
+        target: &ValueTarget,
+    ) -> DomainResult<Vec<ValueVersion>> {
+        Ok(self
+            .replay(session_id)?
+            .0
+            .remove(target)
+            .unwrap_or_default())
+    }
+
+    /// Values whose state at `to_version` differs from their state at `from_version`,
+    /// in target order. Version 0 is the empty session.
+    pub fn diff(
+        &self,
+        session_id: Uuid,
+        from_version: u64,
+        to_version: u64,
+    ) -> DomainResult<SessionDiff> {
+        let (histories, current) = self.replay(session_id)?;
+        if from_version > to_version || to_version > current {
+            return Err(DomainError {
+                code: ErrorCode::PreconditionFailed,
+                message: "Diff versions must satisfy from <= to <= current session version"
+                    .to_string(),
+                details: Some(serde_json::json!({
+                    "from_version": from_version,
+                    "to_version": to_version,
+                    "session_version": current,
+                })),
+            });
+        }
+
+        let at = |versions: &[ValueVersion], version: u64| {
+            versions
+                .iter()
+                .rev()
+                .find(|v| v.version <= version)
+                .filter(|v| !v.removed)
+                .cloned()
+        };
+        let changes = histories
+            .into_iter()
+            .filter_map(|(target, versions)| {
+                let before = at(&versions, from_version);
+                let after = at(&versions, to_version);
+                let unchanged = match (&before, &after) {
+                    (Some(b), Some(a)) => b.same_state(a),
+                    (None, None) => true,
+                    _ => false,
+                };
+                (!unchanged).then_some(ValueDiff {
+                    target,
+                    before,
+                    after,
+                })
+            })
+            .collect();
+        Ok(SessionDiff {
+            session_id,
+            from_version,
+            to_version,
+            changes,
+        })
+    }
+
+    /// Versions of every value the session's events wrote, with the session version.
+    fn replay(
+        &self,
+        session_id: Uuid,
+    ) -> DomainResult<(BTreeMap<ValueTarget, Vec<ValueVersion>>, u64)> {
+        let events = self.events.session_events(session_id)?;
+        let mut histories: BTreeMap<ValueTarget, Vec<ValueVersion>> = BTreeMap::new();
+        for (index, event) in events.iter().enumerate() {
+            let version = index as u64 + 1;
+            for (pointer, removed) in [
+                ("/delta/written_values", false),
+                ("/delta/removed_values", true),
+            ] {
+                let Some(values) = event.data.pointer(pointer) else {
+                    continue;
+                };
+                let values: Vec<WrittenValue> =
+                    serde_json::from_value(values.clone()).map_err(|e| DomainError {
+                        code: ErrorCode::Internal,
+                        message: "Event values cannot be read".to_string(),
+                        details: Some(serde_json::json!({
+                            "event_id": event.event_id,
+                            "reason": e.to_string(),
+                        })),
+                    })?;
+                for value in values {
+                    histories
+                        .entry(ValueTarget::from(&value))
+                        .or_default()
+                        .push(ValueVersion::new(version, event, value, removed));
+                }
+            }
+        }
+        Ok((histories, events.len() as u64))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dispatcher_impl::DefaultCommandDispatcher;
+    use crate::interfaces::{CommandDispatcher, DispatcherDeps};
+    use crate::item_rows::{AddItemRowHandler, DeleteItemRowHandler, ItemRowDeps};
+    use crate::mapping::{AssignFieldValueHandler, AssignItemValueHandler};
+    use crate::test_support::{command, World};
+    use crate::transition_policy::MatrixTransitionPolicy;
+    use crate::types::SessionStatus;
+
+    /// Dispatches `commands` in order, returning the command ids.
+    fn dispatch_all(w: &World, commands: Vec<serde_json::Value>) -> Vec<Uuid> {
+        let b = &w.b;
+        let item_rows = || ItemRowDeps {
+            mapping: &b.mapping,
+            sessions: &b.sessions,
+            schemas: &b.schemas,
+            pages: &b.pages,
+        };
+        let assign_field = AssignFieldValueHandler::new(
+            &b.mapping,
+            &b.sessions,
+            &b.schemas,
+            w.provenance(),
+            w.dictionary(),
+        );
+        let assign_item = AssignItemValueHandler::new(
+            &b.mapping,
+            &b.sessions,
+            &b.schemas,
+            w.provenance(),
+            w.dictionary(),
+        );
+        let add_row = AddItemRowHandler::new(item_rows());
+        let delete_row = DeleteItemRowHandler::new(item_rows());
+        let policy = MatrixTransitionPolicy::new();
+        let dispatcher = DefaultCommandDispatcher::new(DispatcherDeps {
+            handlers: vec![&assign_field, &assign_item, &add_row, &delete_row],
+            transitions: &policy,
+            idempotency: &b.idempotency,
+            events: &b.events,
+            event_reader: &b.events,
+            event_factory: &b.event_factory,
+            invariants: &b.invariants,
+            sessions: &b.sessions,
+            projections: &b.projections,
+            locks: &b.locks,
+            uow: &b.uow,
+        });
+        commands
+            .into_iter()
+            .map(|mut json| {
+                let command_id = Uuid::now_v7();
+                json["command_id"] = serde_json::json!(command_id);
+                dispatcher.dispatch(command(json)).unwrap();
+                command_id
+            })
+            .collect()
+    }
+
+    #[test]
+    fn history_and_diffs_follow_assignments_and_row_deletion() {
+        let w = World::new(&[
+            ("invoice_number", "string", "document", false),
+            ("description", "string", "item", false),
+        ]);
+        let session_id = w.session(SessionStatus::Review);
+        let (document_id, _) = w.document(session_id);
+        let item_id = Uuid::now_v7();
+        let manual = serde_json::json!({ "source": "manual", "actor": "tester" });
+        let field = |raw_value: &str| {
+            serde_json::json!({
+                "type": "AssignFieldValue",
+                "payload": {
+                    "session_id": session_id,
+                    "document_id": document_id,
+                    "schema_field_id": w.field_id("invoice_number"),
+                    "raw_value": raw_value,
+                    "provenance": manual
+                }
+            })
+        };
+        let item = |raw_value: &str| {
+            serde_json::json!({
+                "type": "AssignItemValue",
+                "payload": {
+                    "session_id": session_id,
+                    "item_id": item_id,
+                    "schema_field_id": w.field_id("description"),
+                    "raw_value": raw_value,
+                    "provenance": manual
+                }
+            })
+        };
+        let command_ids = dispatch_all(
+            &w,
+            vec![
+                field("A-1"),
+                serde_json::json!({
+                    "type": "AddItemRow",
+                    "payload": {
+                        "session_id": session_id,
+                        "document_id": document_id,
+                        "row_index": 0,
+                        "item_id": item_id
+                    }
+                }),
+                item("Widget"),
+                item("Gadget"),
+                field("A-2"),
+                serde_json::json!({
+                    "type": "DeleteItemRow",
+                    "payload": { "session_id": session_id, "item_id": item_id }
+                }),
+            ],
+        );
+
+        let history = ValueHistory::new(&w.b.events);
+        assert_eq!(history.session_version(session_id).unwrap(), 6);
+        let field_target = ValueTarget::Field {
+            document_id,
+            schema_field_id: w.field_id("invoice_number"),
+        };
+        let item_target = ValueTarget::Item {
+            item_id,
+            schema_field_id: w.field_id("description"),
+        };
+
+        let versions = history.history(session_id, &item_target).unwrap();
+        let summary: Vec<_> = versions
+            .iter()
+            .map(|v| (v.version, v.raw_value.as_str(), v.removed))
+            .collect();
+        assert_eq!(
+            summary,
+            [
+                (3, "Widget", false),
+                (4, "Gadget", false),
+                (6, "Gadget", true)
+            ]
+        );
+        assert!(versions.iter().all(|v| v.actor == "tester"));
+        let caused_by: Vec<_> = versions.iter().map(|v| v.command_id).collect();
+        assert_eq!(caused_by, [command_ids[2], command_ids[3], command_ids[5]]);
+
+        let raw = |v: &Option<ValueVersion>| v.as_ref().map(|v| v.raw_value.clone());
+        let changes = |from, to| {
+            history
+                .diff(session_id, from, to)
+                .unwrap()
+                .changes
+                .iter()
+                .map(|c| (c.target, raw(&c.before), raw(&c.after)))
+                .collect::<Vec<_>>()
+        };
+        let some = |s: &str| Some(s.to_string());
+
+        // The deleted row's value is gone again by the latest version.
+        assert_eq!(changes(0, 6), [(field_target, None, some("A-2"))]);
+        assert_eq!(
+            changes(0, 4),
+            [
+                (field_target, None, some("A-1")),
+                (item_target, None, some("Gadget")),
+            ]
+        );
+        assert_eq!(
+            changes(3, 6),
+            [
+                (field_target, some("A-1"), some("A-2")),
+                (item_target, some("Widget"), None),
+            ]
+        );
+        assert!(changes(4, 4).is_empty());
+
+        for (from, to) in [(5, 4), (0, 7)] {
+            let err = history.diff(session_id, from, to).unwrap_err();
+            assert!(matches!(err.code, ErrorCode::PreconditionFailed));
+        }
+    }
+}
@@ -0,0 +1,559 @@
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+
+use crate::interfaces::ReviewAction;
+use crate::mapping::FieldValue;
+use crate::review_tasks::{ReviewCategory, ReviewTarget};
+use crate::schema::SchemaField;
+use crate::types::FieldType;
+
+/// Two-digit years below this are read as 20xx, the rest as 19xx.
+pub const TWO_DIGIT_YEAR_PIVOT: i32 = 70;
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum DecimalSeparator {
+    Dot,
+    Comma,
+}
+
+impl DecimalSeparator {
+    fn as_char(self) -> char {
+        match self {
+            DecimalSeparator::Dot => '.',
+            DecimalSeparator::Comma => ',',
+        }
+    }
+}
+
+/// Order of day and month in all-numeric dates such as `03/04/2024`.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum DateOrder {
+    Dmy,
+    Mdy,
+    Ymd,
+}
+
+/// Project-wide conventions for values whose text is ambiguous on its own.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(default)]
+pub struct ValueLocale {
+    pub decimal_separator: DecimalSeparator,
+    pub date_order: DateOrder,
+    /// ISO 4217 code assumed when an amount carries no symbol or code.
+    pub default_currency: Option<String>,
+}
+
+impl Default for ValueLocale {
+    fn default() -> Self {
+        Self {
+            decimal_separator: DecimalSeparator::Dot,
+            date_order: DateOrder::Mdy,
+            default_currency: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct ParseFailure {
+    pub field_type: FieldType,
+    pub reason: String,
+}
+
+/// Canonical text of `raw` for the field's type; `Ok(None)` for blank input.
+///
+/// Canonical forms: integers and decimals as plain digits with `.` and an optional
+/// leading `-`, currencies as `<amount> <ISO code>`, dates as `YYYY-MM-DD`, booleans
+/// as `true`/`false` and enums as the declared enum value.
+pub fn parse_value(
+    field: &SchemaField,
+    raw: &str,
+    locale: &ValueLocale,
+) -> Result<Option<String>, ParseFailure> {
+    let text = raw.split_whitespace().collect::<Vec<_>>().join(" ");
+    if text.is_empty() {
+        return Ok(None);
+    }
+    let parsed = match field.field_type {
+        FieldType::String => Ok(text),
+        FieldType::Integer => parse_number(&text, locale.decimal_separator, true),
+        FieldType::Decimal => parse_number(&text, locale.decimal_separator, false),
+        FieldType::Currency => parse_currency(&text, locale),
+        FieldType::Date => parse_date(&text, locale.date_order),
+        FieldType::Boolean => parse_boolean(&text),
+        FieldType::Enum => parse_enum(&text, field),
+    };
+    parsed.map(Some).map_err(|reason| ParseFailure {
+        field_type: field.field_type,
+        reason,
+    })
+}
+
+/// Review task for a value that was stored but could not be typed.
+pub fn unparseable_review_action(
+    value: &FieldValue,
+    field: &SchemaField,
+    failure: &ParseFailure,
+) -> ReviewAction {
+    ReviewAction::new(
+        value.session_id,
+        ReviewCategory::UnparseableValue,
+        ReviewTarget::field(value.document_id, field.schema_field_id),
+        serde_json::json!({
+            "field_value_id": value.field_value_id,
+            "field_key": field.field_key,
+            "raw_value": value.raw_value,
+            "field_type": failure.field_type,
+            "reason": failure.reason,
+        }),
+    )
+}
+
+/// Parses a grouped number. A lone separator that is not the locale's decimal
+/// separator is read as grouping only when exactly three digits follow it; integers
+/// always read a lone separator followed by three digits as grouping.
+fn parse_number(text: &str, decimal: DecimalSeparator, integer: bool) -> Result<String, String> {
+    let mut body = text.trim();
+    let mut negative = false;
+    if let Some(inner) = body.strip_prefix('(').and_then(|b| b.strip_suffix(')')) {
+        negative = true;
+        body = inner.trim();
+    }
+    for minus in ['-', '\u{2212}'] {
+        if let Some(rest) = body.strip_prefix(minus) {
+            negative = !negative;
+            body = rest.trim_start();
+        } else if let Some(rest) = body.strip_suffix(minus) {
+            negative = !negative;
+            body = rest.trim_end();
+        }
+    }
+    if let Some(rest) = body.strip_prefix('+') {
+        body = rest.trim_start();
+    }
+
+    let chars: Vec<char> = body
+        .chars()
+        .map(|c| match c {
+            '\'' | '\u{2019}' => ' ',
+            c if c.is_whitespace() => ' ',
+            c => c,
+        })
+        .collect();
+    if chars.is_empty()
+        || chars
+            .iter()
+            .any(|c| !(c.is_ascii_digit() || matches!(c, '.' | ',' | ' ')))
+    {
+        return Err("Not a number".to_string());
+    }
+
+    let dots = chars.iter().filter(|c| **c == '.').count();
+    let commas = chars.iter().filter(|c| **c == ',').count();
+    let decimal_at = match (dots, commas) {
+        (0, 0) => None,
+        (d, c) if d > 0 && c > 0 => {
+            let last = chars
+                .iter()
+                .rposition(|c| matches!(c, '.' | ','))
+                .unwrap_or(0);
+            let count = if chars[last] == '.' { d } else { c };
+            if count > 1 {
+                return Err("Number has more than one decimal separator".to_string());
+            }
+            Some(last)
+        }
+        (1, 0) | (0, 1) => {
+            let at = chars
+                .iter()
+                .position(|c| matches!(c, '.' | ','))
+                .unwrap_or(0);
+            let digits_after = chars[at + 1..]
+                .iter()
+                .filter(|c| c.is_ascii_digit())
+                .count();
+            let grouping = if integer || chars[at] != decimal.as_char() {
+                digits_after == 3
+            } else {
+                false
+            };
+            (!grouping).then_some(at)
+        }
+        _ => None,
+    };
+
+    let (int_part, fraction): (&[char], &[char]) = match decimal_at {
+        Some(at) => (&chars[..at], &chars[at + 1..]),
+        None => (&chars[..], &[]),
+    };
+    if fraction.iter().any(|c| !c.is_ascii_digit()) {
+        return Err("Misplaced separator in fractional part".to_string());
+    }
+
+    let groups: Vec<String> = int_part
+        .split(|c| matches!(c, '.' | ',' | ' '))
+        .map(|g| g.iter().collect())
+        .collect();
+    if groups.len() > 1 {
+        let last = groups.len() - 1;
+        let valid = groups.iter().enumerate().all(|(i, g)| match i {
+            0 => (1..=3).contains(&g.len()),
+            i if i == last => g.len() == 3,
+            // Lakh grouping (`1,00,000`) uses two-digit middle groups.
+            _ => matches!(g.len(), 2 | 3),
+        });
+        if !valid {
+            return Err("Digit grouping is not valid".to_string());
+        }
+    }
+    let digits: String = groups.concat();
+    if digits.is_empty() && fraction.is_empty() {
+        return Err("Not a number".to_string());
+    }
+
+    let whole = digits.trim_start_matches('0');
+    let whole = if whole.is_empty() { "0" } else { whole };
+    let fraction: String = fraction.iter().collect();
+    if integer && fraction.chars().any(|c| c != '0') {
+        return Err("Integer has a fractional part".to_string());
+    }
+
+    let mut canonical = whole.to_string();
+    if !integer && !fraction.is_empty() {
+        canonical.push('.');
+        canonical.push_str(&fraction);
+    }
+    let zero = canonical.chars().all(|c| c == '0' || c == '.');
+    if negative && !zero {
+        canonical.insert(0, '-');
+    }
+    Ok(canonical)
+}
+
+/// Longest symbols first so `US$` wins over `$`.
+const CURRENCY_SYMBOLS: &[(&str, &str)] = &[
+    ("US$", "USD"),
+    ("CA$", "CAD"),
+    ("AU$", "AUD"),
+    ("NZ$", "NZD"),
+    ("HK$", "HKD"),
+    ("R$", "BRL"),
+    ("C$", "CAD"),
+    ("A$", "AUD"),
+    ("€", "EUR"),
+    ("£", "GBP"),
+    ("¥", "JPY"),
+    ("₹", "INR"),
+    ("₩", "KRW"),
+    ("₺", "TRY"),
+    ("₽", "RUB"),
+    ("zł", "PLN"),
+];
+
+/// Active ISO 4217 alphabetic codes, funds and precious metals included; the
+/// testing and no-currency codes (`XTS`, `XXX`) are left out.
+const ISO_4217_CODES: &[&str] = &[
+    "AED", "AFN", "ALL", "AMD", "ANG", "AOA", "ARS", "AUD", "AWG", "AZN", "BAM", "BBD", "BDT",
+    "BGN", "BHD", "BIF", "BMD", "BND", "BOB", "BOV", "BRL", "BSD", "BTN", "BWP", "BYN", "BZD",
+    "CAD", "CDF", "CHE", "CHF", "CHW", "CLF", "CLP", "CNY", "COP", "COU", "CRC", "CUC", "CUP",
+    "CVE", "CZK", "DJF", "DKK", "DOP", "DZD", "EGP", "ERN", "ETB", "EUR", "FJD", "FKP", "GBP",
+    "GEL", "GHS", "GIP", "GMD", "GNF", "GTQ", "GYD", "HKD", "HNL", "HTG", "HUF", "IDR", "ILS",
+    "INR", "IQD", "IRR", "ISK", "JMD", "JOD", "JPY", "KES", "KGS", "KHR", "KMF", "KPW", "KRW",
+    "KWD", "KYD", "KZT", "LAK", "LBP", "LKR", "LRD", "LSL", "LYD", "MAD", "MDL", "MGA", "MKD",
+    "MMK", "MNT", "MOP", "MRU", "MUR", "MVR", "MWK", "MXN", "MXV", "MYR", "MZN", "NAD", "NGN",
+    "NIO", "NOK", "NPR", "NZD", "OMR", "PAB", "PEN", "PGK", "PHP", "PKR", "PLN", "PYG", "QAR",
+    "RON", "RSD", "RUB", "RWF", "SAR", "SBD", "SCR", "SDG", "SEK", "SGD", "SHP", "SLE", "SLL",
+    "SOS", "SRD", "SSP", "STN", "SVC", "SYP", "SZL", "THB", "TJS", "TMT", "TND", "TOP", "TRY",
+    "TTD", "TWD", "TZS", "UAH", "UGX", "USD", "USN", "UYI", "UYU", "UYW", "UZS", "VED", "VES",
+    "VND", "VUV", "WST", "XAF", "XAG", "XAU", "XBA", "XBB", "XBC", "XBD", "XCD", "XCG", "XDR",
+    "XOF", "XPD", "XPF", "XPT", "XSU", "XUA", "YER", "ZAR", "ZMW", "ZWG", "ZWL",
+];
+
+/// The upper-cased code when `candidate` is an ISO 4217 code; words such as `Tax`,
+/// `Net` or `Due` next to an amount are not currencies.
+fn iso_code(candidate: &str) -> Option<String> {
+    let code = candidate.to_ascii_uppercase();
+    ISO_4217_CODES
+        .binary_search(&code.as_str())
+        .is_ok()
+        .then_some(code)
+}
+
+fn parse_currency(text: &str, locale: &ValueLocale) -> Result<String, String> {
+    let mut rest = text.to_string();
+    let mut codes: Vec<String> = Vec::new();
+
+    let words: Vec<&str> = text.split(' ').collect();
+    for word in [words.first(), words.last()].into_iter().flatten() {
+        if let Some(code) = iso_code(word) {
+            codes.push(code);
+            rest = rest.replacen(word, "", 1);
+        }
+    }
+    // Codes glued to the amount, e.g. `EUR12.50` or `12.50EUR`.
+    if codes.is_empty() {
+        let trimmed = rest.trim().to_string();
+        let prefix: String = trimmed.chars().take(3).collect();
+        let suffix: String = trimmed
+            .chars()
+            .rev()
+            .take(3)
+            .collect::<Vec<_>>()
+            .into_iter()
+            .rev()
+            .collect();
+        if let Some(code) = iso_code(&prefix) {
+            codes.push(code);
+            rest = trimmed[prefix.len()..].to_string();
+        } else if let Some(code) = iso_code(&suffix) {
+            codes.push(code);
+            rest = trimmed[..trimmed.len() - suffix.len()].to_string();
+        }
+    }
+    for (symbol, code) in CURRENCY_SYMBOLS {
+        if rest.contains(symbol) {
+            codes.push(code.to_string());
+            rest = rest.replacen(symbol, "", 1);
+        }
+    }
+    if rest.contains('$') {
+        let dollar = locale
+            .default_currency
+            .as_deref()
+            .filter(|c| c.ends_with('D'))
+            .unwrap_or("USD");
+        codes.push(dollar.to_string());
+        rest = rest.replacen('$', "", 1);
+    }
+
+    codes.dedup();
+    let code = match codes.as_slice() {
+        [] => locale
+            .default_currency
+            .clone()
+            .ok_or_else(|| "Amount has no currency symbol or code".to_string())?,
+        [code] => code.clone(),
+        _ => return Err(format!("Conflicting currencies: {}", codes.join(", "))),
+    };
+    let amount = parse_number(&rest, locale.decimal_separator, false)?;
+    Ok(format!("{amount} {code}"))
+}
+
+const MONTHS: [&str; 12] = [
+    "january",
+    "february",
+    "march",
+    "april",
+    "may",
+    "june",
+    "july",
+    "august",
+    "september",
+    "october",
+    "november",
+    "december",
+];
+
+const WEEKDAYS: [&str; 7] = [
+    "monday",
+    "tuesday",
+    "wednesday",
+    "thursday",
+    "friday",
+    "saturday",
+    "sunday",
+];
+
+fn month_from_name(word: &str) -> Option<u32> {
+    let word = word.to_lowercase();
+    let word = if word == "sept" {
+        "sep".to_string()
+    } else {
+        word
+    };
+    if word.len() < 3 {
+        return None;
+    }
+    MONTHS
+        .iter()
+        .position(|m| m.starts_with(&word))
+        .map(|i| i as u32 + 1)
+}
+
+fn is_weekday(word: &str) -> bool {
+    let word = word.to_lowercase();
+    word.len() >= 3 && WEEKDAYS.iter().any(|d| d.starts_with(&word))
+}
+
+fn expand_year(token: &str) -> Result<i32, String> {
+    let year: i32 = token
+        .parse()
+        .map_err(|_| "Year is not a number".to_string())?;
+    match token.len() {
+        4 => Ok(year),
+        2 if year < TWO_DIGIT_YEAR_PIVOT => Ok(2000 + year),
+        2 => Ok(1900 + year),
+        _ => Err("Year must have two or four digits".to_string()),
+    }
+}
+
+fn number(token: &str) -> Result<u32, String> {
+    token
+        .parse()
+        .map_err(|_| format!("'{token}' is not a date part"))
+}
+
+fn parse_date(text: &str, order: DateOrder) -> Result<String, String> {
+    // ISO timestamps: keep the date part.
+    let head: String = text.chars().take(10).collect();
+    let text = if NaiveDate::parse_from_str(&head, "%Y-%m-%d").is_ok() {
+        head
+    } else {
+        text.to_string()
+    };
+
+    let tokens: Vec<String> = text
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|t| !t.is_empty())
+        .map(|t| {
+            let digits: String = t.chars().take_while(|c| c.is_ascii_digit()).collect();
+            let suffix = &t[digits.len()..];
+            if !digits.is_empty() && matches!(suffix, "st" | "nd" | "rd" | "th") {
+                digits
+            } else {
+                t.to_string()
+            }
+        })
+        .filter(|t| !is_weekday(t))
+        .collect();
+
+    let (year, month, day) = match tokens.as_slice() {
+        [compact] if compact.len() == 8 && compact.chars().all(|c| c.is_ascii_digit()) => (
+            expand_year(&compact[..4])?,
+            number(&compact[4..6])?,
+            number(&compact[6..])?,
+        ),
+        [a, b, c] => {
+            let named: Vec<(usize, u32)> = [a, b, c]
+                .iter()
+                .enumerate()
+                .filter_map(|(i, t)| month_from_name(t).map(|m| (i, m)))
+                .collect();
+            match named.as_slice() {
+                [(at, month)] => {
+                    let rest: Vec<&String> = [a, b, c]
+                        .into_iter()
+                        .enumerate()
+                        .filter(|(i, _)| i != at)
+                        .map(|(_, t)| t)
+                        .collect();
+                    let (day, year) = if rest[0].len() == 4 {
+                        (rest[1], rest[0])
+                    } else {
+                        (rest[0], rest[1])
+                    };
+                    (expand_year(year)?, *month, number(day)?)
+                }
+                [] if a.len() == 4 => (expand_year(a)?, number(b)?, number(c)?),
+                [] if order == DateOrder::Ymd && c.len() != 4 => {
+                    (expand_year(a)?, number(b)?, number(c)?)
+                }
+                [] => {
+                    let (first, second) = (number(a)?, number(b)?);
+                    // A part above 12 can only be the day; otherwise the locale decides.
+                    let month_first = first <= 12 && (second > 12 || order == DateOrder::Mdy);
+                    let (month, day) = if month_first {
+                        (first, second)
+                    } else {
+                        (second, first)
+                    };
+                    (expand_year(c)?, month, day)
+                }
+                _ => return Err("Date has more than one month name".to_string()),
+            }
+        }
+        _ => return Err("Not a recognizable date".to_string()),
+    };
+
+    NaiveDate::from_ymd_opt(year, month, day)
+        .map(|d| d.format("%Y-%m-%d").to_string())
+        .ok_or_else(|| "No such calendar date".to_string())
+}
+
+const TRUE_WORDS: &[&str] = &[
+    "true", "t", "yes", "y", "1", "x", "on", "checked", "✓", "✔", "☑",
+];
+const FALSE_WORDS: &[&str] = &[
+    "false",
+    "f",
+    "no",
+    "n",
+    "0",
+    "off",
+    "unchecked",
+    "✗",
+    "✘",
+    "☐",
+    "-",
+];
+
+fn parse_boolean(text: &str) -> Result<String, String> {
+    let word = text.to_lowercase();
+    if TRUE_WORDS.contains(&word.as_str()) {
+        Ok("true".to_string())
+    } else if FALSE_WORDS.contains(&word.as_str()) {
+        Ok("false".to_string())
+    } else {
+        Err("Not a yes/no value".to_string())
+    }
+}
+
+fn parse_enum(text: &str, field: &SchemaField) -> Result<String, String> {
+    let wanted = text.to_lowercase();
+    field
+        .enum_values
+        .iter()
+        .find(|v| v.to_lowercase() == wanted)
+        .or_else(|| {
+            field
+                .enum_synonyms
+                .iter()
+                .find(|(synonym, _)| synonym.to_lowercase() == wanted)
+                .map(|(_, value)| value)
+        })
+        .cloned()
+        .ok_or_else(|| format!("'{text}' is not one of {}", field.enum_values.join(", ")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dollars() -> ValueLocale {
+        ValueLocale {
+            default_currency: Some("USD".to_string()),
+            ..ValueLocale::default()
+        }
+    }
+
+    #[test]
+    fn iso_codes_are_sorted_for_lookup() {
+        assert!(ISO_4217_CODES.windows(2).all(|w| w[0] < w[1]));
+    }
+
+    #[test]
+    fn words_next_to_amounts_are_not_currency_codes() {
+        for text in ["Tax 12.50", "Net 100", "Due 5", "12.50 Tax", "TAX12.50"] {
+            assert!(parse_currency(text, &dollars()).is_err(), "{text}");
+        }
+    }
+
+    #[test]
+    fn iso_codes_are_read_before_the_default_currency() {
+        assert_eq!(parse_currency("EUR 12.50", &dollars()).unwrap(), "12.50 EUR");
+        assert_eq!(parse_currency("12.50 chf", &dollars()).unwrap(), "12.50 CHF");
+        assert_eq!(parse_currency("GBP12.50", &dollars()).unwrap(), "12.50 GBP");
+        assert_eq!(parse_currency("12.50", &dollars()).unwrap(), "12.50 USD");
+    }
+}
@@ -0,0 +1,432 @@
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::commands::{AddZone, AnyCommand};
+use crate::errors::{unsupported_command, DomainError, DomainResult, ErrorCode};
+use crate::extraction::{BoundingBox, ExtractionToken, PageCoverage};
+use crate::interfaces::{
+    CommandContext, CommandOutcome, ExtractionStore, GenericCommandHandler, StateDelta,
+    TemplateStore, ValidationTrigger, ZoneStore,
+};
+use crate::provenance::{Origin, Provenance};
+use crate::table_mapping::normalize_header;
+
+/// Default distance a registration token may move from where the template expects it.
+pub const DEFAULT_MAX_SHIFT: f64 = 0.05;
+
+/// A fixed word on the template page used to measure how far a scan is shifted.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ZoneRegistration {
+    pub reference_text: String,
+    /// Where `reference_text` sits on the template page.
+    pub expected_bbox: BoundingBox,
+    #[serde(default = "default_max_shift")]
+    pub max_shift: f64,
+}
+
+fn default_max_shift() -> f64 {
+    DEFAULT_MAX_SHIFT
+}
+
+/// `zones` row: a page-normalized rectangle on a template page that yields one field.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Zone {
+    pub zone_id: Uuid,
+    pub project_id: Uuid,
+    pub template_id: Uuid,
+    pub schema_field_id: Uuid,
+    /// 1-based page of the template the rectangle is drawn on.
+    pub page_number: u32,
+    pub rect: BoundingBox,
+    pub registration: Option<ZoneRegistration>,
+    pub enabled: bool,
+    /// Actor that added the zone.
+    pub created_by: String,
+}
+
+/// A zone read off one page; `rect` is where the zone landed after registration.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ZoneProposal {
+    pub zone_id: Uuid,
+    pub schema_field_id: Uuid,
+    pub document_id: Uuid,
+    pub page_id: Uuid,
+    pub extraction_run_id: Uuid,
+    pub raw_value: String,
+    pub token_ids: Vec<Uuid>,
+    pub rect: BoundingBox,
+    /// `(dx, dy)` applied to the template rectangle; zero when unregistered.
+    pub offset: (f64, f64),
+    pub registration_token_id: Option<Uuid>,
+    pub confidence: f32,
+}
+
+impl ZoneProposal {
+    pub fn provenance(&self) -> Provenance {
+        Provenance::extracted(
+            Origin::Zone {
+                zone_id: self.zone_id,
+                offset: self.offset,
+                registration_token_id: self.registration_token_id,
+            },
+            self.extraction_run_id,
+            self.page_id,
+            self.rect,
+            self.token_ids.clone(),
+            self.confidence,
+        )
+    }
+}
+
+pub fn validate_zone(
+    rect: &BoundingBox,
+    page_number: u32,
+    registration: Option<&ZoneRegistration>,
+) -> DomainResult<()> {
+    let invalid = |message: &str| DomainError {
+        code: ErrorCode::PreconditionFailed,
+        message: message.to_string(),
+        details: Some(serde_json::json!({ "rect": rect, "page_number": page_number })),
+    };
+    if rect.area() <= 0.0 || !rect.is_normalized() {
+        return Err(invalid(
+            "Zone rectangle must be non-empty and page-normalized",
+        ));
+    }
+    if page_number == 0 {
+        return Err(invalid("Zone page number is 1-based"));
+    }
+    if let Some(registration) = registration {
+        if normalize_header(&registration.reference_text).is_empty() {
+            return Err(invalid("Zone registration text must not be empty"));
+        }
+        if !registration.expected_bbox.is_normalized() {
+            return Err(invalid("Zone registration box must be page-normalized"));
+        }
+        if !(0.0..=1.0).contains(&registration.max_shift) {
+            return Err(invalid("Zone registration shift must be within 0.0..=1.0"));
+        }
+    }
+    Ok(())
+}
+
+/// Finds the registration token closest to its expected position and returns the
+/// scan offset it implies. `None` when the token is missing or moved too far.
+pub fn register(
+    registration: &ZoneRegistration,
+    tokens: &[ExtractionToken],
+) -> Option<(Uuid, (f64, f64))> {
+    let wanted = normalize_header(&registration.reference_text);
+    let (ex, ey) = registration.expected_bbox.center();
+    tokens
+        .iter()
+        .filter(|t| normalize_header(&t.text) == wanted)
+        .map(|t| {
+            let (x, y) = t.bbox.center();
+            (t, (x - ex, y - ey))
+        })
+        .filter(|(_, (dx, dy))| {
+            dx.abs() <= registration.max_shift && dy.abs() <= registration.max_shift
+        })
+        .min_by(|(a, (adx, ady)), (b, (bdx, bdy))| {
+            adx.hypot(*ady)
+                .total_cmp(&bdx.hypot(*bdy))
+                .then(a.reading_order.cmp(&b.reading_order))
+        })
+        .map(|(t, offset)| (t.token_id, offset))
+}
+
+fn shifted(rect: &BoundingBox, (dx, dy): (f64, f64)) -> BoundingBox {
+    let x = (rect.x + dx).clamp(0.0, 1.0 - rect.width);
+    let y = (rect.y + dy).clamp(0.0, 1.0 - rect.height);
+    BoundingBox::new(x, y, rect.width, rect.height)
+}
+
+/// Reads one zone off a page. Tokens count when their center falls inside the
+/// (registered) rectangle; the value is their text in reading order.
+pub fn evaluate_zone(
+    zone: &Zone,
+    page: &PageCoverage,
+    extraction_run_id: Uuid,
+    tokens: &[ExtractionToken],
+) -> Option<ZoneProposal> {
+    if !zone.enabled || page.page_number != zone.page_number {
+        return None;
+    }
+
+    let (registration_token_id, offset) = match &zone.registration {
+        Some(registration) => {
+            let (token_id, offset) = register(registration, tokens)?;
+            (Some(token_id), offset)
+        }
+        None => (None, (0.0, 0.0)),
+    };
+    let rect = shifted(&zone.rect, offset);
+
+    let mut inside: Vec<&ExtractionToken> = tokens
+        .iter()
+        .filter(|t| Some(t.token_id) != registration_token_id)
+        .filter(|t| {
+            let (x, y) = t.bbox.center();
+            rect.contains_point(x, y)
+        })
+        .collect();
+    if inside.is_empty() {
+        return None;
+    }
+    inside.sort_by_key(|t| t.reading_order);
+
+    let raw_value = inside
+        .iter()
+        .map(|t| t.text.trim())
+        .filter(|t| !t.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ");
+    let confidence = inside
+        .iter()
+        .map(|t| t.confidence)
+        .fold(f32::INFINITY, f32::min);
+
+    Some(ZoneProposal {
+        zone_id: zone.zone_id,
+        schema_field_id: zone.schema_field_id,
+        document_id: page.document_id,
+        page_id: page.page_id,
+        extraction_run_id,
+        raw_value,
+        token_ids: inside.iter().map(|t| t.token_id).collect(),
+        rect,
+        offset,
+        registration_token_id,
+        confidence,
+    })
+}
+
+/// Runs a template's zones against the latest extraction of a document.
+pub struct ZoneEvaluator<'a> {
+    extraction: &'a dyn ExtractionStore,
+    zones: &'a dyn ZoneStore,
+}
+
+impl<'a> ZoneEvaluator<'a> {
+    pub fn new(extraction: &'a dyn ExtractionStore, zones: &'a dyn ZoneStore) -> Self {
+        Self { extraction, zones }
+    }
+
+    pub fn evaluate_document(
+        &self,
+        template_id: Uuid,
+        session_id: Uuid,
+        pages: &[PageCoverage],
+    ) -> DomainResult<Vec<ZoneProposal>> {
+        let zones = self.zones.zones_for_template(template_id)?;
+        let mut proposals = Vec::new();
+        for page in pages {
+            if !zones
+                .iter()
+                .any(|z| z.enabled && z.page_number == page.page_number)
+            {
+                continue;
+            }
+            let Some(run) = self
+                .extraction
+                .latest_run_for_page(session_id, page.page_id)?
+            else {
+                continue;
+            };
+            let tokens = self
+                .extraction
+                .tokens_for_page(run.extraction_run_id, page.page_id)?;
+            proposals.extend(
+                zones
+                    .iter()
+                    .filter_map(|zone| evaluate_zone(zone, page, run.extraction_run_id, &tokens)),
+            );
+        }
+        Ok(proposals)
+    }
+}
+
+pub struct AddZoneHandler<'a> {
+    zones: &'a dyn ZoneStore,
+    templates: &'a dyn TemplateStore,
+}
+
+impl<'a> AddZoneHandler<'a> {
+    pub fn new(zones: &'a dyn ZoneStore, templates: &'a dyn TemplateStore) -> Self {
+        Self { zones, templates }
+    }
+
+    fn add(&self, cmd: &AddZone) -> DomainResult<CommandOutcome> {
+        let payload = &cmd.payload;
+        let template = self.templates.get(payload.template_id)?;
+        if template.project_id != payload.project_id {
+            return Err(DomainError {
+                code: ErrorCode::PreconditionFailed,
+                message: "Template belongs to a different project".to_string(),
+                details: Some(serde_json::json!({
+                    "project_id": payload.project_id,
+                    "template_id": payload.template_id,
+                })),
+            });
+        }
+        validate_zone(
+            &payload.rect,
+            payload.page_number,
+            payload.registration.as_ref(),
+        )?;
+
+        let zone = Zone {
+            zone_id: Uuid::now_v7(),
+            project_id: payload.project_id,
+            template_id: payload.template_id,
+            schema_field_id: payload.schema_field_id,
+            page_number: payload.page_number,
+            rect: payload.rect,
+            registration: payload.registration.clone(),
+            enabled: true,
+            created_by: cmd.actor.clone(),
+        };
+        self.zones.add(&zone)?;
+
+        Ok(CommandOutcome {
+            state_delta: StateDelta {
+                summary: "Zone created".to_string(),
+                data: serde_json::json!({ "zone": zone }),
+            },
+            transition: None,
+            review_actions: Vec::new(),
+            validation_trigger: ValidationTrigger::None,
+            events: Vec::new(),
+        })
+    }
+}
+
+impl<'a> GenericCommandHandler for AddZoneHandler<'a> {
+    fn can_handle(&self, command_type: &str) -> bool {
+        command_type == "AddZone"
+    }
+
+    fn handle(&self, _ctx: &mut CommandContext, cmd: &AnyCommand) -> DomainResult<CommandOutcome> {
+        match cmd {
+            AnyCommand::AddZone(c) => self.add(c),
+            _ => Err(unsupported_command("AddZoneHandler")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::interfaces::MappingStore;
+    use crate::templates::RegisterTemplateHandler;
+    use crate::test_support::{command, ctx, token, StaticEngine, World};
+    use crate::types::SessionStatus;
+
+    /// An invoice page moved by `(dx, dy)`, with its amount just below the zone.
+    fn scan(dx: f64, dy: f64) -> StaticEngine {
+        let at = |text: &str, x: f64, y: f64, width: f64| {
+            token(text, BoundingBox::new(x + dx, y + dy, width, 0.03))
+        };
+        StaticEngine(vec![
+            at("Acme", 0.05, 0.05, 0.1),
+            at("Invoice", 0.2, 0.05, 0.1),
+            at("123.45", 0.65, 0.53, 0.1),
+        ])
+    }
+
+    fn add_zone(w: &World, template_id: Uuid, field_key: &str, registration: serde_json::Value) {
+        AddZoneHandler::new(&w.b.zones, &w.b.templates)
+            .handle(
+                &mut ctx(),
+                &command(serde_json::json!({
+                    "type": "AddZone",
+                    "payload": {
+                        "project_id": w.project_id,
+                        "template_id": template_id,
+                        "schema_field_id": w.field_id(field_key),
+                        "page_number": 1,
+                        "rect": BoundingBox::new(0.6, 0.5, 0.3, 0.05),
+                        "registration": registration
+                    }
+                })),
+            )
+            .unwrap();
+    }
+
+    #[test]
+    fn registered_zones_follow_a_shifted_scan() {
+        let w = World::new(&[
+            ("total", "decimal", "document", false),
+            ("unregistered_total", "decimal", "document", false),
+        ]);
+        let sample = w.session(SessionStatus::Processing);
+        let (sample_doc, _) = w.document(sample);
+        w.extract(sample, &scan(0.0, 0.0));
+        let registered = RegisterTemplateHandler::new(
+            &w.b.templates,
+            &w.b.sessions,
+            &w.b.pages,
+            &w.b.extraction,
+            &w.b.anchors,
+            &w.b.dictionary,
+        )
+        .handle(
+            &mut ctx(),
+            &command(serde_json::json!({
+                "type": "RegisterTemplate",
+                "payload": {
+                    "project_id": w.project_id,
+                    "session_id": sample,
+                    "document_id": sample_doc,
+                    "name": "Acme invoice"
+                }
+            })),
+        )
+        .unwrap();
+        let template_id: Uuid =
+            serde_json::from_value(registered.state_delta.data["template"]["template_id"].clone())
+                .unwrap();
+        add_zone(
+            &w,
+            template_id,
+            "total",
+            serde_json::json!({
+                "reference_text": "Invoice",
+                "expected_bbox": BoundingBox::new(0.2, 0.05, 0.1, 0.03),
+            }),
+        );
+        add_zone(
+            &w,
+            template_id,
+            "unregistered_total",
+            serde_json::Value::Null,
+        );
+
+        // The amount's center (y = 0.575) is below the drawn rectangle (0.5..0.55).
+        let session = w.session(SessionStatus::Processing);
+        w.document(session);
+        w.extract(session, &scan(0.02, 0.03));
+        let values = w.b.mapping.field_values(session).unwrap();
+        assert_eq!(values.len(), 1);
+        let value = &values[0];
+        assert_eq!(value.schema_field_id, w.field_id("total"));
+        assert_eq!(value.raw_value, "123.45");
+        assert_eq!(value.provenance.template_id, Some(template_id));
+        let Origin::Zone {
+            offset: (dx, dy),
+            registration_token_id,
+            ..
+        } = value.provenance.origin
+        else {
+            panic!(
+                "expected zone provenance, got {:?}",
+                value.provenance.origin
+            );
+        };
+        assert!((dx - 0.02).abs() < 1e-9 && (dy - 0.03).abs() < 1e-9);
+        assert!(registration_token_id.is_some());
+        let rect = value.provenance.bbox.unwrap();
+        assert!((rect.x - 0.62).abs() < 1e-9 && (rect.y - 0.53).abs() < 1e-9);
+    }
+}